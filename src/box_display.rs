@@ -0,0 +1,317 @@
+use crate::boxes::TeXBox;
+use crate::dimension::{Dimen, Scaled};
+use crate::font::Font;
+use crate::list::{HorizontalListElem, VerticalListElem};
+
+/// Formats boxes the way TeX does with \showbox, which looks like
+///
+///   \hbox(6.94444+0.0)x20.0, glue set 1.5fil
+///   .\tenrm a
+///   .\glue 3.33333 plus 1.66666 minus 1.11111
+///
+/// where each element of a box's list is on its own line, prefixed by one "."
+/// for each level of nesting.
+pub struct BoxFormatter<F>
+where
+    F: Fn(&Font) -> String,
+{
+    // Lists nested deeper than this are replaced by " []", like with
+    // \showboxdepth.
+    max_depth: usize,
+    // Only this many elements of each list are shown, and the rest are
+    // replaced by "etc.", like with \showboxbreadth.
+    max_breadth: usize,
+    // Returns the name that a font should be referred to with, like "\tenrm".
+    font_identifier: F,
+    // Whether characters are followed by where they came from, like
+    // " (story.tex:3:2)".
+    show_origins: bool,
+
+    lines: Vec<String>,
+    prefix: String,
+}
+
+impl<F> BoxFormatter<F>
+where
+    F: Fn(&Font) -> String,
+{
+    /// Creates a formatter with the given limits, which are interpreted the
+    /// same way TeX interprets \showboxdepth and \showboxbreadth.
+    pub fn new(depth: i32, breadth: i32, font_identifier: F) -> Self {
+        BoxFormatter {
+            max_depth: depth.max(0) as usize,
+            // TeX uses a default breadth if it isn't positive.
+            max_breadth: if breadth <= 0 { 5 } else { breadth as usize },
+            font_identifier,
+            show_origins: false,
+            lines: Vec::new(),
+            prefix: String::new(),
+        }
+    }
+
+    /// Makes the formatter show the origin of each character after it, when
+    /// that's known.
+    pub fn with_origins(mut self) -> Self {
+        self.show_origins = true;
+        self
+    }
+
+    /// Formats a box and everything in it, with one line per element.
+    pub fn format_box(mut self, tex_box: &TeXBox) -> String {
+        self.add_box(tex_box, Dimen::zero());
+        self.lines.join("\n")
+    }
+
+    pub fn format_horizontal_list(
+        mut self,
+        list: &[HorizontalListElem],
+    ) -> String {
+        self.add_list(list, Self::add_horizontal_list_elem);
+        self.lines.join("\n")
+    }
+
+    pub fn format_vertical_list(mut self, list: &[VerticalListElem]) -> String {
+        self.add_list(list, Self::add_vertical_list_elem);
+        self.lines.join("\n")
+    }
+
+    fn add_line(&mut self, line: String) {
+        self.lines.push(format!("{}{}", self.prefix, line));
+    }
+
+    fn add_list<T>(&mut self, list: &[T], add_elem: fn(&mut Self, &T)) {
+        for (index, elem) in list.iter().enumerate() {
+            if index >= self.max_breadth {
+                self.add_line("etc.".to_string());
+                break;
+            }
+            add_elem(self, elem);
+        }
+    }
+
+    // Adds the elements of a box's list, one level deeper than the box
+    // itself. If that is too deep, TeX just marks the box with " []".
+    fn add_nested_list<T>(&mut self, list: &[T], add_elem: fn(&mut Self, &T)) {
+        if list.is_empty() {
+            return;
+        }
+
+        if self.prefix.len() >= self.max_depth {
+            if let Some(last_line) = self.lines.last_mut() {
+                last_line.push_str(" []");
+            }
+            return;
+        }
+
+        self.prefix.push('.');
+        self.add_list(list, add_elem);
+        self.prefix.pop();
+    }
+
+    fn add_box(&mut self, tex_box: &TeXBox, shift: Dimen) {
+        let (kind, glue_set_ratio) = match tex_box {
+            TeXBox::HorizontalBox(hbox) => ("hbox", &hbox.glue_set_ratio),
+            TeXBox::VerticalBox(vbox) => ("vbox", &vbox.glue_set_ratio),
+        };
+
+        let mut line = format!(
+            "\\{}({}+{})x{}",
+            kind,
+            Scaled(tex_box.height().as_scaled_points()),
+            Scaled(tex_box.depth().as_scaled_points()),
+            Scaled(tex_box.width().as_scaled_points()),
+        );
+        if let Some(ratio) = glue_set_ratio {
+            if !ratio.is_zero() {
+                line.push_str(&format!(", glue set {}", ratio));
+            }
+        }
+        if shift != Dimen::zero() {
+            line.push_str(&format!(
+                ", shifted {}",
+                Scaled(shift.as_scaled_points())
+            ));
+        }
+        self.add_line(line);
+
+        match tex_box {
+            TeXBox::HorizontalBox(hbox) => {
+                self.add_nested_list(&hbox.list, Self::add_horizontal_list_elem)
+            }
+            TeXBox::VerticalBox(vbox) => {
+                self.add_nested_list(&vbox.list, Self::add_vertical_list_elem)
+            }
+        }
+    }
+
+    fn add_horizontal_list_elem(&mut self, elem: &HorizontalListElem) {
+        match elem {
+            HorizontalListElem::Char { chr, font, origin } => {
+                let mut line =
+                    format!("{} {}", (self.font_identifier)(font), chr);
+                if let (true, Some(origin)) = (self.show_origins, origin.get())
+                {
+                    line.push_str(&format!(" ({})", origin));
+                }
+                self.add_line(line);
+            }
+            HorizontalListElem::HSkip(glue) => self
+                .add_line(format!("\\glue {}", glue.to_string_with_unit(""))),
+            // TeX measures shifts in horizontal lists downwards, while we
+            // measure them upwards (like \raise does).
+            HorizontalListElem::Box { tex_box, shift } => {
+                self.add_box(tex_box, *shift * -1)
+            }
+        }
+    }
+
+    fn add_vertical_list_elem(&mut self, elem: &VerticalListElem) {
+        match elem {
+            VerticalListElem::VSkip(glue) => self
+                .add_line(format!("\\glue {}", glue.to_string_with_unit(""))),
+            VerticalListElem::Box { tex_box, shift } => {
+                self.add_box(tex_box, *shift)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::boxes::{
+        GlueSetRatio, GlueSetRatioKind, HorizontalBox, VerticalBox,
+    };
+    use crate::dimension::{FilDimen, FilKind, SpringDimen, Unit};
+    use crate::glue::Glue;
+    use crate::origin::{Provenance, TokenOrigin};
+    use std::rc::Rc;
+
+    fn font_identifier(font: &Font) -> String {
+        format!("\\{}", font.font_name)
+    }
+
+    fn pt(value: f64) -> Dimen {
+        Dimen::from_unit(value, Unit::Point)
+    }
+
+    fn char_elem(chr: char) -> HorizontalListElem {
+        HorizontalListElem::Char {
+            chr,
+            font: Font {
+                font_name: "tenrm".to_string(),
+                scale: pt(10.0),
+            },
+            origin: Provenance::none(),
+        }
+    }
+
+    fn test_box() -> TeXBox {
+        let inner_box = TeXBox::VerticalBox(VerticalBox {
+            height: pt(1.0),
+            depth: pt(0.0),
+            width: pt(2.0),
+            list: vec![VerticalListElem::VSkip(Glue::from_dimen(pt(1.0)))],
+            glue_set_ratio: Some(GlueSetRatio::from(
+                GlueSetRatioKind::Finite,
+                -0.5,
+            )),
+        });
+
+        TeXBox::HorizontalBox(HorizontalBox {
+            height: pt(6.5),
+            depth: pt(0.0),
+            width: pt(20.0),
+            list: vec![
+                char_elem('a'),
+                HorizontalListElem::HSkip(Glue {
+                    space: pt(3.33333),
+                    stretch: SpringDimen::FilDimen(FilDimen::new(
+                        FilKind::Fil,
+                        1.0,
+                    )),
+                    shrink: SpringDimen::Dimen(pt(1.0)),
+                }),
+                HorizontalListElem::Box {
+                    tex_box: inner_box,
+                    shift: pt(2.0),
+                },
+            ],
+            glue_set_ratio: Some(GlueSetRatio::from(
+                GlueSetRatioKind::Fil,
+                1.5,
+            )),
+        })
+    }
+
+    #[test]
+    fn it_formats_boxes_like_tex() {
+        let formatter = BoxFormatter::new(10, 10, font_identifier);
+        assert_eq!(
+            formatter.format_box(&test_box()),
+            [
+                r"\hbox(6.5+0.0)x20.0, glue set 1.5fil",
+                r".\tenrm a",
+                r".\glue 3.33333 plus 1.0fil minus 1.0",
+                r".\vbox(1.0+0.0)x2.0, glue set - 0.5, shifted -2.0",
+                r"..\glue 1.0",
+            ]
+            .join("\n")
+        );
+    }
+
+    #[test]
+    fn it_limits_the_depth_of_formatted_boxes() {
+        let formatter = BoxFormatter::new(1, 10, font_identifier);
+        assert_eq!(
+            formatter.format_box(&test_box()).lines().last(),
+            Some(r".\vbox(1.0+0.0)x2.0, glue set - 0.5, shifted -2.0 []")
+        );
+
+        let formatter = BoxFormatter::new(0, 10, font_identifier);
+        assert_eq!(
+            formatter.format_box(&test_box()),
+            r"\hbox(6.5+0.0)x20.0, glue set 1.5fil []"
+        );
+    }
+
+    #[test]
+    fn it_limits_the_breadth_of_formatted_boxes() {
+        let formatter = BoxFormatter::new(10, 2, font_identifier);
+        assert_eq!(
+            formatter.format_horizontal_list(&[
+                char_elem('a'),
+                char_elem('b'),
+                char_elem('c'),
+            ]),
+            [r"\tenrm a", r"\tenrm b", "etc."].join("\n")
+        );
+    }
+
+    #[test]
+    fn it_shows_the_origins_of_characters() {
+        let mut with_origin = char_elem('b');
+        if let HorizontalListElem::Char { origin, .. } = &mut with_origin {
+            *origin = Provenance::new(Some(Rc::new(TokenOrigin::Source {
+                file: Some(Rc::from("story.tex")),
+                line: 3,
+                column: 2,
+            })));
+        }
+        let list = [char_elem('a'), with_origin];
+
+        let formatter = BoxFormatter::new(10, 10, font_identifier);
+        assert_eq!(
+            formatter.format_horizontal_list(&list),
+            [r"\tenrm a", r"\tenrm b"].join("\n")
+        );
+
+        let formatter =
+            BoxFormatter::new(10, 10, font_identifier).with_origins();
+        assert_eq!(
+            formatter.format_horizontal_list(&list),
+            [r"\tenrm a", r"\tenrm b (story.tex:3:2)"].join("\n")
+        );
+    }
+}
@@ -1,4 +1,5 @@
 use std::collections::HashMap;
+use std::rc::Rc;
 
 use crate::boxes::GlueSetRatio;
 use crate::boxes::TeXBox;
@@ -6,6 +7,7 @@ use crate::dimension::Dimen;
 use crate::dvi::{DVICommand, DVIFile};
 use crate::font::Font;
 use crate::font_metrics::FontMetrics;
+use crate::font_provider::{default_font_provider, FontProvider};
 use crate::list::{HorizontalListElem, VerticalListElem};
 
 pub struct DVIFileWriter {
@@ -20,6 +22,7 @@ pub struct DVIFileWriter {
     num_pages: u16,
     max_stack_depth: u16,
     curr_stack_depth: u16,
+    font_provider: Rc<dyn FontProvider>,
 }
 
 impl DVIFileWriter {
@@ -36,9 +39,16 @@ impl DVIFileWriter {
             num_pages: 0,
             max_stack_depth: 0,
             curr_stack_depth: 0,
+            font_provider: default_font_provider(),
         }
     }
 
+    /// Sets where the metrics for the fonts used in the file are loaded from.
+    /// This should match where the fonts were loaded from while typesetting.
+    pub fn set_font_provider(&mut self, provider: Rc<dyn FontProvider>) {
+        self.font_provider = provider;
+    }
+
     fn add_font_def_with_metrics(
         &mut self,
         font: &Font,
@@ -64,9 +74,11 @@ impl DVIFileWriter {
         let font_num = self.next_font_num;
         self.next_font_num += 1;
 
-        let metrics = FontMetrics::from_font(font).unwrap_or_else(|| {
-            panic!("Error loading font metrics for {}", font.font_name)
-        });
+        let metrics =
+            FontMetrics::from_font_with_provider(font, &*self.font_provider)
+                .unwrap_or_else(|| {
+                    panic!("Error loading font metrics for {}", font.font_name)
+                });
 
         self.add_font_def_with_metrics(font, &metrics, font_num);
         self.font_nums.insert(font.clone(), font_num);
@@ -157,7 +169,7 @@ impl DVIFileWriter {
         glue_set_ratio: &Option<GlueSetRatio>,
     ) {
         match elem {
-            HorizontalListElem::Char { chr, font } => {
+            HorizontalListElem::Char { chr, font, .. } => {
                 let command = if (*chr as u8) < 128 {
                     DVICommand::SetCharN(*chr as u8)
                 } else {
@@ -243,6 +255,19 @@ impl DVIFileWriter {
         });
     }
 
+    /// Starts the file the way TeX does, measuring in scaled points with the
+    /// magnification from \mag and a comment which names the job. Like TeX,
+    /// illegal magnifications are replaced by 1000.
+    pub fn start_job(&mut self, mag: i32, job_name: &str) {
+        let mag = if 0 < mag && mag <= 32768 {
+            mag as u32
+        } else {
+            1000
+        };
+        let comment = format!(" XymosTeX output {}", job_name);
+        self.start((25400000, 473628672), mag, comment.into_bytes());
+    }
+
     pub fn end(&mut self) {
         let post_pointer = self.total_byte_size();
 
@@ -258,7 +283,11 @@ impl DVIFileWriter {
         });
 
         for (font, font_num) in std::mem::take(&mut self.font_nums) {
-            let metrics = FontMetrics::from_font(&font).unwrap_or_else(|| {
+            let metrics = FontMetrics::from_font_with_provider(
+                &font,
+                &*self.font_provider,
+            )
+            .unwrap_or_else(|| {
                 panic!("Error loading font metrics for {}", font.font_name)
             });
 
@@ -281,6 +310,12 @@ impl DVIFileWriter {
     }
 }
 
+impl Default for DVIFileWriter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -288,6 +323,8 @@ mod tests {
     use crate::boxes::{GlueSetRatioKind, HorizontalBox, VerticalBox};
     use crate::dimension::{Dimen, FilDimen, FilKind, SpringDimen, Unit};
     use crate::glue::Glue;
+    use crate::origin::Provenance;
+    use crate::testing::{test_font_metrics, test_font_provider};
 
     lazy_static! {
         static ref CMR10: Font = Font {
@@ -296,13 +333,20 @@ mod tests {
         };
     }
 
+    fn new_test_writer() -> DVIFileWriter {
+        let mut writer = DVIFileWriter::new();
+        writer.set_font_provider(test_font_provider());
+        writer
+    }
+
     #[test]
     fn it_generates_commands_for_chars() {
-        let mut writer = DVIFileWriter::new();
+        let mut writer = new_test_writer();
         writer.add_horizontal_list_elem(
             &HorizontalListElem::Char {
                 chr: 'a',
                 font: CMR10.clone(),
+                origin: Provenance::none(),
             },
             &None,
         );
@@ -310,6 +354,7 @@ mod tests {
             &HorizontalListElem::Char {
                 chr: 200 as char,
                 font: CMR10.clone(),
+                origin: Provenance::none(),
             },
             &None,
         );
@@ -343,11 +388,12 @@ mod tests {
             scale: Dimen::from_unit(10.0, Unit::Point),
         };
 
-        let mut writer = DVIFileWriter::new();
+        let mut writer = new_test_writer();
         writer.add_horizontal_list_elem(
             &HorizontalListElem::Char {
                 chr: 'a',
                 font: CMR10.clone(),
+                origin: Provenance::none(),
             },
             &None,
         );
@@ -355,6 +401,7 @@ mod tests {
             &HorizontalListElem::Char {
                 chr: 'a',
                 font: CMR10.clone(),
+                origin: Provenance::none(),
             },
             &None,
         );
@@ -362,6 +409,7 @@ mod tests {
             &HorizontalListElem::Char {
                 chr: 'a',
                 font: cmr7.clone(),
+                origin: Provenance::none(),
             },
             &None,
         );
@@ -369,6 +417,7 @@ mod tests {
             &HorizontalListElem::Char {
                 chr: 'a',
                 font: cmr7.clone(),
+                origin: Provenance::none(),
             },
             &None,
         );
@@ -376,6 +425,7 @@ mod tests {
             &HorizontalListElem::Char {
                 chr: 'a',
                 font: CMR10.clone(),
+                origin: Provenance::none(),
             },
             &None,
         );
@@ -383,6 +433,7 @@ mod tests {
             &HorizontalListElem::Char {
                 chr: 'a',
                 font: big_cmr10.clone(),
+                origin: Provenance::none(),
             },
             &None,
         );
@@ -390,6 +441,7 @@ mod tests {
             &HorizontalListElem::Char {
                 chr: 'a',
                 font: small_cmr10.clone(),
+                origin: Provenance::none(),
             },
             &None,
         );
@@ -397,6 +449,7 @@ mod tests {
             &HorizontalListElem::Char {
                 chr: 'a',
                 font: big_cmr10,
+                origin: Provenance::none(),
             },
             &None,
         );
@@ -404,6 +457,7 @@ mod tests {
             &HorizontalListElem::Char {
                 chr: 'a',
                 font: small_cmr10,
+                origin: Provenance::none(),
             },
             &None,
         );
@@ -411,6 +465,7 @@ mod tests {
             &HorizontalListElem::Char {
                 chr: 'a',
                 font: cmtt10.clone(),
+                origin: Provenance::none(),
             },
             &None,
         );
@@ -418,6 +473,7 @@ mod tests {
             &HorizontalListElem::Char {
                 chr: 'a',
                 font: cmr7.clone(),
+                origin: Provenance::none(),
             },
             &None,
         );
@@ -425,13 +481,14 @@ mod tests {
             &HorizontalListElem::Char {
                 chr: 'a',
                 font: CMR10.clone(),
+                origin: Provenance::none(),
             },
             &None,
         );
 
-        let cmr10_metrics = FontMetrics::from_font(&CMR10).unwrap();
-        let cmr7_metrics = FontMetrics::from_font(&cmr7).unwrap();
-        let cmtt10_metrics = FontMetrics::from_font(&cmtt10).unwrap();
+        let cmr10_metrics = test_font_metrics(&CMR10).unwrap();
+        let cmr7_metrics = test_font_metrics(&cmr7).unwrap();
+        let cmtt10_metrics = test_font_metrics(&cmtt10).unwrap();
 
         assert_eq!(
             writer.commands,
@@ -509,7 +566,7 @@ mod tests {
 
     #[test]
     fn it_adds_hskips() {
-        let mut writer = DVIFileWriter::new();
+        let mut writer = new_test_writer();
 
         // No stretch/shrink
         writer.add_horizontal_list_elem(
@@ -756,9 +813,9 @@ mod tests {
 
     #[test]
     fn it_adds_basic_horizontal_boxes() {
-        let mut writer = DVIFileWriter::new();
+        let mut writer = new_test_writer();
 
-        let metrics = FontMetrics::from_font(&CMR10).unwrap();
+        let metrics = test_font_metrics(&CMR10).unwrap();
 
         let box1 = TeXBox::HorizontalBox(HorizontalBox {
             height: metrics.get_width('a'),
@@ -768,6 +825,7 @@ mod tests {
             list: vec![HorizontalListElem::Char {
                 chr: 'a',
                 font: CMR10.clone(),
+                origin: Provenance::none(),
             }],
             glue_set_ratio: None,
         });
@@ -801,7 +859,7 @@ mod tests {
 
     #[test]
     fn it_adds_vskips() {
-        let mut writer = DVIFileWriter::new();
+        let mut writer = new_test_writer();
 
         // No stretch/shrink
         writer.add_vertical_list_elem(
@@ -1015,9 +1073,9 @@ mod tests {
 
     #[test]
     fn it_adds_basic_vertical_boxes() {
-        let mut writer = DVIFileWriter::new();
+        let mut writer = new_test_writer();
 
-        let metrics = FontMetrics::from_font(&CMR10).unwrap();
+        let metrics = test_font_metrics(&CMR10).unwrap();
 
         let hbox = TeXBox::HorizontalBox(HorizontalBox {
             height: metrics.get_height('g'),
@@ -1027,6 +1085,7 @@ mod tests {
             list: vec![HorizontalListElem::Char {
                 chr: 'g',
                 font: CMR10.clone(),
+                origin: Provenance::none(),
             }],
             glue_set_ratio: None,
         });
@@ -1108,20 +1167,20 @@ mod tests {
 
     #[test]
     fn it_adds_pages() {
-        let mut writer = DVIFileWriter::new();
+        let mut writer = new_test_writer();
 
-        let metrics = FontMetrics::from_font(&CMR10).unwrap();
+        let metrics = test_font_metrics(&CMR10).unwrap();
 
         with_parser(
             &[
-                r"\vbox{\noindent g\vskip 0pt\noindent a}%",
+                r"\vbox{\baselineskip=12pt \noindent g\vskip 0pt\noindent a}%",
                 r"\vbox{\noindent q}%",
                 r"\vbox{\noindent a}%",
             ],
             |parser| {
-                let page1 = parser.parse_box().unwrap();
-                let page2 = parser.parse_box().unwrap();
-                let page3 = parser.parse_box().unwrap();
+                let page1 = parser.parse_box().unwrap().unwrap();
+                let page2 = parser.parse_box().unwrap().unwrap();
+                let page3 = parser.parse_box().unwrap().unwrap();
 
                 if let TeXBox::VerticalBox(vbox1) = page1 {
                     writer.add_page(
@@ -1225,14 +1284,14 @@ mod tests {
 
     #[test]
     fn it_adds_basic_pre_and_post() {
-        let mut writer = DVIFileWriter::new();
+        let mut writer = new_test_writer();
 
-        let metrics = FontMetrics::from_font(&CMR10).unwrap();
+        let metrics = test_font_metrics(&CMR10).unwrap();
 
         writer.start((25400000, 473628672), 1000, b"hello, world!".to_vec());
 
         with_parser(&[r"\vbox{\noindent a}%"], |parser| {
-            let page1 = parser.parse_box().unwrap();
+            let page1 = parser.parse_box().unwrap().unwrap();
             if let TeXBox::VerticalBox(vbox1) = page1 {
                 writer.add_page(
                     &vbox1.list,
@@ -1306,7 +1365,7 @@ mod tests {
 
     #[test]
     fn it_calculates_num_pages_correctly() {
-        let mut writer = DVIFileWriter::new();
+        let mut writer = new_test_writer();
 
         writer.start((25400000, 473628672), 1000, vec![]);
 
@@ -1362,7 +1421,7 @@ mod tests {
 
     #[test]
     fn it_converts_to_a_file() {
-        let mut writer = DVIFileWriter::new();
+        let mut writer = new_test_writer();
 
         writer.start((25400000, 473628672), 1000, vec![]);
 
@@ -1408,14 +1467,17 @@ mod tests {
 
     #[test]
     fn it_places_boxes_in_boxes_correctly() {
-        let mut writer = DVIFileWriter::new();
+        let mut writer = new_test_writer();
 
-        let metrics = FontMetrics::from_font(&CMR10).unwrap();
+        let metrics = test_font_metrics(&CMR10).unwrap();
 
         with_parser(
-            &[r"\vbox{\hbox{g\vbox{\noindent b\vskip0pt\noindent c}}}%"],
+            &[
+                r"\vbox{\hbox{g\vbox{%",
+                r"    \baselineskip=12pt \noindent b\vskip0pt\noindent c}}}%",
+            ],
             |parser| {
-                let vbox = parser.parse_box().unwrap();
+                let vbox = parser.parse_box().unwrap().unwrap();
                 writer.add_box(&vbox);
             },
         );
@@ -1485,7 +1547,7 @@ mod tests {
 
     #[test]
     fn it_calculates_post_post_correctly() {
-        let mut writer = DVIFileWriter::new();
+        let mut writer = new_test_writer();
         writer.start((25400000, 473628672), 1000, vec![]);
         writer.add_page(&[], &None, [1, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
         writer.end();
@@ -1501,7 +1563,7 @@ mod tests {
 
         assert_eq!(writer.total_byte_size() % 4, 0);
 
-        let mut writer = DVIFileWriter::new();
+        let mut writer = new_test_writer();
         writer.start((25400000, 473628672), 1000, vec![]);
         writer.add_page(&[], &None, [1, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
         writer.commands.push(DVICommand::Nop);
@@ -1518,7 +1580,7 @@ mod tests {
 
         assert_eq!(writer.total_byte_size() % 4, 0);
 
-        let mut writer = DVIFileWriter::new();
+        let mut writer = new_test_writer();
         writer.start((25400000, 473628672), 1000, vec![]);
         writer.add_page(&[], &None, [1, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
         writer.commands.push(DVICommand::Nop);
@@ -1536,7 +1598,7 @@ mod tests {
 
         assert_eq!(writer.total_byte_size() % 4, 0);
 
-        let mut writer = DVIFileWriter::new();
+        let mut writer = new_test_writer();
         writer.start((25400000, 473628672), 1000, vec![]);
         writer.add_page(&[], &None, [1, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
         writer.commands.push(DVICommand::Nop);
@@ -1558,12 +1620,12 @@ mod tests {
 
     #[test]
     fn it_writes_shifted_horizontal_boxes_correctly() {
-        let mut writer = DVIFileWriter::new();
+        let mut writer = new_test_writer();
 
-        let metrics = FontMetrics::from_font(&CMR10).unwrap();
+        let metrics = test_font_metrics(&CMR10).unwrap();
 
         with_parser(&[r"\hbox{a}%"], |parser| {
-            let hbox = parser.parse_box().unwrap();
+            let hbox = parser.parse_box().unwrap().unwrap();
             writer.add_horizontal_list_elem(
                 &HorizontalListElem::Box {
                     tex_box: hbox.clone(),
@@ -1626,12 +1688,12 @@ mod tests {
 
     #[test]
     fn it_writes_shifted_vertical_elements_correctly() {
-        let mut writer = DVIFileWriter::new();
+        let mut writer = new_test_writer();
 
-        let metrics = FontMetrics::from_font(&CMR10).unwrap();
+        let metrics = test_font_metrics(&CMR10).unwrap();
 
         with_parser(&[r"\hbox{a}%"], |parser| {
-            let hbox = parser.parse_box().unwrap();
+            let hbox = parser.parse_box().unwrap().unwrap();
             writer.add_vertical_list_elem(
                 &VerticalListElem::Box {
                     tex_box: hbox.clone(),
@@ -1,4 +1,6 @@
-use crate::dimension::{Dimen, FilDimen, FilKind, SpringDimen};
+use std::fmt;
+
+use crate::dimension::{Dimen, FilDimen, FilKind, Scaled, SpringDimen};
 use crate::glue::Glue;
 use crate::list::{HorizontalListElem, VerticalListElem};
 
@@ -69,6 +71,10 @@ impl GlueSetRatio {
         }
     }
 
+    pub fn is_zero(&self) -> bool {
+        self.stretch == 0
+    }
+
     pub fn apply_to_glue(&self, glue: &Glue) -> Dimen {
         if self.stretch < 0 {
             glue.space + self.multiply_spring_dimen(&glue.shrink)
@@ -78,6 +84,31 @@ impl GlueSetRatio {
     }
 }
 
+impl fmt::Display for GlueSetRatio {
+    // Prints the ratio the way TeX does when showing boxes, like "1.5fil", or
+    // "- 0.5" when the glue is shrinking.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.stretch < 0 {
+            write!(f, "- ")?;
+        }
+
+        // Like TeX, we don't print out absurdly large ratios.
+        let max_ratio = 20000 * 65536;
+        if self.stretch.abs() > max_ratio {
+            write!(f, ">{}", Scaled(max_ratio))?;
+        } else {
+            write!(f, "{}", Scaled(self.stretch.abs()))?;
+        }
+
+        match self.kind {
+            GlueSetRatioKind::Finite => Ok(()),
+            GlueSetRatioKind::Fil => write!(f, "fil"),
+            GlueSetRatioKind::Fill => write!(f, "fill"),
+            GlueSetRatioKind::Filll => write!(f, "filll"),
+        }
+    }
+}
+
 #[derive(Clone, Debug, PartialEq)]
 pub struct HorizontalBox {
     pub height: Dimen,
@@ -101,7 +132,7 @@ impl HorizontalBox {
         self.list
             .iter()
             .flat_map(|elem| match elem {
-                HorizontalListElem::Char { chr: ch, font: _ } => vec![*ch],
+                HorizontalListElem::Char { chr: ch, .. } => vec![*ch],
                 HorizontalListElem::HSkip(_) => vec![' '],
                 HorizontalListElem::Box { tex_box, shift: _ } => {
                     tex_box.to_chars()
@@ -218,6 +249,7 @@ mod tests {
     use crate::dimension::Unit;
     use crate::font::Font;
     use crate::glue::Glue;
+    use crate::origin::Provenance;
 
     lazy_static! {
         static ref CMR10: Font = Font {
@@ -237,6 +269,7 @@ mod tests {
                 HorizontalListElem::Char {
                     chr: 'a',
                     font: CMR10.clone(),
+                    origin: Provenance::none(),
                 },
                 HorizontalListElem::HSkip(Glue::from_dimen(Dimen::zero())),
                 HorizontalListElem::Box {
@@ -249,6 +282,7 @@ mod tests {
                             HorizontalListElem::Char {
                                 chr: 'b',
                                 font: CMR10.clone(),
+                                origin: Provenance::none(),
                             },
                             HorizontalListElem::HSkip(Glue::from_dimen(
                                 Dimen::zero(),
@@ -261,6 +295,7 @@ mod tests {
                 HorizontalListElem::Char {
                     chr: 'c',
                     font: CMR10.clone(),
+                    origin: Provenance::none(),
                 },
             ],
             glue_set_ratio: None,
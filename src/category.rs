@@ -18,3 +18,28 @@ pub enum Category {
     Comment,      // 14
     Invalid,      // 15
 }
+
+impl Category {
+    /// Returns the category with the given number, like in \catcode.
+    pub fn from_number(number: u8) -> Option<Category> {
+        const CATEGORIES: [Category; 16] = [
+            Category::Escape,
+            Category::BeginGroup,
+            Category::EndGroup,
+            Category::MathShift,
+            Category::AlignmentTab,
+            Category::EndOfLine,
+            Category::Parameter,
+            Category::Superscript,
+            Category::Subscript,
+            Category::Ignored,
+            Category::Space,
+            Category::Letter,
+            Category::Other,
+            Category::Active,
+            Category::Comment,
+            Category::Invalid,
+        ];
+        CATEGORIES.get(number as usize).cloned()
+    }
+}
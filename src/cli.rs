@@ -0,0 +1,272 @@
+/// Module for parsing the command line arguments of the xymostex binary. These
+/// follow the conventions of TeX implementations like web2c's tex, so that
+/// xymostex can be used in their place.
+use std::path::{Path, PathBuf};
+
+pub const USAGE: &str = "\
+Usage: xymostex [OPTION]... [&FORMAT] [FILE | COMMANDS]
+Run XymosTeX on FILE, usually creating FILE.dvi. If no file or commands are
+given, the input is read from stdin. If the first line doesn't start with a
+backslash, it's treated as the name of a file to \\input. If &FORMAT is given,
+FORMAT.tex is read before anything else.
+
+Options:
+  --halt-on-error          stop processing at the first error
+  --interaction=STRING     set the interaction mode (STRING=batchmode/
+                           nonstopmode)
+  --jobname=STRING         set the job name, which is used to name the output
+                           files
+  --output-directory=DIR   write the output files in DIR
+  --help                   show this help and exit
+  --version                show the version and exit
+";
+
+/// How much TeX should interact with the user, like \batchmode and friends.
+/// We can't stop and wait for the user to fix errors, so only the modes which
+/// never stop are supported, and the difference between them is that nothing
+/// is printed to the terminal in batch mode.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum InteractionMode {
+    Batch,
+    NonStop,
+}
+
+impl InteractionMode {
+    fn from_name(name: &str) -> Result<Self, String> {
+        match name {
+            "batchmode" => Ok(InteractionMode::Batch),
+            "nonstopmode" => Ok(InteractionMode::NonStop),
+            "scrollmode" | "errorstopmode" => Err(format!(
+                "interaction mode `{}' isn't supported, since XymosTeX can't \
+                 stop to ask about errors (use nonstopmode or batchmode)",
+                name
+            )),
+            _ => Err(format!("invalid interaction mode `{}'", name)),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Options {
+    /// The name of the format given with &FORMAT, if any.
+    pub format: Option<String>,
+    /// The first line of input, if it was given on the command line.
+    pub first_line: Option<String>,
+    pub job_name: Option<String>,
+    pub output_directory: Option<String>,
+    pub interaction: InteractionMode,
+    pub halt_on_error: bool,
+}
+
+impl Options {
+    /// Returns the name used for the output files. Like TeX, this is the name
+    /// of the input file without its directory or extension, unless it's
+    /// given with --jobname, or "texput" if there's no input file.
+    pub fn get_job_name(&self) -> String {
+        if let Some(job_name) = &self.job_name {
+            return job_name.clone();
+        }
+
+        match &self.first_line {
+            Some(line) if !line.starts_with('\\') => {
+                let file_name = line.split_whitespace().next().unwrap_or("");
+                match Path::new(file_name).file_stem() {
+                    Some(stem) => stem.to_string_lossy().into_owned(),
+                    None => "texput".to_string(),
+                }
+            }
+            _ => "texput".to_string(),
+        }
+    }
+
+    /// Returns the lines of input that were given on the command line, with a
+    /// file name turned into an \input like TeX does.
+    pub fn get_first_line(&self) -> Option<String> {
+        self.first_line.as_ref().map(|line| {
+            if line.starts_with('\\') {
+                line.clone()
+            } else {
+                format!("\\input {}", line)
+            }
+        })
+    }
+
+    /// Returns the path that an output file with the given extension should
+    /// be written to.
+    pub fn get_output_path(&self, extension: &str) -> PathBuf {
+        let file_name = format!("{}.{}", self.get_job_name(), extension);
+        match &self.output_directory {
+            Some(directory) => Path::new(directory).join(file_name),
+            None => PathBuf::from(file_name),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Command {
+    Run(Options),
+    Help,
+    Version,
+}
+
+/// Parses the command line arguments (not including the name of the
+/// program). Like web2c's tex, options can start with either - or --, and
+/// their values can either be given after an = or as the next argument.
+pub fn parse_args<I>(args: I) -> Result<Command, String>
+where
+    I: IntoIterator<Item = String>,
+{
+    let mut options = Options {
+        format: None,
+        first_line: None,
+        job_name: None,
+        output_directory: None,
+        interaction: InteractionMode::NonStop,
+        halt_on_error: false,
+    };
+
+    let mut args = args.into_iter();
+    let mut positional = Vec::new();
+
+    while let Some(arg) = args.next() {
+        // Once we've seen the start of the input, everything after it is
+        // part of the first line, even if it looks like an option.
+        if !positional.is_empty() || !arg.starts_with('-') || arg == "-" {
+            positional.push(arg);
+            continue;
+        }
+
+        let option = arg.trim_start_matches('-');
+        let (name, inline_value) = match option.find('=') {
+            Some(index) => (&option[..index], Some(&option[index + 1..])),
+            None => (option, None),
+        };
+
+        let mut get_value = || match inline_value {
+            Some(value) => Ok(value.to_string()),
+            None => args
+                .next()
+                .ok_or_else(|| format!("option `{}' requires a value", arg)),
+        };
+
+        match name {
+            "help" => return Ok(Command::Help),
+            "version" => return Ok(Command::Version),
+            "halt-on-error" => options.halt_on_error = true,
+            "jobname" => options.job_name = Some(get_value()?),
+            "output-directory" => options.output_directory = Some(get_value()?),
+            "interaction" => {
+                options.interaction =
+                    InteractionMode::from_name(&get_value()?)?;
+            }
+            _ => return Err(format!("unrecognized option `{}'", arg)),
+        }
+    }
+
+    let mut positional = positional.into_iter().peekable();
+    if let Some(format) =
+        positional.peek().and_then(|arg| arg.strip_prefix('&'))
+    {
+        options.format = Some(format.to_string());
+        positional.next();
+    }
+
+    let rest: Vec<String> = positional.collect();
+    if !rest.is_empty() {
+        options.first_line = Some(rest.join(" "));
+    }
+
+    Ok(Command::Run(options))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Command, String> {
+        parse_args(args.iter().map(|arg| arg.to_string()))
+    }
+
+    fn parse_options(args: &[&str]) -> Options {
+        match parse(args) {
+            Ok(Command::Run(options)) => options,
+            result => panic!("Expected options, got {:?}", result),
+        }
+    }
+
+    #[test]
+    fn it_parses_no_arguments() {
+        let options = parse_options(&[]);
+        assert_eq!(options.first_line, None);
+        assert_eq!(options.get_first_line(), None);
+        assert_eq!(options.interaction, InteractionMode::NonStop);
+        assert_eq!(options.get_job_name(), "texput");
+        assert_eq!(options.get_output_path("dvi"), PathBuf::from("texput.dvi"));
+    }
+
+    #[test]
+    fn it_parses_input_files() {
+        let options = parse_options(&["dir/story.tex"]);
+        assert_eq!(
+            options.get_first_line(),
+            Some("\\input dir/story.tex".to_string())
+        );
+        assert_eq!(options.get_job_name(), "story");
+    }
+
+    #[test]
+    fn it_parses_formats_and_commands() {
+        let options = parse_options(&["&plain", "\\hbox{a}", "\\end"]);
+        assert_eq!(options.format, Some("plain".to_string()));
+        assert_eq!(
+            options.get_first_line(),
+            Some("\\hbox{a} \\end".to_string())
+        );
+        assert_eq!(options.get_job_name(), "texput");
+    }
+
+    #[test]
+    fn it_parses_options() {
+        let options = parse_options(&[
+            "-jobname=out",
+            "--output-directory",
+            "build",
+            "--interaction=batchmode",
+            "-halt-on-error",
+            "story",
+            "--not-an-option",
+        ]);
+        assert_eq!(options.get_job_name(), "out");
+        assert_eq!(options.interaction, InteractionMode::Batch);
+        assert!(options.halt_on_error);
+        assert_eq!(
+            options.get_output_path("log"),
+            Path::new("build").join("out.log")
+        );
+        assert_eq!(
+            options.get_first_line(),
+            Some("\\input story --not-an-option".to_string())
+        );
+    }
+
+    #[test]
+    fn it_parses_help_and_version() {
+        assert_eq!(parse(&["--help", "story"]), Ok(Command::Help));
+        assert_eq!(parse(&["-version"]), Ok(Command::Version));
+    }
+
+    #[test]
+    fn it_rejects_invalid_options() {
+        assert!(parse(&["--unknown"]).is_err());
+        assert!(parse(&["--interaction=loudmode"]).is_err());
+        assert_eq!(
+            parse(&["--interaction=scrollmode"]),
+            Err("interaction mode `scrollmode' isn't supported, since \
+                 XymosTeX can't stop to ask about errors (use nonstopmode or \
+                 batchmode)"
+                .to_string())
+        );
+        assert!(parse(&["--interaction=errorstopmode"]).is_err());
+        assert!(parse(&["--jobname"]).is_err());
+    }
+}
@@ -0,0 +1,490 @@
+use std::cell::RefCell;
+use std::env;
+use std::fs;
+use std::io;
+use std::io::prelude::*;
+use std::process;
+use std::rc::Rc;
+
+use xymostex::box_display::BoxFormatter;
+use xymostex::debugger::{
+    DebugEvent, DebugView, Debugger, PartialList, PartialListContents,
+};
+use xymostex::font::Font;
+use xymostex::parameter::{
+    DimenParameter, GlueParameter, IntegerParameter, MuGlueParameter,
+};
+use xymostex::parser::Parser;
+use xymostex::state::TeXState;
+use xymostex::token::{format_token_list, Token};
+use xymostex::transcript::format_context_lines;
+
+const HELP: &str = "\
+Commands:
+  step, s              stop at the next expansion or command
+  next, n              like step, but skip over the insides of expansions
+  continue, c          run until the next breakpoint
+  break, b <\\cs|line>  stop whenever \\cs is expanded or executed, or when
+                       the given line is reached
+  delete, d [...]      remove a breakpoint, or all of them
+  where, w             show where we are in the input
+  tokens, t            show the tokens that will be read next
+  mode, m              show the current mode
+  group, g             show how many groups we're inside of
+  list, l              show the lists that are being built
+  count <n>            show the value of \\count<n>
+  box <n>              show the contents of \\box<n>
+  param <name>         show the value of a parameter
+  quit, q              stop debugging
+An empty line repeats step.";
+
+// How far the parser should get before we stop again.
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum RunMode {
+    // Stop at the very next expansion or command.
+    Step,
+    // Stop at the next expansion or command which isn't inside of any
+    // expansions deeper than the given depth.
+    Next(usize),
+    // Only stop at breakpoints.
+    Continue,
+}
+
+#[derive(Debug, PartialEq)]
+enum Breakpoint {
+    ControlSequence(String),
+    Line(usize),
+}
+
+impl Breakpoint {
+    fn parse(text: &str) -> Option<Breakpoint> {
+        if let Some(name) = text.strip_prefix('\\') {
+            if name.is_empty() {
+                None
+            } else {
+                Some(Breakpoint::ControlSequence(name.to_string()))
+            }
+        } else {
+            text.parse().ok().map(Breakpoint::Line)
+        }
+    }
+}
+
+impl std::fmt::Display for Breakpoint {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Breakpoint::ControlSequence(name) => write!(f, "\\{}", name),
+            Breakpoint::Line(line) => write!(f, "line {}", line),
+        }
+    }
+}
+
+// Creates a formatter that shows everything in a list along with where each
+// character came from, and refers to fonts by the control sequence that most
+// recently loaded them like \showbox does.
+fn box_formatter(
+    state: &TeXState,
+) -> BoxFormatter<impl Fn(&Font) -> String + '_> {
+    BoxFormatter::new(i32::max_value(), i32::max_value(), move |font: &Font| {
+        match state.get_font_identifier(font) {
+            Some(Token::ControlSequence(name)) => format!("\\{}", name),
+            Some(Token::Char(ch, _)) => ch.to_string(),
+            None => format!("\\{}", font.font_name),
+        }
+    })
+    .with_origins()
+}
+
+/// A debugger which stops the parser and reads commands from `input` to
+/// decide what to look at and how far to go before stopping again.
+struct InteractiveDebugger<R: BufRead, W: Write> {
+    input: R,
+    output: W,
+    run_mode: RunMode,
+    breakpoints: Vec<Breakpoint>,
+    // The line we were on the last time the parser stopped, so that line
+    // breakpoints only stop the parser once per line.
+    last_line: Option<usize>,
+}
+
+impl<R: BufRead, W: Write> InteractiveDebugger<R, W> {
+    fn new(input: R, output: W) -> Self {
+        InteractiveDebugger {
+            input,
+            output,
+            run_mode: RunMode::Step,
+            breakpoints: Vec::new(),
+            last_line: None,
+        }
+    }
+
+    fn should_stop(&mut self, event: &DebugEvent, view: &DebugView) -> bool {
+        let line = view.line.as_ref().map(|(line, _, _)| *line);
+        let is_new_line = line != self.last_line;
+        self.last_line = line;
+
+        let at_breakpoint = self.breakpoints.iter().any(|breakpoint| {
+            match (breakpoint, event.get_token()) {
+                (
+                    Breakpoint::ControlSequence(name),
+                    Token::ControlSequence(token_name),
+                ) => name == token_name,
+                (Breakpoint::Line(breakpoint_line), _) => {
+                    is_new_line && Some(*breakpoint_line) == line
+                }
+                _ => false,
+            }
+        });
+
+        match self.run_mode {
+            RunMode::Step => true,
+            RunMode::Next(depth) => view.depth <= depth || at_breakpoint,
+            RunMode::Continue => at_breakpoint,
+        }
+    }
+
+    fn print_event(&mut self, event: &DebugEvent, view: &DebugView) {
+        let description = match event {
+            DebugEvent::MacroExpansion { token, replacement } => format!(
+                "Expanding {} into {}",
+                token.to_string().trim_end(),
+                format_token_list(replacement)
+            ),
+            DebugEvent::Command { token, mode } => format!(
+                "Executing {} in {}",
+                token.to_string().trim_end(),
+                mode
+            ),
+        };
+        let _ = writeln!(self.output, "{} (depth {})", description, view.depth);
+        self.print_where(view);
+    }
+
+    fn print_where(&mut self, view: &DebugView) {
+        match &view.line {
+            Some((line, read, unread)) => {
+                let (first, second) =
+                    format_context_lines(&format!("l.{} ", line), read, unread);
+                let _ = writeln!(self.output, "{}\n{}", first, second);
+            }
+            None => {
+                let _ = writeln!(self.output, "At the end of the input");
+            }
+        }
+    }
+
+    fn print_lists(&mut self, view: &DebugView) {
+        for list in view.lists {
+            let PartialList { mode, contents } = list;
+            let formatted = match contents {
+                PartialListContents::Vertical(list) => {
+                    box_formatter(view.state).format_vertical_list(list)
+                }
+                PartialListContents::Horizontal(list) => {
+                    box_formatter(view.state).format_horizontal_list(list)
+                }
+                PartialListContents::Math => {
+                    "(math lists can't be shown yet)".to_string()
+                }
+            };
+            let _ = writeln!(self.output, "### {}", mode);
+            if !formatted.is_empty() {
+                let _ = writeln!(self.output, "{}", formatted);
+            }
+        }
+    }
+
+    // Runs a single command, and returns true if the parser should continue.
+    fn run_command(&mut self, command: &str, view: &DebugView) -> bool {
+        let mut words = command.split_whitespace();
+        let name = words.next().unwrap_or("step");
+        let argument = words.next();
+
+        let response = match (name, argument) {
+            ("step", _) | ("s", _) => {
+                self.run_mode = RunMode::Step;
+                return true;
+            }
+            ("next", _) | ("n", _) => {
+                self.run_mode = RunMode::Next(view.depth);
+                return true;
+            }
+            ("continue", _) | ("c", _) => {
+                self.run_mode = RunMode::Continue;
+                return true;
+            }
+            ("quit", _) | ("q", _) => process::exit(0),
+            ("break", Some(text)) | ("b", Some(text)) => {
+                match Breakpoint::parse(text) {
+                    Some(breakpoint) => {
+                        let response =
+                            format!("Breakpoint set at {}", breakpoint);
+                        self.breakpoints.push(breakpoint);
+                        response
+                    }
+                    None => format!("Invalid breakpoint: {}", text),
+                }
+            }
+            ("delete", None) | ("d", None) => {
+                self.breakpoints.clear();
+                "Deleted all breakpoints".to_string()
+            }
+            ("delete", Some(text)) | ("d", Some(text)) => {
+                let breakpoint = Breakpoint::parse(text);
+                let length = self.breakpoints.len();
+                self.breakpoints.retain(|b| Some(b) != breakpoint.as_ref());
+                if self.breakpoints.len() < length {
+                    format!("Deleted breakpoint at {}", text)
+                } else {
+                    format!("No breakpoint at {}", text)
+                }
+            }
+            ("where", _) | ("w", _) => {
+                self.print_where(view);
+                return false;
+            }
+            ("tokens", _) | ("t", _) => {
+                if view.upcoming_tokens.is_empty() {
+                    "No upcoming tokens".to_string()
+                } else {
+                    format_token_list(&view.upcoming_tokens)
+                }
+            }
+            ("mode", _) | ("m", _) => match view.lists.last() {
+                Some(list) => list.mode.to_string(),
+                None => "No list is being built".to_string(),
+            },
+            ("group", _) | ("g", _) => {
+                format!("Inside of {} group(s)", view.state.get_group_level())
+            }
+            ("list", _) | ("l", _) => {
+                self.print_lists(view);
+                return false;
+            }
+            ("count", Some(text)) => match text.parse::<u8>() {
+                Ok(index) => {
+                    format!("\\count{}={}", index, view.state.get_count(index))
+                }
+                Err(_) => format!("Invalid register: {}", text),
+            },
+            ("box", Some(text)) => match text.parse::<u8>() {
+                Ok(index) => match view.state.get_box_copy(index) {
+                    Some(tex_box) => format!(
+                        "\\box{}=\n{}",
+                        index,
+                        box_formatter(view.state).format_box(&tex_box)
+                    ),
+                    None => format!("\\box{}=void", index),
+                },
+                Err(_) => format!("Invalid register: {}", text),
+            },
+            ("param", Some(text)) => {
+                let name = text.trim_start_matches('\\');
+                let value = if let Some(parameter) =
+                    IntegerParameter::from_primitive(name)
+                {
+                    Some(
+                        view.state.get_integer_parameter(parameter).to_string(),
+                    )
+                } else if let Some(parameter) =
+                    DimenParameter::from_primitive(name)
+                {
+                    Some(view.state.get_dimen_parameter(parameter).to_string())
+                } else if let Some(parameter) =
+                    GlueParameter::from_primitive(name)
+                {
+                    Some(view.state.get_glue_parameter(parameter).to_string())
+                } else {
+                    MuGlueParameter::from_primitive(name).map(|parameter| {
+                        view.state.get_mu_glue_parameter(parameter).to_string()
+                    })
+                };
+                match value {
+                    Some(value) => format!("\\{}={}", name, value),
+                    None => format!("Unknown parameter: {}", text),
+                }
+            }
+            ("help", _) | ("h", _) => HELP.to_string(),
+            _ => format!(
+                "Unknown command: {}. Type `help' for a list of commands.",
+                command.trim()
+            ),
+        };
+
+        let _ = writeln!(self.output, "{}", response);
+        false
+    }
+}
+
+impl<R: BufRead, W: Write> Debugger for InteractiveDebugger<R, W> {
+    fn step(&mut self, event: &DebugEvent, view: &DebugView) {
+        if !self.should_stop(event, view) {
+            return;
+        }
+
+        self.print_event(event, view);
+        loop {
+            let _ = write!(self.output, "(xdb) ");
+            let _ = self.output.flush();
+
+            let mut command = String::new();
+            match self.input.read_line(&mut command) {
+                Ok(0) | Err(_) => {
+                    // Once we run out of commands, we just let the parser
+                    // finish.
+                    self.run_mode = RunMode::Continue;
+                    self.breakpoints.clear();
+                    return;
+                }
+                Ok(_) => {
+                    if self.run_command(&command, view) {
+                        return;
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn main() -> io::Result<()> {
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.len() != 1 {
+        eprintln!("Usage: xymostex-debug <file.tex>");
+        process::exit(1);
+    }
+
+    let source = fs::read_to_string(&args[0])?;
+    let lines: Vec<&str> = source.lines().collect();
+
+    let state = TeXState::new();
+    let mut parser = Parser::new(&lines[..], &state);
+
+    println!("Type `help' for a list of commands.");
+    let debugger = InteractiveDebugger::new(io::stdin().lock(), io::stdout());
+    parser.set_debugger(Rc::new(RefCell::new(debugger)));
+
+    match parser.parse_outer_vertical_box() {
+        Ok(_) => println!("Reached the end of the input."),
+        Err(_) => println!("Stopped because of a fatal error."),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type TestDebugger = InteractiveDebugger<io::Cursor<Vec<u8>>, Vec<u8>>;
+
+    fn debug_box(lines: &[&str], commands: &[&str]) -> String {
+        let input = format!("{}\n", commands.join("\n")).into_bytes();
+        let debugger: Rc<RefCell<TestDebugger>> = Rc::new(RefCell::new(
+            InteractiveDebugger::new(io::Cursor::new(input), Vec::new()),
+        ));
+
+        let state = TeXState::new();
+        let mut parser = Parser::new(lines, &state);
+        parser.set_terminal(Rc::new(RefCell::new(io::sink())));
+        parser.parse_assignment(None).unwrap();
+        parser.set_debugger(debugger.clone());
+        parser.parse_box().unwrap();
+
+        let output = debugger.borrow().output.clone();
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn it_steps_through_expansions_and_commands() {
+        let output = debug_box(
+            &[r"\def\a{\hskip 1pt}%", r"\hbox{\a}%"],
+            &["step", "tokens", "step", "mode"],
+        );
+
+        assert_eq!(
+            output,
+            [
+                r"Expanding \a into \hskip 1pt (depth 0)",
+                r"l.2 \hbox{\a",
+                r"            }%",
+                r"(xdb) Executing \hskip in restricted horizontal mode (depth 1)",
+                r"l.2 \hbox{\a",
+                r"            }%",
+                r"(xdb) \hskip 1pt",
+                r"(xdb) Executing } in restricted horizontal mode (depth 0)",
+                r"l.2 \hbox{\a}",
+                r"             %",
+                r"(xdb) restricted horizontal mode",
+                r"(xdb) ",
+            ]
+            .join("\n")
+        );
+    }
+
+    #[test]
+    fn it_steps_over_expansions() {
+        let output = debug_box(
+            &[r"\def\a{\hskip 1pt}%", r"\hbox{\a}%"],
+            &["next", "list"],
+        );
+
+        assert_eq!(
+            output,
+            [
+                r"Expanding \a into \hskip 1pt (depth 0)",
+                r"l.2 \hbox{\a",
+                r"            }%",
+                r"(xdb) Executing } in restricted horizontal mode (depth 0)",
+                r"l.2 \hbox{\a}",
+                r"             %",
+                r"(xdb) ### restricted horizontal mode",
+                r"\glue 1.0",
+                r"(xdb) ",
+            ]
+            .join("\n")
+        );
+    }
+
+    #[test]
+    fn it_stops_at_breakpoints() {
+        let output = debug_box(
+            &[r"\def\a{\hskip 1pt}%", r"\hbox{\hskip 2pt\a}%"],
+            &["break \\a", "continue", "delete \\a", "continue"],
+        );
+
+        assert_eq!(
+            output,
+            [
+                r"Executing \hskip in restricted horizontal mode (depth 0)",
+                r"l.2 \hbox{\hskip",
+                r"                 2pt\a}%",
+                r"(xdb) Breakpoint set at \a",
+                r"(xdb) Expanding \a into \hskip 1pt (depth 0)",
+                r"l.2 \hbox{\hskip 2pt\a",
+                r"                      }%",
+                r"(xdb) Deleted breakpoint at \a",
+                r"(xdb) ",
+            ]
+            .join("\n")
+        );
+    }
+
+    #[test]
+    fn it_shows_the_replacement_as_the_upcoming_tokens_of_expansions() {
+        let output = debug_box(
+            &[r"\def\a{\hskip 1pt}%", r"\hbox{\a}%"],
+            &["tokens", "continue"],
+        );
+
+        assert_eq!(
+            output,
+            [
+                r"Expanding \a into \hskip 1pt (depth 0)",
+                r"l.2 \hbox{\a",
+                r"            }%",
+                r"(xdb) \hskip 1pt",
+                r"(xdb) ",
+            ]
+            .join("\n")
+        );
+    }
+}
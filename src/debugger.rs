@@ -0,0 +1,65 @@
+use crate::list::{HorizontalListElem, VerticalListElem};
+use crate::state::TeXState;
+use crate::token::Token;
+
+/// What the parser is about to do when it stops to let a debugger look at it.
+#[derive(Debug, PartialEq)]
+pub enum DebugEvent<'b> {
+    /// A macro is about to be replaced by its replacement text.
+    MacroExpansion {
+        token: &'b Token,
+        replacement: &'b [Token],
+    },
+    /// A token is about to be executed as a command in the given mode.
+    Command {
+        token: &'b Token,
+        mode: &'static str,
+    },
+}
+
+impl<'b> DebugEvent<'b> {
+    pub fn get_token(&self) -> &Token {
+        match self {
+            DebugEvent::MacroExpansion { token, .. } => token,
+            DebugEvent::Command { token, .. } => token,
+        }
+    }
+}
+
+/// The contents of a list which is in the middle of being built.
+#[derive(Debug, PartialEq, Clone)]
+pub enum PartialListContents {
+    Vertical(Vec<VerticalListElem>),
+    Horizontal(Vec<HorizontalListElem>),
+    // Math lists can't be copied, so we only keep track of their mode.
+    Math,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct PartialList {
+    pub mode: &'static str,
+    pub contents: PartialListContents,
+}
+
+/// Everything that a debugger can look at when the parser stops.
+pub struct DebugView<'b> {
+    /// The number of macro expansions that are currently being read.
+    pub depth: usize,
+    /// The number of the line that is being read, along with the parts of it
+    /// that have and haven't been read yet.
+    pub line: Option<(usize, String, String)>,
+    /// Tokens which will be read before anything else from the input, in the
+    /// order they will be read.
+    pub upcoming_tokens: Vec<Token>,
+    /// The lists that are being built, from the outermost to the innermost.
+    pub lists: &'b [PartialList],
+    pub state: &'b TeXState,
+}
+
+/// A hook which is called by the parser every time it expands a macro or
+/// executes a command. Unlike an ExpansionTracer, this gets to look at the
+/// whole parser, and the parser waits for it to return before continuing, so
+/// it can be used to step through the input interactively.
+pub trait Debugger {
+    fn step(&mut self, event: &DebugEvent, view: &DebugView);
+}
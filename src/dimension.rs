@@ -1,9 +1,29 @@
 use std::cmp::{Ordering, PartialOrd};
+use std::fmt;
 use std::ops::{Add, Div, Mul, Sub};
 
 static DIMEN_MAX: i32 = (1 << 30) - 1;
 static DIMEN_MIN: i32 = 1 - (1 << 30);
 
+// Keeps the result of checked arithmetic on a number of scaled points only if
+// it's small enough to be a dimension.
+fn within_dimen_range(value: Option<i32>) -> Option<i32> {
+    value.filter(|value| (DIMEN_MIN..=DIMEN_MAX).contains(value))
+}
+
+// Truncates a number of scaled points which was computed with floats, and
+// keeps it only if the result is small enough to be a dimension. This checks
+// the truncated value, since something like 16383.99999pt is just under the
+// limit once it's truncated to a whole number of scaled points.
+fn truncate_within_dimen_range(value: f64) -> Option<i32> {
+    let value = value.trunc();
+    if (DIMEN_MIN as f64) <= value && value <= (DIMEN_MAX as f64) {
+        Some(value as i32)
+    } else {
+        None
+    }
+}
+
 #[derive(Debug, Clone, Copy)]
 pub enum Unit {
     Point,
@@ -57,6 +77,18 @@ impl Dimen {
         Dimen((num * scale.0 / scale.1) as i32).validate()
     }
 
+    /// Like from_unit(), but returns None instead of panicking when the
+    /// result is larger than TeX allows.
+    pub fn checked_from_unit(num: f64, from_unit: Unit) -> Option<Dimen> {
+        let scale = get_scale(from_unit);
+        truncate_within_dimen_range(num * scale.0 / scale.1).map(Dimen)
+    }
+
+    /// The largest dimen that TeX allows, which plain TeX calls \maxdimen.
+    pub fn max_value() -> Dimen {
+        Dimen(DIMEN_MAX)
+    }
+
     pub fn from_scaled_points(num: i32) -> Dimen {
         Dimen(num).validate()
     }
@@ -74,6 +106,23 @@ impl Dimen {
         self.0
     }
 
+    /// Adds two dimens, or returns None if the result is too large, like
+    /// TeX's \advance.
+    pub fn checked_add(self, other: Dimen) -> Option<Dimen> {
+        within_dimen_range(self.0.checked_add(other.0)).map(Dimen)
+    }
+
+    /// Multiplies the dimen by an integer, or returns None if the result is
+    /// too large, like TeX's \multiply.
+    pub fn checked_mul(self, other: i32) -> Option<Dimen> {
+        within_dimen_range(self.0.checked_mul(other)).map(Dimen)
+    }
+
+    /// Divides the dimen by an integer, or returns None when dividing by zero.
+    pub fn checked_div(self, other: i32) -> Option<Dimen> {
+        self.0.checked_div(other).map(Dimen)
+    }
+
     pub fn abs(&self) -> Dimen {
         if *self < Dimen::zero() {
             *self * -1
@@ -83,6 +132,54 @@ impl Dimen {
     }
 }
 
+// Prints a number of scaled points as a decimal number of points the way TeX
+// does, using the fewest decimal digits which still convert back to the same
+// number of scaled points (so 65536sp is "1.0" and 1sp is "0.00002").
+fn write_scaled(f: &mut fmt::Formatter, scaled: i32) -> fmt::Result {
+    let unity = 65536;
+
+    let mut value = scaled;
+    if value < 0 {
+        write!(f, "-")?;
+        value = -value;
+    }
+    write!(f, "{}.", value / unity)?;
+
+    let mut rest = 10 * (value % unity) + 5;
+    let mut delta = 10;
+    loop {
+        if delta > unity {
+            // Round the last digit
+            rest += 0x8000 - 50000;
+        }
+        write!(f, "{}", rest / unity)?;
+        rest = 10 * (rest % unity);
+        delta *= 10;
+        if rest <= delta {
+            break;
+        }
+    }
+
+    Ok(())
+}
+
+/// Wraps a number of scaled points so that it can be printed as a decimal
+/// number without a unit, the way TeX shows box dimensions and glue ratios.
+pub struct Scaled(pub i32);
+
+impl fmt::Display for Scaled {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write_scaled(f, self.0)
+    }
+}
+
+impl fmt::Display for Dimen {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write_scaled(f, self.0)?;
+        write!(f, "pt")
+    }
+}
+
 impl PartialOrd for Dimen {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
         Some(self.0.cmp(&other.0))
@@ -166,6 +263,28 @@ impl FilDimen {
     pub fn new(kind: FilKind, value: f64) -> Self {
         FilDimen(kind, (value * 65536.0) as i32)
     }
+
+    /// Like adding, but returns None if the result is too large. Only
+    /// amounts of the same kind of infinity are actually added together.
+    pub fn checked_add(self, other: FilDimen) -> Option<FilDimen> {
+        if self.0 == other.0 {
+            within_dimen_range(self.1.checked_add(other.1))
+                .map(|value| FilDimen(self.0, value))
+        } else {
+            Some(self + other)
+        }
+    }
+
+    pub fn checked_mul(self, other: i32) -> Option<FilDimen> {
+        within_dimen_range(self.1.checked_mul(other))
+            .map(|value| FilDimen(self.0, value))
+    }
+
+    pub fn checked_div(self, other: i32) -> Option<FilDimen> {
+        self.1
+            .checked_div(other)
+            .map(|value| FilDimen(self.0, value))
+    }
 }
 
 impl Add for FilDimen {
@@ -196,6 +315,17 @@ impl Add for FilDimen {
     }
 }
 
+impl fmt::Display for FilDimen {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let suffix = match self.0 {
+            FilKind::Fil => "fil",
+            FilKind::Fill => "fill",
+            FilKind::Filll => "filll",
+        };
+        write!(f, "{}{}", Scaled(self.1), suffix)
+    }
+}
+
 impl Mul<i32> for FilDimen {
     type Output = FilDimen;
 
@@ -243,6 +373,47 @@ impl Mul<i32> for SpringDimen {
     }
 }
 
+impl SpringDimen {
+    // The checked versions of the arithmetic, which return None when the
+    // result is too large. These are used by \advance and friends.
+
+    pub fn checked_add(self, other: SpringDimen) -> Option<SpringDimen> {
+        match (self, other) {
+            (SpringDimen::Dimen(a), SpringDimen::Dimen(b)) => {
+                a.checked_add(b).map(SpringDimen::Dimen)
+            }
+            (SpringDimen::FilDimen(a), SpringDimen::FilDimen(b)) => {
+                a.checked_add(b).map(SpringDimen::FilDimen)
+            }
+            // Infinite amounts always win over finite ones, so there's
+            // nothing to overflow.
+            (a, b) => Some(a + b),
+        }
+    }
+
+    pub fn checked_mul(self, other: i32) -> Option<SpringDimen> {
+        match self {
+            SpringDimen::Dimen(dimen) => {
+                dimen.checked_mul(other).map(SpringDimen::Dimen)
+            }
+            SpringDimen::FilDimen(fil) => {
+                fil.checked_mul(other).map(SpringDimen::FilDimen)
+            }
+        }
+    }
+
+    pub fn checked_div(self, other: i32) -> Option<SpringDimen> {
+        match self {
+            SpringDimen::Dimen(dimen) => {
+                dimen.checked_div(other).map(SpringDimen::Dimen)
+            }
+            SpringDimen::FilDimen(fil) => {
+                fil.checked_div(other).map(SpringDimen::FilDimen)
+            }
+        }
+    }
+}
+
 /// Represents a math dimension in terms of a number of 1/65536 of an mu. These
 ///  are converted to em in math modes by dividing by 18.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -257,6 +428,36 @@ impl MuDimen {
         MuDimen((num * 65536.0) as i32)
     }
 
+    /// Like new(), but returns None when the result is larger than TeX
+    /// allows for a dimen.
+    pub fn checked_new(num: f64) -> Option<MuDimen> {
+        truncate_within_dimen_range(num * 65536.0).map(MuDimen)
+    }
+
+    pub fn max_value() -> MuDimen {
+        MuDimen(DIMEN_MAX)
+    }
+
+    // Like the methods on Dimen, these return None when the result is too
+    // large.
+
+    pub fn checked_add(self, other: MuDimen) -> Option<MuDimen> {
+        within_dimen_range(self.0.checked_add(other.0)).map(MuDimen)
+    }
+
+    pub fn checked_mul(self, other: i32) -> Option<MuDimen> {
+        within_dimen_range(self.0.checked_mul(other)).map(MuDimen)
+    }
+
+    pub fn checked_div(self, other: i32) -> Option<MuDimen> {
+        self.0.checked_div(other).map(MuDimen)
+    }
+
+    // The number of 1/65536 of an mu in the MuDimen.
+    pub fn as_scaled_mu(&self) -> i32 {
+        self.0
+    }
+
     /// Given the value of the quad dimension from a given font, converts the
     /// MuDimen into a plain Dimen
     pub fn to_dimen(&self, quad: Dimen) -> Dimen {
@@ -268,6 +469,112 @@ impl MuDimen {
     }
 }
 
+impl fmt::Display for MuDimen {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write_scaled(f, self.0)?;
+        write!(f, "mu")
+    }
+}
+
+// This is only used to apply signs, which can't make a MuDimen too large.
+impl Mul<i32> for MuDimen {
+    type Output = MuDimen;
+
+    fn mul(self, other: i32) -> MuDimen {
+        MuDimen(self.0 * other)
+    }
+}
+
+/// The stretch or shrink of math glue, which is like a SpringDimen except that
+/// finite amounts are measured in mu.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum MuSpringDimen {
+    MuDimen(MuDimen),
+    FilDimen(FilDimen),
+}
+
+impl MuSpringDimen {
+    pub fn zero() -> MuSpringDimen {
+        MuSpringDimen::MuDimen(MuDimen::zero())
+    }
+
+    /// Converts the finite part into a Dimen, like MuDimen::to_dimen().
+    /// Infinite amounts don't depend on the font, so they stay the same.
+    pub fn to_spring_dimen(&self, quad: Dimen) -> SpringDimen {
+        match self {
+            MuSpringDimen::MuDimen(mu_dimen) => {
+                SpringDimen::Dimen(mu_dimen.to_dimen(quad))
+            }
+            MuSpringDimen::FilDimen(fil) => SpringDimen::FilDimen(fil.clone()),
+        }
+    }
+
+    // Like the methods on SpringDimen, these return None when the result is
+    // too large.
+
+    pub fn checked_add(self, other: MuSpringDimen) -> Option<MuSpringDimen> {
+        match (self, other) {
+            (MuSpringDimen::MuDimen(a), MuSpringDimen::MuDimen(b)) => {
+                a.checked_add(b).map(MuSpringDimen::MuDimen)
+            }
+            (MuSpringDimen::FilDimen(a), MuSpringDimen::FilDimen(b)) => {
+                a.checked_add(b).map(MuSpringDimen::FilDimen)
+            }
+            // Infinite amounts always win over finite ones.
+            (MuSpringDimen::FilDimen(fil), _)
+            | (_, MuSpringDimen::FilDimen(fil)) => {
+                Some(MuSpringDimen::FilDimen(fil))
+            }
+        }
+    }
+
+    pub fn checked_mul(self, other: i32) -> Option<MuSpringDimen> {
+        match self {
+            MuSpringDimen::MuDimen(mu_dimen) => {
+                mu_dimen.checked_mul(other).map(MuSpringDimen::MuDimen)
+            }
+            MuSpringDimen::FilDimen(fil) => {
+                fil.checked_mul(other).map(MuSpringDimen::FilDimen)
+            }
+        }
+    }
+
+    pub fn checked_div(self, other: i32) -> Option<MuSpringDimen> {
+        match self {
+            MuSpringDimen::MuDimen(mu_dimen) => {
+                mu_dimen.checked_div(other).map(MuSpringDimen::MuDimen)
+            }
+            MuSpringDimen::FilDimen(fil) => {
+                fil.checked_div(other).map(MuSpringDimen::FilDimen)
+            }
+        }
+    }
+}
+
+impl fmt::Display for MuSpringDimen {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MuSpringDimen::MuDimen(mu_dimen) => write!(f, "{}", mu_dimen),
+            MuSpringDimen::FilDimen(fil) => write!(f, "{}", fil),
+        }
+    }
+}
+
+impl Mul<i32> for MuSpringDimen {
+    type Output = MuSpringDimen;
+
+    fn mul(self, other: i32) -> MuSpringDimen {
+        match self {
+            MuSpringDimen::MuDimen(mu_dimen) => {
+                MuSpringDimen::MuDimen(mu_dimen * other)
+            }
+            MuSpringDimen::FilDimen(fil) => {
+                MuSpringDimen::FilDimen(fil * other)
+            }
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -328,6 +635,20 @@ mod tests {
         assert_close(Dimen(1000000000).to_unit(Unit::Point), 15258.78906);
     }
 
+    #[test]
+    fn it_prints_dimens_like_tex() {
+        assert_eq!(Dimen::zero().to_string(), "0.0pt");
+        assert_eq!(Dimen::from_unit(12.5, Unit::Point).to_string(), "12.5pt");
+        assert_eq!(
+            Dimen::from_unit(-1.0, Unit::Inch).to_string(),
+            "-72.26999pt"
+        );
+        assert_eq!(Dimen(1).to_string(), "0.00002pt");
+        assert_eq!(Dimen(218453).to_string(), "3.33333pt");
+        assert_eq!(Scaled(98304).to_string(), "1.5");
+        assert_eq!(FilDimen(FilKind::Fill, 65536).to_string(), "1.0fill");
+    }
+
     #[test]
     fn it_supports_arithmetic() {
         assert_eq!(Dimen(1234) + Dimen(2345), Dimen(3579));
@@ -1,10 +1,12 @@
 use std::collections::{HashMap, HashSet};
 use std::iter::Peekable;
+use std::rc::Rc;
 
 use super::file::{DVICommand, DVIFile};
 use crate::dimension::Dimen;
 use crate::font::Font;
 use crate::font_metrics::FontMetrics;
+use crate::font_provider::{default_font_provider, FontProvider};
 
 #[derive(Debug, Hash, PartialEq, Eq)]
 pub enum DVIOutputElement {
@@ -35,12 +37,16 @@ struct DVIState {
 
     // The stack of state values
     stack: Vec<DVIStateStack>,
+
+    // Where the metrics for the fonts are loaded from
+    font_provider: Rc<dyn FontProvider>,
 }
 
 impl DVIState {
     fn add_font(&mut self, font_num: i32, font: &Font) {
         let metrics =
-            FontMetrics::from_font(font).expect("Failed to load font");
+            FontMetrics::from_font_with_provider(font, &*self.font_provider)
+                .expect("Failed to load font");
 
         self.fonts
             .insert(font_num, (metrics, font.font_name.to_string()));
@@ -215,8 +221,16 @@ where
 /// This interprets the commands of DVI file into the placements of characters
 /// and other DVI elements on the various pages. This does very minor
 /// validation that the structure of the DVI file is correct, and panics if
-/// anything is wrong.
+/// anything is wrong. The fonts are found with default_font_provider().
 pub fn interpret_dvi_file(file: DVIFile) -> Vec<DVIPageOutput> {
+    interpret_dvi_file_with_font_provider(file, default_font_provider())
+}
+
+/// Like interpret_dvi_file(), but loads the fonts from the given provider.
+pub fn interpret_dvi_file_with_font_provider(
+    file: DVIFile,
+    font_provider: Rc<dyn FontProvider>,
+) -> Vec<DVIPageOutput> {
     let mut commands = file.commands.iter().peekable();
 
     match commands.next().expect("Missing Pre") {
@@ -255,6 +269,7 @@ pub fn interpret_dvi_file(file: DVIFile) -> Vec<DVIPageOutput> {
             y: 0,
             z: 0,
         }],
+        font_provider,
     };
 
     loop {
@@ -274,6 +289,7 @@ mod tests {
     use super::*;
 
     use crate::dimension::Unit;
+    use crate::testing::{test_font_metrics, test_font_provider};
 
     fn empty_state() -> DVIState {
         DVIState {
@@ -287,6 +303,7 @@ mod tests {
                 y: 0,
                 z: 0,
             }],
+            font_provider: test_font_provider(),
         }
     }
 
@@ -311,64 +328,67 @@ mod tests {
 
     #[test]
     fn test_interpreting_whole_file() {
-        let pages = interpret_dvi_file(DVIFile {
-            commands: vec![
-                DVICommand::Pre {
-                    format: 2,
-                    num: 25400000,
-                    den: 473628672,
-                    mag: 1000,
-                    comment: vec![],
-                },
-                DVICommand::Bop {
-                    cs: [1, 0, 0, 0, 0, 0, 0, 0, 0, 0],
-                    pointer: -1,
-                },
-                DVICommand::FntDef1 {
-                    font_num: 0,
-                    checksum: 305419896,
-                    scale: 655360,
-                    design_size: 655360,
-                    area: 0,
-                    length: 5,
-                    font_name: "cmr10".to_string(),
-                },
-                DVICommand::FntNumN(0),
-                DVICommand::SetCharN(63),
-                DVICommand::Eop,
-                DVICommand::Bop {
-                    cs: [1, 0, 0, 0, 0, 0, 0, 0, 0, 0],
-                    pointer: 18,
-                },
-                DVICommand::FntNumN(0),
-                DVICommand::SetCharN(89),
-                DVICommand::Eop,
-                DVICommand::Post {
-                    pointer: 18,
-                    num: 25400000,
-                    den: 473628672,
-                    mag: 1000,
-                    max_page_width: 65536,
-                    max_page_height: 65536,
-                    max_stack_depth: 1,
-                    num_pages: 1,
-                },
-                DVICommand::FntDef1 {
-                    font_num: 0,
-                    checksum: 305419896,
-                    scale: 655360,
-                    design_size: 655360,
-                    area: 0,
-                    length: 5,
-                    font_name: "cmr10".to_string(),
-                },
-                DVICommand::PostPost {
-                    post_pointer: 128,
-                    format: 2,
-                    tail: 6,
-                },
-            ],
-        });
+        let pages = interpret_dvi_file_with_font_provider(
+            DVIFile {
+                commands: vec![
+                    DVICommand::Pre {
+                        format: 2,
+                        num: 25400000,
+                        den: 473628672,
+                        mag: 1000,
+                        comment: vec![],
+                    },
+                    DVICommand::Bop {
+                        cs: [1, 0, 0, 0, 0, 0, 0, 0, 0, 0],
+                        pointer: -1,
+                    },
+                    DVICommand::FntDef1 {
+                        font_num: 0,
+                        checksum: 305419896,
+                        scale: 655360,
+                        design_size: 655360,
+                        area: 0,
+                        length: 5,
+                        font_name: "cmr10".to_string(),
+                    },
+                    DVICommand::FntNumN(0),
+                    DVICommand::SetCharN(63),
+                    DVICommand::Eop,
+                    DVICommand::Bop {
+                        cs: [1, 0, 0, 0, 0, 0, 0, 0, 0, 0],
+                        pointer: 18,
+                    },
+                    DVICommand::FntNumN(0),
+                    DVICommand::SetCharN(89),
+                    DVICommand::Eop,
+                    DVICommand::Post {
+                        pointer: 18,
+                        num: 25400000,
+                        den: 473628672,
+                        mag: 1000,
+                        max_page_width: 65536,
+                        max_page_height: 65536,
+                        max_stack_depth: 1,
+                        num_pages: 1,
+                    },
+                    DVICommand::FntDef1 {
+                        font_num: 0,
+                        checksum: 305419896,
+                        scale: 655360,
+                        design_size: 655360,
+                        area: 0,
+                        length: 5,
+                        font_name: "cmr10".to_string(),
+                    },
+                    DVICommand::PostPost {
+                        post_pointer: 128,
+                        format: 2,
+                        tail: 6,
+                    },
+                ],
+            },
+            test_font_provider(),
+        );
 
         assert_eq!(pages.len(), 2);
 
@@ -444,7 +464,7 @@ mod tests {
             DVICommand::Eop,
         ]);
 
-        let metrics = FontMetrics::from_font(&Font {
+        let metrics = test_font_metrics(&Font {
             font_name: "cmr10".to_string(),
             scale: Dimen::from_unit(10.0, Unit::Point),
         })
@@ -604,7 +624,7 @@ mod tests {
             DVICommand::Eop,
         ]);
 
-        let metrics = FontMetrics::from_font(&Font {
+        let metrics = test_font_metrics(&Font {
             font_name: "cmr10".to_string(),
             scale: Dimen::from_unit(10.0, Unit::Point),
         })
@@ -5,4 +5,6 @@ mod interpreter;
 mod parser;
 
 pub use file::{DVICommand, DVIFile};
-pub use interpreter::interpret_dvi_file;
+pub use interpreter::{
+    interpret_dvi_file, interpret_dvi_file_with_font_provider,
+};
@@ -0,0 +1,240 @@
+use std::cell::RefCell;
+use std::io;
+use std::io::prelude::*;
+use std::path::Path;
+use std::rc::Rc;
+
+use crate::box_to_dvi::DVIFileWriter;
+use crate::boxes::VerticalBox;
+use crate::dvi::DVIFile;
+use crate::error::{TeXError, TeXResult};
+use crate::font_provider::FontProvider;
+use crate::parameter::IntegerParameter;
+use crate::parser::Parser;
+use crate::state::TeXState;
+
+/// Where a TeXEngine reads its input from.
+#[derive(Debug, Clone)]
+pub enum InputSource {
+    /// Lines of source, like the ones that are typed in at the terminal.
+    Lines(Vec<String>),
+    /// The name of a file, which is looked up the same way that \input looks
+    /// up files.
+    File(String),
+}
+
+/// Everything that was produced by a single run of a TeXEngine.
+#[derive(Debug)]
+pub struct TeXOutput {
+    /// Everything that was put on the main vertical list before \end. There
+    /// is no \shipout or page builder yet, so the whole run produces this
+    /// one page.
+    pub page: VerticalBox,
+    /// The errors which TeX recovered from while running.
+    pub errors: Vec<TeXError>,
+    // The value of \mag at the end of the run, and the name of the job, which
+    // are written at the start of the DVI file.
+    mag: i32,
+    job_name: String,
+    // Where the fonts used on the pages were loaded from, which the DVI file
+    // needs to describe them.
+    font_provider: Rc<dyn FontProvider>,
+}
+
+impl TeXOutput {
+    pub fn to_dvi_file(&self) -> DVIFile {
+        let mut writer = DVIFileWriter::new();
+        writer.set_font_provider(self.font_provider.clone());
+        writer.start_job(self.mag, &self.job_name);
+        let mut cs = [0; 10];
+        cs[0] = 1;
+        writer.add_page(&self.page.list, &None, cs);
+        writer.end();
+        writer.to_file()
+    }
+
+    pub fn to_dvi_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        self.to_dvi_file()
+            .write_to(&mut bytes)
+            .expect("writing to a Vec can't fail");
+        bytes
+    }
+}
+
+/// Runs TeX on input and collects what it produces. The state is kept
+/// between runs, so macros which are defined in one run can be used in the
+/// next one.
+pub struct TeXEngine {
+    state: TeXState,
+    terminal: Rc<RefCell<dyn Write>>,
+    log: Option<Rc<RefCell<dyn Write>>>,
+    halt_on_error: bool,
+}
+
+impl TeXEngine {
+    /// Creates an engine with a fresh state. Nothing is printed anywhere
+    /// until a terminal or log file is set.
+    pub fn new() -> Self {
+        TeXEngine {
+            state: TeXState::new(),
+            terminal: Rc::new(RefCell::new(io::sink())),
+            log: None,
+            halt_on_error: false,
+        }
+    }
+
+    pub fn get_state(&self) -> &TeXState {
+        &self.state
+    }
+
+    /// Sets where fonts are loaded from. By default, they're found with
+    /// default_font_provider().
+    pub fn set_font_provider(&mut self, provider: Rc<dyn FontProvider>) {
+        self.state.set_font_provider(provider);
+    }
+
+    pub fn set_terminal(&mut self, terminal: Rc<RefCell<dyn Write>>) {
+        self.terminal = terminal;
+    }
+
+    pub fn set_log_file(&mut self, log: Rc<RefCell<dyn Write>>) {
+        self.log = Some(log);
+    }
+
+    pub fn set_halt_on_error(&mut self, halt_on_error: bool) {
+        self.halt_on_error = halt_on_error;
+    }
+
+    /// Runs TeX on the input until it reaches \end. Errors that TeX can
+    /// recover from are returned in the output, so an Err is only returned
+    /// when TeX had to stop early.
+    pub fn run(&mut self, input: &InputSource) -> TeXResult<TeXOutput> {
+        let no_lines: Vec<String> = Vec::new();
+        let lines = match input {
+            InputSource::Lines(lines) => lines,
+            InputSource::File(_) => &no_lines,
+        };
+
+        let mut parser = Parser::new(lines, &self.state);
+        parser.set_terminal(self.terminal.clone());
+        if let Some(log) = &self.log {
+            parser.set_log_file(log.clone());
+        }
+        parser.set_halt_on_error(self.halt_on_error);
+        // Like TeX, the job is named after the file that's being run.
+        let job_name = match input {
+            InputSource::File(file_name) => Path::new(file_name)
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().into_owned()),
+            InputSource::Lines(_) => None,
+        }
+        .unwrap_or_else(|| "texput".to_string());
+        parser.set_job_name(&job_name);
+
+        let result = match input {
+            InputSource::File(file_name) => parser.start_input_file(file_name),
+            InputSource::Lines(_) => Ok(()),
+        }
+        .and_then(|_| parser.parse_outer_vertical_box());
+        parser.close_input_files();
+
+        Ok(TeXOutput {
+            page: result?,
+            errors: parser.take_errors(),
+            mag: self.state.get_integer_parameter(IntegerParameter::Mag),
+            job_name,
+            font_provider: self.state.get_font_provider(),
+        })
+    }
+}
+
+impl Default for TeXEngine {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::dimension::{Dimen, Unit};
+    use crate::dvi::DVICommand;
+    use crate::error::TeXErrorKind;
+    use crate::font_provider::InMemoryFontProvider;
+    use crate::tfm::test_data::CMR10_TFM;
+
+    fn lines(lines: &[&str]) -> InputSource {
+        InputSource::Lines(lines.iter().map(|line| line.to_string()).collect())
+    }
+
+    #[test]
+    fn it_runs_tex_on_lines() {
+        let mut engine = TeXEngine::new();
+        let output = engine.run(&lines(&[r"\hbox{\hskip 1pt}\end"])).unwrap();
+
+        assert_eq!(output.page.width, Dimen::from_unit(1.0, Unit::Point));
+        assert!(output.errors.is_empty());
+
+        let bytes = output.to_dvi_bytes();
+        assert_eq!(DVIFile::new(&bytes[..]).unwrap(), output.to_dvi_file());
+    }
+
+    #[test]
+    fn it_describes_the_job_in_the_dvi_preamble() {
+        let mut engine = TeXEngine::new();
+        let output = engine.run(&lines(&[r"\mag=2000 \hbox{}\end"])).unwrap();
+
+        assert_eq!(
+            output.to_dvi_file().commands[0],
+            DVICommand::Pre {
+                format: 2,
+                num: 25400000,
+                den: 473628672,
+                mag: 2000,
+                comment: b" XymosTeX output texput".to_vec(),
+            }
+        );
+    }
+
+    #[test]
+    fn it_keeps_state_between_runs() {
+        let mut engine = TeXEngine::new();
+        engine
+            .run(&lines(&[r"\def\a{\hbox{\hskip 2pt}}\count1=5 \end"]))
+            .unwrap();
+        assert_eq!(engine.get_state().get_count(1), 5);
+
+        let output = engine.run(&lines(&[r"\a\end"])).unwrap();
+        assert_eq!(output.page.width, Dimen::from_unit(2.0, Unit::Point));
+    }
+
+    #[test]
+    fn it_loads_fonts_from_the_font_provider() {
+        let mut provider = InMemoryFontProvider::new();
+        provider.add_font("cmr10", CMR10_TFM.to_vec());
+
+        let mut engine = TeXEngine::new();
+        engine.set_font_provider(Rc::new(provider));
+        let output = engine
+            .run(&lines(&[r"\font\a=cmr10 \a\hbox{a}\end"]))
+            .unwrap();
+        assert!(output.errors.is_empty());
+
+        let dvi = output.to_dvi_file();
+        assert_eq!(DVIFile::new(&output.to_dvi_bytes()[..]).unwrap(), dvi);
+    }
+
+    #[test]
+    fn it_fails_on_missing_files() {
+        let mut engine = TeXEngine::new();
+        let error = engine
+            .run(&InputSource::File("nonexistent-file".to_string()))
+            .unwrap_err();
+        assert_eq!(
+            error.kind,
+            TeXErrorKind::FileNotFound("nonexistent-file".to_string())
+        );
+    }
+}
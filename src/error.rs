@@ -0,0 +1,363 @@
+use std::fmt;
+use std::rc::Rc;
+
+use crate::origin::TokenOrigin;
+use crate::token::Token;
+
+/// The different things that can go wrong while parsing. Almost all of these
+/// are recoverable: when the parser sees one, it records the error, does what
+/// TeX would do to recover (e.g. inserting a missing } or ignoring an extra
+/// one), and keeps going. Only the kinds for which is_fatal() returns true
+/// stop the parser.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TeXErrorKind {
+    UndefinedControlSequence(Token),
+    // A primitive that we know about but don't know how to handle in the
+    // current situation yet.
+    Unimplemented(String),
+    InvalidCharacter(char),
+    CantUseInMode(Token, &'static str),
+    CantUsePrefixWith(Token),
+    CantUseLongOrOuterWith(Token),
+    CantUseAfterThe(Token),
+    // Something that isn't a variable after \advance, \multiply or \divide,
+    // which is the second token.
+    CantUseAfter(Token, Token),
+
+    MissingBox,
+    MissingLeftBrace,
+    MissingRightBrace,
+    ExtraRightBrace,
+    // A } which tried to end a group that was started by something else,
+    // which is what was probably forgotten.
+    ExtraRightBraceOrForgotten(&'static str),
+    MissingEndGroup,
+    ExtraEndGroup,
+    MissingMathShift,
+    MissingControlSequence,
+    MissingEndcsname,
+
+    ExtraConditionalEnd(Token),
+    MissingRelation(Token),
+    // Fatal, since this only happens when the input ends.
+    IncompleteConditional,
+    // An \outer macro was found while skipping a conditional, which ends
+    // the conditional early.
+    OuterInSkippedConditional(Token),
+
+    MissingNumber,
+    NumberTooBig,
+    BadRegisterCode(i32),
+    // A character code which isn't between 0 and 255.
+    BadCharacterCode(i32),
+    // A stream number which isn't between 0 and 15.
+    BadNumber(i32),
+    ImproperAlphabeticConstant,
+    BadMathCode(i32),
+    // A value for something like \catcode which isn't in the range from 0 to
+    // the second value.
+    InvalidCode(i32, i32),
+    InvalidDelimiterCode(i32),
+    IllegalUnit,
+    IllegalFilUnit,
+    DimensionTooLarge,
+    IllegalMuUnit,
+    MissingKeyword(String),
+    // Dividing by zero or a result that doesn't fit, in \multiply and
+    // friends.
+    ArithmeticOverflow,
+
+    IllegalParameterNumber(Token),
+    ParametersOutOfOrder,
+    TooManyParameters,
+    MacroUseMismatch(Token),
+    // A \par in the arguments of a macro that isn't \long.
+    ParagraphEnded(Token),
+    // Both of these are fatal, since they only happen when the input ends.
+    RunawayArgument(Token),
+    RunawayDefinition(Token),
+    // Fatal for the same reason, when reading the text of something like
+    // \message.
+    RunawayText(Token),
+
+    DoubleSuperscript,
+    DoubleSubscript,
+    AmbiguousFraction,
+
+    // An error message given by \errmessage.
+    ErrMessage(String),
+
+    FontNotLoadable(Token, String),
+    MissingFontIdentifier,
+    IllegalMagnification(i32),
+    // Fatal, since TeX would ask for another file name here and we can't.
+    FileNotFound(String),
+
+    // Fatal errors.
+    EmergencyStop,
+    TooManyErrors,
+}
+
+impl TeXErrorKind {
+    pub fn is_fatal(&self) -> bool {
+        matches!(
+            self,
+            TeXErrorKind::IncompleteConditional
+                | TeXErrorKind::RunawayArgument(_)
+                | TeXErrorKind::RunawayDefinition(_)
+                | TeXErrorKind::RunawayText(_)
+                | TeXErrorKind::FileNotFound(_)
+                | TeXErrorKind::EmergencyStop
+                | TeXErrorKind::TooManyErrors
+        )
+    }
+}
+
+fn print_token(token: &Token) -> String {
+    token.to_string().trim_end().to_string()
+}
+
+impl fmt::Display for TeXErrorKind {
+    // These messages match the ones that TeX prints as closely as possible.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TeXErrorKind::UndefinedControlSequence(_) => {
+                write!(f, "Undefined control sequence")
+            }
+            TeXErrorKind::Unimplemented(what) => {
+                write!(f, "Sorry, {} is not implemented yet", what)
+            }
+            TeXErrorKind::InvalidCharacter(_) => {
+                write!(f, "Text line contains an invalid character")
+            }
+            TeXErrorKind::CantUseInMode(token, mode) => {
+                write!(f, "You can't use `{}' in {}", print_token(token), mode)
+            }
+            TeXErrorKind::CantUsePrefixWith(token) => write!(
+                f,
+                "You can't use a prefix with `{}'",
+                print_token(token)
+            ),
+            TeXErrorKind::CantUseLongOrOuterWith(token) => write!(
+                f,
+                "You can't use `\\long' or `\\outer' with `{}'",
+                print_token(token)
+            ),
+            TeXErrorKind::CantUseAfterThe(token) => {
+                write!(f, "You can't use `{}' after \\the", print_token(token))
+            }
+            TeXErrorKind::CantUseAfter(token, after) => write!(
+                f,
+                "You can't use `{}' after {}",
+                print_token(token),
+                print_token(after)
+            ),
+            TeXErrorKind::MissingBox => {
+                write!(f, "A <box> was supposed to be here")
+            }
+            TeXErrorKind::MissingLeftBrace => write!(f, "Missing {{ inserted"),
+            TeXErrorKind::MissingRightBrace => {
+                write!(f, "Missing }} inserted")
+            }
+            TeXErrorKind::ExtraRightBrace => write!(f, "Too many }}'s"),
+            TeXErrorKind::ExtraRightBraceOrForgotten(forgotten) => {
+                write!(f, "Extra }}, or forgotten {}", forgotten)
+            }
+            TeXErrorKind::MissingEndGroup => {
+                write!(f, "Missing \\endgroup inserted")
+            }
+            TeXErrorKind::ExtraEndGroup => write!(f, "Extra \\endgroup"),
+            TeXErrorKind::MissingMathShift => write!(f, "Missing $ inserted"),
+            TeXErrorKind::MissingControlSequence => {
+                write!(f, "Missing control sequence inserted")
+            }
+            TeXErrorKind::ExtraConditionalEnd(token) => {
+                write!(f, "Extra {}", print_token(token))
+            }
+            TeXErrorKind::MissingRelation(token) => {
+                write!(f, "Missing = inserted for {}", print_token(token))
+            }
+            TeXErrorKind::IncompleteConditional => {
+                write!(f, "Incomplete \\if; all text was ignored after it")
+            }
+            TeXErrorKind::OuterInSkippedConditional(token) => write!(
+                f,
+                "Forbidden control sequence {} found in skipped text",
+                print_token(token)
+            ),
+            TeXErrorKind::MissingNumber => {
+                write!(f, "Missing number, treated as zero")
+            }
+            TeXErrorKind::NumberTooBig => write!(f, "Number too big"),
+            TeXErrorKind::BadNumber(value) => {
+                write!(f, "Bad number ({})", value)
+            }
+            TeXErrorKind::BadRegisterCode(value) => {
+                write!(f, "Bad register code ({})", value)
+            }
+            TeXErrorKind::BadCharacterCode(value) => {
+                write!(f, "Bad character code ({})", value)
+            }
+            TeXErrorKind::ImproperAlphabeticConstant => {
+                write!(f, "Improper alphabetic constant")
+            }
+            TeXErrorKind::BadMathCode(value) => {
+                write!(f, "Bad mathchar ({})", value)
+            }
+            TeXErrorKind::InvalidCode(value, max) => write!(
+                f,
+                "Invalid code ({}), should be in the range 0..{}",
+                value, max
+            ),
+            TeXErrorKind::InvalidDelimiterCode(value) => write!(
+                f,
+                "Invalid code ({}), should be at most 16777215",
+                value
+            ),
+            TeXErrorKind::MissingEndcsname => {
+                write!(f, "Missing \\endcsname inserted")
+            }
+            TeXErrorKind::IllegalUnit => {
+                write!(f, "Illegal unit of measure (pt inserted)")
+            }
+            TeXErrorKind::IllegalFilUnit => {
+                write!(f, "Illegal unit of measure (replaced by filll)")
+            }
+            TeXErrorKind::DimensionTooLarge => {
+                write!(f, "Dimension too large")
+            }
+            TeXErrorKind::IllegalMuUnit => {
+                write!(f, "Illegal unit of measure (mu inserted)")
+            }
+            TeXErrorKind::MissingKeyword(keyword) => {
+                write!(f, "Missing `{}' inserted", keyword)
+            }
+            TeXErrorKind::ArithmeticOverflow => {
+                write!(f, "Arithmetic overflow")
+            }
+            TeXErrorKind::IllegalParameterNumber(token) => write!(
+                f,
+                "Illegal parameter number in definition of {}",
+                print_token(token)
+            ),
+            TeXErrorKind::ParametersOutOfOrder => {
+                write!(f, "Parameters must be numbered consecutively")
+            }
+            TeXErrorKind::TooManyParameters => {
+                write!(f, "You already have nine parameters")
+            }
+            TeXErrorKind::MacroUseMismatch(token) => write!(
+                f,
+                "Use of {} doesn't match its definition",
+                print_token(token)
+            ),
+            TeXErrorKind::ParagraphEnded(token) => write!(
+                f,
+                "Runaway argument? Paragraph ended before {} was complete",
+                print_token(token)
+            ),
+            TeXErrorKind::RunawayArgument(token) => write!(
+                f,
+                "File ended while scanning use of {}",
+                print_token(token)
+            ),
+            TeXErrorKind::RunawayDefinition(token) => write!(
+                f,
+                "File ended while scanning definition of {}",
+                print_token(token)
+            ),
+            TeXErrorKind::RunawayText(token) => write!(
+                f,
+                "File ended while scanning text of {}",
+                print_token(token)
+            ),
+            TeXErrorKind::DoubleSuperscript => {
+                write!(f, "Double superscript")
+            }
+            TeXErrorKind::DoubleSubscript => write!(f, "Double subscript"),
+            TeXErrorKind::AmbiguousFraction => {
+                write!(f, "Ambiguous; you need another {{ and }}")
+            }
+            TeXErrorKind::ErrMessage(message) => write!(f, "{}", message),
+            TeXErrorKind::FontNotLoadable(token, name) => write!(
+                f,
+                "Font {}={} not loadable: Metric (TFM) file not found",
+                print_token(token),
+                name
+            ),
+            TeXErrorKind::MissingFontIdentifier => {
+                write!(f, "Missing font identifier")
+            }
+            TeXErrorKind::IllegalMagnification(value) => write!(
+                f,
+                "Illegal magnification has been changed to 1000 ({})",
+                value
+            ),
+            TeXErrorKind::FileNotFound(name) => {
+                write!(f, "I can't find file `{}'", name)
+            }
+            TeXErrorKind::EmergencyStop => write!(f, "Emergency stop"),
+            TeXErrorKind::TooManyErrors => {
+                write!(f, "That makes 100 errors; please try again.")
+            }
+        }
+    }
+}
+
+/// An error, along with where in the input it happened.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TeXError {
+    pub kind: TeXErrorKind,
+    // The origin of the token that was being looked at when the error
+    // happened, if any.
+    pub origin: Option<Rc<TokenOrigin>>,
+}
+
+impl TeXError {
+    pub fn new(kind: TeXErrorKind, origin: Option<Rc<TokenOrigin>>) -> Self {
+        TeXError { kind, origin }
+    }
+}
+
+impl fmt::Display for TeXError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.origin {
+            Some(origin) => write!(f, "{}: {}", origin, self.kind),
+            None => write!(f, "{}", self.kind),
+        }
+    }
+}
+
+impl std::error::Error for TeXError {}
+
+pub type TeXResult<T> = Result<T, TeXError>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_prints_errors_with_their_location() {
+        let error = TeXError::new(
+            TeXErrorKind::MissingRelation(Token::ControlSequence(
+                "ifnum".to_string(),
+            )),
+            Some(Rc::new(TokenOrigin::Source {
+                file: None,
+                line: 3,
+                column: 12,
+            })),
+        );
+
+        assert_eq!(
+            error.to_string(),
+            "<input>:3:12: Missing = inserted for \\ifnum"
+        );
+    }
+
+    #[test]
+    fn it_knows_which_errors_are_fatal() {
+        assert!(TeXErrorKind::EmergencyStop.is_fatal());
+        assert!(!TeXErrorKind::ExtraRightBrace.is_fatal());
+    }
+}
@@ -1,6 +1,6 @@
 use crate::dimension::{Dimen, Unit};
 use crate::font::Font;
-use crate::paths::get_path_to_font;
+use crate::font_provider::{default_font_provider, FontProvider};
 use crate::tfm::TFMFile;
 
 #[derive(Debug)]
@@ -11,10 +11,18 @@ pub struct FontMetrics {
 }
 
 impl FontMetrics {
+    /// Loads the metrics for a font, finding its TFM file with
+    /// default_font_provider().
     pub fn from_font(font: &Font) -> Option<Self> {
-        let font_filename = format!("{}.tfm", font.font_name);
-        let font_path = get_path_to_font(&font_filename)?;
-        let file = match TFMFile::from_path(&font_path) {
+        Self::from_font_with_provider(font, &*default_font_provider())
+    }
+
+    pub fn from_font_with_provider(
+        font: &Font,
+        provider: &dyn FontProvider,
+    ) -> Option<Self> {
+        let tfm_bytes = provider.get_tfm_bytes(&font.font_name)?;
+        let file = match TFMFile::new(&tfm_bytes[..]) {
             Ok(file) => Some(file),
             Err(err) => {
                 println!("Error loading font: {}", err);
@@ -73,9 +81,11 @@ impl FontMetrics {
 mod tests {
     use super::*;
 
+    use crate::testing::test_font_metrics;
+
     #[test]
     fn it_correctly_loads_font_metrics() {
-        let metrics = FontMetrics::from_font(&Font {
+        let metrics = test_font_metrics(&Font {
             font_name: "cmr10".to_string(),
             scale: Dimen::from_unit(10.0, Unit::Point),
         })
@@ -86,19 +96,19 @@ mod tests {
 
     #[test]
     fn it_loads_scaled_fonts() {
-        let tenpt_metrics = FontMetrics::from_font(&Font {
+        let tenpt_metrics = test_font_metrics(&Font {
             font_name: "cmr10".to_string(),
             scale: Dimen::from_unit(10.0, Unit::Point),
         })
         .unwrap();
 
-        let fivept_metrics = FontMetrics::from_font(&Font {
+        let fivept_metrics = test_font_metrics(&Font {
             font_name: "cmr10".to_string(),
             scale: Dimen::from_unit(5.0, Unit::Point),
         })
         .unwrap();
 
-        let twentypt_metrics = FontMetrics::from_font(&Font {
+        let twentypt_metrics = test_font_metrics(&Font {
             font_name: "cmr10".to_string(),
             scale: Dimen::from_unit(20.0, Unit::Point),
         })
@@ -122,7 +132,7 @@ mod tests {
 
     #[test]
     fn it_scales_font_dimensions() {
-        let twentypt_metrics = FontMetrics::from_font(&Font {
+        let twentypt_metrics = test_font_metrics(&Font {
             font_name: "cmr10".to_string(),
             scale: Dimen::from_unit(20.0, Unit::Point),
         })
@@ -136,12 +146,12 @@ mod tests {
 
     #[test]
     fn it_correctly_gets_successors() {
-        let cmr_metrics = FontMetrics::from_font(&Font {
+        let cmr_metrics = test_font_metrics(&Font {
             font_name: "cmr10".to_string(),
             scale: Dimen::from_unit(20.0, Unit::Point),
         })
         .unwrap();
-        let cmex_metrics = FontMetrics::from_font(&Font {
+        let cmex_metrics = test_font_metrics(&Font {
             font_name: "cmex10".to_string(),
             scale: Dimen::from_unit(10.0, Unit::Point),
         })
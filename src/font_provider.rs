@@ -0,0 +1,131 @@
+/// Module for finding the TFM files that fonts are loaded from. The engine
+/// doesn't care where these come from, so they can be found with kpathsea
+/// like TeX does, or come from somewhere else entirely.
+use std::collections::HashMap;
+use std::env;
+use std::fmt;
+use std::fs;
+use std::path::PathBuf;
+use std::rc::Rc;
+
+#[cfg(feature = "kpathsea")]
+use crate::paths::get_path_to_font;
+
+pub trait FontProvider: fmt::Debug {
+    /// Given a font name (like "cmr10"), returns the contents of the font's
+    /// TFM file if it can be found.
+    fn get_tfm_bytes(&self, font_name: &str) -> Option<Vec<u8>>;
+}
+
+/// Returns the provider that fonts are loaded from when no other one is given.
+/// This uses kpathsea when XymosTeX is built with it, and otherwise looks in
+/// the directories in $TEXFONTS.
+pub fn default_font_provider() -> Rc<dyn FontProvider> {
+    #[cfg(feature = "kpathsea")]
+    {
+        Rc::new(KpathseaFontProvider)
+    }
+    #[cfg(not(feature = "kpathsea"))]
+    {
+        Rc::new(DirectoryFontProvider::from_env_var("TEXFONTS"))
+    }
+}
+
+/// Finds fonts with kpathsea, the same way that TeX does. This requires a
+/// working TeX installation.
+#[cfg(feature = "kpathsea")]
+#[derive(Debug, Default)]
+pub struct KpathseaFontProvider;
+
+#[cfg(feature = "kpathsea")]
+impl FontProvider for KpathseaFontProvider {
+    fn get_tfm_bytes(&self, font_name: &str) -> Option<Vec<u8>> {
+        let path = get_path_to_font(&format!("{}.tfm", font_name))?;
+        fs::read(path).ok()
+    }
+}
+
+/// Looks for fonts in a list of directories, in order.
+#[derive(Debug)]
+pub struct DirectoryFontProvider {
+    directories: Vec<PathBuf>,
+}
+
+impl DirectoryFontProvider {
+    pub fn new(directories: Vec<PathBuf>) -> Self {
+        DirectoryFontProvider { directories }
+    }
+
+    /// Uses the directories in the given environment variable, which is a
+    /// list of paths like $TEXFONTS. Empty entries are skipped, since we
+    /// don't have a default path to fill them in with.
+    pub fn from_env_var(var_name: &str) -> Self {
+        let directories = match env::var_os(var_name) {
+            Some(paths) => env::split_paths(&paths)
+                .filter(|path| !path.as_os_str().is_empty())
+                .collect(),
+            None => Vec::new(),
+        };
+        DirectoryFontProvider::new(directories)
+    }
+}
+
+impl FontProvider for DirectoryFontProvider {
+    fn get_tfm_bytes(&self, font_name: &str) -> Option<Vec<u8>> {
+        let file_name = format!("{}.tfm", font_name);
+        self.directories
+            .iter()
+            .find_map(|directory| fs::read(directory.join(&file_name)).ok())
+    }
+}
+
+/// Serves fonts from TFM files which have already been loaded into memory.
+#[derive(Debug, Default)]
+pub struct InMemoryFontProvider {
+    fonts: HashMap<String, Vec<u8>>,
+}
+
+impl InMemoryFontProvider {
+    pub fn new() -> Self {
+        InMemoryFontProvider {
+            fonts: HashMap::new(),
+        }
+    }
+
+    pub fn add_font(&mut self, font_name: &str, tfm_bytes: Vec<u8>) {
+        self.fonts.insert(font_name.to_string(), tfm_bytes);
+    }
+}
+
+impl FontProvider for InMemoryFontProvider {
+    fn get_tfm_bytes(&self, font_name: &str) -> Option<Vec<u8>> {
+        self.fonts.get(font_name).cloned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::tfm::test_data::CMR10_TFM;
+
+    #[test]
+    fn it_finds_fonts_in_directories() {
+        let provider = DirectoryFontProvider::new(vec![
+            PathBuf::from("src/nonexistent-directory"),
+            PathBuf::from("src/tfm/test_files"),
+        ]);
+
+        assert_eq!(provider.get_tfm_bytes("cmr10"), Some(CMR10_TFM.to_vec()));
+        assert_eq!(provider.get_tfm_bytes("cmr7"), None);
+    }
+
+    #[test]
+    fn it_finds_fonts_in_memory() {
+        let mut provider = InMemoryFontProvider::new();
+        provider.add_font("cmr10", CMR10_TFM.to_vec());
+
+        assert_eq!(provider.get_tfm_bytes("cmr10"), Some(CMR10_TFM.to_vec()));
+        assert_eq!(provider.get_tfm_bytes("cmr7"), None);
+    }
+}
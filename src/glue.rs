@@ -1,6 +1,7 @@
-use std::ops::{Add, Sub};
+use std::fmt;
+use std::ops::{Add, Mul, Sub};
 
-use crate::dimension::{Dimen, MuDimen, SpringDimen};
+use crate::dimension::{Dimen, MuDimen, MuSpringDimen, Scaled, SpringDimen};
 
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub struct Glue {
@@ -21,6 +22,74 @@ impl Glue {
             shrink: SpringDimen::Dimen(Dimen::zero()),
         }
     }
+
+    /// Adds two glues, or returns None if any part of the result is too
+    /// large, like TeX's \advance.
+    pub fn checked_add(self, other: Glue) -> Option<Glue> {
+        Some(Glue {
+            space: self.space.checked_add(other.space)?,
+            stretch: self.stretch.checked_add(other.stretch)?,
+            shrink: self.shrink.checked_add(other.shrink)?,
+        })
+    }
+
+    /// Multiplies each part of the glue by an integer, or returns None if
+    /// any of them are too large, like TeX's \multiply.
+    pub fn checked_mul(self, other: i32) -> Option<Glue> {
+        Some(Glue {
+            space: self.space.checked_mul(other)?,
+            stretch: self.stretch.checked_mul(other)?,
+            shrink: self.shrink.checked_mul(other)?,
+        })
+    }
+
+    /// Divides each part of the glue by an integer, or returns None when
+    /// dividing by zero.
+    pub fn checked_div(self, other: i32) -> Option<Glue> {
+        Some(Glue {
+            space: self.space.checked_div(other)?,
+            stretch: self.stretch.checked_div(other)?,
+            shrink: self.shrink.checked_div(other)?,
+        })
+    }
+}
+
+// Prints the stretch or shrink of a glue, or None if it is zero and so
+// shouldn't be printed at all.
+fn spring_to_string(spring: &SpringDimen, unit: &str) -> Option<String> {
+    match spring {
+        SpringDimen::Dimen(dimen) if *dimen == Dimen::zero() => None,
+        SpringDimen::Dimen(dimen) => {
+            Some(format!("{}{}", Scaled(dimen.as_scaled_points()), unit))
+        }
+        SpringDimen::FilDimen(fil_dimen) if fil_dimen.1 == 0 => None,
+        SpringDimen::FilDimen(fil_dimen) => Some(fil_dimen.to_string()),
+    }
+}
+
+impl Glue {
+    /// Prints the glue the way TeX does, like "3.0pt plus 1.0fil". Finite
+    /// dimensions are followed by the given unit, which TeX leaves out when
+    /// showing the glue in a box.
+    pub fn to_string_with_unit(&self, unit: &str) -> String {
+        let mut result =
+            format!("{}{}", Scaled(self.space.as_scaled_points()), unit);
+        if let Some(stretch) = spring_to_string(&self.stretch, unit) {
+            result.push_str(" plus ");
+            result.push_str(&stretch);
+        }
+        if let Some(shrink) = spring_to_string(&self.shrink, unit) {
+            result.push_str(" minus ");
+            result.push_str(&shrink);
+        }
+        result
+    }
+}
+
+impl fmt::Display for Glue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.to_string_with_unit("pt"))
+    }
 }
 
 impl Add for Glue {
@@ -48,19 +117,101 @@ impl Sub for Glue {
     }
 }
 
+// Like with MuDimen, this is only used to apply signs. \multiply uses
+// checked_mul() instead.
+impl Mul<i32> for Glue {
+    type Output = Glue;
+
+    fn mul(mut self, other: i32) -> Glue {
+        self.space = self.space * other;
+        self.stretch = self.stretch * other;
+        self.shrink = self.shrink * other;
+        self
+    }
+}
+
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub struct MuGlue {
     pub space: MuDimen,
-    pub stretch: MuDimen,
-    pub shrink: MuDimen,
+    pub stretch: MuSpringDimen,
+    pub shrink: MuSpringDimen,
 }
 
 impl MuGlue {
+    pub fn zero() -> MuGlue {
+        MuGlue {
+            space: MuDimen::zero(),
+            stretch: MuSpringDimen::zero(),
+            shrink: MuSpringDimen::zero(),
+        }
+    }
+
     pub fn to_glue(&self, quad: Dimen) -> Glue {
         Glue {
             space: self.space.to_dimen(quad),
-            stretch: SpringDimen::Dimen(self.stretch.to_dimen(quad)),
-            shrink: SpringDimen::Dimen(self.shrink.to_dimen(quad)),
+            stretch: self.stretch.to_spring_dimen(quad),
+            shrink: self.shrink.to_spring_dimen(quad),
+        }
+    }
+
+    // Like the methods on Glue, these return None when the result is too
+    // large or we divide by zero.
+
+    pub fn checked_add(self, other: MuGlue) -> Option<MuGlue> {
+        Some(MuGlue {
+            space: self.space.checked_add(other.space)?,
+            stretch: self.stretch.checked_add(other.stretch)?,
+            shrink: self.shrink.checked_add(other.shrink)?,
+        })
+    }
+
+    pub fn checked_mul(self, other: i32) -> Option<MuGlue> {
+        Some(MuGlue {
+            space: self.space.checked_mul(other)?,
+            stretch: self.stretch.checked_mul(other)?,
+            shrink: self.shrink.checked_mul(other)?,
+        })
+    }
+
+    pub fn checked_div(self, other: i32) -> Option<MuGlue> {
+        Some(MuGlue {
+            space: self.space.checked_div(other)?,
+            stretch: self.stretch.checked_div(other)?,
+            shrink: self.shrink.checked_div(other)?,
+        })
+    }
+}
+
+// Like spring_to_string(), zero amounts of fil are also left out.
+fn is_zero_mu_spring(spring: &MuSpringDimen) -> bool {
+    match spring {
+        MuSpringDimen::MuDimen(mu_dimen) => *mu_dimen == MuDimen::zero(),
+        MuSpringDimen::FilDimen(fil_dimen) => fil_dimen.1 == 0,
+    }
+}
+
+impl fmt::Display for MuGlue {
+    // Like Glue, stretch and shrink are only printed when they aren't zero.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.space)?;
+        if !is_zero_mu_spring(&self.stretch) {
+            write!(f, " plus {}", self.stretch)?;
+        }
+        if !is_zero_mu_spring(&self.shrink) {
+            write!(f, " minus {}", self.shrink)?;
+        }
+        Ok(())
+    }
+}
+
+impl Mul<i32> for MuGlue {
+    type Output = MuGlue;
+
+    fn mul(self, other: i32) -> MuGlue {
+        MuGlue {
+            space: self.space * other,
+            stretch: self.stretch * other,
+            shrink: self.shrink * other,
         }
     }
 }
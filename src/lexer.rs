@@ -1,4 +1,8 @@
+use std::rc::Rc;
+
 use crate::category::Category;
+use crate::error::TeXErrorKind;
+use crate::origin::TokenOrigin;
 use crate::state::TeXState;
 use crate::token::Token;
 
@@ -30,22 +34,24 @@ fn hex_value(ch: char) -> u8 {
     }
 }
 
-pub struct Lexer<'a> {
-    source: Vec<Vec<char>>,
+// One of the files that we're reading lines from. The outermost one is the
+// input that the lexer was created with, which might be the terminal.
+struct InputFile {
+    // The name of the file, if any, which is used to give tokens an origin.
+    name: Option<Rc<str>>,
+    lines: Vec<Vec<char>>,
     row: usize,
     col: usize,
     lex_state: LexState,
-
-    state: &'a TeXState,
 }
 
-impl<'a> Lexer<'a> {
-    pub fn new<T>(lines: &[T], state: &'a TeXState) -> Lexer<'a>
+impl InputFile {
+    fn new<T>(name: Option<Rc<str>>, lines: &[T]) -> Self
     where
         T: AsRef<str>,
         T: std::string::ToString,
     {
-        let source = lines
+        let lines = lines
             .iter()
             .map(|s| {
                 let mut line = s.to_string();
@@ -54,44 +60,168 @@ impl<'a> Lexer<'a> {
             })
             .collect();
 
-        Lexer {
-            source,
+        InputFile {
+            name,
+            lines,
             row: 0,
             col: 0,
             lex_state: LexState::BeginningLine,
+        }
+    }
+
+    // Right after we finish a line we haven't looked at the next one yet, so
+    // the line we just finished is the one we're still reading. Returns None
+    // if we haven't started reading any lines.
+    fn get_current_row_and_col(&self) -> Option<(usize, usize)> {
+        if self.col == 0 && self.row > 0 {
+            Some((self.row - 1, self.lines[self.row - 1].len()))
+        } else if self.row < self.lines.len() {
+            Some((self.row, self.col))
+        } else {
+            None
+        }
+    }
+}
+
+pub struct Lexer<'a> {
+    // The files that are being read, with the innermost \input file last.
+    // There is always at least one.
+    files: Vec<InputFile>,
+
+    // The row and column where the most recently lexed token started, which
+    // is used to give tokens an origin.
+    token_start: (usize, usize),
+
+    state: &'a TeXState,
+}
+
+impl<'a> Lexer<'a> {
+    pub fn new<T>(lines: &[T], state: &'a TeXState) -> Lexer<'a>
+    where
+        T: AsRef<str>,
+        T: std::string::ToString,
+    {
+        Lexer {
+            files: vec![InputFile::new(None, lines)],
+            token_start: (0, 0),
             state,
         }
     }
 
+    fn current_file(&self) -> &InputFile {
+        self.files.last().unwrap()
+    }
+
+    fn current_file_mut(&mut self) -> &mut InputFile {
+        self.files.last_mut().unwrap()
+    }
+
+    /// Starts reading from a new file, like with \input. Once the lexer runs
+    /// out of lines in the file, lex_token() returns None until end_file() is
+    /// called, which returns to reading from the file we were in before.
+    pub fn start_file<T>(&mut self, file_name: &str, lines: &[T])
+    where
+        T: AsRef<str>,
+        T: std::string::ToString,
+    {
+        self.files
+            .push(InputFile::new(Some(Rc::from(file_name)), lines));
+    }
+
+    /// Returns true if we're reading from a file started with start_file().
+    pub fn is_reading_file(&self) -> bool {
+        self.files.len() > 1
+    }
+
+    pub fn end_file(&mut self) {
+        if self.is_reading_file() {
+            self.files.pop();
+        }
+    }
+
+    /// Stops reading the current file after the line that is currently being
+    /// read, like \endinput.
+    pub fn end_file_after_current_line(&mut self) {
+        let file = self.current_file_mut();
+        let end = match file.get_current_row_and_col() {
+            Some((row, _)) => row + 1,
+            None => file.row,
+        };
+        file.lines.truncate(end);
+    }
+
+    /// Returns the number of lines of the current file which have been read,
+    /// like \inputlineno.
+    pub fn get_line_number(&self) -> usize {
+        match self.current_file().get_current_row_and_col() {
+            Some((row, _)) => row + 1,
+            None => self.current_file().row,
+        }
+    }
+
+    /// Returns the position where the most recently lexed token started.
+    pub fn last_token_origin(&self) -> TokenOrigin {
+        let (row, col) = self.token_start;
+        TokenOrigin::Source {
+            file: self.current_file().name.clone(),
+            line: row + 1,
+            column: col + 1,
+        }
+    }
+
+    /// Returns the number of the line that we're in the middle of reading,
+    /// along with the parts of that line which have and haven't been read
+    /// yet. This is used to show where errors happened.
+    pub fn get_current_line(&self) -> Option<(usize, String, String)> {
+        let file = self.current_file();
+        let (row, col) = file.get_current_row_and_col()?;
+
+        // We don't show the newline that we added to the end of each line.
+        let line = &file.lines[row];
+        let end = line.len() - 1;
+        let col = col.min(end);
+        Some((
+            row + 1,
+            line[..col].iter().collect(),
+            line[col..end].iter().collect(),
+        ))
+    }
+
     fn get_plain_char(&mut self) -> PlainLexResult {
-        if self.row == self.source.len() {
+        let file = self.current_file_mut();
+        if file.row == file.lines.len() {
             return PlainLexResult::Eof;
         }
 
-        let line = &self.source[self.row];
+        let line = &file.lines[file.row];
 
-        if self.col == line.len() {
-            self.row += 1;
-            self.col = 0;
+        if file.col == line.len() {
+            file.row += 1;
+            file.col = 0;
             return PlainLexResult::Eol;
         }
 
-        let ch = line[self.col];
-        self.col += 1;
+        let ch = line[file.col];
+        file.col += 1;
         PlainLexResult::Char(ch)
     }
 
     fn unget_plain_char(&mut self, ch: &PlainLexResult) {
+        let file = self.current_file_mut();
         match ch {
-            PlainLexResult::Char(_) => self.col -= 1,
+            PlainLexResult::Char(_) => file.col -= 1,
             PlainLexResult::Eol => {
-                self.row -= 1;
-                self.col = self.source[self.row].len() - 1;
+                file.row -= 1;
+                file.col = file.lines[file.row].len() - 1;
             }
             PlainLexResult::Eof => (),
         }
     }
 
+    fn set_lex_state(&mut self, lex_state: LexState) {
+        self.current_file_mut().lex_state = lex_state;
+    }
+
     fn get_char(&mut self) -> PlainLexResult {
         match self.get_plain_char() {
             PlainLexResult::Char(ch) => self.handle_trigraphs(ch),
@@ -157,17 +287,21 @@ impl<'a> Lexer<'a> {
         }
     }
 
-    pub fn lex_token(&mut self) -> Option<Token> {
+    // Lexes the next token, returning None at the end of the input. If we run
+    // into an invalid character, it is skipped and an error is returned so
+    // the caller can report it and try again.
+    pub fn lex_token(&mut self) -> Result<Option<Token>, TeXErrorKind> {
+        self.token_start = (self.current_file().row, self.current_file().col);
         match self.get_char() {
-            PlainLexResult::Eof => None,
+            PlainLexResult::Eof => Ok(None),
             PlainLexResult::Eol => {
-                self.lex_state = LexState::BeginningLine;
+                self.set_lex_state(LexState::BeginningLine);
                 self.lex_token()
             }
             PlainLexResult::Char(c) => match self.state.get_category(c) {
-                Category::Invalid => panic!("Invalid character: '{}'", c),
+                Category::Invalid => Err(TeXErrorKind::InvalidCharacter(c)),
                 Category::Escape => {
-                    self.lex_state = LexState::SkippingBlanks;
+                    self.set_lex_state(LexState::SkippingBlanks);
 
                     let first_char = match self.get_char() {
                         PlainLexResult::Char(c) => c,
@@ -196,38 +330,39 @@ impl<'a> Lexer<'a> {
                                 }
                             }
 
-                            Some(Token::ControlSequence(sequence))
-                        }
-                        _ => {
-                            Some(Token::ControlSequence(first_char.to_string()))
+                            Ok(Some(Token::ControlSequence(sequence)))
                         }
+                        _ => Ok(Some(Token::ControlSequence(
+                            first_char.to_string(),
+                        ))),
                     }
                 }
-                Category::EndOfLine => match self.lex_state {
+                Category::EndOfLine => match self.current_file().lex_state {
                     LexState::BeginningLine => {
-                        Some(Token::ControlSequence("par".to_string()))
+                        Ok(Some(Token::ControlSequence("par".to_string())))
                     }
                     LexState::MiddleLine => {
-                        Some(Token::Char(' ', Category::Space))
+                        Ok(Some(Token::Char(' ', Category::Space)))
                     }
                     LexState::SkippingBlanks => self.lex_token(),
                 },
                 Category::Space => {
-                    if self.lex_state == LexState::MiddleLine {
-                        self.lex_state = LexState::SkippingBlanks;
-                        Some(Token::Char(' ', Category::Space))
+                    if self.current_file().lex_state == LexState::MiddleLine {
+                        self.set_lex_state(LexState::SkippingBlanks);
+                        Ok(Some(Token::Char(' ', Category::Space)))
                     } else {
                         self.lex_token()
                     }
                 }
                 Category::Comment => {
-                    self.col = self.source[self.row].len();
+                    let file = self.current_file_mut();
+                    file.col = file.lines[file.row].len();
                     self.lex_token()
                 }
                 Category::Ignored => self.lex_token(),
                 cat => {
-                    self.lex_state = LexState::MiddleLine;
-                    Some(Token::Char(c, cat))
+                    self.set_lex_state(LexState::MiddleLine);
+                    Ok(Some(Token::Char(c, cat)))
                 }
             },
         }
@@ -247,7 +382,7 @@ mod tests {
 
         let mut real_toks = Vec::new();
 
-        while let Some(tok) = lexer.lex_token() {
+        while let Some(tok) = lexer.lex_token().unwrap() {
             real_toks.push(tok);
         }
 
@@ -295,9 +430,44 @@ mod tests {
     }
 
     #[test]
-    #[should_panic(expected = "Invalid character:")]
-    fn it_panics_on_invalid_tokens() {
-        assert_lexes_to(&["\u{00ff}"], &[]);
+    fn it_records_where_tokens_start() {
+        let state = TeXState::new();
+        let no_lines: &[&str] = &[];
+        let mut lexer = Lexer::new(no_lines, &state);
+        lexer.start_file("story.tex", &[r"a \bc  %", "  d"]);
+
+        let mut positions = Vec::new();
+        while lexer.lex_token().unwrap().is_some() {
+            match lexer.last_token_origin() {
+                TokenOrigin::Source { file, line, column } => {
+                    assert_eq!(file.as_deref(), Some("story.tex"));
+                    positions.push((line, column));
+                }
+                _ => panic!("Expected source origin"),
+            }
+        }
+
+        assert_eq!(positions, vec![(1, 1), (1, 2), (1, 3), (2, 3), (2, 4)]);
+    }
+
+    #[test]
+    fn it_skips_invalid_characters() {
+        let state = TeXState::new();
+        let mut lexer = Lexer::new(&["a\u{00ff}b%"], &state);
+
+        assert_eq!(
+            lexer.lex_token(),
+            Ok(Some(Token::Char('a', Category::Letter)))
+        );
+        assert_eq!(
+            lexer.lex_token(),
+            Err(TeXErrorKind::InvalidCharacter('\u{00ff}'))
+        );
+        assert_eq!(
+            lexer.lex_token(),
+            Ok(Some(Token::Char('b', Category::Letter)))
+        );
+        assert_eq!(lexer.lex_token(), Ok(None));
     }
 
     #[test]
@@ -399,4 +569,75 @@ mod tests {
             &state,
         );
     }
+
+    #[test]
+    fn it_keeps_track_of_the_current_line() {
+        let state = TeXState::new();
+        let mut lexer = Lexer::new(&["ab%", "c"], &state);
+        assert_eq!(
+            lexer.get_current_line(),
+            Some((1, "".to_string(), "ab%".to_string()))
+        );
+
+        lexer.lex_token().unwrap();
+        assert_eq!(
+            lexer.get_current_line(),
+            Some((1, "a".to_string(), "b%".to_string()))
+        );
+
+        lexer.lex_token().unwrap();
+        lexer.lex_token().unwrap();
+        assert_eq!(
+            lexer.get_current_line(),
+            Some((2, "c".to_string(), "".to_string()))
+        );
+
+        assert_eq!(
+            lexer.lex_token().unwrap(),
+            Some(Token::Char(' ', Category::Space))
+        );
+        assert_eq!(lexer.lex_token().unwrap(), None);
+        assert_eq!(
+            lexer.get_current_line(),
+            Some((2, "c".to_string(), "".to_string()))
+        );
+    }
+
+    #[test]
+    fn it_reads_from_a_stack_of_files() {
+        let state = TeXState::new();
+        let mut lexer = Lexer::new(&["a%", "b%"], &state);
+        lexer.lex_token().unwrap();
+
+        lexer.start_file("inner.tex", &["x%", "y%", "z%"]);
+        assert!(lexer.is_reading_file());
+        // Like TeX, the first line of a file counts as read right away.
+        assert_eq!(lexer.get_line_number(), 1);
+        assert_eq!(
+            lexer.lex_token().unwrap(),
+            Some(Token::Char('x', Category::Letter))
+        );
+        assert_eq!(lexer.get_line_number(), 1);
+        assert_eq!(
+            lexer.last_token_origin(),
+            TokenOrigin::Source {
+                file: Some(Rc::from("inner.tex")),
+                line: 1,
+                column: 1,
+            }
+        );
+
+        lexer.lex_token().unwrap();
+        lexer.end_file_after_current_line();
+        assert_eq!(lexer.lex_token().unwrap(), None);
+        assert_eq!(lexer.get_line_number(), 2);
+
+        lexer.end_file();
+        assert!(!lexer.is_reading_file());
+        assert_eq!(
+            lexer.lex_token().unwrap(),
+            Some(Token::Char('b', Category::Letter))
+        );
+        assert_eq!(lexer.get_line_number(), 2);
+    }
 }
@@ -0,0 +1,63 @@
+#![deny(clippy::all)]
+
+//! XymosTeX is an implementation of TeX. The easiest way to use it from
+//! another program is with a TeXEngine, which runs TeX on some input and
+//! returns the pages that were produced, either as boxes or as a DVI file:
+//!
+//! ```no_run
+//! use xymostex::{InputSource, TeXEngine};
+//!
+//! let mut engine = TeXEngine::new();
+//! let output = engine
+//!     .run(&InputSource::Lines(vec![r"\hbox{Hello}\end".to_string()]))
+//!     .unwrap();
+//! let dvi_bytes = output.to_dvi_bytes();
+//! ```
+//!
+//! The pieces that the engine is built out of, like the Parser and TeXState,
+//! and the readers for TFM and DVI files, can also be used directly.
+
+#[macro_use]
+extern crate lazy_static;
+
+pub mod box_display;
+pub mod box_to_dvi;
+pub mod boxes;
+pub mod category;
+pub mod debugger;
+pub mod dimension;
+pub mod dvi;
+pub mod engine;
+pub mod error;
+pub mod font;
+pub mod font_metrics;
+pub mod font_provider;
+pub mod glue;
+mod lexer;
+pub mod list;
+pub mod makro;
+pub mod math_code;
+pub mod math_list;
+pub mod origin;
+pub mod parameter;
+pub mod parser;
+#[cfg(feature = "kpathsea")]
+mod paths;
+pub mod state;
+pub mod tfm;
+pub mod token;
+pub mod trace;
+pub mod transcript;
+pub mod variable;
+
+#[cfg(test)]
+mod testing;
+#[cfg(test)]
+mod tests;
+
+pub use crate::dvi::DVIFile;
+pub use crate::engine::{InputSource, TeXEngine, TeXOutput};
+pub use crate::font_provider::FontProvider;
+pub use crate::parser::Parser;
+pub use crate::state::TeXState;
+pub use crate::tfm::TFMFile;
@@ -1,20 +1,40 @@
+use std::rc::Rc;
+
 use crate::boxes::TeXBox;
 use crate::dimension::Dimen;
 use crate::font::Font;
 use crate::glue::Glue;
+use crate::origin::{Provenance, TokenOrigin};
 use crate::state::TeXState;
 
 #[derive(Debug, PartialEq, Clone)]
 pub enum HorizontalListElem {
-    Char { chr: char, font: Font },
+    // The origin is the token that the character was typeset from, if known.
+    Char {
+        chr: char,
+        font: Font,
+        origin: Provenance,
+    },
     HSkip(Glue),
-    Box { tex_box: TeXBox, shift: Dimen },
+    Box {
+        tex_box: TeXBox,
+        shift: Dimen,
+    },
 }
 
 impl HorizontalListElem {
+    /// Where the token that a character was typeset from came from, if it's
+    /// known. Other elements don't have an origin.
+    pub fn origin(&self) -> Option<&Rc<TokenOrigin>> {
+        match self {
+            HorizontalListElem::Char { origin, .. } => origin.get(),
+            _ => None,
+        }
+    }
+
     pub fn get_size(&self, state: &TeXState) -> (Dimen, Dimen, Glue) {
         match self {
-            HorizontalListElem::Char { chr, font } => {
+            HorizontalListElem::Char { chr, font, .. } => {
                 let metrics = state.get_metrics_for_font(&font).unwrap();
 
                 let height = metrics.get_height(*chr);
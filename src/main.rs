@@ -1,68 +1,144 @@
 #![deny(clippy::all)]
 
-#[macro_use]
-extern crate lazy_static;
-
-mod box_to_dvi;
-mod boxes;
-mod category;
-mod dimension;
-mod dvi;
-mod font;
-mod font_metrics;
-mod glue;
-mod lexer;
-mod list;
-mod makro;
-mod math_code;
-mod math_list;
-mod parser;
-mod paths;
-mod state;
-mod tfm;
-mod token;
-mod variable;
-
-#[cfg(test)]
-mod testing;
-#[cfg(test)]
-mod tests;
+mod cli;
 
+use std::cell::RefCell;
+use std::env;
 use std::fs;
 use std::io;
 use std::io::prelude::*;
+use std::process;
+use std::rc::Rc;
 
-use crate::box_to_dvi::DVIFileWriter;
-use crate::parser::Parser;
-use crate::state::TeXState;
+use crate::cli::{Command, InteractionMode, Options};
+use xymostex::box_to_dvi::DVIFileWriter;
+use xymostex::font_provider::DirectoryFontProvider;
+use xymostex::parameter::IntegerParameter;
+use xymostex::parser::Parser;
+use xymostex::state::TeXState;
 
-fn main() -> io::Result<()> {
-    let mut lines: Vec<String> = Vec::new();
-
-    // Read in every line of stdin. This currently doesn't let us do parsing as
-    // we go along, but that's fine.
-    let stdin = io::stdin();
-    for line in stdin.lock().lines() {
-        lines.push(line?);
-    }
+// Runs XymosTeX with the given options, and returns the exit code. Like TeX,
+// this is 0 if there were no errors and 1 otherwise.
+fn run(options: &Options) -> io::Result<i32> {
+    let lines: Vec<String> = match options.get_first_line() {
+        Some(line) => vec![line],
+        None => {
+            // Read in every line of stdin. This currently doesn't let us do
+            // parsing as we go along, but that's fine.
+            let stdin = io::stdin();
+            let lines = stdin.lock().lines();
+            lines.collect::<io::Result<_>>()?
+        }
+    };
 
     let state = TeXState::new();
+    // Like TeX, fonts are looked for in the directories in $TEXFONTS when
+    // it's set.
+    if env::var_os("TEXFONTS").is_some() {
+        state.set_font_provider(Rc::new(DirectoryFontProvider::from_env_var(
+            "TEXFONTS",
+        )));
+    }
     let mut parser = Parser::new(&lines[..], &state);
+    parser.set_halt_on_error(options.halt_on_error);
+    parser.set_job_name(&options.get_job_name());
+    if options.interaction == InteractionMode::Batch {
+        parser.set_terminal(Rc::new(RefCell::new(io::sink())));
+    }
+
+    if let Some(directory) = &options.output_directory {
+        fs::create_dir_all(directory)?;
+    }
+    let log_path = options.get_output_path("log");
+    let dvi_path = options.get_output_path("dvi");
+
+    let log_file = Rc::new(RefCell::new(io::BufWriter::new(fs::File::create(
+        &log_path,
+    )?)));
+    writeln!(
+        log_file.borrow_mut(),
+        "This is XymosTeX, Version {}",
+        env!("CARGO_PKG_VERSION")
+    )?;
+    parser.set_log_file(log_file.clone());
+
+    // We can't load dumped formats, so the closest we can get is reading the
+    // source of the format before everything else.
+    let format_result = match &options.format {
+        Some(format) => parser.start_input_file(format),
+        None => Ok(()),
+    };
+
+    // Errors, including fatal ones, are printed by the parser as they
+    // happen.
+    let result = format_result.and_then(|_| parser.parse_outer_vertical_box());
+    parser.close_input_files();
+    let result = match result {
+        Ok(result) => result,
+        Err(_) => {
+            parser.print_line("No pages of output.");
+            parser.print_line(&format!(
+                "Transcript written on {}.",
+                log_path.display()
+            ));
+            log_file.borrow_mut().flush()?;
+            return Ok(1);
+        }
+    };
 
     let mut file_writer = DVIFileWriter::new();
-    file_writer.start(
-        (25400000, 473628672),
-        1000,
-        b"Made by XymosTeX".to_vec(),
+    file_writer.set_font_provider(state.get_font_provider());
+    file_writer.start_job(
+        state.get_integer_parameter(IntegerParameter::Mag),
+        &options.get_job_name(),
     );
-
-    let result = parser.parse_outer_vertical_box();
     file_writer.add_page(&result.list, &None, [1, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
 
     file_writer.end();
 
     let file = file_writer.to_file();
 
-    let output = fs::File::create("texput.dvi")?;
-    file.write_to(output)
+    let output = fs::File::create(&dvi_path)?;
+    file.write_to(output)?;
+
+    parser.print_line(&format!(
+        "Output written on {} (1 page).",
+        dvi_path.display()
+    ));
+    parser
+        .print_line(&format!("Transcript written on {}.", log_path.display()));
+    log_file.borrow_mut().flush()?;
+
+    if parser.get_errors().is_empty() {
+        Ok(0)
+    } else {
+        Ok(1)
+    }
+}
+
+fn main() {
+    let options = match cli::parse_args(env::args().skip(1)) {
+        Ok(Command::Run(options)) => options,
+        Ok(Command::Help) => {
+            print!("{}", cli::USAGE);
+            return;
+        }
+        Ok(Command::Version) => {
+            println!("XymosTeX {}", env!("CARGO_PKG_VERSION"));
+            return;
+        }
+        Err(message) => {
+            eprintln!("xymostex: {}", message);
+            eprintln!("Try `xymostex --help' for more information.");
+            process::exit(1);
+        }
+    };
+
+    match run(&options) {
+        Ok(code) => process::exit(code),
+        Err(error) => {
+            eprintln!("xymostex: {}", error);
+            process::exit(1);
+        }
+    }
 }
@@ -2,7 +2,11 @@
 // keyword.
 
 use std::collections::HashMap;
+use std::fmt;
+use std::rc::Rc;
 
+use crate::category::Category;
+use crate::origin::{TokenOrigin, TokenWithOrigin};
 use crate::token::Token;
 
 #[derive(PartialEq, Eq, Debug)]
@@ -11,12 +15,35 @@ pub enum MacroListElem {
     Parameter(usize),
 }
 
-#[derive(PartialEq, Eq, Debug)]
+#[derive(Debug)]
 pub struct Macro {
     pub parameter_list: Vec<MacroListElem>,
     replacement_list: Vec<MacroListElem>,
+
+    // Set by the \long and \outer prefixes. Arguments to \long macros can
+    // contain \par, and \outer macros can't appear in places like skipped
+    // conditionals.
+    long: bool,
+    outer: bool,
+
+    // Where the macro was defined. This is only informational, so it doesn't
+    // affect equality.
+    definition_origin: Option<Rc<TokenOrigin>>,
+}
+
+// Macros are compared by their meaning, like in \ifx, so two macros which are
+// defined the same way in different places are equal.
+impl PartialEq for Macro {
+    fn eq(&self, other: &Self) -> bool {
+        self.parameter_list == other.parameter_list
+            && self.replacement_list == other.replacement_list
+            && self.long == other.long
+            && self.outer == other.outer
+    }
 }
 
+impl Eq for Macro {}
+
 impl Macro {
     pub fn new(
         parameter_list: Vec<MacroListElem>,
@@ -25,6 +52,9 @@ impl Macro {
         let makro: Macro = Macro {
             parameter_list,
             replacement_list,
+            long: false,
+            outer: false,
+            definition_origin: None,
         };
 
         makro.validate();
@@ -32,6 +62,32 @@ impl Macro {
         makro
     }
 
+    pub fn with_prefixes(mut self, long: bool, outer: bool) -> Macro {
+        self.long = long;
+        self.outer = outer;
+        self
+    }
+
+    pub fn is_long(&self) -> bool {
+        self.long
+    }
+
+    pub fn is_outer(&self) -> bool {
+        self.outer
+    }
+
+    pub fn with_definition_origin(
+        mut self,
+        origin: Option<Rc<TokenOrigin>>,
+    ) -> Macro {
+        self.definition_origin = origin;
+        self
+    }
+
+    pub fn get_definition_origin(&self) -> Option<&Rc<TokenOrigin>> {
+        self.definition_origin.as_ref()
+    }
+
     fn validate(&self) {
         // The parameters in the parameter list need to be in order, so we make
         // sure that's the case.
@@ -62,10 +118,14 @@ impl Macro {
         }
     }
 
+    /// Returns the replacement text of the macro with the given arguments
+    /// substituted in. The tokens from the arguments keep their own origins,
+    /// and the rest of the tokens are given `origin`.
     pub fn get_replacement(
         &self,
-        parameter_values: &HashMap<usize, Vec<Token>>,
-    ) -> Vec<Token> {
+        parameter_values: &HashMap<usize, Vec<TokenWithOrigin>>,
+        origin: &Option<Rc<TokenOrigin>>,
+    ) -> Vec<TokenWithOrigin> {
         self.replacement_list
             .iter()
             .flat_map(|elem| match elem {
@@ -78,12 +138,42 @@ impl Macro {
                         ),
                     }
                 }
-                MacroListElem::Token(tok) => vec![tok.clone()],
+                MacroListElem::Token(tok) => {
+                    vec![(tok.clone(), origin.clone())]
+                }
             })
             .collect()
     }
 }
 
+fn fmt_macro_list(
+    f: &mut fmt::Formatter,
+    list: &[MacroListElem],
+) -> fmt::Result {
+    for elem in list {
+        match elem {
+            MacroListElem::Parameter(param_num) => write!(f, "#{}", param_num)?,
+            // Parameter characters are doubled so that they can't be confused
+            // with actual parameters.
+            MacroListElem::Token(Token::Char(ch, Category::Parameter)) => {
+                write!(f, "{}{}", ch, ch)?
+            }
+            MacroListElem::Token(token) => write!(f, "{}", token)?,
+        }
+    }
+    Ok(())
+}
+
+impl fmt::Display for Macro {
+    // Prints the macro the way TeX does when showing its meaning, with the
+    // parameter text and the replacement text separated by "->".
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt_macro_list(f, &self.parameter_list)?;
+        write!(f, "->")?;
+        fmt_macro_list(f, &self.replacement_list)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -117,24 +207,52 @@ mod tests {
             ],
         );
 
+        let source = |column| {
+            Some(Rc::new(TokenOrigin::Source {
+                file: None,
+                line: 1,
+                column,
+            }))
+        };
+
         let mut replacements = HashMap::new();
-        replacements.insert(1, vec![Token::ControlSequence("c".to_string())]);
+        replacements
+            .insert(1, vec![(Token::ControlSequence("c".to_string()), None)]);
         replacements.insert(
             2,
             vec![
-                Token::ControlSequence("a".to_string()),
-                Token::ControlSequence("b".to_string()),
+                (Token::ControlSequence("a".to_string()), source(1)),
+                (Token::ControlSequence("b".to_string()), source(3)),
             ],
         );
 
         assert_eq!(
             vec![
-                Token::ControlSequence("a".to_string()),
-                Token::ControlSequence("b".to_string()),
-                Token::ControlSequence("boo".to_string()),
-                Token::ControlSequence("c".to_string())
+                (Token::ControlSequence("a".to_string()), source(1)),
+                (Token::ControlSequence("b".to_string()), source(3)),
+                (Token::ControlSequence("boo".to_string()), source(5)),
+                (Token::ControlSequence("c".to_string()), None),
             ],
-            makro.get_replacement(&replacements)
+            makro.get_replacement(&replacements, &source(5))
         );
     }
+
+    #[test]
+    fn it_prints_macros() {
+        let makro = Macro::new(
+            vec![
+                MacroListElem::Parameter(1),
+                MacroListElem::Token(Token::Char('.', Category::Other)),
+                MacroListElem::Parameter(2),
+            ],
+            vec![
+                MacroListElem::Token(Token::ControlSequence("a".to_string())),
+                MacroListElem::Parameter(2),
+                MacroListElem::Token(Token::Char('#', Category::Parameter)),
+                MacroListElem::Token(Token::Char('x', Category::Letter)),
+            ],
+        );
+
+        assert_eq!(makro.to_string(), "#1.#2->\\a #2##x");
+    }
 }
@@ -58,4 +58,14 @@ impl MathCode {
             position,
         }
     }
+
+    pub fn to_number(&self) -> u32 {
+        if self.class == MathClass::Active {
+            return 0x8000;
+        }
+
+        (self.class.clone() as u32) * 0x1000
+            + (self.family as u32) * 0x100
+            + (self.position as u32)
+    }
 }
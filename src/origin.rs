@@ -0,0 +1,138 @@
+use std::fmt;
+use std::rc::Rc;
+
+use crate::token::Token;
+
+/// Describes where a token came from. Tokens read from an input file know the
+/// position they were read from, and tokens which came out of a macro's
+/// replacement text know which macro produced them, where that macro was
+/// defined, and where it was called from.
+#[derive(Debug, PartialEq, Eq)]
+pub enum TokenOrigin {
+    Source {
+        // None if the input didn't come from a named file (e.g. stdin).
+        file: Option<Rc<str>>,
+        // Both line and column are 1-indexed.
+        line: usize,
+        column: usize,
+    },
+    Macro {
+        token: Token,
+        definition: Option<Rc<TokenOrigin>>,
+        call_site: Option<Rc<TokenOrigin>>,
+    },
+}
+
+impl TokenOrigin {
+    /// Follows the chain of macro call sites back to the position in the
+    /// input which ultimately produced this token. Returns the file name (if
+    /// any), line, and column.
+    pub fn source_location(&self) -> Option<(Option<&str>, usize, usize)> {
+        match self {
+            TokenOrigin::Source { file, line, column } => {
+                Some((file.as_deref(), *line, *column))
+            }
+            TokenOrigin::Macro { call_site, .. } => {
+                call_site.as_ref()?.source_location()
+            }
+        }
+    }
+}
+
+impl fmt::Display for TokenOrigin {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TokenOrigin::Source { file, line, column } => write!(
+                f,
+                "{}:{}:{}",
+                file.as_deref().unwrap_or("<input>"),
+                line,
+                column
+            ),
+            TokenOrigin::Macro {
+                token,
+                definition,
+                call_site,
+            } => {
+                match call_site {
+                    Some(call_site) => write!(f, "{}", call_site)?,
+                    None => write!(f, "<unknown>")?,
+                }
+                write!(f, " in expansion of {}", token.to_string().trim_end())?;
+                if let Some(definition) = definition {
+                    write!(f, " (defined at {})", definition)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// A token along with where it came from, if that's known.
+pub type TokenWithOrigin = (Token, Option<Rc<TokenOrigin>>);
+
+/// The origin of an element of a list, if it's known. Elements made by hand
+/// (e.g. in tests) use Provenance::none().
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Provenance(Option<Rc<TokenOrigin>>);
+
+impl Provenance {
+    pub fn new(origin: Option<Rc<TokenOrigin>>) -> Self {
+        Provenance(origin)
+    }
+
+    pub fn none() -> Self {
+        Provenance(None)
+    }
+
+    pub fn get(&self) -> Option<&Rc<TokenOrigin>> {
+        self.0.as_ref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn source(line: usize, column: usize) -> Rc<TokenOrigin> {
+        Rc::new(TokenOrigin::Source {
+            file: Some(Rc::from("story.tex")),
+            line,
+            column,
+        })
+    }
+
+    #[test]
+    fn it_follows_call_sites_to_the_source() {
+        let inner = Rc::new(TokenOrigin::Macro {
+            token: Token::ControlSequence("a".to_string()),
+            definition: Some(source(1, 6)),
+            call_site: Some(source(3, 2)),
+        });
+        let outer = TokenOrigin::Macro {
+            token: Token::ControlSequence("b".to_string()),
+            definition: Some(source(2, 6)),
+            call_site: Some(inner),
+        };
+
+        assert_eq!(outer.source_location(), Some((Some("story.tex"), 3, 2)));
+        assert_eq!(
+            outer.to_string(),
+            "story.tex:3:2 in expansion of \\a (defined at story.tex:1:6) \
+             in expansion of \\b (defined at story.tex:2:6)"
+        );
+    }
+
+    #[test]
+    fn it_compares_provenance_by_origin() {
+        assert_eq!(
+            Provenance::new(Some(source(1, 1))),
+            Provenance::new(Some(source(1, 1)))
+        );
+        assert_ne!(
+            Provenance::new(Some(source(1, 1))),
+            Provenance::new(Some(source(1, 2)))
+        );
+        assert_ne!(Provenance::new(Some(source(1, 1))), Provenance::none());
+    }
+}
@@ -0,0 +1,328 @@
+// Both directions of the lookup between a parameter and the primitive control
+// sequence that names it are the same for every kind of parameter.
+macro_rules! impl_parameter_lookup {
+    ($parameter:ident, $table:ident) => {
+        impl $parameter {
+            pub fn from_primitive(primitive: &str) -> Option<$parameter> {
+                $table
+                    .iter()
+                    .find(|(name, _)| *name == primitive)
+                    .map(|(_, parameter)| *parameter)
+            }
+
+            pub fn get_primitive(self) -> &'static str {
+                $table
+                    .iter()
+                    .find(|(_, parameter)| *parameter == self)
+                    .map(|(name, _)| *name)
+                    .unwrap()
+            }
+        }
+    };
+}
+
+/// TeX's integer parameters. These are read and assigned like count
+/// registers, but are referred to by their own primitive control sequences.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+pub enum IntegerParameter {
+    PreTolerance,
+    Tolerance,
+    LinePenalty,
+    HyphenPenalty,
+    ExHyphenPenalty,
+    ClubPenalty,
+    WidowPenalty,
+    DisplayWidowPenalty,
+    BrokenPenalty,
+    BinOpPenalty,
+    RelPenalty,
+    PreDisplayPenalty,
+    PostDisplayPenalty,
+    InterLinePenalty,
+    DoubleHyphenDemerits,
+    FinalHyphenDemerits,
+    AdjDemerits,
+    Mag,
+    DelimiterFactor,
+    Looseness,
+    Time,
+    Day,
+    Month,
+    Year,
+    ShowBoxBreadth,
+    ShowBoxDepth,
+    HBadness,
+    VBadness,
+    Pausing,
+    TracingOnline,
+    TracingMacros,
+    TracingStats,
+    TracingParagraphs,
+    TracingPages,
+    TracingOutput,
+    TracingLostChars,
+    TracingCommands,
+    TracingRestores,
+    UcHyph,
+    OutputPenalty,
+    MaxDeadCycles,
+    HangAfter,
+    FloatingPenalty,
+    GlobalDefs,
+    Fam,
+    EscapeChar,
+    DefaultHyphenChar,
+    DefaultSkewChar,
+    EndLineChar,
+    NewLineChar,
+    Language,
+    LeftHyphenMin,
+    RightHyphenMin,
+    HoldingInserts,
+    ErrorContextLines,
+    // From e-TeX, since it's very useful for debugging mismatched groups.
+    TracingGroups,
+}
+
+// The primitive control sequence for each of the integer parameters.
+pub const INTEGER_PARAMETERS: &[(&str, IntegerParameter)] = &[
+    ("pretolerance", IntegerParameter::PreTolerance),
+    ("tolerance", IntegerParameter::Tolerance),
+    ("linepenalty", IntegerParameter::LinePenalty),
+    ("hyphenpenalty", IntegerParameter::HyphenPenalty),
+    ("exhyphenpenalty", IntegerParameter::ExHyphenPenalty),
+    ("clubpenalty", IntegerParameter::ClubPenalty),
+    ("widowpenalty", IntegerParameter::WidowPenalty),
+    ("displaywidowpenalty", IntegerParameter::DisplayWidowPenalty),
+    ("brokenpenalty", IntegerParameter::BrokenPenalty),
+    ("binoppenalty", IntegerParameter::BinOpPenalty),
+    ("relpenalty", IntegerParameter::RelPenalty),
+    ("predisplaypenalty", IntegerParameter::PreDisplayPenalty),
+    ("postdisplaypenalty", IntegerParameter::PostDisplayPenalty),
+    ("interlinepenalty", IntegerParameter::InterLinePenalty),
+    (
+        "doublehyphendemerits",
+        IntegerParameter::DoubleHyphenDemerits,
+    ),
+    ("finalhyphendemerits", IntegerParameter::FinalHyphenDemerits),
+    ("adjdemerits", IntegerParameter::AdjDemerits),
+    ("mag", IntegerParameter::Mag),
+    ("delimiterfactor", IntegerParameter::DelimiterFactor),
+    ("looseness", IntegerParameter::Looseness),
+    ("time", IntegerParameter::Time),
+    ("day", IntegerParameter::Day),
+    ("month", IntegerParameter::Month),
+    ("year", IntegerParameter::Year),
+    ("showboxbreadth", IntegerParameter::ShowBoxBreadth),
+    ("showboxdepth", IntegerParameter::ShowBoxDepth),
+    ("hbadness", IntegerParameter::HBadness),
+    ("vbadness", IntegerParameter::VBadness),
+    ("pausing", IntegerParameter::Pausing),
+    ("tracingonline", IntegerParameter::TracingOnline),
+    ("tracingmacros", IntegerParameter::TracingMacros),
+    ("tracingstats", IntegerParameter::TracingStats),
+    ("tracingparagraphs", IntegerParameter::TracingParagraphs),
+    ("tracingpages", IntegerParameter::TracingPages),
+    ("tracingoutput", IntegerParameter::TracingOutput),
+    ("tracinglostchars", IntegerParameter::TracingLostChars),
+    ("tracingcommands", IntegerParameter::TracingCommands),
+    ("tracingrestores", IntegerParameter::TracingRestores),
+    ("uchyph", IntegerParameter::UcHyph),
+    ("outputpenalty", IntegerParameter::OutputPenalty),
+    ("maxdeadcycles", IntegerParameter::MaxDeadCycles),
+    ("hangafter", IntegerParameter::HangAfter),
+    ("floatingpenalty", IntegerParameter::FloatingPenalty),
+    ("globaldefs", IntegerParameter::GlobalDefs),
+    ("fam", IntegerParameter::Fam),
+    ("escapechar", IntegerParameter::EscapeChar),
+    ("defaulthyphenchar", IntegerParameter::DefaultHyphenChar),
+    ("defaultskewchar", IntegerParameter::DefaultSkewChar),
+    ("endlinechar", IntegerParameter::EndLineChar),
+    ("newlinechar", IntegerParameter::NewLineChar),
+    ("language", IntegerParameter::Language),
+    ("lefthyphenmin", IntegerParameter::LeftHyphenMin),
+    ("righthyphenmin", IntegerParameter::RightHyphenMin),
+    ("holdinginserts", IntegerParameter::HoldingInserts),
+    ("errorcontextlines", IntegerParameter::ErrorContextLines),
+    ("tracinggroups", IntegerParameter::TracingGroups),
+];
+
+impl_parameter_lookup!(IntegerParameter, INTEGER_PARAMETERS);
+
+/// TeX's dimen parameters, which are read and assigned like dimen registers.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+pub enum DimenParameter {
+    ParIndent,
+    MathSurround,
+    LineSkipLimit,
+    HSize,
+    VSize,
+    MaxDepth,
+    SplitMaxDepth,
+    BoxMaxDepth,
+    HFuzz,
+    VFuzz,
+    DelimiterShortfall,
+    NullDelimiterSpace,
+    ScriptSpace,
+    PreDisplaySize,
+    DisplayWidth,
+    DisplayIndent,
+    OverfullRule,
+    HangIndent,
+    HOffset,
+    VOffset,
+    EmergencyStretch,
+}
+
+// The primitive control sequence for each of the dimen parameters.
+pub const DIMEN_PARAMETERS: &[(&str, DimenParameter)] = &[
+    ("parindent", DimenParameter::ParIndent),
+    ("mathsurround", DimenParameter::MathSurround),
+    ("lineskiplimit", DimenParameter::LineSkipLimit),
+    ("hsize", DimenParameter::HSize),
+    ("vsize", DimenParameter::VSize),
+    ("maxdepth", DimenParameter::MaxDepth),
+    ("splitmaxdepth", DimenParameter::SplitMaxDepth),
+    ("boxmaxdepth", DimenParameter::BoxMaxDepth),
+    ("hfuzz", DimenParameter::HFuzz),
+    ("vfuzz", DimenParameter::VFuzz),
+    ("delimitershortfall", DimenParameter::DelimiterShortfall),
+    ("nulldelimiterspace", DimenParameter::NullDelimiterSpace),
+    ("scriptspace", DimenParameter::ScriptSpace),
+    ("predisplaysize", DimenParameter::PreDisplaySize),
+    ("displaywidth", DimenParameter::DisplayWidth),
+    ("displayindent", DimenParameter::DisplayIndent),
+    ("overfullrule", DimenParameter::OverfullRule),
+    ("hangindent", DimenParameter::HangIndent),
+    ("hoffset", DimenParameter::HOffset),
+    ("voffset", DimenParameter::VOffset),
+    ("emergencystretch", DimenParameter::EmergencyStretch),
+];
+
+impl_parameter_lookup!(DimenParameter, DIMEN_PARAMETERS);
+
+/// TeX's glue parameters, which are read and assigned like skip registers.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+pub enum GlueParameter {
+    LineSkip,
+    BaselineSkip,
+    ParSkip,
+    AboveDisplaySkip,
+    BelowDisplaySkip,
+    AboveDisplayShortSkip,
+    BelowDisplayShortSkip,
+    LeftSkip,
+    RightSkip,
+    TopSkip,
+    SplitTopSkip,
+    TabSkip,
+    SpaceSkip,
+    XSpaceSkip,
+    ParFillSkip,
+}
+
+// The primitive control sequence for each of the glue parameters.
+pub const GLUE_PARAMETERS: &[(&str, GlueParameter)] = &[
+    ("lineskip", GlueParameter::LineSkip),
+    ("baselineskip", GlueParameter::BaselineSkip),
+    ("parskip", GlueParameter::ParSkip),
+    ("abovedisplayskip", GlueParameter::AboveDisplaySkip),
+    ("belowdisplayskip", GlueParameter::BelowDisplaySkip),
+    (
+        "abovedisplayshortskip",
+        GlueParameter::AboveDisplayShortSkip,
+    ),
+    (
+        "belowdisplayshortskip",
+        GlueParameter::BelowDisplayShortSkip,
+    ),
+    ("leftskip", GlueParameter::LeftSkip),
+    ("rightskip", GlueParameter::RightSkip),
+    ("topskip", GlueParameter::TopSkip),
+    ("splittopskip", GlueParameter::SplitTopSkip),
+    ("tabskip", GlueParameter::TabSkip),
+    ("spaceskip", GlueParameter::SpaceSkip),
+    ("xspaceskip", GlueParameter::XSpaceSkip),
+    ("parfillskip", GlueParameter::ParFillSkip),
+];
+
+impl_parameter_lookup!(GlueParameter, GLUE_PARAMETERS);
+
+/// TeX's muglue parameters, which are read and assigned like muskip
+/// registers.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+pub enum MuGlueParameter {
+    ThinMuSkip,
+    MedMuSkip,
+    ThickMuSkip,
+}
+
+// The primitive control sequence for each of the muglue parameters.
+pub const MU_GLUE_PARAMETERS: &[(&str, MuGlueParameter)] = &[
+    ("thinmuskip", MuGlueParameter::ThinMuSkip),
+    ("medmuskip", MuGlueParameter::MedMuSkip),
+    ("thickmuskip", MuGlueParameter::ThickMuSkip),
+];
+
+impl_parameter_lookup!(MuGlueParameter, MU_GLUE_PARAMETERS);
+
+/// Returns the primitive control sequences of every parameter.
+pub fn all_parameter_primitives() -> impl Iterator<Item = &'static str> {
+    let integers = INTEGER_PARAMETERS.iter().map(|(name, _)| *name);
+    let dimens = DIMEN_PARAMETERS.iter().map(|(name, _)| *name);
+    let glues = GLUE_PARAMETERS.iter().map(|(name, _)| *name);
+    let mu_glues = MU_GLUE_PARAMETERS.iter().map(|(name, _)| *name);
+    integers.chain(dimens).chain(glues).chain(mu_glues)
+}
+
+impl IntegerParameter {
+    /// The value that IniTeX starts with for the parameter. Most parameters
+    /// start at 0.
+    pub fn get_initial_value(self) -> i32 {
+        match self {
+            IntegerParameter::Tolerance => 10000,
+            IntegerParameter::Mag => 1000,
+            IntegerParameter::MaxDeadCycles => 25,
+            IntegerParameter::HangAfter => 1,
+            IntegerParameter::EscapeChar => '\\' as i32,
+            IntegerParameter::EndLineChar => '\r' as i32,
+            // TeX uses the date and time that the run started, but falls
+            // back to noon on 4 July 1776 if it can't find it. We always do
+            // that, so that output doesn't depend on when it was made.
+            IntegerParameter::Time => 12 * 60,
+            IntegerParameter::Day => 4,
+            IntegerParameter::Month => 7,
+            IntegerParameter::Year => 1776,
+            _ => 0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_looks_up_parameters_by_primitive() {
+        assert_eq!(
+            IntegerParameter::from_primitive("showboxdepth"),
+            Some(IntegerParameter::ShowBoxDepth)
+        );
+        assert_eq!(IntegerParameter::from_primitive("count"), None);
+        assert_eq!(
+            IntegerParameter::TracingMacros.get_primitive(),
+            "tracingmacros"
+        );
+        assert_eq!(
+            DimenParameter::from_primitive("hsize"),
+            Some(DimenParameter::HSize)
+        );
+        assert_eq!(GlueParameter::BaselineSkip.get_primitive(), "baselineskip");
+        assert_eq!(
+            MuGlueParameter::from_primitive("medmuskip"),
+            Some(MuGlueParameter::MedMuSkip)
+        );
+        assert_eq!(all_parameter_primitives().count(), 95);
+    }
+}
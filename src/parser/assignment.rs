@@ -2,11 +2,20 @@ use std::rc::Rc;
 
 use crate::category::Category;
 use crate::dimension::Dimen;
+use crate::error::{TeXErrorKind, TeXResult};
 use crate::font::Font;
 use crate::font_metrics::FontMetrics;
 use crate::math_code::MathCode;
 use crate::parser::Parser;
+use crate::state::TokenDefinition;
 use crate::token::Token;
+use crate::variable::CodeVariable;
+
+enum Arithmetic {
+    Advance,
+    Multiply,
+    Divide,
+}
 
 enum AtClause {
     Natural,
@@ -14,241 +23,419 @@ enum AtClause {
     At(Dimen),
 }
 
+// The prefixes (\global, \long and \outer) that came before an assignment.
+#[derive(Clone, Copy, Default)]
+struct Prefixes {
+    global: bool,
+    long: bool,
+    outer: bool,
+}
+
 pub struct SpecialVariables<'a> {
     pub prev_depth: Option<&'a mut Dimen>,
 }
 
 impl<'a> Parser<'a> {
-    fn is_variable_assignment_head(&mut self) -> bool {
-        self.is_integer_variable_head() || self.is_dimen_variable_head()
+    fn is_variable_assignment_head(&mut self) -> TeXResult<bool> {
+        Ok(self.is_integer_variable_head()?
+            || self.is_dimen_variable_head()?
+            || self.is_glue_variable_head()?
+            || self.is_mu_glue_variable_head()?
+            || self.is_token_list_variable_head()?)
     }
 
-    fn is_macro_assignment_head(&mut self) -> bool {
-        self.is_next_expanded_token_in_set_of_primitives(&["def"])
+    fn is_macro_assignment_head(&mut self) -> TeXResult<bool> {
+        self.is_next_expanded_token_in_set_of_primitives(&[
+            "def", "gdef", "edef", "xdef",
+        ])
     }
 
-    fn is_let_assignment_head(&mut self) -> bool {
+    fn is_let_assignment_head(&mut self) -> TeXResult<bool> {
         self.is_next_expanded_token_in_set_of_primitives(&["let"])
     }
 
-    fn is_arithmetic_head(&mut self) -> bool {
+    fn is_arithmetic_head(&mut self) -> TeXResult<bool> {
         self.is_next_expanded_token_in_set_of_primitives(&[
             "advance", "multiply", "divide",
         ])
     }
 
-    fn is_shorthand_definition_head(&mut self) -> bool {
-        self.is_next_expanded_token_in_set_of_primitives(&["mathchardef"])
+    fn is_shorthand_definition_head(&mut self) -> TeXResult<bool> {
+        self.is_next_expanded_token_in_set_of_primitives(&[
+            "mathchardef",
+            "chardef",
+            "countdef",
+            "dimendef",
+            "skipdef",
+            "muskipdef",
+            "toksdef",
+        ])
     }
 
-    fn is_code_assignment_head(&mut self) -> bool {
-        self.is_next_expanded_token_in_set_of_primitives(&["mathcode"])
+    fn is_code_assignment_head(&mut self) -> TeXResult<bool> {
+        self.is_code_variable_head()
     }
 
-    fn is_font_assignment_head(&mut self) -> bool {
+    fn is_font_assignment_head(&mut self) -> TeXResult<bool> {
         self.is_next_expanded_token_in_set_of_primitives(&["font"])
     }
 
-    fn is_fontdef_assignment_head(&mut self) -> bool {
-        match self.peek_expanded_token() {
-            Some(tok) => self.state.get_fontdef(&tok).is_some(),
-            None => false,
+    fn is_fontdef_assignment_head(&mut self) -> TeXResult<bool> {
+        match self.peek_expanded_token()? {
+            Some(tok) => Ok(self.state.get_fontdef(&tok).is_some()),
+            None => Ok(false),
         }
     }
 
-    fn is_intimate_assignment_head(&mut self) -> bool {
+    fn is_intimate_assignment_head(&mut self) -> TeXResult<bool> {
         self.is_next_expanded_token_in_set_of_primitives(&["prevdepth"])
     }
 
-    fn is_global_assignment_head(&mut self) -> bool {
+    fn is_global_assignment_head(&mut self) -> TeXResult<bool> {
         self.is_intimate_assignment_head()
     }
 
-    fn is_simple_assignment_head(&mut self) -> bool {
-        self.is_let_assignment_head()
-            || self.is_variable_assignment_head()
-            || self.is_arithmetic_head()
-            || self.is_box_assignment_head()
-            || self.is_shorthand_definition_head()
-            || self.is_code_assignment_head()
-            || self.is_font_assignment_head()
-            || self.is_fontdef_assignment_head()
-            || self.is_global_assignment_head()
+    fn is_simple_assignment_head(&mut self) -> TeXResult<bool> {
+        Ok(self.is_let_assignment_head()?
+            || self.is_variable_assignment_head()?
+            || self.is_arithmetic_head()?
+            || self.is_box_assignment_head()?
+            || self.is_shorthand_definition_head()?
+            || self.is_code_assignment_head()?
+            || self.is_font_assignment_head()?
+            || self.is_fontdef_assignment_head()?
+            || self.is_global_assignment_head()?)
     }
 
-    fn is_assignment_prefix(&mut self) -> bool {
-        self.is_next_expanded_token_in_set_of_primitives(&["global"])
+    fn is_assignment_prefix(&mut self) -> TeXResult<bool> {
+        self.is_next_expanded_token_in_set_of_primitives(&[
+            "global", "long", "outer",
+        ])
     }
 
-    pub fn is_assignment_head(&mut self) -> bool {
-        self.is_assignment_prefix()
-            || self.is_macro_assignment_head()
-            || self.is_simple_assignment_head()
+    pub fn is_assignment_head(&mut self) -> TeXResult<bool> {
+        Ok(self.is_assignment_prefix()?
+            || self.is_macro_assignment_head()?
+            || self.is_simple_assignment_head()?)
     }
 
-    fn parse_variable_assignment(&mut self, global: bool) {
-        if self.is_integer_variable_head() {
-            let variable = self.parse_integer_variable();
-            self.parse_equals_expanded();
-            let value = self.parse_number();
+    fn parse_variable_assignment(&mut self, global: bool) -> TeXResult<()> {
+        if self.is_integer_variable_head()? {
+            let variable = self.parse_integer_variable()?;
+            self.parse_equals_expanded()?;
+            let value = self.parse_number()?;
             variable.set(self.state, global, value);
-        } else if self.is_dimen_variable_head() {
-            let variable = self.parse_dimen_variable();
-            self.parse_equals_expanded();
-            let value = self.parse_dimen();
+        } else if self.is_dimen_variable_head()? {
+            let variable = self.parse_dimen_variable()?;
+            self.parse_equals_expanded()?;
+            let value = self.parse_dimen()?;
             variable.set(self.state, global, value);
+        } else if self.is_glue_variable_head()? {
+            let variable = self.parse_glue_variable()?;
+            self.parse_equals_expanded()?;
+            let value = self.parse_glue()?;
+            variable.set(self.state, global, &value);
+        } else if self.is_mu_glue_variable_head()? {
+            let variable = self.parse_mu_glue_variable()?;
+            self.parse_equals_expanded()?;
+            let value = self.parse_mu_glue()?;
+            variable.set(self.state, global, &value);
+        } else if self.is_token_list_variable_head()? {
+            let head = self.peek_expanded_token()?.unwrap();
+            let variable = self.parse_token_list_variable()?;
+            self.parse_equals_expanded()?;
+            self.parse_filler_expanded()?;
+            // Token lists can be copied from another token list variable
+            // without going through a general text.
+            let value = if self.is_token_list_variable_head()? {
+                self.parse_token_list_variable()?.get(self.state)
+            } else {
+                Rc::new(self.parse_unexpanded_general_text(&head)?)
+            };
+            variable.set(self.state, global, &value);
         } else {
-            panic!("unimplemented");
+            // This is only called after is_variable_assignment_head().
+            unreachable!("Invalid variable assignment head");
         }
+        Ok(())
     }
 
     // Parses a control sequence or special char token to use for \def or \let
     // names
-    fn parse_unexpanded_control_sequence(&mut self) -> Token {
-        match self.lex_unexpanded_token() {
-            Some(token) => match token {
-                Token::ControlSequence(_) => token,
-                Token::Char(_, Category::Active) => token,
-                _ => panic!(
-                    "Invalid token found while looking for control sequence: {:?}",
-                    token
-                ),
-            },
-            None => panic!("EOF found parsing control sequence"),
+    fn parse_unexpanded_control_sequence(&mut self) -> TeXResult<Token> {
+        match self.lex_unexpanded_token()? {
+            Some(Token::ControlSequence(name)) => {
+                Ok(Token::ControlSequence(name))
+            }
+            Some(Token::Char(ch, Category::Active)) => {
+                Ok(Token::Char(ch, Category::Active))
+            }
+            rest => {
+                // Like TeX, we read the token again and define a control
+                // sequence that can't be typed instead.
+                self.report_error(TeXErrorKind::MissingControlSequence)?;
+                if let Some(token) = rest {
+                    self.add_upcoming_token(token);
+                }
+                Ok(Token::ControlSequence("inaccessible ".to_string()))
+            }
         }
     }
 
-    fn parse_let_assignment(&mut self, global: bool) {
-        let tok = self.lex_expanded_token().unwrap();
+    fn parse_let_assignment(&mut self, global: bool) -> TeXResult<()> {
+        let tok = self.lex_expanded_token()?.unwrap();
 
         if self.state.is_token_equal_to_prim(&tok, "let") {
-            let let_name = self.parse_unexpanded_control_sequence();
-            self.parse_equals_unexpanded();
-            self.parse_optional_space_unexpanded();
-            let let_value = self.lex_unexpanded_token().unwrap();
-
-            self.state.set_let(global, &let_name, &let_value);
+            let let_name = self.parse_unexpanded_control_sequence()?;
+            self.parse_equals_unexpanded()?;
+            self.parse_optional_space_unexpanded()?;
+            if let Some(let_value) = self.lex_unexpanded_token()? {
+                self.state.set_let(global, &let_name, &let_value);
+            }
         } else {
-            panic!("unimplemented");
+            // This is only called after is_let_assignment_head().
+            unreachable!("Invalid let assignment head: {:?}", tok);
         }
+        Ok(())
     }
 
-    fn parse_arithmetic(&mut self, global: bool) {
-        let tok = self.lex_expanded_token().unwrap();
-        let variable = self.parse_integer_variable();
-        self.parse_optional_keyword_expanded("by");
-        self.parse_optional_spaces_expanded();
+    fn parse_arithmetic_by(&mut self) -> TeXResult<()> {
+        self.parse_optional_keyword_expanded("by")?;
+        self.parse_optional_spaces_expanded()
+    }
 
-        if self.state.is_token_equal_to_prim(&tok, "advance") {
-            let number = self.parse_number();
-            // TODO(xymostech): ensure this doesn't overflow
-            variable.set(self.state, global, variable.get(self.state) + number);
+    fn parse_arithmetic(&mut self, global: bool) -> TeXResult<()> {
+        let tok = self.lex_expanded_token()?.unwrap();
+        let op = if self.state.is_token_equal_to_prim(&tok, "advance") {
+            Arithmetic::Advance
         } else if self.state.is_token_equal_to_prim(&tok, "multiply") {
-            let number = self.parse_number();
-            // TODO(xymostech): ensure this doesn't overflow
-            variable.set(self.state, global, variable.get(self.state) * number);
+            Arithmetic::Multiply
         } else if self.state.is_token_equal_to_prim(&tok, "divide") {
-            let number = self.parse_number();
-            variable.set(self.state, global, variable.get(self.state) / number);
+            Arithmetic::Divide
         } else {
             panic!("Invalid arithmetic head: {:?}", tok);
+        };
+
+        // Like TeX, when the result doesn't fit (or we divide by zero) we
+        // report an error and leave the variable unchanged.
+        if self.is_integer_variable_head()? {
+            let variable = self.parse_integer_variable()?;
+            self.parse_arithmetic_by()?;
+            let value = variable.get(self.state);
+            let number = self.parse_number()?;
+            let result = match op {
+                Arithmetic::Advance => value.checked_add(number),
+                Arithmetic::Multiply => value.checked_mul(number),
+                Arithmetic::Divide => value.checked_div(number),
+            }
+            .filter(|result| *result != i32::MIN);
+            match result {
+                Some(result) => variable.set(self.state, global, result),
+                None => self.report_error(TeXErrorKind::ArithmeticOverflow)?,
+            }
+        } else if self.is_dimen_variable_head()? {
+            let variable = self.parse_dimen_variable()?;
+            self.parse_arithmetic_by()?;
+            let value = variable.get(self.state);
+            let result = match op {
+                Arithmetic::Advance => value.checked_add(self.parse_dimen()?),
+                Arithmetic::Multiply => value.checked_mul(self.parse_number()?),
+                Arithmetic::Divide => value.checked_div(self.parse_number()?),
+            };
+            match result {
+                Some(result) => variable.set(self.state, global, result),
+                None => self.report_error(TeXErrorKind::ArithmeticOverflow)?,
+            }
+        } else if self.is_glue_variable_head()? {
+            let variable = self.parse_glue_variable()?;
+            self.parse_arithmetic_by()?;
+            let value = variable.get(self.state);
+            let result = match op {
+                Arithmetic::Advance => value.checked_add(self.parse_glue()?),
+                Arithmetic::Multiply => value.checked_mul(self.parse_number()?),
+                Arithmetic::Divide => value.checked_div(self.parse_number()?),
+            };
+            match result {
+                Some(result) => variable.set(self.state, global, &result),
+                None => self.report_error(TeXErrorKind::ArithmeticOverflow)?,
+            }
+        } else if self.is_mu_glue_variable_head()? {
+            let variable = self.parse_mu_glue_variable()?;
+            self.parse_arithmetic_by()?;
+            let value = variable.get(self.state);
+            let result = match op {
+                Arithmetic::Advance => value.checked_add(self.parse_mu_glue()?),
+                Arithmetic::Multiply => value.checked_mul(self.parse_number()?),
+                Arithmetic::Divide => value.checked_div(self.parse_number()?),
+            };
+            match result {
+                Some(result) => variable.set(self.state, global, &result),
+                None => self.report_error(TeXErrorKind::ArithmeticOverflow)?,
+            }
+        } else if let Some(token) = self.lex_expanded_token()? {
+            self.report_error(TeXErrorKind::CantUseAfter(token, tok))?;
         }
+        Ok(())
     }
 
-    fn parse_macro_assignment(&mut self, global: bool) {
-        let tok = self.lex_expanded_token().unwrap();
+    fn parse_macro_assignment(&mut self, prefixes: Prefixes) -> TeXResult<()> {
+        let tok = self.lex_expanded_token()?.unwrap();
 
-        if self.state.is_token_equal_to_prim(&tok, "def") {
-            let control_sequence = self.parse_unexpanded_control_sequence();
-            let makro = self.parse_macro_definition();
+        // \gdef and \xdef are the global versions of \def and \edef.
+        let global = prefixes.global
+            || self.state.is_token_equal_to_prim(&tok, "gdef")
+            || self.state.is_token_equal_to_prim(&tok, "xdef");
+        let expand = self.state.is_token_equal_to_prim(&tok, "edef")
+            || self.state.is_token_equal_to_prim(&tok, "xdef");
 
-            self.state
-                .set_macro(global, &control_sequence, &Rc::new(makro));
+        let control_sequence = self.parse_unexpanded_control_sequence()?;
+        let definition_origin = self.last_token_origin();
+        let makro = if expand {
+            self.parse_expanded_macro_definition(&control_sequence)?
         } else {
-            panic!("unimplemented");
+            self.parse_macro_definition(&control_sequence)?
         }
+        .with_prefixes(prefixes.long, prefixes.outer)
+        .with_definition_origin(definition_origin);
+
+        self.state
+            .set_macro(global, &control_sequence, &Rc::new(makro));
+        Ok(())
     }
 
-    fn is_box_assignment_head(&mut self) -> bool {
+    fn is_box_assignment_head(&mut self) -> TeXResult<bool> {
         self.is_next_expanded_token_in_set_of_primitives(&["setbox"])
     }
 
-    fn parse_box_assignment(&mut self, global: bool) {
-        let tok = self.lex_expanded_token().unwrap();
+    fn parse_box_assignment(&mut self, global: bool) -> TeXResult<()> {
+        let tok = self.lex_expanded_token()?.unwrap();
 
         if !self.state.is_token_equal_to_prim(&tok, "setbox") {
             panic!("Invalid box assignment head: {:?}", tok);
         }
 
-        let box_index = self.parse_8bit_number();
-        self.parse_equals_expanded();
-        let maybe_tex_box = self.parse_box();
+        let box_index = self.parse_8bit_number()?;
+        self.parse_equals_expanded()?;
+
+        // Like TeX, when \setbox builds a new box, the \afterassignment token
+        // is read right after the { of the box instead of after the box is
+        // done. For \box and \copy, it's still read after the assignment.
+        self.box_after_assignment_token = self.after_assignment_token.take();
+        let maybe_tex_box = self.parse_box()?;
+        if let Some(token) = self.box_after_assignment_token.take() {
+            self.after_assignment_token = Some(token);
+        }
 
         if let Some(tex_box) = maybe_tex_box {
             self.state.set_box(global, box_index, tex_box);
         }
+        Ok(())
     }
 
-    fn parse_shorthand_definition(&mut self, global: bool) {
-        let tok = self.lex_expanded_token().unwrap();
+    fn parse_shorthand_definition(&mut self, global: bool) -> TeXResult<()> {
+        let tok = self.lex_expanded_token()?.unwrap();
+        let control_sequence = self.parse_unexpanded_control_sequence()?;
+
+        // Like TeX, the control sequence means \relax while we read the
+        // number, so seeing it again ends the number instead of expanding
+        // its old meaning.
+        self.state.set_shorthand_definition(
+            global,
+            &control_sequence,
+            &TokenDefinition::Primitive("relax"),
+        );
+        self.parse_equals_expanded()?;
 
         if self.state.is_token_equal_to_prim(&tok, "mathchardef") {
-            let control_sequence = self.parse_unexpanded_control_sequence();
-            self.parse_equals_expanded();
-            let code_value = self.parse_15bit_number();
+            let code_value = self.parse_15bit_number()?;
 
             self.state.set_math_chardef(
                 global,
                 &control_sequence,
                 &MathCode::from_number(code_value as u32),
             );
-        } else {
-            panic!("unimplemented!");
+            return Ok(());
         }
-    }
 
-    fn parse_code_assignment(&mut self, global: bool) {
-        let tok = self.lex_expanded_token().unwrap();
-
-        if self.state.is_token_equal_to_prim(&tok, "mathcode") {
-            let num = self.parse_8bit_number();
-            self.parse_equals_expanded();
-            let code_value = self.parse_number();
-
-            self.state.set_math_code(
+        if self.state.is_token_equal_to_prim(&tok, "chardef") {
+            let ch = self.parse_char_code()?;
+            self.state.set_shorthand_definition(
                 global,
-                num as char,
-                &MathCode::from_number(code_value as u32),
+                &control_sequence,
+                &TokenDefinition::Char(ch),
             );
+            return Ok(());
+        }
+
+        let index = self.parse_8bit_number()?;
+        let definition = if self.state.is_token_equal_to_prim(&tok, "countdef")
+        {
+            TokenDefinition::CountRegister(index)
+        } else if self.state.is_token_equal_to_prim(&tok, "dimendef") {
+            TokenDefinition::DimenRegister(index)
+        } else if self.state.is_token_equal_to_prim(&tok, "skipdef") {
+            TokenDefinition::SkipRegister(index)
+        } else if self.state.is_token_equal_to_prim(&tok, "muskipdef") {
+            TokenDefinition::MuSkipRegister(index)
+        } else if self.state.is_token_equal_to_prim(&tok, "toksdef") {
+            TokenDefinition::ToksRegister(index)
         } else {
-            panic!("unimplemented");
+            panic!("Invalid shorthand definition head: {:?}", tok);
+        };
+
+        self.state.set_shorthand_definition(
+            global,
+            &control_sequence,
+            &definition,
+        );
+        Ok(())
+    }
+
+    fn parse_code_assignment(&mut self, global: bool) -> TeXResult<()> {
+        let variable = self.parse_code_variable()?;
+        self.parse_equals_expanded()?;
+        let mut value = self.parse_number()?;
+
+        let max_value = variable.get_max_value();
+        if let CodeVariable::DelimiterCode(_) = variable {
+            if value > max_value {
+                self.report_error(TeXErrorKind::InvalidDelimiterCode(value))?;
+                value = 0;
+            }
+        } else if value < 0 || value > max_value {
+            self.report_error(TeXErrorKind::InvalidCode(value, max_value))?;
+            value = 0;
         }
+
+        variable.set(self.state, global, value);
+        Ok(())
     }
 
-    fn parse_at_clause(&mut self) -> AtClause {
-        if self.parse_optional_keyword_expanded("at") {
-            let dimen = self.parse_dimen();
-            AtClause::At(dimen)
-        } else if self.parse_optional_keyword_expanded("scaled") {
-            let number = self.parse_number();
-            if number < 0 || number > 32768 {
-                panic!("Invalid magnification: {}", number);
+    fn parse_at_clause(&mut self) -> TeXResult<AtClause> {
+        if self.parse_optional_keyword_expanded("at")? {
+            let dimen = self.parse_dimen()?;
+            Ok(AtClause::At(dimen))
+        } else if self.parse_optional_keyword_expanded("scaled")? {
+            let number = self.parse_number()?;
+            if number <= 0 || number > 32768 {
+                self.report_error(TeXErrorKind::IllegalMagnification(number))?;
+                return Ok(AtClause::Scaled(1000));
             }
-            AtClause::Scaled(number as u16)
+            Ok(AtClause::Scaled(number as u16))
         } else {
-            self.parse_optional_spaces_expanded();
-            AtClause::Natural
+            self.parse_optional_spaces_expanded()?;
+            Ok(AtClause::Natural)
         }
     }
 
-    fn parse_font_assignment(&mut self, global: bool) {
-        let tok = self.lex_expanded_token().unwrap();
+    fn parse_font_assignment(&mut self, global: bool) -> TeXResult<()> {
+        let tok = self.lex_expanded_token()?.unwrap();
 
         if !self.state.is_token_equal_to_prim(&tok, "font") {
             panic!("Invalid font assignment head");
         }
 
-        let fontdef_name = self.parse_unexpanded_control_sequence();
+        let fontdef_name = self.parse_unexpanded_control_sequence()?;
 
         // The TeXbook says that we need to "take precautions so that
         // \font\cs=name\cs won't expand the second \cs until the assignments
@@ -265,18 +452,30 @@ impl<'a> Parser<'a> {
             &Token::ControlSequence("relax".to_string()),
         );
 
-        self.parse_equals_expanded();
+        self.parse_equals_expanded()?;
 
-        let font_name = self.parse_file_name();
-        let at = self.parse_at_clause();
+        let font_name = self.parse_file_name()?;
+        let at = self.parse_at_clause()?;
 
-        let font_metrics = FontMetrics::from_font(&Font {
-            font_name: font_name.clone(),
-            // Since we're only accessing the design size, the scale for
-            // the font doesn't matter here.
-            scale: Dimen::zero(),
-        })
-        .unwrap_or_else(|| panic!("Invalid font name: {}", font_name));
+        let maybe_font_metrics = FontMetrics::from_font_with_provider(
+            &Font {
+                font_name: font_name.clone(),
+                // Since we're only accessing the design size, the scale for
+                // the font doesn't matter here.
+                scale: Dimen::zero(),
+            },
+            &*self.state.get_font_provider(),
+        );
+        let font_metrics = match maybe_font_metrics {
+            Some(font_metrics) => font_metrics,
+            None => {
+                // The font name stays assigned to \relax.
+                return self.report_error(TeXErrorKind::FontNotLoadable(
+                    fontdef_name,
+                    font_name,
+                ));
+            }
+        };
 
         let design_size = 65536.0 * font_metrics.get_design_size();
 
@@ -301,46 +500,56 @@ impl<'a> Parser<'a> {
         };
 
         self.state.set_fontdef(global, &fontdef_name, &font);
+        self.state.set_font_identifier(&font, &fontdef_name);
+        Ok(())
     }
 
-    fn parse_fontdef_assignment(&mut self, global: bool) {
-        let tok = self.lex_expanded_token().unwrap();
+    fn parse_fontdef_assignment(&mut self, global: bool) -> TeXResult<()> {
+        let tok = self.lex_expanded_token()?.unwrap();
         let font = self.state.get_fontdef(&tok).unwrap();
         self.state.set_current_font(global, &font);
+        Ok(())
     }
 
     fn parse_intimate_assignment(
         &mut self,
         maybe_special_vars: Option<SpecialVariables>,
-    ) {
-        let tok = self.lex_expanded_token().unwrap();
+    ) -> TeXResult<()> {
+        let tok = self.lex_expanded_token()?.unwrap();
 
         if self.state.is_token_equal_to_prim(&tok, "prevdepth") {
-            self.parse_equals_expanded();
-            let dimen = self.parse_dimen();
-
-            if let Some(special_vars) = maybe_special_vars {
-                if let Some(prev_depth) = special_vars.prev_depth {
-                    *prev_depth = dimen;
-                } else {
-                    panic!("Invalid prevdepth assignment");
+            // \prevdepth can only be set in vertical mode, which is the only
+            // place where we're passed somewhere to store it.
+            let prev_depth = match maybe_special_vars
+                .and_then(|special_vars| special_vars.prev_depth)
+            {
+                Some(prev_depth) => prev_depth,
+                None => {
+                    return self.report_error(TeXErrorKind::CantUseInMode(
+                        tok,
+                        "non-vertical mode",
+                    ))
                 }
-            } else {
-                panic!("Invalid prevdepth assignment");
-            }
+            };
+
+            self.parse_equals_expanded()?;
+            *prev_depth = self.parse_dimen()?;
         } else {
-            panic!("unimplemented");
+            // This is only called after is_intimate_assignment_head().
+            unreachable!("Invalid intimate assignment head: {:?}", tok);
         }
+        Ok(())
     }
 
     fn parse_global_assignment(
         &mut self,
         special_vars: Option<SpecialVariables>,
-    ) {
-        if self.is_intimate_assignment_head() {
+    ) -> TeXResult<()> {
+        if self.is_intimate_assignment_head()? {
             self.parse_intimate_assignment(special_vars)
         } else {
-            panic!("unimplemented");
+            // This is only called after is_global_assignment_head().
+            unreachable!("Invalid global assignment head");
         }
     }
 
@@ -348,55 +557,108 @@ impl<'a> Parser<'a> {
         &mut self,
         global: bool,
         special_vars: Option<SpecialVariables>,
-    ) {
-        if self.is_variable_assignment_head() {
+    ) -> TeXResult<()> {
+        if self.is_variable_assignment_head()? {
             self.parse_variable_assignment(global)
-        } else if self.is_let_assignment_head() {
+        } else if self.is_let_assignment_head()? {
             self.parse_let_assignment(global)
-        } else if self.is_arithmetic_head() {
+        } else if self.is_arithmetic_head()? {
             self.parse_arithmetic(global)
-        } else if self.is_box_assignment_head() {
+        } else if self.is_box_assignment_head()? {
             self.parse_box_assignment(global)
-        } else if self.is_shorthand_definition_head() {
+        } else if self.is_shorthand_definition_head()? {
             self.parse_shorthand_definition(global)
-        } else if self.is_code_assignment_head() {
+        } else if self.is_code_assignment_head()? {
             self.parse_code_assignment(global)
-        } else if self.is_font_assignment_head() {
+        } else if self.is_font_assignment_head()? {
             self.parse_font_assignment(global)
-        } else if self.is_fontdef_assignment_head() {
+        } else if self.is_fontdef_assignment_head()? {
             self.parse_fontdef_assignment(global)
-        } else if self.is_global_assignment_head() {
+        } else if self.is_global_assignment_head()? {
             self.parse_global_assignment(special_vars)
         } else {
-            panic!("unimplemented");
+            // This is only called after is_simple_assignment_head().
+            unreachable!("Invalid simple assignment head");
         }
     }
 
-    fn parse_assignment_global(
+    fn parse_prefixed_assignment(
         &mut self,
-        global: bool,
+        mut prefixes: Prefixes,
         special_vars: Option<SpecialVariables>,
-    ) {
-        if self.is_macro_assignment_head() {
-            self.parse_macro_assignment(global)
-        } else if self.is_simple_assignment_head() {
-            self.parse_simple_assignment(global, special_vars)
+    ) -> TeXResult<()> {
+        if self.is_macro_assignment_head()? {
+            self.parse_macro_assignment(prefixes)
+        } else if self.is_simple_assignment_head()? {
+            if prefixes.long || prefixes.outer {
+                // TeX ignores \long and \outer for anything other than
+                // macro definitions, but still does the assignment.
+                if let Some(next) = self.peek_expanded_token()? {
+                    self.report_error(TeXErrorKind::CantUseLongOrOuterWith(
+                        next,
+                    ))?;
+                }
+            }
+            self.parse_simple_assignment(prefixes.global, special_vars)
         } else {
-            let tok = self.lex_expanded_token().unwrap();
+            let tok = self.lex_expanded_token()?.unwrap();
             if self.state.is_token_equal_to_prim(&tok, "global") {
-                if self.is_assignment_head() {
-                    self.parse_assignment_global(true, special_vars);
-                } else {
-                    panic!("Non-assignment head found after \\global");
-                }
+                prefixes.global = true;
+            } else if self.state.is_token_equal_to_prim(&tok, "long") {
+                prefixes.long = true;
+            } else if self.state.is_token_equal_to_prim(&tok, "outer") {
+                prefixes.outer = true;
             } else {
                 panic!("Invalid start found in parse_assignment");
             }
+
+            if self.is_assignment_head()? {
+                self.parse_prefixed_assignment(prefixes, special_vars)
+            } else {
+                // TeX ignores the prefixes and carries on with whatever came
+                // after them.
+                match self.peek_expanded_token()? {
+                    Some(next) => {
+                        self.report_error(TeXErrorKind::CantUsePrefixWith(next))
+                    }
+                    None => Ok(()),
+                }
+            }
         }
     }
 
-    pub fn parse_assignment(&mut self, special_vars: Option<SpecialVariables>) {
-        self.parse_assignment_global(false, special_vars);
+    pub fn parse_assignment(
+        &mut self,
+        special_vars: Option<SpecialVariables>,
+    ) -> TeXResult<()> {
+        self.parse_prefixed_assignment(Prefixes::default(), special_vars)?;
+
+        if let Some(token) = self.after_assignment_token.take() {
+            self.add_upcoming_token(token);
+        }
+        Ok(())
+    }
+
+    pub fn is_after_assignment_head(&mut self) -> TeXResult<bool> {
+        self.is_next_expanded_token_in_set_of_primitives(&["afterassignment"])
+    }
+
+    /// Saves the token after \afterassignment, to be read right after the
+    /// next assignment. Only the most recently saved token is kept.
+    pub fn parse_after_assignment(&mut self) -> TeXResult<()> {
+        self.lex_expanded_token()?;
+        if let Some(token) = self.lex_unexpanded_token()? {
+            self.after_assignment_token = Some(token);
+        }
+        Ok(())
+    }
+
+    // Called after the { of a box that's being built by \setbox, to read
+    // the \afterassignment token there.
+    pub fn insert_box_after_assignment_token(&mut self) {
+        if let Some(token) = self.box_after_assignment_token.take() {
+            self.add_upcoming_token(token);
+        }
     }
 }
 
@@ -405,14 +667,17 @@ mod tests {
     use super::*;
 
     use crate::category::Category;
-    use crate::dimension::{Dimen, Unit};
+    use crate::dimension::{
+        Dimen, FilDimen, FilKind, MuDimen, MuSpringDimen, SpringDimen, Unit,
+    };
+    use crate::glue::{Glue, MuGlue};
     use crate::makro::{Macro, MacroListElem};
     use crate::testing::with_parser;
 
     #[test]
     fn it_assigns_macros() {
         with_parser(&["\\def\\a #1x{#1y#1}%"], |parser| {
-            parser.parse_assignment(None);
+            parser.parse_assignment(None).unwrap();
 
             assert_eq!(
                 *parser
@@ -444,9 +709,9 @@ mod tests {
     fn it_sets_global_defs() {
         with_parser(&["\\global\\def\\a{x}%"], |parser| {
             parser.state.push_state();
-            assert!(parser.is_assignment_head());
-            parser.parse_assignment(None);
-            assert_eq!(parser.lex_unexpanded_token(), None);
+            assert!(parser.is_assignment_head().unwrap());
+            parser.parse_assignment(None).unwrap();
+            assert_eq!(parser.lex_unexpanded_token().unwrap(), None);
             parser.state.pop_state();
 
             assert_eq!(
@@ -465,10 +730,232 @@ mod tests {
         });
     }
 
+    #[test]
+    fn it_sets_gdefs_and_xdefs() {
+        with_parser(&[r"\def\b{y}%", r"\gdef\a{\b}\xdef\c{\b}%"], |parser| {
+            parser.parse_assignment(None).unwrap();
+            parser.state.push_state();
+            parser.parse_assignment(None).unwrap();
+            parser.parse_assignment(None).unwrap();
+            parser.state.pop_state();
+
+            assert_eq!(
+                *parser
+                    .state
+                    .get_macro(&Token::ControlSequence("a".to_string()))
+                    .unwrap(),
+                Macro::new(
+                    vec![],
+                    vec![MacroListElem::Token(Token::ControlSequence(
+                        "b".to_string()
+                    ))]
+                )
+            );
+            assert_eq!(
+                *parser
+                    .state
+                    .get_macro(&Token::ControlSequence("c".to_string()))
+                    .unwrap(),
+                Macro::new(
+                    vec![],
+                    vec![MacroListElem::Token(Token::Char(
+                        'y',
+                        Category::Letter
+                    ))]
+                )
+            );
+        });
+    }
+
+    #[test]
+    fn it_expands_edefs() {
+        with_parser(
+            &[
+                r"\def\b#1{#1y}\def\c{z}%",
+                r"\edef\a#1{\b x\noexpand\c#1\c}%",
+            ],
+            |parser| {
+                parser.parse_assignment(None).unwrap();
+                parser.parse_assignment(None).unwrap();
+                parser.parse_assignment(None).unwrap();
+
+                assert_eq!(
+                    *parser
+                        .state
+                        .get_macro(&Token::ControlSequence("a".to_string()))
+                        .unwrap(),
+                    Macro::new(
+                        vec![MacroListElem::Parameter(1)],
+                        vec![
+                            MacroListElem::Token(Token::Char(
+                                'x',
+                                Category::Letter
+                            )),
+                            MacroListElem::Token(Token::Char(
+                                'y',
+                                Category::Letter
+                            )),
+                            MacroListElem::Token(Token::ControlSequence(
+                                "c".to_string()
+                            )),
+                            MacroListElem::Parameter(1),
+                            MacroListElem::Token(Token::Char(
+                                'z',
+                                Category::Letter
+                            )),
+                        ]
+                    )
+                );
+            },
+        );
+    }
+
+    #[test]
+    fn it_doesnt_expand_the_in_edefs() {
+        with_parser(
+            &[r"\def\a{x}\toks0={\a#}%", r"\edef\b{\the\toks0 \a}%"],
+            |parser| {
+                parser.parse_assignment(None).unwrap();
+                parser.parse_assignment(None).unwrap();
+                parser.parse_assignment(None).unwrap();
+
+                assert_eq!(
+                    *parser
+                        .state
+                        .get_macro(&Token::ControlSequence("b".to_string()))
+                        .unwrap(),
+                    Macro::new(
+                        vec![],
+                        vec![
+                            MacroListElem::Token(Token::ControlSequence(
+                                "a".to_string()
+                            )),
+                            MacroListElem::Token(Token::Char(
+                                '#',
+                                Category::Parameter
+                            )),
+                            MacroListElem::Token(Token::Char(
+                                'x',
+                                Category::Letter
+                            )),
+                        ]
+                    )
+                );
+            },
+        );
+    }
+
+    #[test]
+    fn it_reads_after_assignment_tokens_after_assignments() {
+        with_parser(
+            &[
+                r"\afterassignment\a\afterassignment\b\count1=5 %",
+                r"\def\c{}%",
+            ],
+            |parser| {
+                assert!(parser.is_after_assignment_head().unwrap());
+                parser.parse_after_assignment().unwrap();
+                assert!(parser.is_after_assignment_head().unwrap());
+                parser.parse_after_assignment().unwrap();
+                parser.parse_assignment(None).unwrap();
+
+                // Only the last token is kept, and it's only used once.
+                assert_eq!(
+                    parser.lex_unexpanded_token().unwrap(),
+                    Some(Token::ControlSequence("b".to_string()))
+                );
+                parser.parse_assignment(None).unwrap();
+                assert_eq!(parser.lex_unexpanded_token().unwrap(), None);
+            },
+        );
+    }
+
+    #[test]
+    fn it_reads_after_assignment_tokens_at_the_start_of_set_boxes() {
+        with_parser(
+            &[
+                r"\def\a{\global\count1=\wd1 }%",
+                r"\setbox1=\hbox to 5pt{}%",
+                r"\afterassignment\a\setbox0=\hbox{}%",
+                r"\afterassignment\a\setbox0=\box1 %",
+            ],
+            |parser| {
+                parser.parse_assignment(None).unwrap();
+                parser.parse_assignment(None).unwrap();
+
+                // Inside of the box, \wd1 is still 5pt.
+                parser.parse_after_assignment().unwrap();
+                parser.parse_assignment(None).unwrap();
+                assert_eq!(parser.state.get_count(1), 5 * 65536);
+
+                // After \setbox0=\box1, \wd1 is empty.
+                parser.parse_after_assignment().unwrap();
+                parser.parse_assignment(None).unwrap();
+                parser.parse_assignment(None).unwrap();
+                assert_eq!(parser.state.get_count(1), 0);
+            },
+        );
+    }
+
+    #[test]
+    fn it_sets_long_and_outer_macros() {
+        with_parser(
+            &[r"\long\def\a{}\outer\global\long\edef\b{}%"],
+            |parser| {
+                parser.parse_assignment(None).unwrap();
+                parser.state.push_state();
+                parser.parse_assignment(None).unwrap();
+                parser.state.pop_state();
+
+                let a = parser
+                    .state
+                    .get_macro(&Token::ControlSequence("a".to_string()))
+                    .unwrap();
+                assert!(a.is_long());
+                assert!(!a.is_outer());
+
+                let b = parser
+                    .state
+                    .get_macro(&Token::ControlSequence("b".to_string()))
+                    .unwrap();
+                assert!(b.is_long());
+                assert!(b.is_outer());
+            },
+        );
+    }
+
+    #[test]
+    fn it_ignores_long_and_outer_for_other_assignments() {
+        with_parser(&[r"\long\count1=2 \outer x%"], |parser| {
+            parser.parse_assignment(None).unwrap();
+            assert_eq!(parser.state.get_count(1), 2);
+            parser.parse_assignment(None).unwrap();
+            assert_eq!(
+                parser.lex_unexpanded_token().unwrap(),
+                Some(Token::Char('x', Category::Letter))
+            );
+
+            let kinds: Vec<TeXErrorKind> =
+                parser.take_errors().into_iter().map(|e| e.kind).collect();
+            assert_eq!(
+                kinds,
+                vec![
+                    TeXErrorKind::CantUseLongOrOuterWith(
+                        Token::ControlSequence("count".to_string())
+                    ),
+                    TeXErrorKind::CantUsePrefixWith(Token::Char(
+                        'x',
+                        Category::Letter
+                    )),
+                ]
+            );
+        });
+    }
+
     #[test]
     fn it_assigns_lets_for_characters() {
         with_parser(&["\\let\\a=b%"], |parser| {
-            parser.parse_assignment(None);
+            parser.parse_assignment(None).unwrap();
 
             assert_eq!(
                 parser.state.get_renamed_token(&Token::ControlSequence(
@@ -482,8 +969,8 @@ mod tests {
     #[test]
     fn it_assigns_lets_for_previously_defined_macros() {
         with_parser(&["\\def\\a{x}%", "\\let\\b=\\a%"], |parser| {
-            parser.parse_assignment(None);
-            parser.parse_assignment(None);
+            parser.parse_assignment(None).unwrap();
+            parser.parse_assignment(None).unwrap();
 
             assert_eq!(
                 *parser
@@ -505,7 +992,7 @@ mod tests {
     fn it_doesnt_assign_lets_for_active_tokens() {
         with_parser(&["\\let\\a=@%"], |parser| {
             parser.state.set_category(false, '@', Category::Active);
-            parser.parse_assignment(None);
+            parser.parse_assignment(None).unwrap();
 
             assert_eq!(
                 parser.state.get_renamed_token(&Token::ControlSequence(
@@ -519,7 +1006,7 @@ mod tests {
     #[test]
     fn it_assigns_lets_for_primitives() {
         with_parser(&["\\let\\a=\\def%"], |parser| {
-            parser.parse_assignment(None);
+            parser.parse_assignment(None).unwrap();
 
             assert!(parser.state.is_token_equal_to_prim(
                 &Token::ControlSequence("a".to_string()),
@@ -531,8 +1018,8 @@ mod tests {
     #[test]
     fn it_lets_let_be_let() {
         with_parser(&["\\let\\a=\\let%", "\\a\\x=y%"], |parser| {
-            parser.parse_assignment(None);
-            parser.parse_assignment(None);
+            parser.parse_assignment(None).unwrap();
+            parser.parse_assignment(None).unwrap();
 
             assert_eq!(
                 parser.state.get_renamed_token(&Token::ControlSequence(
@@ -546,8 +1033,8 @@ mod tests {
     #[test]
     fn it_lets_def_be_let() {
         with_parser(&["\\let\\a=\\def%", "\\a\\x #1{#1}%"], |parser| {
-            parser.parse_assignment(None);
-            parser.parse_assignment(None);
+            parser.parse_assignment(None).unwrap();
+            parser.parse_assignment(None).unwrap();
 
             assert_eq!(
                 *parser
@@ -566,7 +1053,7 @@ mod tests {
     fn it_sets_global_lets() {
         with_parser(&["\\global\\let\\a=b%"], |parser| {
             parser.state.push_state();
-            parser.parse_assignment(None);
+            parser.parse_assignment(None).unwrap();
             parser.state.pop_state();
 
             assert_eq!(
@@ -583,9 +1070,9 @@ mod tests {
         with_parser(
             &["\\count0=2%", "\\count100 -12345%", "\\count10=\\count100%"],
             |parser| {
-                parser.parse_assignment(None);
-                parser.parse_assignment(None);
-                parser.parse_assignment(None);
+                parser.parse_assignment(None).unwrap();
+                parser.parse_assignment(None).unwrap();
+                parser.parse_assignment(None).unwrap();
 
                 assert_eq!(parser.state.get_count(0), 2);
                 assert_eq!(parser.state.get_count(100), -12345);
@@ -598,7 +1085,7 @@ mod tests {
     fn it_sets_count_variables_globally() {
         with_parser(&["\\global\\count0=2%"], |parser| {
             parser.state.push_state();
-            parser.parse_assignment(None);
+            parser.parse_assignment(None).unwrap();
             parser.state.pop_state();
 
             assert_eq!(parser.state.get_count(0), 2);
@@ -616,32 +1103,232 @@ mod tests {
                 "\\divide\\count0 by\\count1%",
             ],
             |parser| {
-                parser.parse_assignment(None);
-                parser.parse_assignment(None);
+                parser.parse_assignment(None).unwrap();
+                parser.parse_assignment(None).unwrap();
 
                 assert_eq!(parser.state.get_count(0), 150);
                 assert_eq!(parser.state.get_count(1), 5);
 
-                assert!(parser.is_assignment_head());
-                parser.parse_assignment(None);
+                assert!(parser.is_assignment_head().unwrap());
+                parser.parse_assignment(None).unwrap();
                 assert_eq!(parser.state.get_count(0), 157);
 
-                assert!(parser.is_assignment_head());
-                parser.parse_assignment(None);
+                assert!(parser.is_assignment_head().unwrap());
+                parser.parse_assignment(None).unwrap();
                 assert_eq!(parser.state.get_count(1), 10);
 
-                assert!(parser.is_assignment_head());
-                parser.parse_assignment(None);
+                assert!(parser.is_assignment_head().unwrap());
+                parser.parse_assignment(None).unwrap();
                 assert_eq!(parser.state.get_count(0), 15);
             },
         );
     }
 
+    #[test]
+    fn it_sets_registers() {
+        with_parser(
+            &[
+                r"\dimen1=2pt%",
+                r"\dimen2=-\dimen1%",
+                r"\skip1=1pt plus 2fil minus 3pt%",
+                r"\skip2=-\skip1%",
+                r"\dimen3=\skip1%",
+                r"\count1=\dimen1%",
+                r"\muskip1=3mu plus 2mu%",
+                r"\toks1={a\b}%",
+                r"\toks2=\toks1%",
+            ],
+            |parser| {
+                for _ in 0..9 {
+                    assert!(parser.is_assignment_head().unwrap());
+                    parser.parse_assignment(None).unwrap();
+                }
+
+                let point = Dimen::from_unit(1.0, Unit::Point);
+                assert_eq!(parser.state.get_dimen(1), point * 2);
+                assert_eq!(parser.state.get_dimen(2), point * -2);
+                let glue = Glue {
+                    space: point,
+                    stretch: SpringDimen::FilDimen(FilDimen::new(
+                        FilKind::Fil,
+                        2.0,
+                    )),
+                    shrink: SpringDimen::Dimen(point * 3),
+                };
+                assert_eq!(parser.state.get_skip(1), glue);
+                assert_eq!(parser.state.get_skip(2), glue * -1);
+                assert_eq!(parser.state.get_dimen(3), point);
+                assert_eq!(parser.state.get_count(1), 131072);
+                assert_eq!(
+                    parser.state.get_muskip(1),
+                    MuGlue {
+                        space: MuDimen::new(3.0),
+                        stretch: MuSpringDimen::MuDimen(MuDimen::new(2.0)),
+                        shrink: MuSpringDimen::zero(),
+                    }
+                );
+                let tokens = vec![
+                    Token::Char('a', Category::Letter),
+                    Token::ControlSequence("b".to_string()),
+                ];
+                assert_eq!(*parser.state.get_toks(1), tokens);
+                assert_eq!(*parser.state.get_toks(2), tokens);
+            },
+        );
+    }
+
+    #[test]
+    fn it_parses_register_arithmetic() {
+        with_parser(
+            &[
+                r"\dimen1=3pt%",
+                r"\advance\dimen1 by 2pt%",
+                r"\multiply\dimen1 2%",
+                r"\divide\dimen1 by 4%",
+                r"\skip1=1pt plus 1fil%",
+                r"\advance\skip1 by 1pt minus 1pt%",
+                r"\multiply\skip1 by 3%",
+                r"\muskip1=2mu%",
+                r"\advance\muskip1 by\muskip1%",
+                r"\divide\muskip1 by 4%",
+            ],
+            |parser| {
+                for _ in 0..10 {
+                    assert!(parser.is_assignment_head().unwrap());
+                    parser.parse_assignment(None).unwrap();
+                }
+
+                assert_eq!(
+                    parser.state.get_dimen(1),
+                    Dimen::from_unit(2.5, Unit::Point)
+                );
+                assert_eq!(
+                    parser.state.get_skip(1),
+                    Glue {
+                        space: Dimen::from_unit(6.0, Unit::Point),
+                        stretch: SpringDimen::FilDimen(FilDimen::new(
+                            FilKind::Fil,
+                            3.0,
+                        )),
+                        shrink: SpringDimen::Dimen(Dimen::from_unit(
+                            3.0,
+                            Unit::Point
+                        )),
+                    }
+                );
+                assert_eq!(
+                    parser.state.get_muskip(1),
+                    MuGlue {
+                        space: MuDimen::new(1.0),
+                        ..MuGlue::zero()
+                    }
+                );
+            },
+        );
+    }
+
+    #[test]
+    fn it_reports_arithmetic_overflow() {
+        with_parser(
+            &[
+                r"\count1=5 \divide\count1 by 0%",
+                r"\count2=1073741824 \multiply\count2 by 2%",
+                r"\dimen1=16000pt \multiply\dimen1 by 2%",
+                r"\dimen2=16000pt \advance\dimen2 by 16000pt%",
+                r"\skip1=1pt \divide\skip1 by 0%",
+                r"\skip2=16000pt \multiply\skip2 by 2%",
+                r"\skip3=0pt plus 16000fil \advance\skip3 by 0pt plus 16000fil%",
+                r"\muskip1=16000mu \multiply\muskip1 by 100000%",
+                r"\muskip2=16000mu \advance\muskip2 by 16000mu%",
+                r"\muskip3=1mu \divide\muskip3 by 0%",
+                r"\advance\toks%",
+            ],
+            |parser| {
+                for _ in 0..21 {
+                    parser.parse_assignment(None).unwrap();
+                }
+
+                // The variables are left unchanged.
+                assert_eq!(parser.state.get_count(1), 5);
+                assert_eq!(parser.state.get_count(2), 1073741824);
+                assert_eq!(
+                    parser.state.get_dimen(1),
+                    Dimen::from_unit(16000.0, Unit::Point)
+                );
+                assert_eq!(
+                    parser.state.get_dimen(2),
+                    Dimen::from_unit(16000.0, Unit::Point)
+                );
+                assert_eq!(
+                    parser.state.get_skip(1),
+                    Glue::from_dimen(Dimen::from_unit(1.0, Unit::Point))
+                );
+                assert_eq!(
+                    parser.state.get_skip(2),
+                    Glue::from_dimen(Dimen::from_unit(16000.0, Unit::Point))
+                );
+                assert_eq!(
+                    parser.state.get_skip(3),
+                    Glue {
+                        stretch: SpringDimen::FilDimen(FilDimen::new(
+                            FilKind::Fil,
+                            16000.0
+                        )),
+                        ..Glue::zero()
+                    }
+                );
+                assert_eq!(
+                    parser.state.get_muskip(1),
+                    MuGlue {
+                        space: MuDimen::new(16000.0),
+                        ..MuGlue::zero()
+                    }
+                );
+                assert_eq!(
+                    parser.state.get_muskip(2),
+                    MuGlue {
+                        space: MuDimen::new(16000.0),
+                        ..MuGlue::zero()
+                    }
+                );
+                assert_eq!(
+                    parser.state.get_muskip(3),
+                    MuGlue {
+                        space: MuDimen::new(1.0),
+                        ..MuGlue::zero()
+                    }
+                );
+
+                let kinds: Vec<TeXErrorKind> =
+                    parser.take_errors().into_iter().map(|e| e.kind).collect();
+                assert_eq!(
+                    kinds,
+                    vec![
+                        TeXErrorKind::ArithmeticOverflow,
+                        TeXErrorKind::ArithmeticOverflow,
+                        TeXErrorKind::ArithmeticOverflow,
+                        TeXErrorKind::ArithmeticOverflow,
+                        TeXErrorKind::ArithmeticOverflow,
+                        TeXErrorKind::ArithmeticOverflow,
+                        TeXErrorKind::ArithmeticOverflow,
+                        TeXErrorKind::ArithmeticOverflow,
+                        TeXErrorKind::ArithmeticOverflow,
+                        TeXErrorKind::ArithmeticOverflow,
+                        TeXErrorKind::CantUseAfter(
+                            Token::ControlSequence("toks".to_string()),
+                            Token::ControlSequence("advance".to_string())
+                        ),
+                    ]
+                );
+            },
+        );
+    }
+
     #[test]
     fn it_sets_boxes() {
         with_parser(&["\\setbox123=\\hbox{a}%"], |parser| {
-            assert!(parser.is_assignment_head());
-            parser.parse_assignment(None);
+            assert!(parser.is_assignment_head().unwrap());
+            parser.parse_assignment(None).unwrap();
 
             assert!(parser.state.get_box(123).is_some());
         });
@@ -652,14 +1339,14 @@ mod tests {
         with_parser(
             &[r"\setbox0=\hbox{a}%", r"\wd0=2pt%", r"\ht0=3pt%"],
             |parser| {
-                assert!(parser.is_assignment_head());
-                parser.parse_assignment(None);
+                assert!(parser.is_assignment_head().unwrap());
+                parser.parse_assignment(None).unwrap();
 
-                assert!(parser.is_assignment_head());
-                parser.parse_assignment(None);
+                assert!(parser.is_assignment_head().unwrap());
+                parser.parse_assignment(None).unwrap();
 
-                assert!(parser.is_assignment_head());
-                parser.parse_assignment(None);
+                assert!(parser.is_assignment_head().unwrap());
+                parser.parse_assignment(None).unwrap();
 
                 assert_eq!(
                     parser.state.with_box(0, |tex_box| *tex_box.width()),
@@ -683,20 +1370,20 @@ mod tests {
                 r"\x\y\z%",
             ],
             |parser| {
-                assert!(parser.is_assignment_head());
-                parser.parse_assignment(None);
+                assert!(parser.is_assignment_head().unwrap());
+                parser.parse_assignment(None).unwrap();
 
-                assert!(parser.is_assignment_head());
-                parser.parse_assignment(None);
+                assert!(parser.is_assignment_head().unwrap());
+                parser.parse_assignment(None).unwrap();
 
-                assert!(parser.is_assignment_head());
-                parser.parse_assignment(None);
-                assert!(parser.is_assignment_head());
-                parser.parse_assignment(None);
+                assert!(parser.is_assignment_head().unwrap());
+                parser.parse_assignment(None).unwrap();
+                assert!(parser.is_assignment_head().unwrap());
+                parser.parse_assignment(None).unwrap();
 
-                let x = parser.parse_unexpanded_control_sequence();
-                let y = parser.parse_unexpanded_control_sequence();
-                let z = parser.parse_unexpanded_control_sequence();
+                let x = parser.parse_unexpanded_control_sequence().unwrap();
+                let y = parser.parse_unexpanded_control_sequence().unwrap();
+                let z = parser.parse_unexpanded_control_sequence().unwrap();
 
                 assert_eq!(
                     parser.state.get_math_chardef(&x),
@@ -714,16 +1401,74 @@ mod tests {
         );
     }
 
+    #[test]
+    fn it_sets_shorthand_definitions() {
+        with_parser(
+            &[
+                r"\chardef\a=65 \countdef\pageno=0 \dimendef\d=1 %",
+                r"\skipdef\s=2 \muskipdef\m=3 \toksdef\t=4 %",
+                r"\pageno=5 \d=1pt \s=2pt \m=3mu \t={x}%",
+                r"\advance\pageno by\a \count1=\d%",
+            ],
+            |parser| {
+                for _ in 0..13 {
+                    assert!(parser.is_assignment_head().unwrap());
+                    parser.parse_assignment(None).unwrap();
+                }
+
+                let point = Dimen::from_unit(1.0, Unit::Point);
+                assert_eq!(parser.state.get_count(0), 70);
+                assert_eq!(parser.state.get_dimen(1), point);
+                assert_eq!(
+                    parser.state.get_skip(2),
+                    Glue::from_dimen(point * 2)
+                );
+                assert_eq!(
+                    parser.state.get_muskip(3),
+                    MuGlue {
+                        space: MuDimen::new(3.0),
+                        ..MuGlue::zero()
+                    }
+                );
+                assert_eq!(
+                    *parser.state.get_toks(4),
+                    vec![Token::Char('x', Category::Letter)]
+                );
+                assert_eq!(parser.state.get_count(1), 65536);
+            },
+        );
+    }
+
+    #[test]
+    fn it_reads_shorthand_definitions_as_relax() {
+        with_parser(&[r"\def\a{}\chardef\a=1\a%"], |parser| {
+            parser.parse_assignment(None).unwrap();
+            parser.parse_assignment(None).unwrap();
+
+            // The number ends at \a, which isn't expanded as a macro.
+            assert_eq!(
+                parser.lex_unexpanded_token().unwrap(),
+                Some(Token::ControlSequence("a".to_string()))
+            );
+            assert_eq!(
+                parser
+                    .state
+                    .get_chardef(&Token::ControlSequence("a".to_string())),
+                Some(1 as char)
+            );
+        });
+    }
+
     #[test]
     fn it_sets_mathcodes() {
         with_parser(
             &[r#"\mathcode`*="2203%"#, r#"\mathcode`<="313C%"#],
             |parser| {
-                assert!(parser.is_assignment_head());
-                parser.parse_assignment(None);
+                assert!(parser.is_assignment_head().unwrap());
+                parser.parse_assignment(None).unwrap();
 
-                assert!(parser.is_assignment_head());
-                parser.parse_assignment(None);
+                assert!(parser.is_assignment_head().unwrap());
+                parser.parse_assignment(None).unwrap();
 
                 assert_eq!(
                     parser.state.get_math_code('*'),
@@ -737,6 +1482,102 @@ mod tests {
         );
     }
 
+    #[test]
+    fn it_sets_catcodes() {
+        with_parser(
+            &[
+                r"\catcode`@=11 \def\a@b{x}%",
+                r"{\catcode`\[=1 \catcode`]=2 %",
+                r"\count1=\catcode`[]\a@b%",
+            ],
+            |parser| {
+                parser.parse_assignment(None).unwrap();
+                parser.parse_assignment(None).unwrap();
+                assert_eq!(parser.state.get_category('@'), Category::Letter);
+
+                assert_eq!(
+                    parser.lex_expanded_token().unwrap(),
+                    Some(Token::Char('{', Category::BeginGroup))
+                );
+                parser.state.push_state();
+                parser.parse_assignment(None).unwrap();
+                parser.parse_assignment(None).unwrap();
+                parser.parse_assignment(None).unwrap();
+                assert_eq!(parser.state.get_count(1), 1);
+                assert_eq!(
+                    parser.lex_expanded_token().unwrap(),
+                    Some(Token::Char(']', Category::EndGroup))
+                );
+                parser.state.pop_state();
+                assert_eq!(parser.state.get_category('['), Category::Other);
+
+                assert_eq!(
+                    parser.lex_expanded_token().unwrap(),
+                    Some(Token::Char('x', Category::Letter))
+                );
+            },
+        );
+    }
+
+    #[test]
+    fn it_sets_the_other_code_tables() {
+        with_parser(
+            &[
+                r"\lccode`A=`z \uccode`z=`A %",
+                r"\global\sfcode`)=0 \delcode`(=`[ %",
+                r"\count1=\lccode`A \count2=\uccode`b %",
+                r"\count3=\sfcode`) \count4=\sfcode`B \count5=\delcode`x %",
+            ],
+            |parser| {
+                for _ in 0..9 {
+                    parser.parse_assignment(None).unwrap();
+                }
+
+                assert_eq!(parser.state.get_lowercase_code('A'), 'z');
+                assert_eq!(parser.state.get_uppercase_code('z'), 'A');
+                assert_eq!(parser.state.get_space_factor_code(')'), 0);
+                assert_eq!(parser.state.get_delimiter_code('('), '[' as i32);
+
+                assert_eq!(parser.state.get_count(1), 'z' as i32);
+                assert_eq!(parser.state.get_count(2), 'B' as i32);
+                assert_eq!(parser.state.get_count(3), 0);
+                assert_eq!(parser.state.get_count(4), 999);
+                assert_eq!(parser.state.get_count(5), -1);
+            },
+        );
+    }
+
+    #[test]
+    fn it_fails_on_invalid_codes() {
+        with_parser(
+            &[
+                r"\catcode`z=16 \lccode`a=-1 %",
+                r"\delcode`a=-5 \delcode`b=16777216 %",
+            ],
+            |parser| {
+                for _ in 0..4 {
+                    parser.parse_assignment(None).unwrap();
+                }
+
+                assert_eq!(parser.state.get_category('z'), Category::Escape);
+                assert_eq!(parser.state.get_lowercase_code('a'), '\0');
+                assert_eq!(parser.state.get_delimiter_code('a'), -5);
+                assert_eq!(parser.state.get_delimiter_code('b'), 0);
+
+                let errors: Vec<TeXErrorKind> =
+                    parser.take_errors().into_iter().map(|e| e.kind).collect();
+                assert_eq!(
+                    errors,
+                    vec![
+                        TeXErrorKind::InvalidCode(16, 15),
+                        TeXErrorKind::InvalidCode(-1, 255),
+                        TeXErrorKind::InvalidDelimiterCode(16777216),
+                    ]
+                );
+            },
+        );
+    }
+
     #[test]
     fn it_assigns_fonts() {
         with_parser(
@@ -746,14 +1587,14 @@ mod tests {
                 r"\font\boo=cmtt10 scaled 2000%",
             ],
             |parser| {
-                assert!(parser.is_assignment_head());
-                parser.parse_assignment(None);
+                assert!(parser.is_assignment_head().unwrap());
+                parser.parse_assignment(None).unwrap();
 
-                assert!(parser.is_assignment_head());
-                parser.parse_assignment(None);
+                assert!(parser.is_assignment_head().unwrap());
+                parser.parse_assignment(None).unwrap();
 
-                assert!(parser.is_assignment_head());
-                parser.parse_assignment(None);
+                assert!(parser.is_assignment_head().unwrap());
+                parser.parse_assignment(None).unwrap();
 
                 assert_eq!(
                     parser.state.get_fontdef(&Token::ControlSequence(
@@ -791,8 +1632,8 @@ mod tests {
     #[test]
     fn it_expands_macros_in_font_assignment() {
         with_parser(&[r"\def\y{10}%", r"\font\z=cmr\y%"], |parser| {
-            parser.parse_assignment(None);
-            parser.parse_assignment(None);
+            parser.parse_assignment(None).unwrap();
+            parser.parse_assignment(None).unwrap();
 
             assert_eq!(
                 parser
@@ -807,11 +1648,17 @@ mod tests {
     }
 
     #[test]
-    #[should_panic(expected = "Invalid font name: cmr")]
     fn it_does_not_expand_the_assigned_font_name_in_font_assignment() {
         with_parser(&[r"\def\x{10}%", r"\font\x=cmr\x%"], |parser| {
-            parser.parse_assignment(None);
-            parser.parse_assignment(None);
+            parser.parse_assignment(None).unwrap();
+            parser.parse_assignment(None).unwrap();
+
+            let x = Token::ControlSequence("x".to_string());
+            assert_eq!(parser.lex_unexpanded_token().unwrap(), Some(x.clone()));
+            assert_eq!(
+                parser.take_errors()[0].kind,
+                TeXErrorKind::FontNotLoadable(x, "cmr".to_string())
+            );
         });
     }
 
@@ -826,10 +1673,10 @@ mod tests {
                 r"\x%",
             ],
             |parser| {
-                assert!(parser.is_assignment_head());
-                parser.parse_assignment(None);
-                assert!(parser.is_assignment_head());
-                parser.parse_assignment(None);
+                assert!(parser.is_assignment_head().unwrap());
+                parser.parse_assignment(None).unwrap();
+                assert!(parser.is_assignment_head().unwrap());
+                parser.parse_assignment(None).unwrap();
 
                 assert_eq!(
                     parser.state.get_current_font(),
@@ -839,10 +1686,10 @@ mod tests {
                     }
                 );
 
-                assert!(parser.is_assignment_head());
-                parser.parse_assignment(None);
-                assert!(parser.is_assignment_head());
-                parser.parse_assignment(None);
+                assert!(parser.is_assignment_head().unwrap());
+                parser.parse_assignment(None).unwrap();
+                assert!(parser.is_assignment_head().unwrap());
+                parser.parse_assignment(None).unwrap();
 
                 assert_eq!(
                     parser.state.get_current_font(),
@@ -852,10 +1699,10 @@ mod tests {
                     }
                 );
 
-                assert!(parser.is_assignment_head());
-                parser.parse_assignment(None);
-                assert!(parser.is_assignment_head());
-                parser.parse_assignment(None);
+                assert!(parser.is_assignment_head().unwrap());
+                parser.parse_assignment(None).unwrap();
+                assert!(parser.is_assignment_head().unwrap());
+                parser.parse_assignment(None).unwrap();
 
                 assert_eq!(
                     parser.state.get_current_font(),
@@ -865,10 +1712,10 @@ mod tests {
                     }
                 );
 
-                assert!(parser.is_assignment_head());
-                parser.parse_assignment(None);
-                assert!(parser.is_assignment_head());
-                parser.parse_assignment(None);
+                assert!(parser.is_assignment_head().unwrap());
+                parser.parse_assignment(None).unwrap();
+                assert!(parser.is_assignment_head().unwrap());
+                parser.parse_assignment(None).unwrap();
 
                 assert_eq!(
                     parser.state.get_current_font(),
@@ -890,19 +1737,63 @@ mod tests {
                 prev_depth: Some(&mut prev_depth),
             };
 
-            assert!(parser.is_assignment_head());
-            parser.parse_assignment(Some(special_variables));
+            assert!(parser.is_assignment_head().unwrap());
+            parser.parse_assignment(Some(special_variables)).unwrap();
 
             assert_eq!(prev_depth, Dimen::from_unit(2.0, Unit::Point));
         });
     }
 
     #[test]
-    #[should_panic(expected = "Invalid prevdepth assignment")]
     fn it_fails_to_assign_prevdepth_values_with_unassigned_special_variable() {
         with_parser(&[r"\prevdepth=2pt%"], |parser| {
             parser
-                .parse_assignment(Some(SpecialVariables { prev_depth: None }));
+                .parse_assignment(Some(SpecialVariables { prev_depth: None }))
+                .unwrap();
+            assert_eq!(
+                parser.take_errors()[0].kind,
+                TeXErrorKind::CantUseInMode(
+                    Token::ControlSequence("prevdepth".to_string()),
+                    "non-vertical mode"
+                )
+            );
+
+            // The value is left to be read again.
+            assert_eq!(
+                parser.lex_unexpanded_token().unwrap(),
+                Some(Token::Char('=', Category::Other))
+            );
+            parser.parse_dimen().unwrap();
+        });
+    }
+
+    #[test]
+    fn it_recovers_from_bad_assignments() {
+        with_parser(&[r"\global x%", r"\def a{}%"], |parser| {
+            parser.parse_assignment(None).unwrap();
+            assert_eq!(
+                parser.lex_unexpanded_token().unwrap(),
+                Some(Token::Char('x', Category::Letter))
+            );
+
+            parser.parse_assignment(None).unwrap();
+            assert!(parser
+                .state
+                .get_macro(&Token::ControlSequence("inaccessible ".to_string()))
+                .is_some());
+
+            let kinds: Vec<TeXErrorKind> =
+                parser.take_errors().into_iter().map(|e| e.kind).collect();
+            assert_eq!(
+                kinds,
+                vec![
+                    TeXErrorKind::CantUsePrefixWith(Token::Char(
+                        'x',
+                        Category::Letter
+                    )),
+                    TeXErrorKind::MissingControlSequence,
+                ]
+            );
         });
     }
 }
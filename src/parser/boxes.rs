@@ -3,8 +3,10 @@ use crate::boxes::{
 };
 use crate::category::Category;
 use crate::dimension::{Dimen, SpringDimen};
+use crate::error::{TeXErrorKind, TeXResult};
 use crate::glue::Glue;
 use crate::list::HorizontalListElem;
+use crate::parser::group::GroupKind;
 use crate::parser::Parser;
 use crate::token::Token;
 
@@ -171,10 +173,12 @@ impl<'a> Parser<'a> {
         layout: &BoxLayout,
         restricted: bool,
         indent: bool,
-    ) -> HorizontalBox {
-        let list = self.parse_horizontal_list(restricted, indent);
-        self.combine_horizontal_list_into_horizontal_box_with_layout(
-            list, layout,
+    ) -> TeXResult<HorizontalBox> {
+        let list = self.parse_horizontal_list(restricted, indent)?;
+        Ok(
+            self.combine_horizontal_list_into_horizontal_box_with_layout(
+                list, layout,
+            ),
         )
     }
 
@@ -184,19 +188,19 @@ impl<'a> Parser<'a> {
     pub fn parse_unrestricted_horizontal_box(
         &mut self,
         indent: bool,
-    ) -> TeXBox {
+    ) -> TeXResult<TeXBox> {
         let hbox =
-            self.parse_horizontal_box(&BoxLayout::Natural, false, indent);
-        TeXBox::HorizontalBox(hbox)
+            self.parse_horizontal_box(&BoxLayout::Natural, false, indent)?;
+        Ok(TeXBox::HorizontalBox(hbox))
     }
 
     fn parse_vertical_box(
         &mut self,
         layout: &BoxLayout,
         internal: bool,
-    ) -> VerticalBox {
+    ) -> TeXResult<VerticalBox> {
         // Parse the actual list of elements
-        let list = self.parse_vertical_list(internal);
+        let list = self.parse_vertical_list(internal)?;
 
         // Keep track of the total height of the elements
         let mut height = Glue::zero();
@@ -226,98 +230,125 @@ impl<'a> Parser<'a> {
         // Figure out the true height and set ratio
         let (set_height, glue_set) = get_set_dimen_and_ratio(height, layout);
 
-        VerticalBox {
+        Ok(VerticalBox {
             height: set_height,
             depth: prev_depth,
             width,
 
             list,
             glue_set_ratio: glue_set,
-        }
+        })
     }
 
-    fn parse_box_specification(&mut self) -> BoxLayout {
-        if self.parse_optional_keyword_expanded("to") {
-            let dimen = self.parse_dimen();
-            self.parse_filler_expanded();
-            BoxLayout::Fixed(dimen)
-        } else if self.parse_optional_keyword_expanded("spread") {
-            let dimen = self.parse_dimen();
-            self.parse_filler_expanded();
-            BoxLayout::Spread(dimen)
+    fn parse_box_specification(&mut self) -> TeXResult<BoxLayout> {
+        if self.parse_optional_keyword_expanded("to")? {
+            let dimen = self.parse_dimen()?;
+            self.parse_filler_expanded()?;
+            Ok(BoxLayout::Fixed(dimen))
+        } else if self.parse_optional_keyword_expanded("spread")? {
+            let dimen = self.parse_dimen()?;
+            self.parse_filler_expanded()?;
+            Ok(BoxLayout::Spread(dimen))
         } else {
-            self.parse_filler_expanded();
-            BoxLayout::Natural
+            self.parse_filler_expanded()?;
+            Ok(BoxLayout::Natural)
         }
     }
 
-    pub fn is_box_head(&mut self) -> bool {
+    pub fn is_box_head(&mut self) -> TeXResult<bool> {
         self.is_next_expanded_token_in_set_of_primitives(&[
             "hbox", "vbox", "box", "copy",
         ])
     }
 
-    pub fn parse_box(&mut self) -> Option<TeXBox> {
-        let head = self.lex_expanded_token().unwrap();
+    // Parses the { which starts the contents of a box. If it's missing, TeX
+    // acts as if it had been there.
+    fn parse_box_begin_group(&mut self) -> TeXResult<()> {
+        match self.lex_expanded_token()? {
+            Some(Token::Char(_, Category::BeginGroup)) => Ok(()),
+            rest => {
+                self.report_error(TeXErrorKind::MissingLeftBrace)?;
+                if let Some(token) = rest {
+                    self.add_upcoming_token(token);
+                }
+                Ok(())
+            }
+        }
+    }
+
+    // Parses the } which ends the contents of a box, acting as if it had been
+    // there if it's missing.
+    fn parse_box_end_group(&mut self) -> TeXResult<()> {
+        match self.lex_expanded_token()? {
+            Some(Token::Char(_, Category::EndGroup)) => Ok(()),
+            rest => {
+                self.report_error(TeXErrorKind::MissingRightBrace)?;
+                if let Some(token) = rest {
+                    self.add_upcoming_token(token);
+                }
+                Ok(())
+            }
+        }
+    }
+
+    pub fn parse_box(&mut self) -> TeXResult<Option<TeXBox>> {
+        let head = self.lex_expanded_token()?.unwrap();
 
         if self.state.is_token_equal_to_prim(&head, "hbox") {
-            let layout = self.parse_box_specification();
+            let layout = self.parse_box_specification()?;
 
             // We expect a { after the box specification
-            match self.lex_expanded_token() {
-                Some(Token::Char(_, Category::BeginGroup)) => (),
-                _ => panic!("Expected { when parsing box"),
-            }
+            self.parse_box_begin_group()?;
 
-            self.state.push_state();
+            self.push_state(GroupKind::HorizontalBox);
+            self.insert_box_after_assignment_token();
 
-            let hbox = self.parse_horizontal_box(&layout, true, false);
+            let hbox = self.parse_horizontal_box(&layout, true, false)?;
 
-            self.state.pop_state();
+            // And there should always be a } after the horizontal list. The
+            // group ends after it's read, so that any \aftergroup tokens
+            // come after the box.
+            self.parse_box_end_group()?;
 
-            // And there should always be a } after the horizontal list
-            match self.lex_expanded_token() {
-                Some(Token::Char(_, Category::EndGroup)) => (),
-                _ => panic!("Expected } when parsing box"),
-            }
+            self.pop_state();
 
-            Some(TeXBox::HorizontalBox(hbox))
+            Ok(Some(TeXBox::HorizontalBox(hbox)))
         } else if self.state.is_token_equal_to_prim(&head, "vbox") {
-            let layout = self.parse_box_specification();
+            let layout = self.parse_box_specification()?;
 
             // We expect a { after the box specification
-            match self.lex_expanded_token() {
-                Some(Token::Char(_, Category::BeginGroup)) => (),
-                _ => panic!("Expected { when parsing box"),
-            }
+            self.parse_box_begin_group()?;
 
-            self.state.push_state();
+            self.push_state(GroupKind::VerticalBox);
+            self.insert_box_after_assignment_token();
 
-            let vbox = self.parse_vertical_box(&layout, true);
+            let vbox = self.parse_vertical_box(&layout, true)?;
 
-            self.state.pop_state();
+            // And there should always be a } after the vertical list
+            self.parse_box_end_group()?;
 
-            // And there should always be a } after the horizontal list
-            match self.lex_expanded_token() {
-                Some(Token::Char(_, Category::EndGroup)) => (),
-                _ => panic!("Expected } when parsing box"),
-            }
+            self.pop_state();
 
-            Some(TeXBox::VerticalBox(vbox))
+            Ok(Some(TeXBox::VerticalBox(vbox)))
         } else if self.state.is_token_equal_to_prim(&head, "box") {
-            let box_index = self.parse_8bit_number();
-            self.state.get_box(box_index)
+            let box_index = self.parse_8bit_number()?;
+            Ok(self.state.get_box(box_index))
         } else if self.state.is_token_equal_to_prim(&head, "copy") {
-            let box_index = self.parse_8bit_number();
-            self.state.get_box_copy(box_index)
+            let box_index = self.parse_8bit_number()?;
+            Ok(self.state.get_box_copy(box_index))
         } else {
-            panic!("unimplemented");
+            // Things like \raise and \setbox expect a box, but don't check
+            // for one before calling this. Like TeX, we read the token again
+            // as if the box had been left out.
+            self.add_upcoming_token(head);
+            self.report_error(TeXErrorKind::MissingBox)?;
+            Ok(None)
         }
     }
 
     // Used for early testing, when we want to output test the output of
     // parsing an entire box.
-    pub fn parse_outer_vertical_box(&mut self) -> VerticalBox {
+    pub fn parse_outer_vertical_box(&mut self) -> TeXResult<VerticalBox> {
         self.parse_vertical_box(&BoxLayout::Natural, false)
     }
 }
@@ -328,7 +359,7 @@ mod tests {
 
     use crate::dimension::{Dimen, FilDimen, FilKind, Unit};
     use crate::font::Font;
-    use crate::testing::with_parser;
+    use crate::testing::{with_parser, WithoutOrigins};
 
     lazy_static! {
         static ref CMR10: Font = Font {
@@ -340,8 +371,9 @@ mod tests {
     #[test]
     fn it_parses_boxes_with_characters() {
         with_parser(&["gb%"], |parser| {
-            let hbox =
-                parser.parse_horizontal_box(&BoxLayout::Natural, true, false);
+            let hbox = parser
+                .parse_horizontal_box(&BoxLayout::Natural, true, false)
+                .unwrap();
 
             let metrics = parser.state.get_metrics_for_font(&CMR10).unwrap();
 
@@ -357,8 +389,9 @@ mod tests {
     #[test]
     fn it_parses_boxes_with_glue() {
         with_parser(&["\\hskip 1pt \\hskip 2pt plus 1fil%"], |parser| {
-            let hbox =
-                parser.parse_horizontal_box(&BoxLayout::Natural, true, false);
+            let hbox = parser
+                .parse_horizontal_box(&BoxLayout::Natural, true, false)
+                .unwrap();
 
             assert_eq!(hbox.height, Dimen::zero());
             assert_eq!(hbox.depth, Dimen::zero());
@@ -369,8 +402,9 @@ mod tests {
     #[test]
     fn it_parses_boxes_with_glue_and_characters() {
         with_parser(&["b\\hskip 2pt g%"], |parser| {
-            let hbox =
-                parser.parse_horizontal_box(&BoxLayout::Natural, true, false);
+            let hbox = parser
+                .parse_horizontal_box(&BoxLayout::Natural, true, false)
+                .unwrap();
 
             assert_eq!(hbox.list.len(), 3);
 
@@ -396,11 +430,13 @@ mod tests {
                 + metrics.get_width('b')
                 + Dimen::from_unit(5.0, Unit::Point);
 
-            let hbox = parser.parse_horizontal_box(
-                &BoxLayout::Fixed(fixed_width),
-                true,
-                false,
-            );
+            let hbox = parser
+                .parse_horizontal_box(
+                    &BoxLayout::Fixed(fixed_width),
+                    true,
+                    false,
+                )
+                .unwrap();
 
             assert_eq!(hbox.width, fixed_width);
             assert_eq!(
@@ -419,11 +455,13 @@ mod tests {
                 + metrics.get_width('b')
                 + Dimen::from_unit(5.0, Unit::Point);
 
-            let hbox = parser.parse_horizontal_box(
-                &BoxLayout::Fixed(fixed_width),
-                true,
-                false,
-            );
+            let hbox = parser
+                .parse_horizontal_box(
+                    &BoxLayout::Fixed(fixed_width),
+                    true,
+                    false,
+                )
+                .unwrap();
 
             assert_eq!(hbox.width, fixed_width);
             assert_eq!(
@@ -439,11 +477,13 @@ mod tests {
                 + metrics.get_width('b')
                 + Dimen::from_unit(5.0, Unit::Point);
 
-            let hbox = parser.parse_horizontal_box(
-                &BoxLayout::Fixed(fixed_width),
-                true,
-                false,
-            );
+            let hbox = parser
+                .parse_horizontal_box(
+                    &BoxLayout::Fixed(fixed_width),
+                    true,
+                    false,
+                )
+                .unwrap();
 
             assert_eq!(hbox.width, fixed_width);
             assert_eq!(
@@ -459,11 +499,13 @@ mod tests {
                 + metrics.get_width('b')
                 + Dimen::from_unit(5.0, Unit::Point);
 
-            let hbox = parser.parse_horizontal_box(
-                &BoxLayout::Fixed(fixed_width),
-                true,
-                false,
-            );
+            let hbox = parser
+                .parse_horizontal_box(
+                    &BoxLayout::Fixed(fixed_width),
+                    true,
+                    false,
+                )
+                .unwrap();
 
             assert_eq!(hbox.width, fixed_width);
             assert_eq!(
@@ -484,11 +526,13 @@ mod tests {
                     + metrics.get_width('b')
                     + Dimen::from_unit(6.0, Unit::Point);
 
-                let hbox = parser.parse_horizontal_box(
-                    &BoxLayout::Fixed(fixed_width),
-                    true,
-                    false,
-                );
+                let hbox = parser
+                    .parse_horizontal_box(
+                        &BoxLayout::Fixed(fixed_width),
+                        true,
+                        false,
+                    )
+                    .unwrap();
 
                 assert_eq!(hbox.width, fixed_width);
                 assert_eq!(
@@ -506,11 +550,13 @@ mod tests {
             let fixed_width = metrics.get_width('a') + metrics.get_width('b')
                 - Dimen::from_unit(1.0, Unit::Point);
 
-            let hbox = parser.parse_horizontal_box(
-                &BoxLayout::Fixed(fixed_width),
-                true,
-                false,
-            );
+            let hbox = parser
+                .parse_horizontal_box(
+                    &BoxLayout::Fixed(fixed_width),
+                    true,
+                    false,
+                )
+                .unwrap();
 
             assert_eq!(hbox.width, fixed_width);
             assert_eq!(
@@ -527,11 +573,13 @@ mod tests {
             let fixed_width = metrics.get_width('a') + metrics.get_width('b')
                 - Dimen::from_unit(4.0, Unit::Point);
 
-            let hbox = parser.parse_horizontal_box(
-                &BoxLayout::Fixed(fixed_width),
-                true,
-                false,
-            );
+            let hbox = parser
+                .parse_horizontal_box(
+                    &BoxLayout::Fixed(fixed_width),
+                    true,
+                    false,
+                )
+                .unwrap();
 
             assert_eq!(hbox.width, fixed_width);
             assert_eq!(
@@ -544,11 +592,13 @@ mod tests {
     #[test]
     fn it_stretches_boxes_with_finite_glue_when_spread() {
         with_parser(&["a\\hskip 0pt plus3pt b%"], |parser| {
-            let hbox = parser.parse_horizontal_box(
-                &BoxLayout::Spread(Dimen::from_unit(6.0, Unit::Point)),
-                true,
-                false,
-            );
+            let hbox = parser
+                .parse_horizontal_box(
+                    &BoxLayout::Spread(Dimen::from_unit(6.0, Unit::Point)),
+                    true,
+                    false,
+                )
+                .unwrap();
 
             let metrics = parser.state.get_metrics_for_font(&CMR10).unwrap();
             let expected_width = metrics.get_width('a')
@@ -566,11 +616,13 @@ mod tests {
     #[test]
     fn it_stretches_boxes_with_infinite_glue_when_spread() {
         with_parser(&["a\\hskip 0pt plus1fill b%"], |parser| {
-            let hbox = parser.parse_horizontal_box(
-                &BoxLayout::Spread(Dimen::from_unit(6.0, Unit::Point)),
-                true,
-                false,
-            );
+            let hbox = parser
+                .parse_horizontal_box(
+                    &BoxLayout::Spread(Dimen::from_unit(6.0, Unit::Point)),
+                    true,
+                    false,
+                )
+                .unwrap();
 
             let metrics = parser.state.get_metrics_for_font(&CMR10).unwrap();
             let expected_width = metrics.get_width('a')
@@ -588,11 +640,13 @@ mod tests {
     #[test]
     fn it_shrinks_boxes_with_finite_glue_when_spread() {
         with_parser(&["a\\hskip 0pt minus2pt b%"], |parser| {
-            let hbox = parser.parse_horizontal_box(
-                &BoxLayout::Spread(Dimen::from_unit(-1.0, Unit::Point)),
-                true,
-                false,
-            );
+            let hbox = parser
+                .parse_horizontal_box(
+                    &BoxLayout::Spread(Dimen::from_unit(-1.0, Unit::Point)),
+                    true,
+                    false,
+                )
+                .unwrap();
 
             let metrics = parser.state.get_metrics_for_font(&CMR10).unwrap();
             let expected_width = metrics.get_width('a')
@@ -615,8 +669,8 @@ mod tests {
                 + metrics.get_width('b')
                 + metrics.get_width('c');
 
-            assert!(parser.is_box_head());
-            let hbox = parser.parse_box().unwrap();
+            assert!(parser.is_box_head().unwrap());
+            let hbox = parser.parse_box().unwrap().unwrap();
             if let TeXBox::HorizontalBox(hbox) = hbox {
                 assert_eq!(hbox.list.len(), 3);
                 assert_eq!(hbox.glue_set_ratio, None);
@@ -630,8 +684,8 @@ mod tests {
     #[test]
     fn it_parses_horizontal_boxes_with_fixed_width() {
         with_parser(&["\\hbox to20pt{a\\hskip 0pt plus1filc}%"], |parser| {
-            assert!(parser.is_box_head());
-            let hbox = parser.parse_box().unwrap();
+            assert!(parser.is_box_head().unwrap());
+            let hbox = parser.parse_box().unwrap().unwrap();
             if let TeXBox::HorizontalBox(hbox) = hbox {
                 assert_eq!(hbox.list.len(), 3);
                 assert_eq!(hbox.width, Dimen::from_unit(20.0, Unit::Point));
@@ -649,8 +703,8 @@ mod tests {
                 + metrics.get_width('c')
                 + Dimen::from_unit(5.0, Unit::Point);
 
-            assert!(parser.is_box_head());
-            let hbox = parser.parse_box().unwrap();
+            assert!(parser.is_box_head().unwrap());
+            let hbox = parser.parse_box().unwrap().unwrap();
             if let TeXBox::HorizontalBox(hbox) = hbox {
                 assert_eq!(hbox.list.len(), 3);
                 assert_eq!(hbox.width, expected_width);
@@ -663,63 +717,74 @@ mod tests {
     #[test]
     fn it_parses_boxes_from_box_registers() {
         with_parser(&[r"\setbox0=\hbox{a}%", r"\box0", r"\box0"], |parser| {
-            parser.parse_assignment(None);
+            parser.parse_assignment(None).unwrap();
 
             let metrics = parser.state.get_metrics_for_font(&CMR10).unwrap();
 
-            assert!(parser.is_box_head());
-            let parsed_box = parser.parse_box().unwrap();
+            assert!(parser.is_box_head().unwrap());
+            let parsed_box = parser.parse_box().unwrap().unwrap();
             assert_eq!(parsed_box.width(), &metrics.get_width('a'));
 
-            assert!(parser.is_box_head());
-            assert_eq!(parser.parse_box(), None);
+            assert!(parser.is_box_head().unwrap());
+            assert_eq!(parser.parse_box().unwrap(), None);
         });
     }
 
     #[test]
     fn it_parses_copied_boxes_from_box_registers() {
         with_parser(&[r"\setbox0=\hbox{a}%", r"\copy0", r"\box0"], |parser| {
-            parser.parse_assignment(None);
+            parser.parse_assignment(None).unwrap();
 
-            assert!(parser.is_box_head());
-            let copied_box = parser.parse_box().unwrap();
+            assert!(parser.is_box_head().unwrap());
+            let copied_box = parser.parse_box().unwrap().unwrap();
             assert_eq!(copied_box, parser.state.get_box_copy(0).unwrap());
 
-            assert!(parser.is_box_head());
-            let parsed_box = parser.parse_box().unwrap();
+            assert!(parser.is_box_head().unwrap());
+            let parsed_box = parser.parse_box().unwrap().unwrap();
             assert_eq!(parsed_box, copied_box);
         });
     }
 
     #[test]
     fn it_parses_vertical_lists() {
-        with_parser(&[r"aby%", r"\vskip 2pt%", r"g%"], |parser| {
-            let metrics = parser.state.get_metrics_for_font(&CMR10).unwrap();
-
-            let vbox = parser.parse_vertical_box(&BoxLayout::Natural, true);
-
-            // Sanity check the number of elements to make sure something
-            // didn't go horribly wrong.
-            assert_eq!(vbox.list.len(), 4);
-
-            // The height will be the height of the first box + the 12pt of
-            // interline glue + the 2pt glue
-            let expected_height = metrics.get_height('b')
-                + Dimen::from_unit(12.0, Unit::Point)
-                + Dimen::from_unit(2.0, Unit::Point);
-            assert_eq!(vbox.height, expected_height);
+        with_parser(
+            &[
+                r"\parindent=20pt \baselineskip=12pt%",
+                r"aby%",
+                r"\vskip 2pt%",
+                r"g%",
+            ],
+            |parser| {
+                let metrics =
+                    parser.state.get_metrics_for_font(&CMR10).unwrap();
 
-            // The depth will just be the depth of the second box.
-            assert_eq!(vbox.depth, metrics.get_depth('g'));
+                let vbox = parser
+                    .parse_vertical_box(&BoxLayout::Natural, true)
+                    .unwrap();
 
-            // The width will be the width of the first box, which is indented
-            // and contains a, b, and y.
-            let expected_width = Dimen::from_unit(20.0, Unit::Point)
-                + metrics.get_width('a')
-                + metrics.get_width('b')
-                + metrics.get_width('y');
-            assert_eq!(vbox.width, expected_width);
-        });
+                // Sanity check the number of elements to make sure something
+                // didn't go horribly wrong.
+                assert_eq!(vbox.list.len(), 4);
+
+                // The height will be the height of the first box + the 12pt of
+                // interline glue + the 2pt glue
+                let expected_height = metrics.get_height('b')
+                    + Dimen::from_unit(12.0, Unit::Point)
+                    + Dimen::from_unit(2.0, Unit::Point);
+                assert_eq!(vbox.height, expected_height);
+
+                // The depth will just be the depth of the second box.
+                assert_eq!(vbox.depth, metrics.get_depth('g'));
+
+                // The width will be the width of the first box, which is indented
+                // and contains a, b, and y.
+                let expected_width = Dimen::from_unit(20.0, Unit::Point)
+                    + metrics.get_width('a')
+                    + metrics.get_width('b')
+                    + metrics.get_width('y');
+                assert_eq!(vbox.width, expected_width);
+            },
+        );
     }
 
     #[test]
@@ -731,23 +796,23 @@ mod tests {
                 r"\wd1=1pt \ht1=1pt \dp1=1pt%",
                 r"\setbox2=\hbox{}%",
                 r"\wd2=2pt \ht2=2pt \dp2=2pt%",
+                r"\baselineskip=9pt minus3fil%",
                 r"\vskip0pt plus1fil minus1fil%",
                 r"\box1%",
-                // This is added to add to account for the
-                // \baselineskip=9pt minus3fil
-                r"\vskip-3pt minus3fil%",
                 r"\box2%",
                 r"\vskip0pt plus1fil minus1fil%",
             ],
             |parser| {
-                let vbox = parser.parse_vertical_box(
-                    &BoxLayout::Fixed(Dimen::from_unit(4.0, Unit::Point)),
-                    true,
-                );
+                let vbox = parser
+                    .parse_vertical_box(
+                        &BoxLayout::Fixed(Dimen::from_unit(4.0, Unit::Point)),
+                        true,
+                    )
+                    .unwrap();
 
                 // Sanity check the number of elements to make sure something
                 // didn't go horribly wrong.
-                assert_eq!(vbox.list.len(), 6);
+                assert_eq!(vbox.list.len(), 5);
 
                 // Since we specified a fixed layout, this is just the fixed amount
                 assert_eq!(vbox.height, Dimen::from_unit(4.0, Unit::Point));
@@ -777,8 +842,8 @@ mod tests {
                 let metrics =
                     parser.state.get_metrics_for_font(&CMR10).unwrap();
 
-                parser.parse_assignment(None);
-                let vbox = parser.parse_box().unwrap();
+                parser.parse_assignment(None).unwrap();
+                let vbox = parser.parse_box().unwrap().unwrap();
 
                 assert_eq!(*vbox.height(), Dimen::from_unit(20.0, Unit::Point));
                 assert_eq!(*vbox.depth(), metrics.get_depth('g'));
@@ -786,6 +851,95 @@ mod tests {
         );
     }
 
+    #[test]
+    fn it_ignores_missing_boxes() {
+        with_parser(&[r"\setbox0=x%"], |parser| {
+            parser.parse_assignment(None).unwrap();
+            assert_eq!(parser.state.get_box(0), None);
+            assert_eq!(parser.take_errors()[0].kind, TeXErrorKind::MissingBox);
+            assert_eq!(
+                parser.lex_unexpanded_token().unwrap(),
+                Some(Token::Char('x', Category::Letter))
+            );
+        });
+    }
+
+    #[test]
+    fn it_ends_boxes_before_end() {
+        with_parser(&[r"\hbox x\end%"], |parser| {
+            let hbox = parser.parse_box().unwrap().unwrap();
+            assert_eq!(hbox.to_chars(), vec!['x']);
+
+            let errors = parser.take_errors();
+            assert_eq!(errors[0].kind, TeXErrorKind::MissingLeftBrace);
+            assert_eq!(errors[1].kind, TeXErrorKind::MissingRightBrace);
+            assert_eq!(errors.len(), 2);
+            assert_eq!(
+                parser.lex_unexpanded_token().unwrap(),
+                Some(Token::ControlSequence("end".to_string()))
+            );
+        });
+
+        with_parser(&[r"\hbox{a\end%"], |parser| {
+            let hbox = parser.parse_box().unwrap().unwrap();
+            assert_eq!(hbox.to_chars(), vec!['a']);
+            assert_eq!(
+                parser.take_errors()[0].kind,
+                TeXErrorKind::MissingRightBrace
+            );
+            assert_eq!(
+                parser.lex_unexpanded_token().unwrap(),
+                Some(Token::ControlSequence("end".to_string()))
+            );
+        });
+
+        with_parser(&[r"\hbox{a{b\begingroup c\end%"], |parser| {
+            let hbox = parser.parse_box().unwrap().unwrap();
+            assert_eq!(hbox.to_chars(), vec!['a', 'b', 'c']);
+            assert_eq!(parser.get_group_depth(), 0);
+
+            let errors = parser.take_errors();
+            assert_eq!(errors[0].kind, TeXErrorKind::MissingEndGroup);
+            assert_eq!(errors[1].kind, TeXErrorKind::MissingRightBrace);
+            assert_eq!(errors[2].kind, TeXErrorKind::MissingRightBrace);
+            assert_eq!(errors.len(), 3);
+            assert_eq!(
+                parser.lex_unexpanded_token().unwrap(),
+                Some(Token::ControlSequence("end".to_string()))
+            );
+        });
+    }
+
+    #[test]
+    fn it_ends_boxes_at_the_end_of_the_input() {
+        with_parser(&[r"\hbox{a{b%"], |parser| {
+            let hbox = parser.parse_box().unwrap().unwrap();
+            assert_eq!(hbox.to_chars(), vec!['a', 'b']);
+            assert_eq!(parser.get_group_depth(), 0);
+
+            let errors = parser.take_errors();
+            assert_eq!(errors[0].kind, TeXErrorKind::MissingRightBrace);
+            assert_eq!(errors[1].kind, TeXErrorKind::MissingRightBrace);
+            assert_eq!(errors.len(), 2);
+        });
+    }
+
+    #[test]
+    fn it_ignores_vertical_material_in_horizontal_boxes() {
+        with_parser(&[r"\hbox{\vskip1pt}%"], |parser| {
+            let hbox = parser.parse_box().unwrap().unwrap();
+            // The 1pt is typeset as characters
+            assert_eq!(hbox.to_chars(), vec!['1', 'p', 't']);
+            assert_eq!(
+                parser.take_errors()[0].kind,
+                TeXErrorKind::CantUseInMode(
+                    Token::ControlSequence("vskip".to_string()),
+                    "restricted horizontal mode"
+                )
+            );
+        });
+    }
+
     #[test]
     fn it_parses_state_group_around_box_definitions() {
         with_parser(
@@ -801,10 +955,13 @@ mod tests {
                 r"\setbox1=\hbox{1\hbox{12}1\vbox{12}1}%",
             ],
             |parser| {
-                parser.parse_assignment(None);
-                parser.parse_assignment(None);
+                parser.parse_assignment(None).unwrap();
+                parser.parse_assignment(None).unwrap();
 
-                assert_eq!(parser.state.get_box(0), parser.state.get_box(1),);
+                assert_eq!(
+                    parser.state.get_box(0).without_origins(),
+                    parser.state.get_box(1).without_origins()
+                );
             },
         );
     }
@@ -819,10 +976,10 @@ mod tests {
                 r"\setbox3=\hbox to1500sp{\hskip 0pt plus 1000fil}%",
             ],
             |parser| {
-                parser.parse_assignment(None);
-                parser.parse_assignment(None);
-                parser.parse_assignment(None);
-                parser.parse_assignment(None);
+                parser.parse_assignment(None).unwrap();
+                parser.parse_assignment(None).unwrap();
+                parser.parse_assignment(None).unwrap();
+                parser.parse_assignment(None).unwrap();
 
                 assert_eq!(
                     parser.state.get_box(0),
@@ -0,0 +1,112 @@
+use crate::error::TeXResult;
+use crate::parser::Parser;
+use crate::token::Token;
+
+impl<'a> Parser<'a> {
+    pub fn is_case_change_head(&mut self) -> TeXResult<bool> {
+        self.is_next_expanded_token_in_set_of_primitives(&[
+            "uppercase",
+            "lowercase",
+        ])
+    }
+
+    /// Parses \uppercase{...} or \lowercase{...}. The characters in the
+    /// braced text are changed using the \uccode or \lccode of each one,
+    /// keeping their categories, and the result is read next. Characters with
+    /// a code of 0 and control sequences are left alone.
+    pub fn parse_case_change(&mut self) -> TeXResult<()> {
+        let head = self.lex_expanded_token()?.unwrap();
+        let uppercase = self.state.is_token_equal_to_prim(&head, "uppercase");
+
+        let tokens = self.parse_unexpanded_general_text(&head)?;
+        let changed_tokens = tokens
+            .into_iter()
+            .map(|token| match token {
+                Token::Char(ch, cat) => {
+                    let code = if uppercase {
+                        self.state.get_uppercase_code(ch)
+                    } else {
+                        self.state.get_lowercase_code(ch)
+                    };
+                    if code == '\0' {
+                        Token::Char(ch, cat)
+                    } else {
+                        Token::Char(code, cat)
+                    }
+                }
+                Token::ControlSequence(_) => token,
+            })
+            .collect();
+
+        self.add_upcoming_tokens(changed_tokens);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::category::Category;
+    use crate::testing::with_parser;
+
+    fn lex_all_tokens(parser: &mut Parser) -> Vec<Token> {
+        let mut tokens = Vec::new();
+        while let Some(token) = parser.lex_unexpanded_token().unwrap() {
+            tokens.push(token);
+        }
+        tokens
+    }
+
+    #[test]
+    fn it_changes_the_case_of_characters() {
+        with_parser(&[r"\uppercase{aB\x{c1}}%"], |parser| {
+            assert!(parser.is_case_change_head().unwrap());
+            parser.parse_case_change().unwrap();
+            assert_eq!(
+                lex_all_tokens(parser),
+                vec![
+                    Token::Char('A', Category::Letter),
+                    Token::Char('B', Category::Letter),
+                    Token::ControlSequence("x".to_string()),
+                    Token::Char('{', Category::BeginGroup),
+                    Token::Char('C', Category::Letter),
+                    Token::Char('1', Category::Other),
+                    Token::Char('}', Category::EndGroup),
+                ]
+            );
+        });
+
+        with_parser(&[r"\lowercase{Ab\X}%"], |parser| {
+            parser.parse_case_change().unwrap();
+            assert_eq!(
+                lex_all_tokens(parser),
+                vec![
+                    Token::Char('a', Category::Letter),
+                    Token::Char('b', Category::Letter),
+                    Token::ControlSequence("X".to_string()),
+                ]
+            );
+        });
+    }
+
+    #[test]
+    fn it_uses_the_case_codes_and_keeps_categories() {
+        with_parser(
+            &[r"\lccode`\!=`\? \catcode`\Q=12 %", r"\lowercase{!Q\def}%"],
+            |parser| {
+                parser.parse_assignment(None).unwrap();
+                parser.parse_assignment(None).unwrap();
+                parser.parse_case_change().unwrap();
+                assert_eq!(
+                    lex_all_tokens(parser),
+                    vec![
+                        Token::Char('?', Category::Other),
+                        Token::Char('q', Category::Other),
+                        Token::ControlSequence("def".to_string()),
+                    ]
+                );
+            },
+        );
+    }
+}
@@ -1,14 +1,22 @@
+use crate::boxes::TeXBox;
 use crate::category::Category;
+use crate::error::{TeXErrorKind, TeXResult};
 use crate::parser::Parser;
 use crate::token::Token;
 
+const CONDITIONAL_PRIMITIVES: &[&str] = &[
+    "iftrue", "iffalse", "ifnum", "ifdim", "ifodd", "ifx", "if", "ifcat",
+    "ifcase", "ifvmode", "ifhmode", "ifmmode", "ifinner", "ifvoid", "ifhbox",
+    "ifvbox", "ifeof",
+];
+
 enum Relation {
     GreaterThan,
     EqualTo,
     LessThan,
 }
 
-fn check_relation(rel: Relation, left: i32, right: i32) -> bool {
+fn check_relation<T: PartialOrd>(rel: Relation, left: T, right: T) -> bool {
     match rel {
         Relation::GreaterThan => left > right,
         Relation::EqualTo => left == right,
@@ -16,118 +24,334 @@ fn check_relation(rel: Relation, left: i32, right: i32) -> bool {
     }
 }
 
+// The token that skip_to_fi_or_else() stopped skipping at.
+enum SkipEnd {
+    Fi,
+    Else,
+    Or,
+}
+
 impl<'a> Parser<'a> {
-    fn is_conditional_start(&mut self, token: &Token) -> bool {
-        self.state.is_token_equal_to_prim(token, "iftrue")
-            || self.state.is_token_equal_to_prim(token, "iffalse")
-            || self.state.is_token_equal_to_prim(token, "ifnum")
-    }
-
-    pub fn is_conditional_head(&mut self) -> bool {
-        match self.peek_unexpanded_token() {
-            Some(token) => {
-                self.is_conditional_start(&token)
-                    || self.state.is_token_equal_to_prim(&token, "else")
-                    || self.state.is_token_equal_to_prim(&token, "fi")
-            }
-            _ => false,
+    pub fn is_conditional_start(&mut self, token: &Token) -> bool {
+        CONDITIONAL_PRIMITIVES
+            .iter()
+            .any(|prim| self.state.is_token_equal_to_prim(token, prim))
+    }
+
+    pub fn is_conditional_head(&mut self) -> TeXResult<bool> {
+        match self.peek_unexpanded_token()? {
+            Some(token) => Ok(self.is_conditional_start(&token)
+                || self.state.is_token_equal_to_prim(&token, "else")
+                || self.state.is_token_equal_to_prim(&token, "or")
+                || self.state.is_token_equal_to_prim(&token, "fi")),
+            _ => Ok(false),
         }
     }
 
-    // Skips tokens until a \fi or \else is parsed. Returns true if the token
-    // we found is \else, false if it is \fi.
-    fn skip_to_fi_or_else(&mut self) -> bool {
-        let mut ends_with_else = false;
+    fn is_outer_macro(&self, token: &Token) -> bool {
+        match self.state.get_macro(token) {
+            Some(makro) => makro.is_outer(),
+            None => false,
+        }
+    }
+
+    // \outer macros aren't allowed in skipped text. When TeX finds one, it
+    // acts as if there were a \fi right before it, so the skipping stops and
+    // the macro is read again normally.
+    fn end_skipping_at_outer_macro(&mut self, token: Token) -> TeXResult<()> {
+        self.report_error(TeXErrorKind::OuterInSkippedConditional(
+            token.clone(),
+        ))?;
+        self.add_upcoming_token(token);
+        Ok(())
+    }
+
+    // Skips tokens until a \fi, \else or \or is parsed, and returns which one
+    // we found.
+    fn skip_to_fi_or_else(&mut self) -> TeXResult<SkipEnd> {
         loop {
-            let token = self.lex_unexpanded_token().unwrap();
+            let token = match self.lex_unexpanded_token()? {
+                Some(token) => token,
+                None => {
+                    return Err(
+                        self.fatal_error(TeXErrorKind::IncompleteConditional)
+                    )
+                }
+            };
             if self.is_conditional_start(&token) {
                 // If we see a conditional start while we're skipping, we need
                 // to just skip to the end of that inner conditional before we
                 // continue looking for the outer \fi.
-                self.skip_to_fi();
+                self.skip_to_fi()?;
             } else if self.state.is_token_equal_to_prim(&token, "fi") {
-                break;
+                return Ok(SkipEnd::Fi);
             } else if self.state.is_token_equal_to_prim(&token, "else") {
-                ends_with_else = true;
-                break;
+                return Ok(SkipEnd::Else);
+            } else if self.state.is_token_equal_to_prim(&token, "or") {
+                return Ok(SkipEnd::Or);
+            } else if self.is_outer_macro(&token) {
+                self.end_skipping_at_outer_macro(token)?;
+                return Ok(SkipEnd::Fi);
             }
         }
-        ends_with_else
     }
 
     // Skips tokens until a \fi is found.
-    fn skip_to_fi(&mut self) {
+    fn skip_to_fi(&mut self) -> TeXResult<()> {
         loop {
-            let token = self.lex_unexpanded_token().unwrap();
+            let token = match self.lex_unexpanded_token()? {
+                Some(token) => token,
+                None => {
+                    return Err(
+                        self.fatal_error(TeXErrorKind::IncompleteConditional)
+                    )
+                }
+            };
             if self.is_conditional_start(&token) {
                 // If we see a conditional start while we're skipping, we need
                 // to just skip to the end of that inner conditional before we
                 // continue looking for the outer \fi.
-                self.skip_to_fi();
+                self.skip_to_fi()?;
             } else if self.state.is_token_equal_to_prim(&token, "fi") {
                 break;
+            } else if self.is_outer_macro(&token) {
+                self.end_skipping_at_outer_macro(token)?;
+                break;
             }
         }
+        Ok(())
     }
 
     fn handle_true(&mut self) {
         self.conditional_depth += 1;
     }
 
-    fn handle_false(&mut self) {
-        if self.skip_to_fi_or_else() {
-            // If we skipped all the way to a \fi, we don't add to our depth of
-            // conditionals because we already exited this one. If we only
-            // skipped to a \else, we are now inside a conditional.
-            self.conditional_depth += 1;
+    fn handle_false(&mut self) -> TeXResult<()> {
+        loop {
+            match self.skip_to_fi_or_else()? {
+                // If we skipped all the way to a \fi, we don't add to our
+                // depth of conditionals because we already exited this one.
+                SkipEnd::Fi => break,
+                // If we only skipped to a \else, we are now inside a
+                // conditional.
+                SkipEnd::Else => {
+                    self.conditional_depth += 1;
+                    break;
+                }
+                // \or only belongs in \ifcase, so TeX complains about it and
+                // keeps skipping.
+                SkipEnd::Or => {
+                    self.report_error(TeXErrorKind::ExtraConditionalEnd(
+                        Token::ControlSequence("or".to_string()),
+                    ))?;
+                }
+            }
         }
+        Ok(())
     }
 
-    fn parse_relation(&mut self) -> Relation {
-        let relation = match self.lex_expanded_token() {
+    fn handle_result(&mut self, result: bool) -> TeXResult<()> {
+        if result {
+            self.handle_true();
+            Ok(())
+        } else {
+            self.handle_false()
+        }
+    }
+
+    fn parse_relation(&mut self, conditional: &Token) -> TeXResult<Relation> {
+        let relation = match self.lex_expanded_token()? {
             Some(Token::Char('<', Category::Other)) => Relation::LessThan,
             Some(Token::Char('=', Category::Other)) => Relation::EqualTo,
             Some(Token::Char('>', Category::Other)) => Relation::GreaterThan,
-            rest => panic!("Invalid relation: {:?}", rest),
+            rest => {
+                // TeX treats a missing relation as if it were an =, and
+                // leaves whatever was there to be read again.
+                self.report_error(TeXErrorKind::MissingRelation(
+                    conditional.clone(),
+                ))?;
+                if let Some(token) = rest {
+                    self.add_upcoming_token(token);
+                }
+                Relation::EqualTo
+            }
         };
-        self.parse_optional_spaces_expanded();
-        relation
+        self.parse_optional_spaces_expanded()?;
+        Ok(relation)
+    }
+
+    // Parses one of the tokens compared by \if and \ifcat, expanding macros
+    // before it. Returns the character code and category that the token
+    // stands for, or None for tokens which don't stand for a character (like
+    // \relax), which all compare equal to each other.
+    fn parse_char_code_and_category(
+        &mut self,
+    ) -> TeXResult<Option<(char, Category)>> {
+        let token = match self.lex_expanded_token()? {
+            Some(token) => token,
+            None => return Ok(None),
+        };
+
+        match token {
+            Token::Char(ch, cat) if cat != Category::Active => {
+                Ok(Some((ch, cat)))
+            }
+            _ => match self.state.get_renamed_token(&token) {
+                // Control sequences which have been \let equal to a
+                // character stand for that character.
+                Some(Token::Char(ch, cat)) => Ok(Some((ch, cat))),
+                // Active characters only get here without being expanded if
+                // they were marked with \noexpand, and then they stand for
+                // themselves.
+                _ => match token {
+                    Token::Char(ch, Category::Active)
+                        if self.last_token_noexpanded =>
+                    {
+                        Ok(Some((ch, Category::Active)))
+                    }
+                    _ => Ok(None),
+                },
+            },
+        }
+    }
+
+    // TeX starts out in vertical mode, so we use that if we're not parsing
+    // any list yet.
+    fn get_current_mode(&self) -> &'static str {
+        self.modes.last().copied().unwrap_or("vertical mode")
     }
 
-    pub fn expand_conditional(&mut self) {
-        let token = self.lex_unexpanded_token().unwrap();
+    pub fn expand_conditional(&mut self) -> TeXResult<()> {
+        let token = self.lex_unexpanded_token()?.unwrap();
 
         if self.state.is_token_equal_to_prim(&token, "fi") {
             if self.conditional_depth == 0 {
-                panic!("Extra \\fi");
+                return self
+                    .report_error(TeXErrorKind::ExtraConditionalEnd(token));
             }
             self.conditional_depth -= 1;
-        } else if self.state.is_token_equal_to_prim(&token, "else") {
+        } else if self.state.is_token_equal_to_prim(&token, "else")
+            || self.state.is_token_equal_to_prim(&token, "or")
+        {
             if self.conditional_depth == 0 {
-                panic!("Extra \\else");
+                return self
+                    .report_error(TeXErrorKind::ExtraConditionalEnd(token));
             }
             self.conditional_depth -= 1;
-            // When we encounter an \else, we know that we're in a 'true'
-            // conditional because in a 'false' conditional, we always already
-            // parse the \else token in skip_to_fi_or_else(). Thus, we just
-            // need to skip tokens until we see a \fi.
-            self.skip_to_fi();
-        } else if self.state.is_token_equal_to_prim(&token, "iftrue") {
-            self.handle_true();
-        } else if self.state.is_token_equal_to_prim(&token, "iffalse") {
-            self.handle_false();
-        } else if self.state.is_token_equal_to_prim(&token, "ifnum") {
-            let num1 = self.parse_number();
-            let relation = self.parse_relation();
-            let num2 = self.parse_number();
-
-            if check_relation(relation, num1, num2) {
+            // When we encounter an \else or \or, we know that we're in a
+            // 'true' conditional (or the chosen case of an \ifcase) because
+            // in a 'false' conditional, we always already parse the \else
+            // token in skip_to_fi_or_else(). Thus, we just need to skip
+            // tokens until we see a \fi.
+            self.skip_to_fi()?;
+        } else if self.state.is_token_equal_to_prim(&token, "ifcase") {
+            let mut case = self.parse_number()?;
+
+            // Skip over the cases before the chosen one. If there aren't
+            // enough cases, the \else case is used if there is one.
+            let mut found_case = true;
+            while case != 0 {
+                match self.skip_to_fi_or_else()? {
+                    SkipEnd::Or => case -= 1,
+                    SkipEnd::Else => break,
+                    SkipEnd::Fi => {
+                        found_case = false;
+                        break;
+                    }
+                }
+            }
+
+            self.trace_conditional(&token, found_case);
+            if found_case {
                 self.handle_true();
-            } else {
-                self.handle_false();
             }
         } else {
-            panic!("unimplemented");
+            let result = self.evaluate_condition(&token)?;
+            self.trace_conditional(&token, result);
+            self.handle_result(result)?;
+        }
+        Ok(())
+    }
+
+    // Parses the rest of an \if-style conditional that starts with `token`,
+    // and returns whether it is true.
+    fn evaluate_condition(&mut self, token: &Token) -> TeXResult<bool> {
+        let state = self.state;
+        let is = |prim| state.is_token_equal_to_prim(token, prim);
+
+        if is("iftrue") {
+            Ok(true)
+        } else if is("iffalse") {
+            Ok(false)
+        } else if is("ifnum") {
+            let num1 = self.parse_number()?;
+            let relation = self.parse_relation(token)?;
+            let num2 = self.parse_number()?;
+            Ok(check_relation(relation, num1, num2))
+        } else if is("ifdim") {
+            let dimen1 = self.parse_dimen()?;
+            let relation = self.parse_relation(token)?;
+            let dimen2 = self.parse_dimen()?;
+            Ok(check_relation(relation, dimen1, dimen2))
+        } else if is("ifodd") {
+            Ok(self.parse_number()? % 2 != 0)
+        } else if is("ifx") {
+            let token1 = self.lex_unexpanded_token()?;
+            let token2 = self.lex_unexpanded_token()?;
+            match (token1, token2) {
+                (Some(token1), Some(token2)) => {
+                    Ok(self.state.is_meaning_equal(&token1, &token2))
+                }
+                _ => Ok(false),
+            }
+        } else if is("if") || is("ifcat") {
+            let compare_categories = is("ifcat");
+            let char1 = self.parse_char_code_and_category()?;
+            let char2 = self.parse_char_code_and_category()?;
+            Ok(match (char1, char2) {
+                (Some((ch1, cat1)), Some((ch2, cat2))) => {
+                    if compare_categories {
+                        cat1 == cat2
+                    } else {
+                        ch1 == ch2
+                    }
+                }
+                (None, None) => true,
+                _ => false,
+            })
+        } else if is("ifvmode") {
+            Ok(self.get_current_mode().ends_with("vertical mode"))
+        } else if is("ifhmode") {
+            Ok(self.get_current_mode().ends_with("horizontal mode"))
+        } else if is("ifmmode") {
+            Ok(self.get_current_mode().ends_with("math mode"))
+        } else if is("ifinner") {
+            Ok(matches!(
+                self.get_current_mode(),
+                "internal vertical mode"
+                    | "restricted horizontal mode"
+                    | "math mode"
+            ))
+        } else if is("ifvoid") || is("ifhbox") || is("ifvbox") {
+            let box_index = self.parse_8bit_number()?;
+            let is_hbox = self.state.with_box(box_index, |tex_box| {
+                matches!(tex_box, TeXBox::HorizontalBox(_))
+            });
+            Ok(if is("ifvoid") {
+                is_hbox.is_none()
+            } else if is("ifhbox") {
+                is_hbox == Some(true)
+            } else {
+                is_hbox == Some(false)
+            })
+        } else if is("ifeof") {
+            // We don't support \openin yet, so no input streams are ever
+            // open, and TeX treats streams that aren't open as being at the
+            // end of the file.
+            self.parse_4bit_number()?;
+            Ok(true)
+        } else {
+            panic!("expand_conditional called on a non-conditional token");
         }
     }
 }
@@ -145,54 +369,54 @@ mod tests {
     #[test]
     fn it_parses_single_body_iftrue() {
         with_parser(&["\\iftrue x\\fi%"], |parser| {
-            assert_eq!(parser.is_conditional_head(), true);
-            parser.expand_conditional();
+            assert_eq!(parser.is_conditional_head().unwrap(), true);
+            parser.expand_conditional().unwrap();
             assert_eq!(
-                parser.lex_unexpanded_token(),
+                parser.lex_unexpanded_token().unwrap(),
                 Some(Token::Char('x', Category::Letter))
             );
-            assert_eq!(parser.is_conditional_head(), true);
-            parser.expand_conditional();
-            assert_eq!(parser.lex_unexpanded_token(), None);
+            assert_eq!(parser.is_conditional_head().unwrap(), true);
+            parser.expand_conditional().unwrap();
+            assert_eq!(parser.lex_unexpanded_token().unwrap(), None);
         });
     }
 
     #[test]
     fn it_parses_iftrue_with_else() {
         with_parser(&["\\iftrue x\\else y\\fi%"], |parser| {
-            assert_eq!(parser.is_conditional_head(), true);
-            parser.expand_conditional();
+            assert_eq!(parser.is_conditional_head().unwrap(), true);
+            parser.expand_conditional().unwrap();
             assert_eq!(
-                parser.lex_unexpanded_token(),
+                parser.lex_unexpanded_token().unwrap(),
                 Some(Token::Char('x', Category::Letter))
             );
-            assert_eq!(parser.is_conditional_head(), true);
-            parser.expand_conditional();
-            assert_eq!(parser.lex_unexpanded_token(), None);
+            assert_eq!(parser.is_conditional_head().unwrap(), true);
+            parser.expand_conditional().unwrap();
+            assert_eq!(parser.lex_unexpanded_token().unwrap(), None);
         });
     }
 
     #[test]
     fn it_parses_single_body_iffalse() {
         with_parser(&["\\iffalse x\\fi%"], |parser| {
-            assert_eq!(parser.is_conditional_head(), true);
-            parser.expand_conditional();
-            assert_eq!(parser.lex_unexpanded_token(), None);
+            assert_eq!(parser.is_conditional_head().unwrap(), true);
+            parser.expand_conditional().unwrap();
+            assert_eq!(parser.lex_unexpanded_token().unwrap(), None);
         });
     }
 
     #[test]
     fn it_parses_iffalse_with_else() {
         with_parser(&["\\iffalse x\\else y\\fi%"], |parser| {
-            assert_eq!(parser.is_conditional_head(), true);
-            parser.expand_conditional();
+            assert_eq!(parser.is_conditional_head().unwrap(), true);
+            parser.expand_conditional().unwrap();
             assert_eq!(
-                parser.lex_unexpanded_token(),
+                parser.lex_unexpanded_token().unwrap(),
                 Some(Token::Char('y', Category::Letter))
             );
-            assert_eq!(parser.is_conditional_head(), true);
-            parser.expand_conditional();
-            assert_eq!(parser.lex_unexpanded_token(), None);
+            assert_eq!(parser.is_conditional_head().unwrap(), true);
+            parser.expand_conditional().unwrap();
+            assert_eq!(parser.lex_unexpanded_token().unwrap(), None);
         });
     }
 
@@ -236,19 +460,19 @@ mod tests {
                 )),
             );
 
-            assert_eq!(parser.is_conditional_head(), true);
-            parser.expand_conditional();
+            assert_eq!(parser.is_conditional_head().unwrap(), true);
+            parser.expand_conditional().unwrap();
             assert_eq!(
-                parser.lex_expanded_token(),
+                parser.lex_expanded_token().unwrap(),
                 Some(Token::Char('w', Category::Letter))
             );
             assert_eq!(
-                parser.lex_expanded_token(),
+                parser.lex_expanded_token().unwrap(),
                 Some(Token::Char('x', Category::Letter))
             );
-            assert_eq!(parser.is_conditional_head(), true);
-            parser.expand_conditional();
-            assert_eq!(parser.lex_unexpanded_token(), None);
+            assert_eq!(parser.is_conditional_head().unwrap(), true);
+            parser.expand_conditional().unwrap();
+            assert_eq!(parser.lex_unexpanded_token().unwrap(), None);
         });
     }
 
@@ -278,24 +502,24 @@ mod tests {
                     &Token::ControlSequence("else".to_string()),
                 );
 
-                assert_eq!(parser.is_conditional_head(), true);
-                parser.expand_conditional();
+                assert_eq!(parser.is_conditional_head().unwrap(), true);
+                parser.expand_conditional().unwrap();
                 assert_eq!(
-                    parser.lex_expanded_token(),
+                    parser.lex_expanded_token().unwrap(),
                     Some(Token::Char('a', Category::Letter))
                 );
-                assert_eq!(parser.is_conditional_head(), true);
-                parser.expand_conditional();
+                assert_eq!(parser.is_conditional_head().unwrap(), true);
+                parser.expand_conditional().unwrap();
 
-                assert_eq!(parser.is_conditional_head(), true);
-                parser.expand_conditional();
+                assert_eq!(parser.is_conditional_head().unwrap(), true);
+                parser.expand_conditional().unwrap();
                 assert_eq!(
-                    parser.lex_expanded_token(),
+                    parser.lex_expanded_token().unwrap(),
                     Some(Token::Char('b', Category::Letter))
                 );
-                assert_eq!(parser.is_conditional_head(), true);
-                parser.expand_conditional();
-                assert_eq!(parser.lex_unexpanded_token(), None);
+                assert_eq!(parser.is_conditional_head().unwrap(), true);
+                parser.expand_conditional().unwrap();
+                assert_eq!(parser.lex_unexpanded_token().unwrap(), None);
             },
         );
     }
@@ -310,34 +534,34 @@ mod tests {
             ],
             |parser| {
                 // 1<2 -> t
-                assert_eq!(parser.is_conditional_head(), true);
-                parser.expand_conditional();
+                assert_eq!(parser.is_conditional_head().unwrap(), true);
+                parser.expand_conditional().unwrap();
                 assert_eq!(
-                    parser.lex_expanded_token(),
+                    parser.lex_expanded_token().unwrap(),
                     Some(Token::Char('t', Category::Letter))
                 );
-                assert_eq!(parser.is_conditional_head(), true);
-                parser.expand_conditional();
+                assert_eq!(parser.is_conditional_head().unwrap(), true);
+                parser.expand_conditional().unwrap();
 
                 // 1>2 -> f
-                assert_eq!(parser.is_conditional_head(), true);
-                parser.expand_conditional();
+                assert_eq!(parser.is_conditional_head().unwrap(), true);
+                parser.expand_conditional().unwrap();
                 assert_eq!(
-                    parser.lex_expanded_token(),
+                    parser.lex_expanded_token().unwrap(),
                     Some(Token::Char('f', Category::Letter))
                 );
-                assert_eq!(parser.is_conditional_head(), true);
-                parser.expand_conditional();
+                assert_eq!(parser.is_conditional_head().unwrap(), true);
+                parser.expand_conditional().unwrap();
 
                 // 1=2 -> f
-                assert_eq!(parser.is_conditional_head(), true);
-                parser.expand_conditional();
+                assert_eq!(parser.is_conditional_head().unwrap(), true);
+                parser.expand_conditional().unwrap();
                 assert_eq!(
-                    parser.lex_expanded_token(),
+                    parser.lex_expanded_token().unwrap(),
                     Some(Token::Char('f', Category::Letter))
                 );
-                assert_eq!(parser.is_conditional_head(), true);
-                parser.expand_conditional();
+                assert_eq!(parser.is_conditional_head().unwrap(), true);
+                parser.expand_conditional().unwrap();
             },
         );
     }
@@ -345,12 +569,12 @@ mod tests {
     #[test]
     fn it_allows_spaces_in_ifnum() {
         with_parser(&["\\ifnum 1       <      2      t\\fi%"], |parser| {
-            parser.expand_conditional();
+            parser.expand_conditional().unwrap();
             assert_eq!(
-                parser.lex_expanded_token(),
+                parser.lex_expanded_token().unwrap(),
                 Some(Token::Char('t', Category::Letter))
             );
-            parser.expand_conditional();
+            parser.expand_conditional().unwrap();
         });
     }
 
@@ -363,14 +587,14 @@ mod tests {
                 parser.state.set_count(false, 1, 20);
 
                 // 10<20 -> t
-                assert_eq!(parser.is_conditional_head(), true);
-                parser.expand_conditional();
+                assert_eq!(parser.is_conditional_head().unwrap(), true);
+                parser.expand_conditional().unwrap();
                 assert_eq!(
-                    parser.lex_expanded_token(),
+                    parser.lex_expanded_token().unwrap(),
                     Some(Token::Char('t', Category::Letter))
                 );
-                assert_eq!(parser.is_conditional_head(), true);
-                parser.expand_conditional();
+                assert_eq!(parser.is_conditional_head().unwrap(), true);
+                parser.expand_conditional().unwrap();
             },
         );
     }
@@ -386,47 +610,280 @@ mod tests {
             ],
             |parser| {
                 // true inside true
-                assert!(parser.is_conditional_head());
-                parser.expand_conditional();
-                assert!(parser.is_conditional_head());
-                parser.expand_conditional();
+                assert!(parser.is_conditional_head().unwrap());
+                parser.expand_conditional().unwrap();
+                assert!(parser.is_conditional_head().unwrap());
+                parser.expand_conditional().unwrap();
                 assert_eq!(
-                    parser.lex_unexpanded_token(),
+                    parser.lex_unexpanded_token().unwrap(),
                     Some(Token::Char('x', Category::Letter))
                 );
-                assert!(parser.is_conditional_head());
-                parser.expand_conditional();
-                assert!(parser.is_conditional_head());
-                parser.expand_conditional();
+                assert!(parser.is_conditional_head().unwrap());
+                parser.expand_conditional().unwrap();
+                assert!(parser.is_conditional_head().unwrap());
+                parser.expand_conditional().unwrap();
 
                 // true inside false
-                parser.expand_conditional();
+                parser.expand_conditional().unwrap();
 
                 // true inside else of true
-                assert!(parser.is_conditional_head());
-                parser.expand_conditional();
+                assert!(parser.is_conditional_head().unwrap());
+                parser.expand_conditional().unwrap();
                 assert_eq!(
-                    parser.lex_unexpanded_token(),
+                    parser.lex_unexpanded_token().unwrap(),
                     Some(Token::Char('x', Category::Letter))
                 );
-                assert!(parser.is_conditional_head());
-                parser.expand_conditional();
-                assert!(parser.is_conditional_head());
+                assert!(parser.is_conditional_head().unwrap());
+                parser.expand_conditional().unwrap();
+                assert!(parser.is_conditional_head().unwrap());
 
                 // true inside else of false
-                assert!(parser.is_conditional_head());
-                parser.expand_conditional();
-                assert!(parser.is_conditional_head());
-                parser.expand_conditional();
+                assert!(parser.is_conditional_head().unwrap());
+                parser.expand_conditional().unwrap();
+                assert!(parser.is_conditional_head().unwrap());
+                parser.expand_conditional().unwrap();
                 assert_eq!(
-                    parser.lex_unexpanded_token(),
+                    parser.lex_unexpanded_token().unwrap(),
                     Some(Token::Char('x', Category::Letter))
                 );
-                assert!(parser.is_conditional_head());
-                parser.expand_conditional();
-                assert!(parser.is_conditional_head());
-                parser.expand_conditional();
+                assert!(parser.is_conditional_head().unwrap());
+                parser.expand_conditional().unwrap();
+                assert!(parser.is_conditional_head().unwrap());
+                parser.expand_conditional().unwrap();
             },
         );
     }
+
+    #[test]
+    fn it_reports_extra_fis_and_elses() {
+        with_parser(&["\\fi\\else x%"], |parser| {
+            parser.expand_conditional().unwrap();
+            parser.expand_conditional().unwrap();
+            assert_eq!(
+                parser.lex_unexpanded_token().unwrap(),
+                Some(Token::Char('x', Category::Letter))
+            );
+
+            let kinds: Vec<TeXErrorKind> =
+                parser.take_errors().into_iter().map(|e| e.kind).collect();
+            assert_eq!(
+                kinds,
+                vec![
+                    TeXErrorKind::ExtraConditionalEnd(Token::ControlSequence(
+                        "fi".to_string()
+                    )),
+                    TeXErrorKind::ExtraConditionalEnd(Token::ControlSequence(
+                        "else".to_string()
+                    )),
+                ]
+            );
+        });
+    }
+
+    #[test]
+    fn it_stops_skipping_at_outer_macros() {
+        with_parser(
+            &[r"\outer\def\a{x}%", r"\iffalse\iftrue\a\else y\fi z%"],
+            |parser| {
+                parser.parse_assignment(None).unwrap();
+                parser.expand_conditional().unwrap();
+                assert_eq!(
+                    parser.lex_expanded_token().unwrap(),
+                    Some(Token::Char('x', Category::Letter))
+                );
+                assert_eq!(
+                    parser.lex_expanded_token().unwrap(),
+                    Some(Token::Char('y', Category::Letter))
+                );
+                assert_eq!(
+                    parser.lex_expanded_token().unwrap(),
+                    Some(Token::Char('z', Category::Letter))
+                );
+
+                // The \outer macro ends both of the conditionals, so the
+                // \else and \fi are extra.
+                let kinds: Vec<TeXErrorKind> =
+                    parser.take_errors().into_iter().map(|e| e.kind).collect();
+                let a = Token::ControlSequence("a".to_string());
+                assert_eq!(
+                    kinds,
+                    vec![
+                        TeXErrorKind::OuterInSkippedConditional(a.clone()),
+                        TeXErrorKind::OuterInSkippedConditional(a),
+                        TeXErrorKind::ExtraConditionalEnd(
+                            Token::ControlSequence("else".to_string())
+                        ),
+                        TeXErrorKind::ExtraConditionalEnd(
+                            Token::ControlSequence("fi".to_string())
+                        ),
+                    ]
+                );
+            },
+        );
+    }
+
+    #[test]
+    fn it_treats_missing_relations_as_equals() {
+        with_parser(&["\\ifnum 2 2 t\\else f\\fi%"], |parser| {
+            parser.expand_conditional().unwrap();
+            assert_eq!(
+                parser.lex_expanded_token().unwrap(),
+                Some(Token::Char('t', Category::Letter))
+            );
+            parser.expand_conditional().unwrap();
+
+            let errors = parser.take_errors();
+            assert_eq!(errors.len(), 1);
+            assert_eq!(
+                errors[0].kind,
+                TeXErrorKind::MissingRelation(Token::ControlSequence(
+                    "ifnum".to_string()
+                ))
+            );
+        });
+    }
+
+    // Expands all of the input and returns the characters that are left.
+    fn expand_to_string(parser: &mut Parser) -> String {
+        let mut result = String::new();
+        while let Some(token) = parser.lex_expanded_token().unwrap() {
+            if let Token::Char(ch, _) = token {
+                result.push(ch);
+            }
+        }
+        result
+    }
+
+    #[test]
+    fn it_parses_ifdim_and_ifodd() {
+        with_parser(
+            &[
+                r"\ifdim1pt<2pt a\fi\ifdim 1in>1cm b\fi\ifdim1pt=2pt \else c\fi",
+                r"\ifodd3 d\fi\ifodd-5 e\fi\ifodd0 \else f\fi%",
+            ],
+            |parser| {
+                assert_eq!(expand_to_string(parser), "abcdef");
+            },
+        );
+    }
+
+    #[test]
+    fn it_compares_meanings_with_ifx() {
+        with_parser(
+            &[
+                r"\def\a{x}\def\b{x}\long\def\c{x}\def\d#1{x}\let\e=a%",
+                r"\ifx\a\b 1\fi\ifx\a\c\else 2\fi\ifx\a\d\else 3\fi%",
+                r"\ifx\e a4\fi\ifx aa5\fi\ifx ab\else 6\fi%",
+                r"\ifx\undefined\alsoundefined 7\fi\ifx\relax\a\else 8\fi%",
+            ],
+            |parser| {
+                for _ in 0..5 {
+                    parser.parse_assignment(None).unwrap();
+                }
+                assert_eq!(expand_to_string(parser), "12345678");
+            },
+        );
+    }
+
+    #[test]
+    fn it_compares_characters_with_if_and_ifcat() {
+        with_parser(
+            &[
+                r"\def\a{aa}\let\b=c\catcode`\~=13 \def~{x}%",
+                r"\if\a 1\fi\if\b c2\fi\if\relax\hbox 3\fi\if a\relax\else 4\fi",
+                r"\ifcat\a 5\fi\ifcat 1a\else 6\fi\ifcat\noexpand~\noexpand~7\fi",
+                r"\if\noexpand~~\else 8\fi%",
+            ],
+            |parser| {
+                for _ in 0..4 {
+                    parser.parse_assignment(None).unwrap();
+                }
+                assert_eq!(expand_to_string(parser), "12345678");
+            },
+        );
+    }
+
+    #[test]
+    fn it_parses_ifcase() {
+        with_parser(
+            &[
+                r"\ifcase0 a\or b\or c\fi",
+                r"\ifcase2 a\or b\or c\fi",
+                r"\ifcase 5 a\or b\else d\fi",
+                r"\ifcase 5 a\or b\fi",
+                r"\ifcase1 \ifcase1 a\or e\fi\or b\fi%",
+            ],
+            |parser| {
+                assert_eq!(expand_to_string(parser), "acdb");
+                assert!(parser.take_errors().is_empty());
+            },
+        );
+    }
+
+    #[test]
+    fn it_reports_extra_ors() {
+        with_parser(&[r"\iffalse a\or b\else c\fi\or%"], |parser| {
+            assert_eq!(expand_to_string(parser), "c");
+
+            let kinds: Vec<TeXErrorKind> =
+                parser.take_errors().into_iter().map(|e| e.kind).collect();
+            let or = Token::ControlSequence("or".to_string());
+            assert_eq!(
+                kinds,
+                vec![
+                    TeXErrorKind::ExtraConditionalEnd(or.clone()),
+                    TeXErrorKind::ExtraConditionalEnd(or),
+                ]
+            );
+        });
+    }
+
+    #[test]
+    fn it_checks_the_current_mode() {
+        let check_mode = |mode: &'static str| {
+            let mut result = String::new();
+            with_parser(
+                &[r"\ifvmode v\fi\ifhmode h\fi\ifmmode m\fi\ifinner i\fi%"],
+                |parser| {
+                    parser.modes.push(mode);
+                    result = expand_to_string(parser);
+                },
+            );
+            result
+        };
+
+        assert_eq!(check_mode("vertical mode"), "v");
+        assert_eq!(check_mode("internal vertical mode"), "vi");
+        assert_eq!(check_mode("horizontal mode"), "h");
+        assert_eq!(check_mode("restricted horizontal mode"), "hi");
+        assert_eq!(check_mode("math mode"), "mi");
+    }
+
+    #[test]
+    fn it_checks_box_registers() {
+        with_parser(
+            &[
+                r"\setbox1=\hbox{}\setbox2=\vbox{}%",
+                r"\ifvoid0 a\fi\ifhbox0 b\fi\ifvbox0 c\fi",
+                r"\ifvoid1 d\fi\ifhbox1 e\fi\ifvbox1 f\fi",
+                r"\ifvoid2 g\fi\ifhbox2 h\fi\ifvbox2 i\fi%",
+            ],
+            |parser| {
+                parser.parse_assignment(None).unwrap();
+                parser.parse_assignment(None).unwrap();
+                assert_eq!(expand_to_string(parser), "aei");
+            },
+        );
+    }
+
+    #[test]
+    fn it_treats_all_input_streams_as_ended() {
+        with_parser(&[r"\ifeof3 a\fi\ifeof16 b\fi%"], |parser| {
+            assert_eq!(expand_to_string(parser), "ab");
+            assert_eq!(
+                parser.take_errors()[0].kind,
+                TeXErrorKind::BadNumber(16)
+            );
+        });
+    }
 }
@@ -0,0 +1,140 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crate::debugger::{DebugEvent, DebugView, Debugger, PartialList};
+use crate::parser::Parser;
+
+impl<'a> Parser<'a> {
+    /// Attaches a debugger, which the parser will stop at before every macro
+    /// expansion and command from now on.
+    pub fn set_debugger(&mut self, debugger: Rc<RefCell<dyn Debugger>>) {
+        self.debugger = Some(debugger);
+    }
+
+    // The list parsers call this before parsing each element of a list so
+    // that a debugger can see what has been built so far. Copying the list is
+    // expensive, so this only happens when a debugger is attached.
+    pub fn push_debug_list<F>(&mut self, get_list: F)
+    where
+        F: FnOnce() -> PartialList,
+    {
+        if self.debugger.is_some() {
+            self.debug_lists.push(get_list());
+        }
+    }
+
+    pub fn pop_debug_list(&mut self) {
+        if self.debugger.is_some() {
+            self.debug_lists.pop();
+        }
+    }
+
+    pub fn debug_step(&self, event: DebugEvent) {
+        self.debug_step_at_depth(event, self.expansion_bases.len());
+    }
+
+    /// Stops at the macro expansion which was traced most recently. This is
+    /// called once the replacement has been added to the upcoming tokens, so
+    /// that the debugger sees it as what will be read next. The depth is the
+    /// one the macro was expanded at, not counting its own expansion.
+    pub fn debug_macro_expansion(&self) {
+        if let Some((_, token, replacement)) = self.expansion_bases.last() {
+            self.debug_step_at_depth(
+                DebugEvent::MacroExpansion { token, replacement },
+                self.expansion_bases.len() - 1,
+            );
+        }
+    }
+
+    fn debug_step_at_depth(&self, event: DebugEvent, depth: usize) {
+        if let Some(debugger) = &self.debugger {
+            let view = DebugView {
+                depth,
+                line: self.lexer.get_current_line(),
+                upcoming_tokens: self
+                    .upcoming_tokens
+                    .iter()
+                    .rev()
+                    .map(|(token, _)| token.clone())
+                    .collect(),
+                lists: &self.debug_lists,
+                state: self.state,
+            };
+            debugger.borrow_mut().step(&event, &view);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::debugger::PartialListContents;
+    use crate::testing::with_parser;
+    use crate::token::format_token_list;
+
+    // Records a description of each step, along with the depth, upcoming
+    // tokens, and the length of the innermost list at that step.
+    struct StepRecorder {
+        steps: Vec<(String, usize, String, usize)>,
+    }
+
+    impl Debugger for StepRecorder {
+        fn step(&mut self, event: &DebugEvent, view: &DebugView) {
+            let description = match event {
+                DebugEvent::MacroExpansion { token, .. } => {
+                    format!("expand {}", token)
+                }
+                DebugEvent::Command { token, mode } => {
+                    format!("{}: {}", mode, token)
+                }
+            };
+            let list_length = match &view.lists.last().unwrap().contents {
+                PartialListContents::Horizontal(list) => list.len(),
+                _ => panic!("Unexpected list"),
+            };
+            self.steps.push((
+                description,
+                view.depth,
+                format_token_list(&view.upcoming_tokens),
+                list_length,
+            ));
+        }
+    }
+
+    #[test]
+    fn it_stops_at_expansions_and_commands() {
+        with_parser(&[r"\def\a{\hskip 1pt}%", r"\hbox{\a}%"], |parser| {
+            parser.parse_assignment(None).unwrap();
+
+            let recorder =
+                Rc::new(RefCell::new(StepRecorder { steps: vec![] }));
+            parser.set_debugger(recorder.clone());
+            parser.parse_box().unwrap();
+
+            assert_eq!(
+                recorder.borrow().steps,
+                vec![
+                    (
+                        "expand \\a ".to_string(),
+                        0,
+                        "\\hskip 1pt".to_string(),
+                        0
+                    ),
+                    (
+                        "restricted horizontal mode: \\hskip ".to_string(),
+                        1,
+                        "\\hskip 1pt".to_string(),
+                        0
+                    ),
+                    (
+                        "restricted horizontal mode: }".to_string(),
+                        0,
+                        "}".to_string(),
+                        1
+                    ),
+                ]
+            );
+        });
+    }
+}
@@ -1,5 +1,8 @@
 use crate::category::Category;
-use crate::dimension::{Dimen, FilDimen, FilKind, SpringDimen, Unit};
+use crate::dimension::{
+    Dimen, FilDimen, FilKind, MuDimen, MuSpringDimen, SpringDimen, Unit,
+};
+use crate::error::{TeXErrorKind, TeXResult};
 use crate::parser::number::{is_token_digit, token_digit_value};
 use crate::parser::primitives::token_equals_keyword_char;
 use crate::parser::Parser;
@@ -42,33 +45,59 @@ enum UnitOrFil {
 const MAG_FACTOR: i32 = 1000;
 
 impl<'a> Parser<'a> {
-    pub fn parse_dimen(&mut self) -> Dimen {
-        match self.parse_spring_dimen(false) {
-            SpringDimen::Dimen(dimen) => dimen,
+    pub fn parse_dimen(&mut self) -> TeXResult<Dimen> {
+        match self.parse_spring_dimen(false)? {
+            SpringDimen::Dimen(dimen) => Ok(dimen),
             _ => unreachable!(),
         }
     }
 
-    /// Parses a SpringDimen. If allow_fil is false, this will panic if it
-    /// sees units with fils.
-    pub fn parse_spring_dimen(&mut self, allow_fil: bool) -> SpringDimen {
-        let sign = self.parse_optional_signs();
-        let value = self.parse_unsigned_dimen(allow_fil);
+    /// Parses a SpringDimen. If allow_fil is false, units with fils are
+    /// reported as illegal units.
+    pub fn parse_spring_dimen(
+        &mut self,
+        allow_fil: bool,
+    ) -> TeXResult<SpringDimen> {
+        let sign = self.parse_optional_signs()?;
+        let value = self.parse_unsigned_dimen(allow_fil)?;
 
-        value * sign
+        Ok(value * sign)
     }
 
-    fn parse_unsigned_dimen(&mut self, allow_fil: bool) -> SpringDimen {
-        self.parse_normal_dimen(allow_fil)
+    pub fn parse_unsigned_dimen(
+        &mut self,
+        allow_fil: bool,
+    ) -> TeXResult<SpringDimen> {
+        if self.is_internal_dimen_head()? {
+            Ok(SpringDimen::Dimen(self.parse_internal_dimen()?))
+        } else if self.is_internal_glue_head()? {
+            // Glue is coerced into a dimen by dropping its stretch and shrink.
+            Ok(SpringDimen::Dimen(self.parse_internal_glue()?.space))
+        } else {
+            self.parse_normal_dimen(allow_fil)
+        }
     }
 
-    fn parse_normal_dimen(&mut self, allow_fil: bool) -> SpringDimen {
-        let factor = self.parse_factor();
-        let (unit_factor, unit_or_fil) = self.parse_unit_of_measure(allow_fil);
+    fn parse_normal_dimen(
+        &mut self,
+        allow_fil: bool,
+    ) -> TeXResult<SpringDimen> {
+        let factor = self.parse_factor()?;
+        let (unit_factor, unit_or_fil) =
+            self.parse_unit_of_measure(allow_fil)?;
 
-        match unit_or_fil {
+        Ok(match unit_or_fil {
             UnitOrFil::Unit(unit) => {
-                SpringDimen::Dimen(Dimen::from_unit(factor * unit_factor, unit))
+                let value = factor * unit_factor;
+                match Dimen::checked_from_unit(value, unit) {
+                    Some(dimen) => SpringDimen::Dimen(dimen),
+                    None => {
+                        // Like TeX, use the largest dimen instead.
+                        self.report_error(TeXErrorKind::DimensionTooLarge)?;
+                        let sign = if value < 0.0 { -1 } else { 1 };
+                        SpringDimen::Dimen(Dimen::max_value() * sign)
+                    }
+                }
             }
             UnitOrFil::Fil => SpringDimen::FilDimen(FilDimen::new(
                 FilKind::Fil,
@@ -82,36 +111,37 @@ impl<'a> Parser<'a> {
                 FilKind::Filll,
                 factor * unit_factor,
             )),
-        }
+        })
     }
 
-    fn is_almost_normal_integer_head(&mut self) -> bool {
+    fn is_almost_normal_integer_head(&mut self) -> TeXResult<bool> {
         self.is_internal_integer_head()
     }
 
     // Parses a <normal integer> except without the integer constant, because
     // we handle a superset of the integer constant parsing in parsing a
     // decimal constant.
-    fn parse_almost_normal_integer(&mut self) -> i32 {
-        if self.is_internal_integer_head() {
+    fn parse_almost_normal_integer(&mut self) -> TeXResult<i32> {
+        if self.is_internal_integer_head()? {
             self.parse_internal_integer()
         } else {
-            panic!("unimplemented")
+            // This is only called after is_almost_normal_integer_head().
+            unreachable!("Invalid almost normal integer head")
         }
     }
 
-    fn is_decimal_constant_head(&mut self) -> bool {
-        match self.peek_expanded_token() {
+    fn is_decimal_constant_head(&mut self) -> TeXResult<bool> {
+        match self.peek_expanded_token()? {
             Some(token) => {
-                is_token_digit(&token) || is_decimal_point_token(&token)
+                Ok(is_token_digit(&token) || is_decimal_point_token(&token))
             }
-            _ => false,
+            _ => Ok(false),
         }
     }
 
     // Parses a decimal constant, which is any number of digits and a single
     // decimal point (either . or ,).
-    fn parse_decimal_constant(&mut self) -> f64 {
+    fn parse_decimal_constant(&mut self) -> TeXResult<f64> {
         // The final decimal value
         let mut value: f64 = 0.0;
 
@@ -129,9 +159,9 @@ impl<'a> Parser<'a> {
         let mut seen_any_digits = false;
 
         loop {
-            match self.peek_expanded_token() {
+            match self.peek_expanded_token()? {
                 Some(ref token) if is_token_digit(token) => {
-                    self.lex_expanded_token();
+                    self.lex_expanded_token()?;
                     let token_value = token_digit_value(token) as f64;
                     if seen_decimal_point {
                         // If we've already seen a decimal point, then add to
@@ -154,7 +184,7 @@ impl<'a> Parser<'a> {
                     // If we see a decimal point (and haven't seen a decimal
                     // point yet), keep track of that, which will affect how we
                     // handle new digits.
-                    self.lex_expanded_token();
+                    self.lex_expanded_token()?;
                     seen_decimal_point = true;
                     seen_any_digits = true;
                 }
@@ -163,21 +193,22 @@ impl<'a> Parser<'a> {
         }
 
         if !seen_any_digits {
-            panic!("No digits found while parsing decimal constant");
+            self.report_error(TeXErrorKind::MissingNumber)?;
         }
 
-        value
+        Ok(value)
     }
 
     // Parses a <factor> (which is the number part of a <number><unit>
     // dimension).
-    fn parse_factor(&mut self) -> f64 {
-        if self.is_almost_normal_integer_head() {
-            self.parse_almost_normal_integer() as f64
-        } else if self.is_decimal_constant_head() {
+    fn parse_factor(&mut self) -> TeXResult<f64> {
+        if self.is_almost_normal_integer_head()? {
+            Ok(self.parse_almost_normal_integer()? as f64)
+        } else if self.is_decimal_constant_head()? {
             self.parse_decimal_constant()
         } else {
-            panic!("unimplemented");
+            self.report_error(TeXErrorKind::MissingNumber)?;
+            Ok(0.0)
         }
     }
 
@@ -190,12 +221,21 @@ impl<'a> Parser<'a> {
     //   like an em which depends on the current font we're using.
     // * Sometimes, we find a "true" unit, which depends on the current
     //   magnification (from \mag)
-    fn parse_unit_of_measure(&mut self, allow_fil: bool) -> (f64, UnitOrFil) {
-        if self.is_internal_integer_head() {
-            let value = self.parse_internal_integer();
-            (value as f64, UnitOrFil::Unit(Unit::ScaledPoint))
+    fn parse_unit_of_measure(
+        &mut self,
+        allow_fil: bool,
+    ) -> TeXResult<(f64, UnitOrFil)> {
+        if self.is_internal_integer_head()? {
+            let value = self.parse_internal_integer()?;
+            Ok((value as f64, UnitOrFil::Unit(Unit::ScaledPoint)))
+        } else if self.is_internal_dimen_head()? {
+            let value = self.parse_internal_dimen()?.as_scaled_points();
+            Ok((value as f64, UnitOrFil::Unit(Unit::ScaledPoint)))
+        } else if self.is_internal_glue_head()? {
+            let value = self.parse_internal_glue()?.space.as_scaled_points();
+            Ok((value as f64, UnitOrFil::Unit(Unit::ScaledPoint)))
         } else {
-            match self.parse_unit(allow_fil) {
+            Ok(match self.parse_unit(allow_fil)? {
                 ParsedUnit::PhysicalUnit(is_true, unit) => {
                     if is_true {
                         // TODO(xymostech): Lookup the \mag factor from the
@@ -207,129 +247,223 @@ impl<'a> Parser<'a> {
                 }
                 // These need font metrics to be looked up before we can
                 // correctly get the values for this.
-                ParsedUnit::Em => panic!("unimplemented"),
-                ParsedUnit::Ex => panic!("unimplemented"),
+                ParsedUnit::Em | ParsedUnit::Ex => {
+                    self.report_error(TeXErrorKind::Unimplemented(
+                        "em and ex units".to_string(),
+                    ))?;
+                    (1.0, UnitOrFil::Unit(Unit::Point))
+                }
                 ParsedUnit::Fil => (1.0, UnitOrFil::Fil),
                 ParsedUnit::Fill => (1.0, UnitOrFil::Fill),
                 ParsedUnit::Filll => (1.0, UnitOrFil::Filll),
-            }
+            })
         }
     }
 
-    fn parse_unit(&mut self, allow_fil: bool) -> ParsedUnit {
-        self.parse_optional_spaces_expanded();
+    // When we see an illegal unit, TeX puts the tokens that it read back into
+    // the input and acts like it saw "pt" instead.
+    fn recover_from_illegal_unit(
+        &mut self,
+        is_true_unit: bool,
+        tokens: Vec<Option<Token>>,
+    ) -> TeXResult<ParsedUnit> {
+        self.report_error(TeXErrorKind::IllegalUnit)?;
+        self.add_upcoming_tokens(tokens.into_iter().flatten().collect());
+        Ok(ParsedUnit::PhysicalUnit(is_true_unit, Unit::Point))
+    }
+
+    fn parse_unit(&mut self, allow_fil: bool) -> TeXResult<ParsedUnit> {
+        self.parse_optional_spaces_expanded()?;
 
         let mut is_true_unit = false;
 
         // Check to see if our unit starts with a 't', in which case we're
         // parsing a 'true' unit (because none of the other units start with
         // 't').
-        let true_start = self.peek_expanded_token().unwrap();
-        if token_equals_keyword_char(&true_start, 't') {
-            // We're seeing a 'true' at the start of the unit. Parse that.
-            self.parse_keyword_expanded("true");
-            self.parse_optional_spaces_expanded();
+        if let Some(true_start) = self.peek_expanded_token()? {
+            if token_equals_keyword_char(&true_start, 't') {
+                // We're seeing a 'true' at the start of the unit. Parse that.
+                self.parse_keyword_expanded("true")?;
+                self.parse_optional_spaces_expanded()?;
 
-            is_true_unit = true;
+                is_true_unit = true;
+            }
         }
 
         // Since all of the units have at least 2 characters, just parse both
         // character tokens up front.
-        let unit_first = self.lex_expanded_token().unwrap();
-        let unit_second = self.lex_expanded_token().unwrap();
+        let maybe_first = self.lex_expanded_token()?;
+        let maybe_second = self.lex_expanded_token()?;
+        let (unit_first, unit_second) = match (&maybe_first, &maybe_second) {
+            (Some(first), Some(second)) => (first.clone(), second.clone()),
+            _ => {
+                return self.recover_from_illegal_unit(
+                    is_true_unit,
+                    vec![maybe_first, maybe_second],
+                )
+            }
+        };
 
         // Check to see which unit the first and second tokens match.
-        if equals_unit(&unit_first, &unit_second, ['p', 't']) {
-            self.parse_optional_space_expanded();
-            ParsedUnit::PhysicalUnit(is_true_unit, Unit::Point)
-        } else if equals_unit(&unit_first, &unit_second, ['p', 'c']) {
-            self.parse_optional_space_expanded();
-            ParsedUnit::PhysicalUnit(is_true_unit, Unit::Pica)
-        } else if equals_unit(&unit_first, &unit_second, ['i', 'n']) {
-            self.parse_optional_space_expanded();
-            ParsedUnit::PhysicalUnit(is_true_unit, Unit::Inch)
-        } else if equals_unit(&unit_first, &unit_second, ['b', 'p']) {
-            self.parse_optional_space_expanded();
-            ParsedUnit::PhysicalUnit(is_true_unit, Unit::BigPoint)
-        } else if equals_unit(&unit_first, &unit_second, ['c', 'm']) {
-            self.parse_optional_space_expanded();
-            ParsedUnit::PhysicalUnit(is_true_unit, Unit::Centimeter)
-        } else if equals_unit(&unit_first, &unit_second, ['m', 'm']) {
-            self.parse_optional_space_expanded();
-            ParsedUnit::PhysicalUnit(is_true_unit, Unit::Millimeter)
-        } else if equals_unit(&unit_first, &unit_second, ['d', 'd']) {
-            self.parse_optional_space_expanded();
-            ParsedUnit::PhysicalUnit(is_true_unit, Unit::DidotPoint)
-        } else if equals_unit(&unit_first, &unit_second, ['c', 'c']) {
-            self.parse_optional_space_expanded();
-            ParsedUnit::PhysicalUnit(is_true_unit, Unit::Cicero)
-        } else if equals_unit(&unit_first, &unit_second, ['s', 'p']) {
-            self.parse_optional_space_expanded();
-            ParsedUnit::PhysicalUnit(is_true_unit, Unit::ScaledPoint)
-        } else if equals_unit(&unit_first, &unit_second, ['e', 'm']) {
-            self.parse_optional_space_expanded();
-            if is_true_unit {
-                panic!("Invalid unit with true: em");
-            }
-            ParsedUnit::Em
-        } else if equals_unit(&unit_first, &unit_second, ['e', 'x']) {
-            self.parse_optional_space_expanded();
-            if is_true_unit {
-                panic!("Invalid unit with true: ex");
-            }
-            ParsedUnit::Ex
-        } else if equals_unit(&unit_first, &unit_second, ['f', 'i']) {
-            if !allow_fil {
-                panic!("Invalid unit: fil*");
+        let physical_unit =
+            if equals_unit(&unit_first, &unit_second, ['p', 't']) {
+                Some(Unit::Point)
+            } else if equals_unit(&unit_first, &unit_second, ['p', 'c']) {
+                Some(Unit::Pica)
+            } else if equals_unit(&unit_first, &unit_second, ['i', 'n']) {
+                Some(Unit::Inch)
+            } else if equals_unit(&unit_first, &unit_second, ['b', 'p']) {
+                Some(Unit::BigPoint)
+            } else if equals_unit(&unit_first, &unit_second, ['c', 'm']) {
+                Some(Unit::Centimeter)
+            } else if equals_unit(&unit_first, &unit_second, ['m', 'm']) {
+                Some(Unit::Millimeter)
+            } else if equals_unit(&unit_first, &unit_second, ['d', 'd']) {
+                Some(Unit::DidotPoint)
+            } else if equals_unit(&unit_first, &unit_second, ['c', 'c']) {
+                Some(Unit::Cicero)
+            } else if equals_unit(&unit_first, &unit_second, ['s', 'p']) {
+                Some(Unit::ScaledPoint)
+            } else {
+                None
+            };
+
+        if let Some(unit) = physical_unit {
+            self.parse_optional_space_expanded()?;
+            Ok(ParsedUnit::PhysicalUnit(is_true_unit, unit))
+        } else if equals_unit(&unit_first, &unit_second, ['e', 'm'])
+            && !is_true_unit
+        {
+            self.parse_optional_space_expanded()?;
+            Ok(ParsedUnit::Em)
+        } else if equals_unit(&unit_first, &unit_second, ['e', 'x'])
+            && !is_true_unit
+        {
+            self.parse_optional_space_expanded()?;
+            Ok(ParsedUnit::Ex)
+        } else if equals_unit(&unit_first, &unit_second, ['f', 'i'])
+            && allow_fil
+            && !is_true_unit
+        {
+            let maybe_first_l = self.lex_expanded_token()?;
+            match maybe_first_l {
+                Some(ref tok) if token_equals_keyword_char(tok, 'l') => (),
+                _ => {
+                    return self.recover_from_illegal_unit(
+                        is_true_unit,
+                        vec![maybe_first, maybe_second, maybe_first_l],
+                    )
+                }
             }
 
-            if is_true_unit {
-                panic!("Invalid unit with true: fil*");
-            }
+            let unit = match self.parse_fil_order()? {
+                FilKind::Fil => ParsedUnit::Fil,
+                FilKind::Fill => ParsedUnit::Fill,
+                FilKind::Filll => ParsedUnit::Filll,
+            };
 
-            let first_l = self.lex_expanded_token().unwrap();
-            if !token_equals_keyword_char(&first_l, 'l') {
-                panic!(
-                    "Invalid unit: {:?}{:?}{:?}",
-                    unit_first, unit_second, first_l
-                );
-            }
+            self.parse_optional_space_expanded()?;
 
-            let mut unit = ParsedUnit::Fil;
-            loop {
-                let maybe_next_l = self.peek_expanded_token();
-                match maybe_next_l {
-                    Some(ref tok) if token_equals_keyword_char(tok, 'l') => {
-                        self.lex_expanded_token();
-                        unit = match unit {
-                            ParsedUnit::Fil => ParsedUnit::Fill,
-                            ParsedUnit::Fill => ParsedUnit::Filll,
-                            ParsedUnit::Filll => panic!("Invalid unit: fillll"),
-                            _ => unreachable!(),
-                        };
-                    }
-                    _ => break,
+            Ok(unit)
+        } else {
+            self.recover_from_illegal_unit(
+                is_true_unit,
+                vec![maybe_first, maybe_second],
+            )
+        }
+    }
+
+    // Parses the l's after a "fil" unit, and returns which kind of fil the
+    // unit was. Like TeX, l's after "filll" are complained about and ignored.
+    fn parse_fil_order(&mut self) -> TeXResult<FilKind> {
+        let mut kind = FilKind::Fil;
+        loop {
+            match self.peek_expanded_token()? {
+                Some(ref tok) if token_equals_keyword_char(tok, 'l') => {
+                    self.lex_expanded_token()?;
+                    kind = match kind {
+                        FilKind::Fil => FilKind::Fill,
+                        FilKind::Fill => FilKind::Filll,
+                        FilKind::Filll => {
+                            self.report_error(TeXErrorKind::IllegalFilUnit)?;
+                            FilKind::Filll
+                        }
+                    };
                 }
+                _ => break,
             }
+        }
+        Ok(kind)
+    }
 
-            self.parse_optional_space_expanded();
+    /// Parses a <mudimen>, which is like a dimen except that the only unit
+    /// allowed is mu.
+    pub fn parse_mu_dimen(&mut self) -> TeXResult<MuDimen> {
+        match self.parse_mu_spring_dimen(false)? {
+            MuSpringDimen::MuDimen(mu_dimen) => Ok(mu_dimen),
+            _ => unreachable!(),
+        }
+    }
 
-            unit
-        } else {
-            panic!("Invalid unit: {:?}{:?}", unit_first, unit_second);
+    /// Parses the stretch or shrink of math glue. Like with
+    /// parse_spring_dimen(), fil units are only allowed if allow_fil is true.
+    pub fn parse_mu_spring_dimen(
+        &mut self,
+        allow_fil: bool,
+    ) -> TeXResult<MuSpringDimen> {
+        let sign = self.parse_optional_signs()?;
+        if self.is_internal_mu_glue_head()? {
+            let space = self.parse_internal_mu_glue()?.space;
+            return Ok(MuSpringDimen::MuDimen(space * sign));
         }
+
+        let factor = self.parse_factor()?;
+
+        self.parse_optional_spaces_expanded()?;
+        if allow_fil && self.parse_optional_keyword_expanded("fil")? {
+            let kind = self.parse_fil_order()?;
+            self.parse_optional_space_expanded()?;
+            let fil = FilDimen::new(kind, factor) * sign;
+            return Ok(MuSpringDimen::FilDimen(fil));
+        }
+
+        let unit_factor = if self.is_internal_mu_glue_head()? {
+            let space = self.parse_internal_mu_glue()?.space;
+            space.as_scaled_mu() as f64 / 65536.0
+        } else {
+            if self.parse_optional_keyword_expanded("mu")? {
+                self.parse_optional_space_expanded()?;
+            } else {
+                // Like with other illegal units, TeX acts like it saw the
+                // unit it was expecting.
+                self.report_error(TeXErrorKind::IllegalMuUnit)?;
+            }
+            1.0
+        };
+
+        let value = factor * unit_factor;
+        let mu_dimen = match MuDimen::checked_new(value) {
+            Some(mu_dimen) => mu_dimen * sign,
+            None => {
+                self.report_error(TeXErrorKind::DimensionTooLarge)?;
+                let value_sign = if value < 0.0 { -1 } else { 1 };
+                MuDimen::max_value() * (sign * value_sign)
+            }
+        };
+        Ok(MuSpringDimen::MuDimen(mu_dimen))
     }
 
-    pub fn is_internal_dimen_head(&mut self) -> bool {
+    pub fn is_internal_dimen_head(&mut self) -> TeXResult<bool> {
         self.is_dimen_variable_head()
     }
 
-    pub fn parse_internal_dimen(&mut self) -> Dimen {
-        if self.is_dimen_variable_head() {
-            let variable = self.parse_dimen_variable();
-            variable.get(self.state)
+    pub fn parse_internal_dimen(&mut self) -> TeXResult<Dimen> {
+        if self.is_dimen_variable_head()? {
+            let variable = self.parse_dimen_variable()?;
+            Ok(variable.get(self.state))
         } else {
-            panic!("unimplemented");
+            // This is only called after is_internal_dimen_head().
+            unreachable!("Invalid internal dimen head");
         }
     }
 }
@@ -350,39 +484,39 @@ mod tests {
             ],
             |parser| {
                 assert_eq!(
-                    parser.parse_dimen(),
+                    parser.parse_dimen().unwrap(),
                     Dimen::from_unit(1.0, Unit::Point)
                 );
                 assert_eq!(
-                    parser.parse_dimen(),
+                    parser.parse_dimen().unwrap(),
                     Dimen::from_unit(1.0, Unit::Pica)
                 );
                 assert_eq!(
-                    parser.parse_dimen(),
+                    parser.parse_dimen().unwrap(),
                     Dimen::from_unit(1.0, Unit::Inch)
                 );
                 assert_eq!(
-                    parser.parse_dimen(),
+                    parser.parse_dimen().unwrap(),
                     Dimen::from_unit(1.0, Unit::BigPoint)
                 );
                 assert_eq!(
-                    parser.parse_dimen(),
+                    parser.parse_dimen().unwrap(),
                     Dimen::from_unit(1.0, Unit::Centimeter)
                 );
                 assert_eq!(
-                    parser.parse_dimen(),
+                    parser.parse_dimen().unwrap(),
                     Dimen::from_unit(1.0, Unit::Millimeter)
                 );
                 assert_eq!(
-                    parser.parse_dimen(),
+                    parser.parse_dimen().unwrap(),
                     Dimen::from_unit(1.0, Unit::DidotPoint)
                 );
                 assert_eq!(
-                    parser.parse_dimen(),
+                    parser.parse_dimen().unwrap(),
                     Dimen::from_unit(1.0, Unit::Cicero)
                 );
                 assert_eq!(
-                    parser.parse_dimen(),
+                    parser.parse_dimen().unwrap(),
                     Dimen::from_unit(1.0, Unit::ScaledPoint)
                 );
             },
@@ -398,22 +532,22 @@ mod tests {
                 "1,23 %",
             ],
             |parser| {
-                assert_eq!(parser.parse_decimal_constant(), 1.0);
-                parser.parse_optional_spaces_expanded();
-                assert_eq!(parser.parse_decimal_constant(), 1.0);
-                parser.parse_optional_spaces_expanded();
-                assert_eq!(parser.parse_decimal_constant(), 1.0);
-                parser.parse_optional_spaces_expanded();
-                assert_eq!(parser.parse_decimal_constant(), 1234.0);
-                parser.parse_optional_spaces_expanded();
-                assert_eq!(parser.parse_decimal_constant(), 1.2);
-                parser.parse_optional_spaces_expanded();
-                assert_eq!(parser.parse_decimal_constant(), 0.2);
-                parser.parse_optional_spaces_expanded();
-                assert_eq!(parser.parse_decimal_constant(), 0.234);
-                parser.parse_optional_spaces_expanded();
-                assert_eq!(parser.parse_decimal_constant(), 1.23);
-                parser.parse_optional_spaces_expanded();
+                assert_eq!(parser.parse_decimal_constant().unwrap(), 1.0);
+                parser.parse_optional_spaces_expanded().unwrap();
+                assert_eq!(parser.parse_decimal_constant().unwrap(), 1.0);
+                parser.parse_optional_spaces_expanded().unwrap();
+                assert_eq!(parser.parse_decimal_constant().unwrap(), 1.0);
+                parser.parse_optional_spaces_expanded().unwrap();
+                assert_eq!(parser.parse_decimal_constant().unwrap(), 1234.0);
+                parser.parse_optional_spaces_expanded().unwrap();
+                assert_eq!(parser.parse_decimal_constant().unwrap(), 1.2);
+                parser.parse_optional_spaces_expanded().unwrap();
+                assert_eq!(parser.parse_decimal_constant().unwrap(), 0.2);
+                parser.parse_optional_spaces_expanded().unwrap();
+                assert_eq!(parser.parse_decimal_constant().unwrap(), 0.234);
+                parser.parse_optional_spaces_expanded().unwrap();
+                assert_eq!(parser.parse_decimal_constant().unwrap(), 1.23);
+                parser.parse_optional_spaces_expanded().unwrap();
             },
         );
     }
@@ -423,7 +557,7 @@ mod tests {
         with_parser(&["3.4\\count0%"], |parser| {
             parser.state.set_count(false, 0, 123);
             assert_eq!(
-                parser.parse_dimen(),
+                parser.parse_dimen().unwrap(),
                 Dimen::from_unit(3.4 * 123.0, Unit::ScaledPoint)
             );
         });
@@ -434,7 +568,7 @@ mod tests {
         with_parser(&["\\count0 cc%"], |parser| {
             parser.state.set_count(false, 0, 123);
             assert_eq!(
-                parser.parse_dimen(),
+                parser.parse_dimen().unwrap(),
                 Dimen::from_unit(123.0, Unit::Cicero)
             );
         });
@@ -444,7 +578,7 @@ mod tests {
     fn it_parses_true_units() {
         with_parser(&["3.4 true pt%"], |parser| {
             assert_eq!(
-                parser.parse_dimen(),
+                parser.parse_dimen().unwrap(),
                 Dimen::from_unit(3.4, Unit::Point)
             );
         });
@@ -454,17 +588,58 @@ mod tests {
     fn it_parses_negative_dimens() {
         with_parser(&["-3.4pt%"], |parser| {
             assert_eq!(
-                parser.parse_dimen(),
+                parser.parse_dimen().unwrap(),
                 Dimen::from_unit(-3.4, Unit::Point)
             );
         });
     }
 
+    #[test]
+    fn it_uses_the_largest_dimen_for_dimens_which_are_too_large() {
+        with_parser(&["99999pt%", "-16384pt%", "2\\dimen0%"], |parser| {
+            parser.state.set_dimen(false, 0, Dimen::max_value());
+
+            assert_eq!(parser.parse_dimen().unwrap(), Dimen::max_value());
+            assert_eq!(parser.parse_dimen().unwrap(), Dimen::max_value() * -1);
+            assert_eq!(parser.parse_dimen().unwrap(), Dimen::max_value());
+
+            let errors = parser.take_errors();
+            assert_eq!(errors.len(), 3);
+            for error in errors {
+                assert_eq!(error.kind, TeXErrorKind::DimensionTooLarge);
+            }
+        });
+    }
+
+    #[test]
+    fn it_allows_dimens_just_under_the_largest_dimen() {
+        with_parser(&[r"\dimen0=16383.99999pt%", "16383.99999mu%"], |parser| {
+            parser.parse_assignment(None).unwrap();
+            assert_eq!(parser.state.get_dimen(0), Dimen::max_value());
+            assert_eq!(parser.parse_mu_dimen().unwrap(), MuDimen::max_value());
+        });
+    }
+
+    #[test]
+    fn it_uses_the_largest_mu_dimen_for_mu_dimens_which_are_too_large() {
+        with_parser(&["99999mu%", "-99999mu%"], |parser| {
+            assert_eq!(parser.parse_mu_dimen().unwrap(), MuDimen::max_value());
+            assert_eq!(
+                parser.parse_mu_dimen().unwrap(),
+                MuDimen::max_value() * -1
+            );
+
+            let errors = parser.take_errors();
+            assert_eq!(errors.len(), 2);
+            assert_eq!(errors[0].kind, TeXErrorKind::DimensionTooLarge);
+        });
+    }
+
     #[test]
     fn it_parses_optional_spaces_after_units() {
         with_parser(&["1pt %"], |parser| {
-            parser.parse_dimen();
-            assert_eq!(parser.lex_unexpanded_token(), None);
+            parser.parse_dimen().unwrap();
+            assert_eq!(parser.lex_unexpanded_token().unwrap(), None);
         });
     }
 
@@ -474,33 +649,70 @@ mod tests {
             &["1fil %", "1fill %", "1filll %", "12.3fil %", "-1fil %"],
             |parser| {
                 assert_eq!(
-                    parser.parse_spring_dimen(true),
+                    parser.parse_spring_dimen(true).unwrap(),
                     SpringDimen::FilDimen(FilDimen::new(FilKind::Fil, 1.0))
                 );
                 assert_eq!(
-                    parser.parse_spring_dimen(true),
+                    parser.parse_spring_dimen(true).unwrap(),
                     SpringDimen::FilDimen(FilDimen::new(FilKind::Fill, 1.0))
                 );
                 assert_eq!(
-                    parser.parse_spring_dimen(true),
+                    parser.parse_spring_dimen(true).unwrap(),
                     SpringDimen::FilDimen(FilDimen::new(FilKind::Filll, 1.0))
                 );
                 assert_eq!(
-                    parser.parse_spring_dimen(true),
+                    parser.parse_spring_dimen(true).unwrap(),
                     SpringDimen::FilDimen(FilDimen::new(FilKind::Fil, 12.3))
                 );
                 assert_eq!(
-                    parser.parse_spring_dimen(true),
+                    parser.parse_spring_dimen(true).unwrap(),
                     SpringDimen::FilDimen(FilDimen::new(FilKind::Fil, -1.0))
                 );
             },
         );
     }
 
+    #[test]
+    fn it_inserts_pt_for_illegal_units() {
+        with_parser(&["2xy%", "1fil%"], |parser| {
+            assert_eq!(
+                parser.parse_dimen().unwrap(),
+                Dimen::from_unit(2.0, Unit::Point)
+            );
+            assert_eq!(
+                parser.lex_unexpanded_token().unwrap(),
+                Some(Token::Char('x', Category::Letter))
+            );
+            assert_eq!(
+                parser.lex_unexpanded_token().unwrap(),
+                Some(Token::Char('y', Category::Letter))
+            );
+
+            // fils aren't allowed in normal dimens.
+            assert_eq!(
+                parser.parse_dimen().unwrap(),
+                Dimen::from_unit(1.0, Unit::Point)
+            );
+            for ch in "fil".chars() {
+                assert_eq!(
+                    parser.lex_unexpanded_token().unwrap(),
+                    Some(Token::Char(ch, Category::Letter))
+                );
+            }
+
+            let kinds: Vec<TeXErrorKind> =
+                parser.take_errors().into_iter().map(|e| e.kind).collect();
+            assert_eq!(
+                kinds,
+                vec![TeXErrorKind::IllegalUnit, TeXErrorKind::IllegalUnit]
+            );
+        });
+    }
+
     #[test]
     fn it_parses_internal_dimens() {
         with_parser(&[r"\setbox0=\hbox{a}%", r"\wd0%", r"\ht0"], |parser| {
-            parser.parse_assignment(None);
+            parser.parse_assignment(None).unwrap();
 
             let metrics = parser
                 .state
@@ -510,11 +722,17 @@ mod tests {
                 })
                 .unwrap();
 
-            assert!(parser.is_internal_dimen_head());
-            assert_eq!(parser.parse_internal_dimen(), metrics.get_width('a'));
+            assert!(parser.is_internal_dimen_head().unwrap());
+            assert_eq!(
+                parser.parse_internal_dimen().unwrap(),
+                metrics.get_width('a')
+            );
 
-            assert!(parser.is_internal_dimen_head());
-            assert_eq!(parser.parse_internal_dimen(), metrics.get_height('a'));
+            assert!(parser.is_internal_dimen_head().unwrap());
+            assert_eq!(
+                parser.parse_internal_dimen().unwrap(),
+                metrics.get_height('a')
+            );
         });
     }
 }
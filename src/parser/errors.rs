@@ -0,0 +1,324 @@
+use crate::category::Category;
+use crate::error::{TeXError, TeXErrorKind, TeXResult};
+use crate::parameter::IntegerParameter;
+use crate::parser::Parser;
+use crate::token::{format_token_list, Token};
+use crate::transcript::{format_context_lines, Selector};
+
+// Like TeX, we give up if there are too many errors in a single paragraph,
+// since that usually means that something is hopelessly wrong.
+const MAX_ERRORS_PER_PARAGRAPH: usize = 100;
+
+impl<'a> Parser<'a> {
+    /// Records an error that we're going to recover from, pointing at the
+    /// most recently lexed token. The caller is responsible for recovering the
+    /// way TeX would (e.g. by inserting a missing token or ignoring a bad
+    /// one). This only returns an error if there have been too many errors
+    /// or we've been asked to halt on errors, and we should stop parsing.
+    pub fn report_error(&mut self, kind: TeXErrorKind) -> TeXResult<()> {
+        let error = TeXError::new(kind, self.last_token_origin());
+        self.print_error(&error);
+        self.errors.push(error.clone());
+
+        if self.halt_on_error {
+            return Err(error);
+        }
+
+        self.error_count += 1;
+        if self.error_count >= MAX_ERRORS_PER_PARAGRAPH {
+            Err(self.fatal_error(TeXErrorKind::TooManyErrors))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Records an error that we can't recover from, and returns it so that it
+    /// can be passed up to the caller.
+    pub fn fatal_error(&mut self, kind: TeXErrorKind) -> TeXError {
+        let error = TeXError::new(kind, self.last_token_origin());
+        self.print_error(&error);
+        self.errors.push(error.clone());
+        error
+    }
+
+    // Prints an error message, followed by where in the input we are, like
+    //   ! Undefined control sequence.
+    //   l.2 \hbox{\foo
+    //                  bar}
+    fn print_error(&mut self, error: &TeXError) {
+        let message = error.kind.to_string();
+        self.transcript.print_nl(Selector::TerminalAndLog);
+        self.transcript.print(Selector::TerminalAndLog, "! ");
+        self.transcript.print(Selector::TerminalAndLog, &message);
+        // Most messages don't end in a period, but a few end in a full
+        // sentence of their own.
+        if !message.ends_with('.') {
+            self.transcript.print(Selector::TerminalAndLog, ".");
+        }
+
+        for line in self.get_error_context() {
+            self.transcript.print_nl(Selector::TerminalAndLog);
+            self.transcript.print(Selector::TerminalAndLog, &line);
+        }
+        self.transcript.print_ln(Selector::TerminalAndLog);
+    }
+
+    // Describes each level of input that we're in the middle of reading, from
+    // the innermost to the outermost, with two lines per level. Like TeX, the
+    // innermost and outermost levels are always shown, but only
+    // \errorcontextlines levels in between are.
+    fn get_error_context(&self) -> Vec<String> {
+        let mut levels = Vec::new();
+
+        // Each macro expansion is a level, and tokens which have been put
+        // back to be read again without being part of an expansion are a
+        // level of their own. For macros, the part of the replacement text
+        // which has already been read is shown too.
+        let mut end = self.upcoming_tokens.len();
+        for (base, name, replacement) in self.expansion_bases.iter().rev() {
+            let num_read = replacement.len().saturating_sub(end - *base);
+            levels.push((
+                format!("{}->", name),
+                &replacement[..num_read],
+                &self.upcoming_tokens[*base..end],
+            ));
+            end = *base;
+        }
+        levels.push((
+            "<to be read again> ".to_string(),
+            &[],
+            &self.upcoming_tokens[..end],
+        ));
+
+        let max_levels = self
+            .state
+            .get_integer_parameter(IntegerParameter::ErrorContextLines);
+        let mut lines = Vec::new();
+        let mut num_levels = 0;
+        for (description, read, tokens) in levels {
+            if tokens.is_empty() {
+                continue;
+            }
+
+            if num_levels == 0 || num_levels < max_levels {
+                let unread: Vec<Token> = tokens
+                    .iter()
+                    .rev()
+                    .map(|(token, _)| token.clone())
+                    .collect();
+                let (first, second) = format_context_lines(
+                    &description,
+                    &format_token_list(read),
+                    &format_token_list(&unread),
+                );
+                lines.push(first);
+                lines.push(second);
+            } else if num_levels == max_levels {
+                lines.push("...".to_string());
+            }
+            num_levels += 1;
+        }
+
+        if let Some((line_number, read, unread)) = self.lexer.get_current_line()
+        {
+            let (first, second) = format_context_lines(
+                &format!("l.{} ", line_number),
+                &read,
+                &unread,
+            );
+            lines.push(first);
+            lines.push(second);
+        }
+
+        lines
+    }
+
+    /// Skips over the next token, which we don't know what to do with in the
+    /// given mode, and reports an error about it. Control sequences and
+    /// active characters with no meaning are undefined, and anything else is
+    /// something that we haven't implemented yet.
+    pub fn skip_unexpected_token(&mut self, mode: &str) -> TeXResult<()> {
+        let token = match self.lex_expanded_token()? {
+            Some(token) => token,
+            None => return Ok(()),
+        };
+
+        let is_undefined = match token {
+            Token::ControlSequence(_) | Token::Char(_, Category::Active) => {
+                self.state.get_definition(&token).is_none()
+            }
+            _ => false,
+        };
+
+        let kind = if is_undefined {
+            TeXErrorKind::UndefinedControlSequence(token)
+        } else {
+            TeXErrorKind::Unimplemented(format!(
+                "`{}' in {}",
+                token.to_string().trim_end(),
+                mode
+            ))
+        };
+        self.report_error(kind)
+    }
+
+    /// Makes every error fatal, like TeX's -halt-on-error option.
+    pub fn set_halt_on_error(&mut self, halt_on_error: bool) {
+        self.halt_on_error = halt_on_error;
+    }
+
+    // Called when a paragraph ends, to reset the count of errors that we use
+    // to decide if we should give up.
+    pub fn reset_error_count(&mut self) {
+        self.error_count = 0;
+    }
+
+    /// Returns all of the errors that have been reported so far, including
+    /// ones that were recovered from.
+    pub fn get_errors(&self) -> &[TeXError] {
+        &self.errors
+    }
+
+    /// Returns all of the errors that have been reported so far and forgets
+    /// about them.
+    pub fn take_errors(&mut self) -> Vec<TeXError> {
+        std::mem::take(&mut self.errors)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::category::Category;
+    use crate::testing::{with_parser, with_terminal};
+    use crate::token::Token;
+
+    #[test]
+    fn it_records_errors_with_their_origin() {
+        with_parser(&["a\u{00ff}b%"], |parser| {
+            assert_eq!(
+                parser.lex_unexpanded_token().unwrap(),
+                Some(Token::Char('a', Category::Letter))
+            );
+            assert_eq!(
+                parser.lex_unexpanded_token().unwrap(),
+                Some(Token::Char('b', Category::Letter))
+            );
+
+            let errors = parser.take_errors();
+            assert_eq!(errors.len(), 1);
+            assert_eq!(
+                errors[0].kind,
+                TeXErrorKind::InvalidCharacter('\u{00ff}')
+            );
+            assert_eq!(
+                errors[0].origin.as_ref().unwrap().source_location(),
+                Some((None, 1, 2))
+            );
+        });
+    }
+
+    #[test]
+    fn it_gives_up_after_too_many_errors() {
+        let line = format!("{}%", "\u{00ff}".repeat(MAX_ERRORS_PER_PARAGRAPH));
+        with_parser(&[&line], |parser| {
+            assert_eq!(
+                parser.lex_unexpanded_token().unwrap_err().kind,
+                TeXErrorKind::TooManyErrors
+            );
+            assert_eq!(
+                parser.take_errors().len(),
+                MAX_ERRORS_PER_PARAGRAPH + 1
+            );
+        });
+    }
+
+    #[test]
+    fn it_halts_on_the_first_error_if_asked_to() {
+        with_parser(&["a\u{00ff}b%"], |parser| {
+            parser.set_halt_on_error(true);
+            parser.lex_unexpanded_token().unwrap();
+            assert_eq!(
+                parser.lex_unexpanded_token().unwrap_err().kind,
+                TeXErrorKind::InvalidCharacter('\u{00ff}')
+            );
+            assert_eq!(parser.take_errors().len(), 1);
+            parser.lex_unexpanded_token().unwrap();
+        });
+    }
+
+    #[test]
+    fn it_prints_what_has_been_read_from_macros_in_error_context() {
+        let terminal =
+            with_terminal(&[r"\def\a#1{x#1\foo y}%", r"\a w%"], |parser| {
+                parser.parse_assignment(None).unwrap();
+                parser.lex_expanded_token().unwrap();
+                parser.lex_expanded_token().unwrap();
+
+                parser.skip_unexpected_token("vertical mode").unwrap();
+                assert_eq!(
+                    parser.take_errors()[0].kind,
+                    TeXErrorKind::UndefinedControlSequence(
+                        Token::ControlSequence("foo".to_string())
+                    )
+                );
+
+                parser.lex_expanded_token().unwrap();
+            });
+
+        assert_eq!(
+            terminal,
+            [
+                "! Undefined control sequence.",
+                r"\a ->xw\foo ",
+                "            y",
+                r"l.2 \a w",
+                "        %",
+                "",
+            ]
+            .join("\n")
+        );
+    }
+
+    #[test]
+    fn it_prints_errors_with_context() {
+        let terminal = with_terminal(
+            &[
+                r"\def\a{\b y}\def\b{\c z}\def\c{\foo x}%",
+                r"\errorcontextlines=1 \a w%",
+            ],
+            |parser| {
+                for _ in 0..4 {
+                    parser.parse_assignment(None).unwrap();
+                }
+
+                parser.skip_unexpected_token("vertical mode").unwrap();
+                assert_eq!(
+                    parser.take_errors()[0].kind,
+                    TeXErrorKind::UndefinedControlSequence(
+                        Token::ControlSequence("foo".to_string())
+                    )
+                );
+
+                for _ in 0..4 {
+                    parser.lex_expanded_token().unwrap();
+                }
+            },
+        );
+
+        assert_eq!(
+            terminal,
+            [
+                "! Undefined control sequence.",
+                r"\c ->\foo ",
+                "          x",
+                "...",
+                r"l.2 \errorcontextlines=1 \a",
+                "                            w%",
+                "",
+            ]
+            .join("\n")
+        );
+    }
+}
@@ -1,62 +1,336 @@
+use std::rc::Rc;
+
+use crate::category::Category;
+use crate::error::{TeXErrorKind, TeXResult};
+use crate::origin::TokenOrigin;
 use crate::parser::Parser;
+use crate::state::TokenDefinition;
 use crate::token::Token;
 
+// The primitives which are expanded instead of being executed, other than the
+// conditionals.
+const EXPANDABLE_PRIMITIVES: &[&str] = &[
+    "else",
+    "or",
+    "fi",
+    "number",
+    "romannumeral",
+    "the",
+    "meaning",
+    "string",
+    "fontname",
+    "jobname",
+    "input",
+    "endinput",
+    "expandafter",
+    "noexpand",
+    "csname",
+];
+
 impl<'a> Parser<'a> {
-    pub fn lex_expanded_token(&mut self) -> Option<Token> {
-        if self.is_conditional_head() {
+    // Expands the next token once if it's expandable, so that its expansion
+    // is what's read next. Returns false if the next token isn't expandable.
+    fn expand_next_token(&mut self) -> TeXResult<bool> {
+        if self.is_next_token_noexpanded() {
+            return Ok(false);
+        }
+
+        if self.is_conditional_head()? {
             // Handle conditionals, like \ifnum
-            self.expand_conditional();
-            return self.lex_expanded_token();
-        } else if self.is_print_head() {
+            self.expand_conditional()?;
+            return Ok(true);
+        } else if self.is_print_head()? {
             // Handle printing, like \number\count1
-            let replacement = self.expand_print();
+            let head = self.peek_unexpanded_token()?;
+            self.trace_primitive(&head);
+            let replacement = self.expand_print()?;
             self.add_upcoming_tokens(replacement);
-            return self.lex_expanded_token();
+            return Ok(true);
+        } else if self.is_input_head()? {
+            // Handle \input and \endinput
+            let head = self.peek_unexpanded_token()?;
+            self.trace_primitive(&head);
+            self.expand_input()?;
+            return Ok(true);
+        } else if self.is_expansion_control_head()? {
+            // Handle \expandafter, \noexpand and \csname
+            let head = self.peek_unexpanded_token()?;
+            self.trace_primitive(&head);
+            self.expand_expansion_control()?;
+            return Ok(true);
         }
 
-        match self.lex_unexpanded_token() {
-            None => None,
-            Some(token) => {
+        match self.peek_unexpanded_token()? {
+            Some(token) if self.state.get_macro(&token).is_some() => {
                 // Handle macro expansion
-                if let Some(makro) = self.state.get_macro(&token) {
-                    let replacement_map = self.parse_replacement_map(&makro);
-                    let replacement = makro.get_replacement(&replacement_map);
-                    self.add_upcoming_tokens(replacement);
-                    self.lex_expanded_token()
-                } else {
-                    // Passthrough anything else
-                    Some(token)
+                self.lex_unexpanded_token()?;
+                let makro = self.state.get_macro(&token).unwrap();
+                let call_site = self.last_origin.clone();
+                let replacement_map =
+                    match self.parse_replacement_map(&token, &makro)? {
+                        Some(replacement_map) => replacement_map,
+                        // If the arguments didn't match the macro's
+                        // parameters, we've already reported an error and the
+                        // expansion is abandoned.
+                        None => return Ok(true),
+                    };
+                // The tokens from the definition of the macro share an origin
+                // which points back to this expansion, and the tokens from
+                // the arguments keep the origins they had.
+                let origin = Some(Rc::new(TokenOrigin::Macro {
+                    token: token.clone(),
+                    definition: makro.get_definition_origin().cloned(),
+                    call_site: call_site.clone(),
+                }));
+                let replacement =
+                    makro.get_replacement(&replacement_map, &origin);
+                self.trace_macro_expansion(
+                    &token,
+                    call_site.as_ref(),
+                    &makro,
+                    &replacement_map,
+                    &replacement,
+                );
+
+                for entry in replacement.into_iter().rev() {
+                    self.upcoming_tokens.push(entry);
                 }
+                self.debug_macro_expansion();
+                Ok(true)
             }
+            _ => Ok(false),
         }
     }
 
-    pub fn peek_expanded_token(&mut self) -> Option<Token> {
-        match self.lex_expanded_token() {
+    /// Expands tokens until the next one is either unexpandable or \the, and
+    /// returns true if it is \the. This is used where the tokens that \the
+    /// produces shouldn't be expanded any further, like in \edef.
+    pub fn is_unexpanded_the_head(&mut self) -> TeXResult<bool> {
+        loop {
+            if !self.is_next_token_noexpanded() {
+                if let Some(token) = self.peek_unexpanded_token()? {
+                    if self.state.is_token_equal_to_prim(&token, "the") {
+                        return Ok(true);
+                    }
+                }
+            }
+            if !self.expand_next_token()? {
+                return Ok(false);
+            }
+        }
+    }
+
+    /// Expands the \the at the head of the input, returning its tokens
+    /// instead of reading them next.
+    pub fn expand_the(&mut self) -> TeXResult<Vec<Token>> {
+        let head = self.lex_unexpanded_token()?;
+        self.trace_primitive(&head);
+        self.parse_the_tokens()
+    }
+
+    pub fn lex_expanded_token(&mut self) -> TeXResult<Option<Token>> {
+        while self.expand_next_token()? {}
+        // Passthrough anything else
+        self.lex_unexpanded_token()
+    }
+
+    pub fn peek_expanded_token(&mut self) -> TeXResult<Option<Token>> {
+        // Peeking shouldn't change what the last lexed token was, so we keep
+        // track of the current origin and restore it after.
+        let previous_origin = self.last_origin.clone();
+        let result = match self.lex_expanded_token()? {
             Some(token) => {
-                self.add_upcoming_token(token.clone());
+                self.put_back_token(token.clone());
                 Some(token)
             }
             None => None,
-        }
+        };
+        self.last_origin = previous_origin;
+        Ok(result)
     }
 
-    pub fn lex_unexpanded_token(&mut self) -> Option<Token> {
-        if self.upcoming_tokens.is_empty() {
-            self.lexer.lex_token()
-        } else {
-            self.upcoming_tokens.pop()
+    pub fn lex_unexpanded_token(&mut self) -> TeXResult<Option<Token>> {
+        self.end_finished_expansions();
+        // Tokens which were waiting to be read when an \input file started
+        // are read after the file ends.
+        if self.upcoming_tokens.len() > self.get_input_file_base() {
+            let (token, origin) = self.upcoming_tokens.pop().unwrap();
+            self.last_origin = origin;
+            self.last_token_noexpanded =
+                self.noexpand_index == Some(self.upcoming_tokens.len());
+            if self.last_token_noexpanded {
+                self.noexpand_index = None;
+            }
+            return Ok(Some(token));
+        }
+
+        self.last_token_noexpanded = false;
+        loop {
+            let result = self.lexer.lex_token();
+            self.last_origin = Some(Rc::new(self.lexer.last_token_origin()));
+            match result {
+                Ok(None) if self.lexer.is_reading_file() => {
+                    self.end_input_file();
+                    return self.lex_unexpanded_token();
+                }
+                Ok(token) => return Ok(token),
+                // The lexer skips over invalid characters, so we just need to
+                // report them and try again.
+                Err(kind) => self.report_error(kind)?,
+            }
         }
     }
 
-    pub fn peek_unexpanded_token(&mut self) -> Option<Token> {
-        match self.lex_unexpanded_token() {
+    pub fn peek_unexpanded_token(&mut self) -> TeXResult<Option<Token>> {
+        let previous_origin = self.last_origin.clone();
+        let result = match self.lex_unexpanded_token()? {
             Some(token) => {
-                self.add_upcoming_token(token.clone());
+                self.put_back_token(token.clone());
                 Some(token)
             }
             None => None,
+        };
+        self.last_origin = previous_origin;
+        Ok(result)
+    }
+
+    // Undoes lexing the most recently lexed token, keeping track of whether it
+    // was from \noexpand.
+    fn put_back_token(&mut self, token: Token) {
+        self.add_upcoming_token(token);
+        if self.last_token_noexpanded {
+            self.noexpand_index = Some(self.upcoming_tokens.len() - 1);
+        }
+    }
+
+    fn is_next_token_noexpanded(&self) -> bool {
+        self.noexpand_index.is_some()
+            && self.noexpand_index == self.upcoming_tokens.len().checked_sub(1)
+    }
+
+    /// Returns true if the next token is \relax, or something that acts like
+    /// it because it was passed to \noexpand. These do nothing when they're
+    /// executed.
+    pub fn is_relax_head(&mut self) -> TeXResult<bool> {
+        match self.peek_expanded_token()? {
+            Some(token) => Ok(self.is_next_token_noexpanded()
+                || self.state.is_token_equal_to_prim(&token, "relax")),
+            None => Ok(false),
+        }
+    }
+
+    // Returns true if the token would be expanded instead of executed. Like
+    // in TeX, undefined control sequences count as expandable.
+    fn is_expandable(&mut self, token: &Token) -> bool {
+        if let Token::Char(_, cat) = token {
+            if *cat != Category::Active {
+                return false;
+            }
+        }
+
+        match self.state.get_definition(token) {
+            None | Some(TokenDefinition::Macro(_)) => true,
+            Some(TokenDefinition::Primitive(primitive)) => {
+                EXPANDABLE_PRIMITIVES.contains(&primitive)
+                    || self.is_conditional_start(token)
+            }
+            Some(_) => false,
+        }
+    }
+
+    fn is_expansion_control_head(&mut self) -> TeXResult<bool> {
+        match self.peek_unexpanded_token()? {
+            Some(token) => {
+                Ok(self.state.is_token_equal_to_prim(&token, "expandafter")
+                    || self.state.is_token_equal_to_prim(&token, "noexpand")
+                    || self.state.is_token_equal_to_prim(&token, "csname"))
+            }
+            None => Ok(false),
+        }
+    }
+
+    // Parses the name of a control sequence from \csname...\endcsname. The
+    // tokens in between are expanded, and should all be characters.
+    fn parse_csname(&mut self) -> TeXResult<Token> {
+        let mut name = String::new();
+        loop {
+            match self.lex_expanded_token()? {
+                Some(token)
+                    if self
+                        .state
+                        .is_token_equal_to_prim(&token, "endcsname") =>
+                {
+                    break
+                }
+                Some(Token::Char(ch, cat)) if cat != Category::Active => {
+                    name.push(ch)
+                }
+                rest => {
+                    // TeX acts as if the \endcsname was there if there's
+                    // anything else.
+                    self.report_error(TeXErrorKind::MissingEndcsname)?;
+                    if let Some(token) = rest {
+                        self.add_upcoming_token(token);
+                    }
+                    break;
+                }
+            }
         }
+        Ok(Token::ControlSequence(name))
+    }
+
+    fn expand_expansion_control(&mut self) -> TeXResult<()> {
+        let head = self.lex_unexpanded_token()?.unwrap();
+
+        if self.state.is_token_equal_to_prim(&head, "expandafter") {
+            // Expands the token after the next one, and then puts the next
+            // one back in front of the expansion.
+            let token = match self.lex_unexpanded_token()? {
+                Some(token) => token,
+                None => return Ok(()),
+            };
+            let origin = self.last_origin.clone();
+            let noexpanded = self.last_token_noexpanded;
+
+            self.expand_next_token()?;
+
+            self.upcoming_tokens.push((token, origin));
+            if noexpanded {
+                self.noexpand_index = Some(self.upcoming_tokens.len() - 1);
+            }
+        } else if self.state.is_token_equal_to_prim(&head, "noexpand") {
+            let token = match self.lex_unexpanded_token()? {
+                Some(token) => token,
+                None => return Ok(()),
+            };
+            let expandable = self.is_expandable(&token);
+            self.add_upcoming_token(token);
+            if expandable {
+                self.noexpand_index = Some(self.upcoming_tokens.len() - 1);
+            }
+        } else if self.state.is_token_equal_to_prim(&head, "csname") {
+            let token = self.parse_csname()?;
+            // Control sequences which are made this way and aren't defined
+            // yet become \relax.
+            if self.state.get_definition(&token).is_none() {
+                self.state.set_let(
+                    false,
+                    &token,
+                    &Token::ControlSequence("relax".to_string()),
+                );
+            }
+            self.add_upcoming_token(token);
+        } else {
+            panic!("Invalid expansion control head: {:?}", head);
+        }
+
+        Ok(())
+    }
+
+    /// Returns where the most recently lexed token came from. Peeking at
+    /// tokens doesn't affect this.
+    pub fn last_token_origin(&self) -> Option<Rc<TokenOrigin>> {
+        self.last_origin.clone()
     }
 
     // Sometimes, we need to undo the lexing of a token. This function accepts
@@ -68,12 +342,14 @@ impl<'a> Parser<'a> {
     //  * When we're following the instructions to "insert the token <tok> into
     //    the input", like we do when seeing vertical mode material in
     //    horizontal mode.
+    // The token is given the origin of the most recently lexed token, which
+    // is the token itself when we're undoing a lex.
     //
     // Note: Use this function sparingly outside of this file! For efficiency's
     // sake, we should try to peek tokens instead of manually parsing and
     // un-parsing them.
     pub fn add_upcoming_token(&mut self, token: Token) {
-        self.upcoming_tokens.push(token);
+        self.upcoming_tokens.push((token, self.last_origin.clone()));
     }
 
     // Adds multiple tokens with add_upcoming_token(). We add the tokens in
@@ -93,7 +369,6 @@ mod tests {
 
     use std::rc::Rc;
 
-    use crate::category::Category;
     use crate::makro::{Macro, MacroListElem};
     use crate::testing::with_parser;
 
@@ -101,7 +376,7 @@ mod tests {
     fn it_lexes_tokens() {
         with_parser(&["a%"], |parser| {
             assert_eq!(
-                parser.lex_unexpanded_token(),
+                parser.lex_unexpanded_token().unwrap(),
                 Some(Token::Char('a', Category::Letter))
             );
         });
@@ -111,11 +386,11 @@ mod tests {
     fn it_peeks_tokens() {
         with_parser(&["a%"], |parser| {
             assert_eq!(
-                parser.peek_unexpanded_token(),
+                parser.peek_unexpanded_token().unwrap(),
                 Some(Token::Char('a', Category::Letter))
             );
             assert_eq!(
-                parser.lex_unexpanded_token(),
+                parser.lex_unexpanded_token().unwrap(),
                 Some(Token::Char('a', Category::Letter))
             );
         });
@@ -141,23 +416,23 @@ mod tests {
             );
 
             assert_eq!(
-                parser.lex_expanded_token(),
+                parser.lex_expanded_token().unwrap(),
                 Some(Token::Char('x', Category::Letter))
             );
             assert_eq!(
-                parser.lex_expanded_token(),
+                parser.lex_expanded_token().unwrap(),
                 Some(Token::Char('a', Category::Letter))
             );
             assert_eq!(
-                parser.lex_expanded_token(),
+                parser.lex_expanded_token().unwrap(),
                 Some(Token::Char('b', Category::Letter))
             );
             assert_eq!(
-                parser.lex_expanded_token(),
+                parser.lex_expanded_token().unwrap(),
                 Some(Token::Char('a', Category::Letter))
             );
             assert_eq!(
-                parser.lex_expanded_token(),
+                parser.lex_expanded_token().unwrap(),
                 Some(Token::Char('b', Category::Letter))
             );
         });
@@ -167,10 +442,10 @@ mod tests {
     fn it_expands_conditionals() {
         with_parser(&["\\iftrue x\\else y\\fi%"], |parser| {
             assert_eq!(
-                parser.lex_expanded_token(),
+                parser.lex_expanded_token().unwrap(),
                 Some(Token::Char('x', Category::Letter))
             );
-            assert_eq!(parser.lex_expanded_token(), None,);
+            assert_eq!(parser.lex_expanded_token().unwrap(), None,);
         });
     }
 
@@ -190,19 +465,19 @@ mod tests {
             );
 
             assert_eq!(
-                parser.peek_expanded_token(),
+                parser.peek_expanded_token().unwrap(),
                 Some(Token::Char('x', Category::Letter))
             );
             assert_eq!(
-                parser.lex_expanded_token(),
+                parser.lex_expanded_token().unwrap(),
                 Some(Token::Char('x', Category::Letter))
             );
             assert_eq!(
-                parser.peek_expanded_token(),
+                parser.peek_expanded_token().unwrap(),
                 Some(Token::Char('b', Category::Letter))
             );
             assert_eq!(
-                parser.lex_expanded_token(),
+                parser.lex_expanded_token().unwrap(),
                 Some(Token::Char('b', Category::Letter))
             );
         });
@@ -211,23 +486,148 @@ mod tests {
     #[test]
     fn it_prints_numbers() {
         with_parser(&["\\count1=-100 %", "\\number\\count1%"], |parser| {
-            parser.parse_assignment(None);
+            parser.parse_assignment(None).unwrap();
             assert_eq!(
-                parser.lex_expanded_token(),
+                parser.lex_expanded_token().unwrap(),
                 Some(Token::Char('-', Category::Other))
             );
             assert_eq!(
-                parser.lex_expanded_token(),
+                parser.lex_expanded_token().unwrap(),
                 Some(Token::Char('1', Category::Other))
             );
             assert_eq!(
-                parser.lex_expanded_token(),
+                parser.lex_expanded_token().unwrap(),
                 Some(Token::Char('0', Category::Other))
             );
             assert_eq!(
-                parser.lex_expanded_token(),
+                parser.lex_expanded_token().unwrap(),
                 Some(Token::Char('0', Category::Other))
             );
         });
     }
+
+    #[test]
+    fn it_doesnt_change_the_last_origin_when_peeking() {
+        with_parser(&["ab%"], |parser| {
+            parser.lex_unexpanded_token().unwrap();
+            let origin = parser.last_token_origin();
+            parser.peek_expanded_token().unwrap();
+            parser.peek_unexpanded_token().unwrap();
+            assert_eq!(parser.last_token_origin(), origin);
+
+            parser.lex_unexpanded_token().unwrap();
+            assert_eq!(
+                parser.last_token_origin().unwrap().source_location(),
+                Some((None, 1, 2))
+            );
+        });
+    }
+
+    fn lex_all_tokens(parser: &mut Parser) -> Vec<Token> {
+        let mut tokens = Vec::new();
+        while let Some(token) = parser.lex_expanded_token().unwrap() {
+            tokens.push(token);
+        }
+        tokens
+    }
+
+    #[test]
+    fn it_expands_after_the_next_token() {
+        with_parser(
+            &[r"\def\a#1{[#1]}\def\b{xy}%", r"\expandafter\a\b%"],
+            |parser| {
+                parser.parse_assignment(None).unwrap();
+                parser.parse_assignment(None).unwrap();
+                assert_eq!(
+                    lex_all_tokens(parser),
+                    vec![
+                        Token::Char('[', Category::Other),
+                        Token::Char('x', Category::Letter),
+                        Token::Char(']', Category::Other),
+                        Token::Char('y', Category::Letter),
+                    ]
+                );
+            },
+        );
+    }
+
+    #[test]
+    fn it_doesnt_expand_noexpanded_tokens() {
+        with_parser(&[r"\def\a{x}%", r"\noexpand\a\a\noexpand\b%"], |parser| {
+            parser.parse_assignment(None).unwrap();
+
+            let a = Token::ControlSequence("a".to_string());
+            assert_eq!(parser.peek_expanded_token().unwrap(), Some(a.clone()));
+            assert!(parser.is_relax_head().unwrap());
+            assert_eq!(parser.lex_expanded_token().unwrap(), Some(a));
+            assert!(!parser.is_relax_head().unwrap());
+            assert_eq!(
+                parser.lex_expanded_token().unwrap(),
+                Some(Token::Char('x', Category::Letter))
+            );
+            assert!(parser.is_relax_head().unwrap());
+            assert_eq!(
+                parser.lex_expanded_token().unwrap(),
+                Some(Token::ControlSequence("b".to_string()))
+            );
+        });
+    }
+
+    #[test]
+    fn it_doesnt_change_unexpandable_noexpanded_tokens() {
+        with_parser(&[r"\noexpand\count a%"], |parser| {
+            assert!(!parser.is_relax_head().unwrap());
+            assert_eq!(
+                parser.lex_expanded_token().unwrap(),
+                Some(Token::ControlSequence("count".to_string()))
+            );
+            assert_eq!(
+                parser.lex_expanded_token().unwrap(),
+                Some(Token::Char('a', Category::Letter))
+            );
+        });
+    }
+
+    #[test]
+    fn it_expands_control_sequences_from_csname() {
+        with_parser(
+            &[
+                r"\def\a{x}\def\b{a}%",
+                r"\csname\b\endcsname\csname y z\endcsname%",
+            ],
+            |parser| {
+                parser.parse_assignment(None).unwrap();
+                parser.parse_assignment(None).unwrap();
+
+                let y_z = Token::ControlSequence("y z".to_string());
+                assert_eq!(
+                    lex_all_tokens(parser),
+                    vec![Token::Char('x', Category::Letter), y_z.clone()]
+                );
+                assert!(parser.state.is_token_equal_to_prim(&y_z, "relax"));
+                // \b isn't changed, since it was already defined.
+                assert!(parser
+                    .state
+                    .get_macro(&Token::ControlSequence("b".to_string()))
+                    .is_some());
+            },
+        );
+    }
+
+    #[test]
+    fn it_inserts_missing_endcsnames() {
+        with_parser(&[r"\csname a\par%"], |parser| {
+            assert_eq!(
+                lex_all_tokens(parser),
+                vec![
+                    Token::ControlSequence("a".to_string()),
+                    Token::ControlSequence("par".to_string()),
+                ]
+            );
+            assert_eq!(
+                parser.take_errors()[0].kind,
+                TeXErrorKind::MissingEndcsname
+            );
+        });
+    }
 }
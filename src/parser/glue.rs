@@ -1,28 +1,83 @@
 use crate::parser::Parser;
 
-use crate::dimension::{Dimen, SpringDimen};
-use crate::glue::Glue;
+use crate::dimension::{Dimen, MuSpringDimen, SpringDimen};
+use crate::error::TeXResult;
+use crate::glue::{Glue, MuGlue};
 
 impl<'a> Parser<'a> {
-    pub fn parse_glue(&mut self) -> Glue {
-        let space = self.parse_dimen();
+    pub fn parse_glue(&mut self) -> TeXResult<Glue> {
+        let sign = self.parse_optional_signs()?;
+        if self.is_internal_glue_head()? {
+            return Ok(self.parse_internal_glue()? * sign);
+        }
+
+        let space = match self.parse_unsigned_dimen(false)? * sign {
+            SpringDimen::Dimen(dimen) => dimen,
+            _ => unreachable!(),
+        };
 
         let mut stretch = SpringDimen::Dimen(Dimen::zero());
         let mut shrink = SpringDimen::Dimen(Dimen::zero());
 
-        if self.parse_optional_keyword_expanded("plus") {
-            stretch = self.parse_spring_dimen(true);
+        if self.parse_optional_keyword_expanded("plus")? {
+            stretch = self.parse_spring_dimen(true)?;
         }
 
-        if self.parse_optional_keyword_expanded("minus") {
-            shrink = self.parse_spring_dimen(true);
+        if self.parse_optional_keyword_expanded("minus")? {
+            shrink = self.parse_spring_dimen(true)?;
         }
 
-        Glue {
+        Ok(Glue {
             space,
             stretch,
             shrink,
+        })
+    }
+
+    /// Parses a <muglue>, which is like <glue> except that finite amounts are
+    /// measured in mu.
+    pub fn parse_mu_glue(&mut self) -> TeXResult<MuGlue> {
+        let sign = self.parse_optional_signs()?;
+        if self.is_internal_mu_glue_head()? {
+            return Ok(self.parse_internal_mu_glue()? * sign);
+        }
+
+        let space = self.parse_mu_dimen()? * sign;
+
+        let mut stretch = MuSpringDimen::zero();
+        let mut shrink = MuSpringDimen::zero();
+
+        if self.parse_optional_keyword_expanded("plus")? {
+            stretch = self.parse_mu_spring_dimen(true)?;
         }
+
+        if self.parse_optional_keyword_expanded("minus")? {
+            shrink = self.parse_mu_spring_dimen(true)?;
+        }
+
+        Ok(MuGlue {
+            space,
+            stretch,
+            shrink,
+        })
+    }
+
+    pub fn is_internal_glue_head(&mut self) -> TeXResult<bool> {
+        self.is_glue_variable_head()
+    }
+
+    pub fn parse_internal_glue(&mut self) -> TeXResult<Glue> {
+        let variable = self.parse_glue_variable()?;
+        Ok(variable.get(self.state))
+    }
+
+    pub fn is_internal_mu_glue_head(&mut self) -> TeXResult<bool> {
+        self.is_mu_glue_variable_head()
+    }
+
+    pub fn parse_internal_mu_glue(&mut self) -> TeXResult<MuGlue> {
+        let variable = self.parse_mu_glue_variable()?;
+        Ok(variable.get(self.state))
     }
 }
 
@@ -31,7 +86,7 @@ mod tests {
     use super::*;
 
     use crate::category::Category;
-    use crate::dimension::Unit;
+    use crate::dimension::{FilDimen, FilKind, MuDimen, Unit};
     use crate::testing::with_parser;
     use crate::token::Token;
 
@@ -39,7 +94,7 @@ mod tests {
     fn it_parses_glue_without_stretch_and_shrink() {
         with_parser(&["1pt %"], |parser| {
             assert_eq!(
-                parser.parse_glue(),
+                parser.parse_glue().unwrap(),
                 Glue {
                     space: Dimen::from_unit(1.0, Unit::Point),
                     stretch: SpringDimen::Dimen(Dimen::zero()),
@@ -53,7 +108,7 @@ mod tests {
     fn it_parses_glue_without_shrink() {
         with_parser(&["1pt plus 2pt %"], |parser| {
             assert_eq!(
-                parser.parse_glue(),
+                parser.parse_glue().unwrap(),
                 Glue {
                     space: Dimen::from_unit(1.0, Unit::Point),
                     stretch: SpringDimen::Dimen(Dimen::from_unit(
@@ -70,7 +125,7 @@ mod tests {
     fn it_parses_glue_without_stretch() {
         with_parser(&["1pt minus 3pt %"], |parser| {
             assert_eq!(
-                parser.parse_glue(),
+                parser.parse_glue().unwrap(),
                 Glue {
                     space: Dimen::from_unit(1.0, Unit::Point),
                     stretch: SpringDimen::Dimen(Dimen::zero()),
@@ -87,7 +142,7 @@ mod tests {
     fn it_parses_glue_with_stretch_and_shrink() {
         with_parser(&["1pt plus 2pt minus 3pt %"], |parser| {
             assert_eq!(
-                parser.parse_glue(),
+                parser.parse_glue().unwrap(),
                 Glue {
                     space: Dimen::from_unit(1.0, Unit::Point),
                     stretch: SpringDimen::Dimen(Dimen::from_unit(
@@ -107,7 +162,7 @@ mod tests {
     fn it_doesnt_fail_when_seeing_a_partial_keyword() {
         with_parser(&["1pt plu%", "1pt plus 2pt minu%"], |parser| {
             assert_eq!(
-                parser.parse_glue(),
+                parser.parse_glue().unwrap(),
                 Glue {
                     space: Dimen::from_unit(1.0, Unit::Point),
                     stretch: SpringDimen::Dimen(Dimen::zero()),
@@ -116,20 +171,20 @@ mod tests {
             );
 
             assert_eq!(
-                parser.lex_expanded_token(),
+                parser.lex_expanded_token().unwrap(),
                 Some(Token::Char('p', Category::Letter))
             );
             assert_eq!(
-                parser.lex_expanded_token(),
+                parser.lex_expanded_token().unwrap(),
                 Some(Token::Char('l', Category::Letter))
             );
             assert_eq!(
-                parser.lex_expanded_token(),
+                parser.lex_expanded_token().unwrap(),
                 Some(Token::Char('u', Category::Letter))
             );
 
             assert_eq!(
-                parser.parse_glue(),
+                parser.parse_glue().unwrap(),
                 Glue {
                     space: Dimen::from_unit(1.0, Unit::Point),
                     stretch: SpringDimen::Dimen(Dimen::from_unit(
@@ -141,21 +196,41 @@ mod tests {
             );
 
             assert_eq!(
-                parser.lex_expanded_token(),
+                parser.lex_expanded_token().unwrap(),
                 Some(Token::Char('m', Category::Letter))
             );
             assert_eq!(
-                parser.lex_expanded_token(),
+                parser.lex_expanded_token().unwrap(),
                 Some(Token::Char('i', Category::Letter))
             );
             assert_eq!(
-                parser.lex_expanded_token(),
+                parser.lex_expanded_token().unwrap(),
                 Some(Token::Char('n', Category::Letter))
             );
             assert_eq!(
-                parser.lex_expanded_token(),
+                parser.lex_expanded_token().unwrap(),
                 Some(Token::Char('u', Category::Letter))
             );
         });
     }
+
+    #[test]
+    fn it_parses_mu_glue_with_fil_units() {
+        with_parser(&["1mu plus 2fill minus 3filll %"], |parser| {
+            assert_eq!(
+                parser.parse_mu_glue().unwrap(),
+                MuGlue {
+                    space: MuDimen::new(1.0),
+                    stretch: MuSpringDimen::FilDimen(FilDimen::new(
+                        FilKind::Fill,
+                        2.0
+                    )),
+                    shrink: MuSpringDimen::FilDimen(FilDimen::new(
+                        FilKind::Filll,
+                        3.0
+                    )),
+                }
+            );
+        });
+    }
 }
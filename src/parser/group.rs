@@ -0,0 +1,290 @@
+use crate::category::Category;
+use crate::error::{TeXErrorKind, TeXResult};
+use crate::parser::Parser;
+use crate::token::Token;
+
+/// The different kinds of groups. TeX keeps track of which kind of group is
+/// innermost so that each group can only be ended by the same kind of thing
+/// that started it.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum GroupKind {
+    // Started by a { in horizontal or vertical mode
+    Simple,
+    // The braces around the contents of an \hbox or a \vbox
+    HorizontalBox,
+    VerticalBox,
+    // Started by a { in math mode
+    Math,
+    // Started by \begingroup, and only ended by \endgroup
+    SemiSimple,
+    // Started by the $ which enters math mode
+    MathShift,
+}
+
+impl GroupKind {
+    // The name that e-TeX uses for the group in \tracinggroups output.
+    pub fn get_name(self) -> &'static str {
+        match self {
+            GroupKind::Simple => "simple",
+            GroupKind::HorizontalBox => "hbox",
+            GroupKind::VerticalBox => "vbox",
+            GroupKind::Math => "math",
+            GroupKind::SemiSimple => "semi simple",
+            GroupKind::MathShift => "math shift",
+        }
+    }
+}
+
+pub struct Group {
+    kind: GroupKind,
+    // The input line that the group started on, for tracing
+    start_line: i32,
+    // The tokens saved with \aftergroup inside of the group
+    after_group_tokens: Vec<Token>,
+}
+
+impl<'a> Parser<'a> {
+    /// Starts a new group, so that changes to the state can be undone when
+    /// it ends.
+    pub fn push_state(&mut self, kind: GroupKind) {
+        self.state.push_state();
+
+        let start_line = self.get_input_line_number();
+        self.groups.push(Group {
+            kind,
+            start_line,
+            after_group_tokens: Vec::new(),
+        });
+        self.trace_group(false, kind, self.groups.len(), start_line);
+    }
+
+    /// Ends the innermost group, restoring everything that was changed inside
+    /// of it. The tokens that were saved with \aftergroup inside the group
+    /// are read next, in the order that they were saved.
+    pub fn pop_state(&mut self) {
+        let maybe_group = self.groups.pop();
+        if let Some(group) = &maybe_group {
+            self.trace_group(
+                true,
+                group.kind,
+                self.groups.len() + 1,
+                group.start_line,
+            );
+        }
+
+        let restored_entries = self.state.pop_state();
+        self.trace_restores(restored_entries);
+
+        if let Some(group) = maybe_group {
+            self.add_upcoming_tokens(group.after_group_tokens);
+        }
+    }
+
+    /// The number of groups that haven't ended yet. Lists keep track of this
+    /// when they start, so that they can tell which groups were started
+    /// inside of them.
+    pub fn get_group_depth(&self) -> usize {
+        self.groups.len()
+    }
+
+    fn get_current_group_kind(&self) -> Option<GroupKind> {
+        self.groups.last().map(|group| group.kind)
+    }
+
+    /// Returns whether a } should end a list which started when the group
+    /// depth was `group_base`. If it doesn't, it should be parsed with
+    /// parse_end_group() instead.
+    pub fn does_end_group_end_list(&self, group_base: usize) -> bool {
+        self.groups.len() <= group_base
+            && self.get_current_group_kind() != Some(GroupKind::MathShift)
+    }
+
+    /// Parses a } which ends a group that was started inside of the current
+    /// list. If the group wasn't started with a {, TeX complains about it and
+    /// ignores the }.
+    pub fn parse_end_group(&mut self) -> TeXResult<()> {
+        self.lex_expanded_token()?;
+
+        match self.get_current_group_kind() {
+            Some(GroupKind::SemiSimple) => self.report_error(
+                TeXErrorKind::ExtraRightBraceOrForgotten("\\endgroup"),
+            ),
+            Some(GroupKind::MathShift) => {
+                self.report_error(TeXErrorKind::ExtraRightBraceOrForgotten("$"))
+            }
+            _ => {
+                self.pop_state();
+                Ok(())
+            }
+        }
+    }
+
+    /// Inserts the token which would end the innermost group, for when
+    /// something else tries to end it. TeX does this so that the groups that
+    /// are still open get ended before the one that was meant to be.
+    pub fn insert_group_end(&mut self) -> TeXResult<()> {
+        let (token, error) = match self.get_current_group_kind() {
+            None => return Ok(()),
+            Some(GroupKind::SemiSimple) => (
+                Token::ControlSequence("endgroup".to_string()),
+                TeXErrorKind::MissingEndGroup,
+            ),
+            Some(GroupKind::MathShift) => (
+                Token::Char('$', Category::MathShift),
+                TeXErrorKind::MissingMathShift,
+            ),
+            Some(_) => (
+                Token::Char('}', Category::EndGroup),
+                TeXErrorKind::MissingRightBrace,
+            ),
+        };
+
+        self.add_upcoming_token(token);
+        self.report_error(error)
+    }
+
+    pub fn is_semi_simple_group_head(&mut self) -> TeXResult<bool> {
+        self.is_next_expanded_token_in_set_of_primitives(&[
+            "begingroup",
+            "endgroup",
+        ])
+    }
+
+    pub fn parse_semi_simple_group(&mut self) -> TeXResult<()> {
+        let token = self.lex_expanded_token()?.unwrap();
+
+        if self.state.is_token_equal_to_prim(&token, "begingroup") {
+            self.push_state(GroupKind::SemiSimple);
+            return Ok(());
+        }
+
+        match self.get_current_group_kind() {
+            Some(GroupKind::SemiSimple) => {
+                self.pop_state();
+                Ok(())
+            }
+            // There's nothing to end at the outer level, so the \endgroup is
+            // ignored.
+            None => self.report_error(TeXErrorKind::ExtraEndGroup),
+            // Otherwise, the innermost group is ended first and then the
+            // \endgroup is read again.
+            Some(_) => {
+                self.add_upcoming_token(token);
+                self.insert_group_end()
+            }
+        }
+    }
+
+    pub fn is_after_group_head(&mut self) -> TeXResult<bool> {
+        self.is_next_expanded_token_in_set_of_primitives(&["aftergroup"])
+    }
+
+    pub fn parse_after_group(&mut self) -> TeXResult<()> {
+        self.lex_expanded_token()?;
+        let token = match self.lex_unexpanded_token()? {
+            Some(token) => token,
+            None => return Ok(()),
+        };
+
+        // Like in TeX, tokens saved outside of any group are never used.
+        if let Some(group) = self.groups.last_mut() {
+            group.after_group_tokens.push(token);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::testing::with_parser;
+
+    #[test]
+    fn it_inserts_after_group_tokens_when_groups_end() {
+        with_parser(&[r"\aftergroup\a\aftergroup b%"], |parser| {
+            parser.push_state(GroupKind::Simple);
+            assert!(parser.is_after_group_head().unwrap());
+            parser.parse_after_group().unwrap();
+            assert!(parser.is_after_group_head().unwrap());
+            parser.parse_after_group().unwrap();
+            assert_eq!(parser.lex_unexpanded_token().unwrap(), None);
+
+            parser.pop_state();
+            assert_eq!(
+                parser.lex_unexpanded_token().unwrap(),
+                Some(Token::ControlSequence("a".to_string()))
+            );
+            assert_eq!(
+                parser.lex_unexpanded_token().unwrap(),
+                Some(Token::Char('b', Category::Letter))
+            );
+        });
+    }
+
+    #[test]
+    fn it_parses_semi_simple_groups() {
+        with_parser(&[r"\begingroup\count1=2 \endgroup%"], |parser| {
+            assert!(parser.is_semi_simple_group_head().unwrap());
+            parser.parse_semi_simple_group().unwrap();
+            assert_eq!(parser.get_group_depth(), 1);
+
+            parser.parse_assignment(None).unwrap();
+            assert_eq!(parser.state.get_count(1), 2);
+
+            assert!(parser.is_semi_simple_group_head().unwrap());
+            parser.parse_semi_simple_group().unwrap();
+            assert_eq!(parser.get_group_depth(), 0);
+            assert_eq!(parser.state.get_count(1), 0);
+        });
+    }
+
+    #[test]
+    fn it_ignores_extra_endgroups() {
+        with_parser(&[r"\endgroup%"], |parser| {
+            parser.parse_semi_simple_group().unwrap();
+            assert_eq!(parser.lex_unexpanded_token().unwrap(), None);
+            assert_eq!(
+                parser.take_errors()[0].kind,
+                TeXErrorKind::ExtraEndGroup
+            );
+        });
+    }
+
+    #[test]
+    fn it_ends_other_groups_before_endgroups() {
+        with_parser(&[r"\endgroup%"], |parser| {
+            parser.push_state(GroupKind::Simple);
+            parser.parse_semi_simple_group().unwrap();
+
+            assert_eq!(
+                parser.lex_unexpanded_token().unwrap(),
+                Some(Token::Char('}', Category::EndGroup))
+            );
+            assert_eq!(
+                parser.lex_unexpanded_token().unwrap(),
+                Some(Token::ControlSequence("endgroup".to_string()))
+            );
+            assert_eq!(
+                parser.take_errors()[0].kind,
+                TeXErrorKind::MissingRightBrace
+            );
+            parser.pop_state();
+        });
+    }
+
+    #[test]
+    fn it_doesnt_end_semi_simple_groups_with_braces() {
+        with_parser(&[r"}%"], |parser| {
+            parser.push_state(GroupKind::SemiSimple);
+            assert!(!parser.does_end_group_end_list(0));
+            parser.parse_end_group().unwrap();
+            assert_eq!(parser.get_group_depth(), 1);
+            assert_eq!(
+                parser.take_errors()[0].kind,
+                TeXErrorKind::ExtraRightBraceOrForgotten("\\endgroup")
+            );
+            parser.pop_state();
+        });
+    }
+}
@@ -1,20 +1,17 @@
 use crate::boxes::{HorizontalBox, TeXBox};
 use crate::category::Category;
-use crate::dimension::{Dimen, SpringDimen, Unit};
+use crate::debugger::{PartialList, PartialListContents};
+use crate::dimension::{Dimen, SpringDimen};
+use crate::error::{TeXErrorKind, TeXResult};
 use crate::glue::Glue;
 use crate::list::HorizontalListElem;
 use crate::math_list::MathStyle;
+use crate::origin::Provenance;
+use crate::parameter::{DimenParameter, GlueParameter};
+use crate::parser::group::GroupKind;
 use crate::parser::Parser;
 use crate::token::Token;
 
-fn get_space_glue() -> Glue {
-    Glue {
-        space: Dimen::from_unit(3.33333, Unit::Point),
-        stretch: SpringDimen::Dimen(Dimen::from_unit(1.66666, Unit::Point)),
-        shrink: SpringDimen::Dimen(Dimen::from_unit(1.11111, Unit::Point)),
-    }
-}
-
 enum ElemResult {
     Elem(HorizontalListElem),
     Elems(Vec<HorizontalListElem>),
@@ -22,175 +19,288 @@ enum ElemResult {
 }
 
 impl<'a> Parser<'a> {
+    /// The glue that a space token turns into. This is \spaceskip if it is
+    /// nonzero, and otherwise the interword glue of the current font.
+    fn get_space_glue(&self) -> Glue {
+        let space_skip =
+            self.state.get_glue_parameter(GlueParameter::SpaceSkip);
+        if space_skip != Glue::zero() {
+            return space_skip;
+        }
+
+        let font = self.state.get_current_font();
+        self.state
+            .with_metrics_for_font(&font, |metrics| Glue {
+                space: metrics.get_font_dimension(2),
+                stretch: SpringDimen::Dimen(metrics.get_font_dimension(3)),
+                shrink: SpringDimen::Dimen(metrics.get_font_dimension(4)),
+            })
+            .unwrap_or_else(Glue::zero)
+    }
+
     /// Returns if the next token is the start of something that only makes
     /// sense in vertical mode.
-    fn is_vertical_material_head(&mut self) -> bool {
+    fn is_vertical_material_head(&mut self) -> TeXResult<bool> {
         self.is_next_expanded_token_in_set_of_primitives(&["vskip", "end"])
     }
 
+    // Boxes can't be ended early like paragraphs can, so vertical mode
+    // material doesn't end a restricted horizontal list. Like TeX, \end (or
+    // the end of the input) ends the groups that are still open one at a
+    // time until the box itself is ended, and other vertical commands are
+    // ignored.
+    fn parse_vertical_material_in_restricted_mode(
+        &mut self,
+        mode: &'static str,
+    ) -> TeXResult<()> {
+        let token = match self.peek_expanded_token()? {
+            Some(token) => token,
+            None => return self.insert_group_end(),
+        };
+
+        if self.state.is_token_equal_to_prim(&token, "end")
+            && self.get_group_depth() > 0
+        {
+            self.insert_group_end()
+        } else {
+            self.lex_expanded_token()?;
+            self.report_error(TeXErrorKind::CantUseInMode(token, mode))
+        }
+    }
+
     fn parse_horizontal_list_elem(
         &mut self,
-        group_level: &mut usize,
+        group_base: usize,
         restricted: bool,
-    ) -> ElemResult {
-        let expanded_token = self.peek_expanded_token();
+    ) -> TeXResult<ElemResult> {
+        let mode = if restricted {
+            "restricted horizontal mode"
+        } else {
+            "horizontal mode"
+        };
+        let expanded_token = self.peek_expanded_token()?;
+        self.trace_command(mode, &expanded_token);
         let expanded_renamed_token = self.replace_renamed_token(expanded_token);
-        match expanded_renamed_token {
+        Ok(match expanded_renamed_token {
+            None if restricted && self.get_group_depth() > 0 => {
+                self.parse_vertical_material_in_restricted_mode(mode)?;
+                self.parse_horizontal_list_elem(group_base, restricted)?
+            }
             None => ElemResult::Nothing,
             Some(Token::Char(ch, cat)) => match cat {
                 Category::Letter => {
-                    self.lex_expanded_token();
+                    self.lex_expanded_token()?;
                     ElemResult::Elem(HorizontalListElem::Char {
                         chr: ch,
                         font: self.state.get_current_font(),
+                        origin: Provenance::new(self.last_token_origin()),
                     })
                 }
                 Category::Other => {
-                    self.lex_expanded_token();
+                    self.lex_expanded_token()?;
                     ElemResult::Elem(HorizontalListElem::Char {
                         chr: ch,
                         font: self.state.get_current_font(),
+                        origin: Provenance::new(self.last_token_origin()),
                     })
                 }
                 Category::Space => {
-                    self.lex_expanded_token();
-                    ElemResult::Elem(
-                        HorizontalListElem::HSkip(get_space_glue()),
-                    )
+                    self.lex_expanded_token()?;
+                    ElemResult::Elem(HorizontalListElem::HSkip(
+                        self.get_space_glue(),
+                    ))
                 }
                 Category::BeginGroup => {
-                    self.lex_expanded_token();
-                    *group_level += 1;
-                    self.state.push_state();
-                    self.parse_horizontal_list_elem(group_level, restricted)
+                    self.lex_expanded_token()?;
+                    self.push_state(GroupKind::Simple);
+                    self.parse_horizontal_list_elem(group_base, restricted)?
                 }
                 Category::EndGroup => {
-                    if *group_level == 0 {
+                    if self.does_end_group_end_list(group_base) {
                         ElemResult::Nothing
                     } else {
-                        self.lex_expanded_token();
-                        *group_level -= 1;
-                        self.state.pop_state();
-                        self.parse_horizontal_list_elem(group_level, restricted)
+                        self.parse_end_group()?;
+                        self.parse_horizontal_list_elem(group_base, restricted)?
                     }
                 }
                 Category::MathShift => {
-                    self.lex_expanded_token();
+                    self.lex_expanded_token()?;
 
-                    let next_token = self.peek_unexpanded_token();
+                    let next_token = self.peek_unexpanded_token()?;
                     let is_next_token_math_shift = match next_token {
                         Some(Token::Char(_, Category::MathShift)) => true,
                         _ => false,
                     };
 
-                    if !restricted && is_next_token_math_shift {
-                        self.lex_unexpanded_token();
+                    let is_display = !restricted && is_next_token_math_shift;
+                    if is_display {
+                        self.lex_unexpanded_token()?;
+                        // Until display math is implemented, we typeset it
+                        // like normal math.
+                        self.report_error(TeXErrorKind::Unimplemented(
+                            "display math mode".to_string(),
+                        ))?;
+                    }
 
-                        panic!("display math mode unimplemented!");
-                    } else {
-                        self.state.push_state();
+                    self.push_state(GroupKind::MathShift);
 
-                        let math_list = self.parse_math_list();
-                        let horizontal_list = self
-                            .convert_math_list_to_horizontal_list(
-                                math_list,
-                                MathStyle::TextStyle,
-                            );
+                    let math_list = self.parse_math_list()?;
+                    let horizontal_list = self
+                        .convert_math_list_to_horizontal_list(
+                            math_list,
+                            MathStyle::TextStyle,
+                        );
 
-                        match self.lex_expanded_token() {
-                            Some(Token::Char(_, Category::MathShift)) => {}
-                            rest => {
-                                panic!("Invalid end to math mode: {:?}", rest)
-                            }
-                        }
+                    self.parse_math_shift_end()?;
+                    if is_display {
+                        self.parse_math_shift_end()?;
+                    }
 
-                        self.state.pop_state();
+                    self.pop_state();
 
-                        ElemResult::Elems(horizontal_list)
-                    }
+                    ElemResult::Elems(horizontal_list)
+                }
+                _ => {
+                    self.skip_unexpected_token(mode)?;
+                    self.parse_horizontal_list_elem(group_base, restricted)?
                 }
-                _ => panic!("unimplemented"),
             },
             Some(ref tok) if self.state.is_token_equal_to_prim(tok, "par") => {
-                self.lex_expanded_token();
+                self.lex_expanded_token()?;
 
                 if restricted {
-                    self.parse_horizontal_list_elem(group_level, restricted)
+                    self.parse_horizontal_list_elem(group_base, restricted)?
                 } else {
                     // In unrestricted horizontal mode, \par terminates the
                     // list parsing.
                     // TODO(xymostech): This also is supposed to do some extra
                     // work before finishing the list.
+                    self.reset_error_count();
                     ElemResult::Nothing
                 }
             }
             Some(ref tok)
                 if self.state.is_token_equal_to_prim(tok, "hskip") =>
             {
-                self.lex_expanded_token();
-                let glue = self.parse_glue();
+                self.lex_expanded_token()?;
+                let glue = self.parse_glue()?;
                 ElemResult::Elem(HorizontalListElem::HSkip(glue))
             }
             Some(ref tok)
                 if self.state.is_token_equal_to_prim(tok, "raise") =>
             {
-                self.lex_expanded_token();
-                let shift = self.parse_dimen();
-                if let Some(tex_box) = self.parse_box() {
+                self.lex_expanded_token()?;
+                let shift = self.parse_dimen()?;
+                if let Some(tex_box) = self.parse_box()? {
                     ElemResult::Elem(HorizontalListElem::Box { tex_box, shift })
                 } else {
-                    self.parse_horizontal_list_elem(group_level, restricted)
+                    self.parse_horizontal_list_elem(group_base, restricted)?
                 }
             }
             Some(ref tok)
                 if self.state.is_token_equal_to_prim(tok, "lower") =>
             {
-                self.lex_expanded_token();
-                let shift = self.parse_dimen();
-                if let Some(tex_box) = self.parse_box() {
+                self.lex_expanded_token()?;
+                let shift = self.parse_dimen()?;
+                if let Some(tex_box) = self.parse_box()? {
                     ElemResult::Elem(HorizontalListElem::Box {
                         tex_box,
                         shift: shift * -1,
                     })
                 } else {
-                    self.parse_horizontal_list_elem(group_level, restricted)
+                    self.parse_horizontal_list_elem(group_base, restricted)?
                 }
             }
             Some(ref tok) if self.state.is_token_equal_to_prim(tok, "char") => {
-                self.lex_expanded_token();
-                let char_number = self.parse_8bit_number();
+                self.lex_expanded_token()?;
+                let origin = self.last_token_origin();
+                let chr = self.parse_char_code()?;
+
+                ElemResult::Elem(HorizontalListElem::Char {
+                    chr,
+                    font: self.state.get_current_font(),
+                    origin: Provenance::new(origin),
+                })
+            }
+            // Characters defined with \chardef work like \char.
+            Some(ref tok) if self.state.get_chardef(tok).is_some() => {
+                let chr = self.state.get_chardef(tok).unwrap();
+                self.lex_expanded_token()?;
 
                 ElemResult::Elem(HorizontalListElem::Char {
-                    chr: char_number as char,
+                    chr,
                     font: self.state.get_current_font(),
+                    origin: Provenance::new(self.last_token_origin()),
                 })
             }
             _ => {
-                if self.is_assignment_head() {
-                    self.parse_assignment(None);
-                    self.parse_horizontal_list_elem(group_level, restricted)
-                } else if self.is_box_head() {
-                    let maybe_tex_box = self.parse_box();
+                if self.is_assignment_head()? {
+                    self.parse_assignment(None)?;
+                    self.parse_horizontal_list_elem(group_base, restricted)?
+                } else if self.is_show_head()? {
+                    self.parse_show()?;
+                    self.parse_horizontal_list_elem(group_base, restricted)?
+                } else if self.is_message_head()? {
+                    self.parse_message()?;
+                    self.parse_horizontal_list_elem(group_base, restricted)?
+                } else if self.is_after_group_head()? {
+                    self.parse_after_group()?;
+                    self.parse_horizontal_list_elem(group_base, restricted)?
+                } else if self.is_after_assignment_head()? {
+                    self.parse_after_assignment()?;
+                    self.parse_horizontal_list_elem(group_base, restricted)?
+                } else if self.is_semi_simple_group_head()? {
+                    self.parse_semi_simple_group()?;
+                    self.parse_horizontal_list_elem(group_base, restricted)?
+                } else if self.is_case_change_head()? {
+                    self.parse_case_change()?;
+                    self.parse_horizontal_list_elem(group_base, restricted)?
+                } else if self.is_relax_head()? {
+                    self.lex_expanded_token()?;
+                    self.parse_horizontal_list_elem(group_base, restricted)?
+                } else if self.is_box_head()? {
+                    let maybe_tex_box = self.parse_box()?;
                     if let Some(tex_box) = maybe_tex_box {
                         ElemResult::Elem(HorizontalListElem::Box {
                             tex_box,
                             shift: Dimen::zero(),
                         })
                     } else {
-                        self.parse_horizontal_list_elem(group_level, restricted)
+                        self.parse_horizontal_list_elem(group_base, restricted)?
                     }
-                } else if self.is_vertical_material_head() {
-                    // If we see vertical mode material, we add a \par token to
-                    // the input stream, continue and let that be parsed, after
-                    // which we'll see the vertical mode material again.
-                    self.add_upcoming_token(Token::ControlSequence(
-                        "par".to_string(),
-                    ));
-                    self.parse_horizontal_list_elem(group_level, restricted)
+                } else if self.is_vertical_material_head()? {
+                    if restricted {
+                        self.parse_vertical_material_in_restricted_mode(mode)?;
+                    } else {
+                        // If we see vertical mode material, we add a \par
+                        // token to the input stream, continue and let that be
+                        // parsed, after which we'll see the vertical mode
+                        // material again.
+                        self.add_upcoming_token(Token::ControlSequence(
+                            "par".to_string(),
+                        ));
+                    }
+                    self.parse_horizontal_list_elem(group_base, restricted)?
                 } else {
-                    panic!("unimplemented!");
+                    self.skip_unexpected_token(mode)?;
+                    self.parse_horizontal_list_elem(group_base, restricted)?
                 }
             }
+        })
+    }
+
+    // Parses the $ which ends math mode. If it's missing, TeX acts as if it
+    // had been there.
+    fn parse_math_shift_end(&mut self) -> TeXResult<()> {
+        match self.lex_expanded_token()? {
+            Some(Token::Char(_, Category::MathShift)) => Ok(()),
+            rest => {
+                self.report_error(TeXErrorKind::MissingMathShift)?;
+                if let Some(token) = rest {
+                    self.add_upcoming_token(token);
+                }
+                Ok(())
+            }
         }
     }
 
@@ -198,7 +308,7 @@ impl<'a> Parser<'a> {
         &mut self,
         restricted: bool,
         indent: bool,
-    ) -> Vec<HorizontalListElem> {
+    ) -> TeXResult<Vec<HorizontalListElem>> {
         let mut result = Vec::new();
 
         // Optionally add in indentation
@@ -208,8 +318,8 @@ impl<'a> Parser<'a> {
         // control this.
         if indent {
             let mut hbox = HorizontalBox::empty();
-            // TODO(xymostech): This should be \parindent, not a fixed 20pt.
-            hbox.width = Dimen::from_unit(20.0, Unit::Point);
+            hbox.width =
+                self.state.get_dimen_parameter(DimenParameter::ParIndent);
             let tex_box = TeXBox::HorizontalBox(hbox);
             result.push(HorizontalListElem::Box {
                 tex_box,
@@ -217,18 +327,32 @@ impl<'a> Parser<'a> {
             });
         }
 
-        let mut group_level = 0;
+        let group_base = self.get_group_depth();
 
+        let mode = if restricted {
+            "restricted horizontal mode"
+        } else {
+            "horizontal mode"
+        };
+        self.modes.push(mode);
         loop {
-            match self.parse_horizontal_list_elem(&mut group_level, restricted)
-            {
+            self.push_debug_list(|| PartialList {
+                mode,
+                contents: PartialListContents::Horizontal(result.clone()),
+            });
+            let elem =
+                self.parse_horizontal_list_elem(group_base, restricted)?;
+            self.pop_debug_list();
+
+            match elem {
                 ElemResult::Nothing => break,
                 ElemResult::Elem(elem) => result.push(elem),
                 ElemResult::Elems(mut elems) => result.append(&mut elems),
             }
         }
+        self.modes.pop();
 
-        result
+        Ok(result)
     }
 }
 
@@ -236,10 +360,13 @@ impl<'a> Parser<'a> {
 mod tests {
     use super::*;
 
-    use crate::dimension::{FilDimen, FilKind};
+    use std::rc::Rc;
+
+    use crate::dimension::{FilDimen, FilKind, Unit};
     use crate::font::Font;
     use crate::math_code::MathCode;
-    use crate::testing::with_parser;
+    use crate::origin::TokenOrigin;
+    use crate::testing::{with_parser, WithoutOrigins};
 
     lazy_static! {
         static ref CMR10: Font = Font {
@@ -262,7 +389,10 @@ mod tests {
     ) {
         with_parser(lines, |parser| {
             assert_eq!(
-                parser.parse_horizontal_list(restricted, false),
+                parser
+                    .parse_horizontal_list(restricted, false)
+                    .unwrap()
+                    .without_origins(),
                 expected_toks
             );
         });
@@ -280,10 +410,12 @@ mod tests {
                 HorizontalListElem::Char {
                     chr: 'a',
                     font: CMR10.clone(),
+                    origin: Provenance::none(),
                 },
                 HorizontalListElem::Char {
                     chr: 'b',
                     font: CMR10.clone(),
+                    origin: Provenance::none(),
                 },
             ],
         );
@@ -297,14 +429,17 @@ mod tests {
                 HorizontalListElem::Char {
                     chr: 'a',
                     font: CMR10.clone(),
+                    origin: Provenance::none(),
                 },
                 HorizontalListElem::Char {
                     chr: 'b',
                     font: CMR10.clone(),
+                    origin: Provenance::none(),
                 },
                 HorizontalListElem::Char {
                     chr: 'c',
                     font: CMR10.clone(),
+                    origin: Provenance::none(),
                 },
             ],
         );
@@ -318,14 +453,17 @@ mod tests {
                 HorizontalListElem::Char {
                     chr: 'a',
                     font: CMR10.clone(),
+                    origin: Provenance::none(),
                 },
                 HorizontalListElem::Char {
                     chr: 'b',
                     font: CMR10.clone(),
+                    origin: Provenance::none(),
                 },
                 HorizontalListElem::Char {
                     chr: 'c',
                     font: CMR10.clone(),
+                    origin: Provenance::none(),
                 },
             ],
         );
@@ -338,6 +476,7 @@ mod tests {
             &[HorizontalListElem::Char {
                 chr: 'a',
                 font: CMR10.clone(),
+                origin: Provenance::none(),
             }],
         );
     }
@@ -350,15 +489,56 @@ mod tests {
                 HorizontalListElem::Char {
                     chr: 'y',
                     font: CMR10.clone(),
+                    origin: Provenance::none(),
                 },
                 HorizontalListElem::Char {
                     chr: 'x',
                     font: CMR10.clone(),
+                    origin: Provenance::none(),
                 },
             ],
         );
     }
 
+    #[test]
+    fn it_reads_after_group_tokens_after_groups() {
+        let char_elem = |chr| HorizontalListElem::Char {
+            chr,
+            font: CMR10.clone(),
+            origin: Provenance::none(),
+        };
+        assert_parses_to(
+            &[r"\def\a{x}{\aftergroup\a\aftergroup yz}w%"],
+            &[
+                char_elem('z'),
+                char_elem('x'),
+                char_elem('y'),
+                char_elem('w'),
+            ],
+        );
+    }
+
+    #[test]
+    fn it_ends_boxes_before_endgroups() {
+        with_parser(&[r"\hbox{\begingroup a\endgroup\endgroup%"], |parser| {
+            assert_eq!(
+                parser.parse_horizontal_list(true, false).unwrap().len(),
+                1
+            );
+            assert_eq!(parser.get_group_depth(), 0);
+
+            let error_kinds: Vec<_> = parser
+                .take_errors()
+                .into_iter()
+                .map(|error| error.kind)
+                .collect();
+            assert_eq!(
+                error_kinds,
+                &[TeXErrorKind::MissingRightBrace, TeXErrorKind::ExtraEndGroup]
+            );
+        });
+    }
+
     #[test]
     fn it_parses_space_to_glue() {
         assert_parses_to(
@@ -367,8 +547,39 @@ mod tests {
                 HorizontalListElem::Char {
                     chr: 'a',
                     font: CMR10.clone(),
+                    origin: Provenance::none(),
                 },
-                HorizontalListElem::HSkip(get_space_glue()),
+                HorizontalListElem::HSkip(Glue {
+                    space: Dimen::from_scaled_points(218453),
+                    stretch: SpringDimen::Dimen(Dimen::from_scaled_points(
+                        109226,
+                    )),
+                    shrink: SpringDimen::Dimen(Dimen::from_scaled_points(
+                        72818,
+                    )),
+                }),
+            ],
+        );
+    }
+
+    #[test]
+    fn it_uses_spaceskip_for_spaces() {
+        assert_parses_to(
+            &[r"\spaceskip=2pt plus 1fil a %"],
+            &[
+                HorizontalListElem::Char {
+                    chr: 'a',
+                    font: CMR10.clone(),
+                    origin: Provenance::none(),
+                },
+                HorizontalListElem::HSkip(Glue {
+                    space: Dimen::from_unit(2.0, Unit::Point),
+                    stretch: SpringDimen::FilDimen(FilDimen::new(
+                        FilKind::Fil,
+                        1.0,
+                    )),
+                    shrink: SpringDimen::Dimen(Dimen::zero()),
+                }),
             ],
         );
     }
@@ -376,10 +587,10 @@ mod tests {
     #[test]
     fn it_stops_parsing_at_mismatched_brace() {
         with_parser(&["a{b{c}d{e}f}g}%"], |parser| {
-            let hlist = parser.parse_horizontal_list(true, false);
+            let hlist = parser.parse_horizontal_list(true, false).unwrap();
             assert_eq!(hlist.len(), 7);
             assert_eq!(
-                parser.lex_expanded_token(),
+                parser.lex_expanded_token().unwrap(),
                 Some(Token::Char('}', Category::EndGroup))
             );
         });
@@ -393,6 +604,7 @@ mod tests {
                 HorizontalListElem::Char {
                     chr: 'a',
                     font: CMR10.clone(),
+                    origin: Provenance::none(),
                 },
                 HorizontalListElem::HSkip(Glue {
                     space: Dimen::from_unit(-3.0, Unit::Point),
@@ -405,6 +617,7 @@ mod tests {
                 HorizontalListElem::Char {
                     chr: 'b',
                     font: CMR10.clone(),
+                    origin: Provenance::none(),
                 },
             ],
         );
@@ -420,11 +633,15 @@ mod tests {
                 + Dimen::from_unit(2.0, Unit::Point);
 
             assert_eq!(
-                parser.parse_horizontal_list(true, false),
+                parser
+                    .parse_horizontal_list(true, false)
+                    .unwrap()
+                    .without_origins(),
                 &[
                     HorizontalListElem::Char {
                         chr: 'a',
                         font: CMR10.clone(),
+                        origin: Provenance::none(),
                     },
                     HorizontalListElem::Box {
                         tex_box: TeXBox::HorizontalBox(HorizontalBox {
@@ -436,6 +653,7 @@ mod tests {
                                 HorizontalListElem::Char {
                                     chr: 'a',
                                     font: CMR10.clone(),
+                                    origin: Provenance::none(),
                                 },
                                 HorizontalListElem::HSkip(Glue {
                                     space: Dimen::from_unit(2.0, Unit::Point),
@@ -447,6 +665,7 @@ mod tests {
                                 HorizontalListElem::Char {
                                     chr: 'g',
                                     font: CMR10.clone(),
+                                    origin: Provenance::none(),
                                 },
                             ],
                             glue_set_ratio: None,
@@ -456,6 +675,7 @@ mod tests {
                     HorizontalListElem::Char {
                         chr: 'b',
                         font: CMR10.clone(),
+                        origin: Provenance::none(),
                     },
                 ]
             );
@@ -467,7 +687,7 @@ mod tests {
         with_parser(&[r"\setbox0=\hbox{a}%", r"\box0%"], |parser| {
             let metrics = parser.state.get_metrics_for_font(&CMR10).unwrap();
 
-            let list = parser.parse_horizontal_list(true, false);
+            let list = parser.parse_horizontal_list(true, false).unwrap();
 
             assert_eq!(list.len(), 1);
             if let HorizontalListElem::Box {
@@ -490,10 +710,12 @@ mod tests {
                 HorizontalListElem::Char {
                     chr: 'a',
                     font: CMR10.clone(),
+                    origin: Provenance::none(),
                 },
                 HorizontalListElem::Char {
                     chr: 'b',
                     font: CMR10.clone(),
+                    origin: Provenance::none(),
                 },
             ],
         );
@@ -508,14 +730,17 @@ mod tests {
                 HorizontalListElem::Char {
                     chr: 'a',
                     font: CMR10.clone(),
+                    origin: Provenance::none(),
                 },
                 HorizontalListElem::Char {
                     chr: 'b',
                     font: CMR10.clone(),
+                    origin: Provenance::none(),
                 },
                 HorizontalListElem::Char {
                     chr: 'c',
                     font: CMR10.clone(),
+                    origin: Provenance::none(),
                 },
             ],
             false,
@@ -531,10 +756,12 @@ mod tests {
                 HorizontalListElem::Char {
                     chr: 'a',
                     font: CMR10.clone(),
+                    origin: Provenance::none(),
                 },
                 HorizontalListElem::Char {
                     chr: 'b',
                     font: CMR10.clone(),
+                    origin: Provenance::none(),
                 },
             ],
             true,
@@ -546,14 +773,17 @@ mod tests {
                 HorizontalListElem::Char {
                     chr: 'a',
                     font: CMR10.clone(),
+                    origin: Provenance::none(),
                 },
                 HorizontalListElem::Char {
                     chr: 'b',
                     font: CMR10.clone(),
+                    origin: Provenance::none(),
                 },
                 HorizontalListElem::Char {
                     chr: 'c',
                     font: CMR10.clone(),
+                    origin: Provenance::none(),
                 },
             ],
             true,
@@ -562,20 +792,35 @@ mod tests {
 
     #[test]
     fn it_adds_indentation() {
-        with_parser(&[r"\setbox0=\hbox{}%", r"\wd0=20pt%", "a%"], |parser| {
-            parser.parse_assignment(None);
-            parser.parse_assignment(None);
+        let lines = &[
+            r"\parindent=15pt%",
+            r"\setbox0=\hbox{}%",
+            r"\wd0=15pt%",
+            "a%",
+        ];
+        with_parser(lines, |parser| {
+            parser.parse_assignment(None).unwrap();
+            parser.parse_assignment(None).unwrap();
+            parser.parse_assignment(None).unwrap();
 
             assert_eq!(
-                parser.parse_horizontal_list(false, true),
+                parser
+                    .parse_horizontal_list(false, true)
+                    .unwrap()
+                    .without_origins(),
                 &[
                     HorizontalListElem::Box {
-                        tex_box: parser.state.get_box(0).unwrap(),
+                        tex_box: parser
+                            .state
+                            .get_box(0)
+                            .unwrap()
+                            .without_origins(),
                         shift: Dimen::zero()
                     },
                     HorizontalListElem::Char {
                         chr: 'a',
                         font: CMR10.clone(),
+                        origin: Provenance::none(),
                     },
                 ]
             );
@@ -587,14 +832,18 @@ mod tests {
         // \par is defined normally, so we just end horizontal mode
         with_parser(&[r"a\end%"], |parser| {
             assert_eq!(
-                parser.parse_horizontal_list(false, false),
+                parser
+                    .parse_horizontal_list(false, false)
+                    .unwrap()
+                    .without_origins(),
                 &[HorizontalListElem::Char {
                     chr: 'a',
                     font: CMR10.clone(),
+                    origin: Provenance::none(),
                 },]
             );
             assert_eq!(
-                parser.lex_unexpanded_token(),
+                parser.lex_unexpanded_token().unwrap(),
                 Some(Token::ControlSequence("end".to_string()))
             );
         });
@@ -604,20 +853,25 @@ mod tests {
             &[r"\let\endgraf=\par%", r"\def\par{b\endgraf}%", r"a\end%"],
             |parser| {
                 assert_eq!(
-                    parser.parse_horizontal_list(false, false),
+                    parser
+                        .parse_horizontal_list(false, false)
+                        .unwrap()
+                        .without_origins(),
                     &[
                         HorizontalListElem::Char {
                             chr: 'a',
                             font: CMR10.clone(),
+                            origin: Provenance::none(),
                         },
                         HorizontalListElem::Char {
                             chr: 'b',
                             font: CMR10.clone(),
+                            origin: Provenance::none(),
                         },
                     ]
                 );
                 assert_eq!(
-                    parser.lex_unexpanded_token(),
+                    parser.lex_unexpanded_token().unwrap(),
                     Some(Token::ControlSequence("end".to_string()))
                 );
             },
@@ -625,17 +879,50 @@ mod tests {
     }
 
     #[test]
-    #[should_panic(expected = "unimplemented")]
     fn it_fails_parsing_mathchardefs() {
         with_parser(&[r"\hello%", r"\hello%"], |parser| {
-            let tok = parser.lex_unexpanded_token().unwrap();
+            let tok = parser.lex_unexpanded_token().unwrap().unwrap();
             parser.state.set_math_chardef(
                 false,
                 &tok,
                 &MathCode::from_number(0x7161),
             );
 
-            parser.parse_horizontal_list(false, false);
+            assert_eq!(
+                parser
+                    .parse_horizontal_list(false, false)
+                    .unwrap()
+                    .without_origins(),
+                &[]
+            );
+            assert_eq!(
+                parser.take_errors()[0].kind,
+                TeXErrorKind::Unimplemented(
+                    r"`\hello' in horizontal mode".to_string()
+                )
+            );
+        });
+    }
+
+    #[test]
+    fn it_inserts_missing_math_shifts() {
+        // Like in TeX, the } can't end math mode, so it's dropped and the $
+        // and } are inserted at the end of the input instead.
+        with_parser(&[r"\hbox{$}%"], |parser| {
+            parser.parse_box().unwrap().unwrap();
+            let error_kinds: Vec<_> = parser
+                .take_errors()
+                .into_iter()
+                .map(|error| error.kind)
+                .collect();
+            assert_eq!(
+                error_kinds,
+                &[
+                    TeXErrorKind::ExtraRightBraceOrForgotten("$"),
+                    TeXErrorKind::MissingMathShift,
+                    TeXErrorKind::MissingRightBrace,
+                ]
+            );
         });
     }
 
@@ -643,15 +930,20 @@ mod tests {
     fn it_parses_math_shifts() {
         with_parser(&[r"$ab$%"], |parser| {
             assert_eq!(
-                parser.parse_horizontal_list(false, false),
+                parser
+                    .parse_horizontal_list(false, false)
+                    .unwrap()
+                    .without_origins(),
                 &[
                     HorizontalListElem::Char {
                         chr: 'a',
-                        font: CMMI10.clone()
+                        font: CMMI10.clone(),
+                        origin: Provenance::none(),
                     },
                     HorizontalListElem::Char {
                         chr: 'b',
-                        font: CMMI10.clone()
+                        font: CMMI10.clone(),
+                        origin: Provenance::none(),
                     },
                 ]
             );
@@ -666,19 +958,25 @@ mod tests {
             ],
             |parser| {
                 assert_eq!(
-                    parser.parse_horizontal_list(false, false),
+                    parser
+                        .parse_horizontal_list(false, false)
+                        .unwrap()
+                        .without_origins(),
                     &[
                         HorizontalListElem::Char {
                             chr: '1',
                             font: CMR10.clone(),
+                            origin: Provenance::none(),
                         },
                         HorizontalListElem::Char {
                             chr: '2',
                             font: CMR10.clone(),
+                            origin: Provenance::none(),
                         },
                         HorizontalListElem::Char {
                             chr: '1',
                             font: CMR10.clone(),
+                            origin: Provenance::none(),
                         },
                     ]
                 );
@@ -701,35 +999,45 @@ mod tests {
             ],
             |parser| {
                 assert_eq!(
-                    parser.parse_horizontal_list(false, false),
+                    parser
+                        .parse_horizontal_list(false, false)
+                        .unwrap()
+                        .without_origins(),
                     &[
                         HorizontalListElem::Char {
                             chr: 'a',
                             font: CMR10.clone(),
+                            origin: Provenance::none(),
                         },
                         HorizontalListElem::Char {
                             chr: 'b',
                             font: CMR10.clone(),
+                            origin: Provenance::none(),
                         },
                         HorizontalListElem::Char {
                             chr: 'c',
                             font: cmr7.clone(),
+                            origin: Provenance::none(),
                         },
                         HorizontalListElem::Char {
                             chr: 'd',
                             font: CMR10.clone(),
+                            origin: Provenance::none(),
                         },
                         HorizontalListElem::Char {
                             chr: 'e',
                             font: CMR10.clone(),
+                            origin: Provenance::none(),
                         },
                         HorizontalListElem::Char {
                             chr: 'f',
                             font: cmr7.clone(),
+                            origin: Provenance::none(),
                         },
                         HorizontalListElem::Char {
                             chr: 'g',
                             font: cmr7.clone(),
+                            origin: Provenance::none(),
                         },
                     ]
                 );
@@ -746,11 +1054,16 @@ mod tests {
                 r"\raise 2pt \hbox{a}c\lower 3pt \vbox{b}%",
             ],
             |parser| {
-                let abox = parser.parse_box().unwrap();
-                let bbox = parser.parse_box().unwrap();
+                let abox =
+                    parser.parse_box().unwrap().unwrap().without_origins();
+                let bbox =
+                    parser.parse_box().unwrap().unwrap().without_origins();
 
                 assert_eq!(
-                    parser.parse_horizontal_list(false, false),
+                    parser
+                        .parse_horizontal_list(false, false)
+                        .unwrap()
+                        .without_origins(),
                     &[
                         HorizontalListElem::Box {
                             tex_box: abox,
@@ -759,6 +1072,7 @@ mod tests {
                         HorizontalListElem::Char {
                             chr: 'c',
                             font: CMR10.clone(),
+                            origin: Provenance::none(),
                         },
                         HorizontalListElem::Box {
                             tex_box: bbox,
@@ -782,19 +1096,25 @@ mod tests {
             ],
             |parser| {
                 assert_eq!(
-                    parser.parse_horizontal_list(false, false),
+                    parser
+                        .parse_horizontal_list(false, false)
+                        .unwrap()
+                        .without_origins(),
                     &[
                         HorizontalListElem::Char {
                             chr: 'a',
                             font: CMR10.clone(),
+                            origin: Provenance::none(),
                         },
                         HorizontalListElem::Char {
                             chr: 'b',
                             font: CMR10.clone(),
+                            origin: Provenance::none(),
                         },
                         HorizontalListElem::Char {
                             chr: 'c',
                             font: CMR10.clone(),
+                            origin: Provenance::none(),
                         },
                     ]
                 );
@@ -806,26 +1126,164 @@ mod tests {
     fn it_parses_explicit_char_commands() {
         with_parser(&[r"\char0 \char33 \char97 \char127%"], |parser| {
             assert_eq!(
-                parser.parse_horizontal_list(false, false),
+                parser
+                    .parse_horizontal_list(false, false)
+                    .unwrap()
+                    .without_origins(),
                 &[
                     HorizontalListElem::Char {
                         chr: 0 as char,
                         font: CMR10.clone(),
+                        origin: Provenance::none(),
                     },
                     HorizontalListElem::Char {
                         chr: '!',
                         font: CMR10.clone(),
+                        origin: Provenance::none(),
                     },
                     HorizontalListElem::Char {
                         chr: 'a',
                         font: CMR10.clone(),
+                        origin: Provenance::none(),
                     },
                     HorizontalListElem::Char {
                         chr: 127 as char,
                         font: CMR10.clone(),
+                        origin: Provenance::none(),
                     },
                 ]
             );
         });
     }
+
+    #[test]
+    fn it_parses_chardef_characters() {
+        with_parser(&[r"\chardef\a=98 a\a%"], |parser| {
+            assert_eq!(
+                parser
+                    .parse_horizontal_list(false, false)
+                    .unwrap()
+                    .without_origins(),
+                &[
+                    HorizontalListElem::Char {
+                        chr: 'a',
+                        font: CMR10.clone(),
+                        origin: Provenance::none(),
+                    },
+                    HorizontalListElem::Char {
+                        chr: 'b',
+                        font: CMR10.clone(),
+                        origin: Provenance::none(),
+                    },
+                ]
+            );
+        });
+    }
+
+    #[test]
+    fn it_records_where_characters_came_from() {
+        with_parser(&[r"\def\a{b}%", r"x\a\char99%"], |parser| {
+            parser.parse_assignment(None).unwrap();
+
+            let list = parser.parse_horizontal_list(true, false).unwrap();
+            let locations: Vec<_> = list
+                .iter()
+                .map(|elem| elem.origin().unwrap().source_location())
+                .collect();
+            assert_eq!(
+                locations,
+                vec![
+                    Some((None, 2, 1)),
+                    Some((None, 2, 2)),
+                    Some((None, 2, 4))
+                ]
+            );
+
+            assert_eq!(
+                list[1].origin().unwrap().to_string(),
+                r"<input>:2:2 in expansion of \a (defined at <input>:1:5)"
+            );
+        });
+    }
+
+    #[test]
+    fn it_records_the_files_characters_came_from() {
+        with_parser(&[r"\input src/parser/test_files/input %"], |parser| {
+            let list = parser.parse_horizontal_list(true, false).unwrap();
+            let file = "src/parser/test_files/input.tex";
+
+            assert_eq!(
+                list[0].origin().unwrap().source_location(),
+                Some((Some(file), 1, 1))
+            );
+            // The space from the end of the line doesn't have an origin.
+            assert_eq!(list[1].origin(), None);
+            assert_eq!(
+                list[2].origin().unwrap().source_location(),
+                Some((Some(file), 2, 1))
+            );
+        });
+    }
+
+    #[test]
+    fn it_records_the_macros_characters_came_from() {
+        let source = |line, column| {
+            Some(Rc::new(TokenOrigin::Source {
+                file: None,
+                line,
+                column,
+            }))
+        };
+
+        with_parser(&[r"\def\a{b}%", r"\def\c{\a}%", r"\c%"], |parser| {
+            parser.parse_assignment(None).unwrap();
+            parser.parse_assignment(None).unwrap();
+
+            let list = parser.parse_horizontal_list(true, false).unwrap();
+            assert_eq!(
+                list[0].origin(),
+                Some(&Rc::new(TokenOrigin::Macro {
+                    token: Token::ControlSequence("a".to_string()),
+                    definition: source(1, 5),
+                    call_site: Some(Rc::new(TokenOrigin::Macro {
+                        token: Token::ControlSequence("c".to_string()),
+                        definition: source(2, 5),
+                        call_site: source(3, 1),
+                    })),
+                }))
+            );
+        });
+    }
+
+    #[test]
+    fn it_keeps_the_origins_of_macro_arguments() {
+        with_parser(
+            &[r"\def\a#1{#1x}%", r"\a{%", r"y\undefined}%"],
+            |parser| {
+                parser.parse_assignment(None).unwrap();
+
+                let list = parser.parse_horizontal_list(true, false).unwrap();
+                assert_eq!(
+                    list[0].origin().unwrap().to_string(),
+                    "<input>:3:1"
+                );
+                assert_eq!(
+                    list[1].origin().unwrap().to_string(),
+                    r"<input>:2:1 in expansion of \a (defined at <input>:1:5)"
+                );
+
+                let errors = parser.take_errors();
+                assert_eq!(
+                    errors[0].kind,
+                    TeXErrorKind::UndefinedControlSequence(
+                        Token::ControlSequence("undefined".to_string())
+                    )
+                );
+                assert_eq!(
+                    errors[0].origin.as_ref().unwrap().source_location(),
+                    Some((None, 3, 2))
+                );
+            },
+        );
+    }
 }
@@ -0,0 +1,244 @@
+use std::fs;
+use std::path::Path;
+
+use crate::error::{TeXErrorKind, TeXResult};
+use crate::parser::Parser;
+#[cfg(feature = "kpathsea")]
+use crate::paths::get_path_to_tex_file;
+use crate::transcript::Selector;
+
+// Given the name of a file from \input (like "story"), returns a path to the
+// file if it can be found. Like TeX, ".tex" is added to names which don't
+// already have an extension. Files are looked for relative to the current
+// directory first, and then wherever kpathsea knows to look (if XymosTeX was
+// built with it).
+fn find_tex_file(file_name: &str) -> Option<String> {
+    let file_name = if Path::new(file_name).extension().is_none() {
+        format!("{}.tex", file_name)
+    } else {
+        file_name.to_string()
+    };
+
+    if Path::new(&file_name).is_file() {
+        return Some(file_name);
+    }
+
+    #[cfg(feature = "kpathsea")]
+    {
+        get_path_to_tex_file(&file_name)
+    }
+    #[cfg(not(feature = "kpathsea"))]
+    {
+        None
+    }
+}
+
+impl<'a> Parser<'a> {
+    pub fn is_input_head(&mut self) -> TeXResult<bool> {
+        match self.peek_unexpanded_token()? {
+            Some(token) => {
+                Ok(self.state.is_token_equal_to_prim(&token, "input")
+                    || self.state.is_token_equal_to_prim(&token, "endinput"))
+            }
+            _ => Ok(false),
+        }
+    }
+
+    // Returns how many of the upcoming tokens were already waiting to be read
+    // when the innermost \input file was started. Those are read once the
+    // file ends, so only the tokens above this come before the file.
+    pub fn get_input_file_base(&self) -> usize {
+        self.input_file_bases.last().cloned().unwrap_or(0)
+    }
+
+    /// Starts reading from a file, which will be read before anything else
+    /// that's waiting to be read. Like TeX, the name of the file is printed
+    /// after a "(" when it's opened, and a ")" is printed once it ends.
+    pub fn start_input_file(&mut self, file_name: &str) -> TeXResult<()> {
+        let path = find_tex_file(file_name);
+        let source =
+            path.as_ref().and_then(|path| fs::read_to_string(path).ok());
+        let (path, source) = match (path, source) {
+            (Some(path), Some(source)) => (path, source),
+            _ => {
+                return Err(self.fatal_error(TeXErrorKind::FileNotFound(
+                    file_name.to_string(),
+                )))
+            }
+        };
+
+        self.print_on_current_line(&format!("({}", path));
+
+        let lines: Vec<&str> = source.lines().collect();
+        self.lexer.start_file(&path, &lines);
+        self.input_file_bases.push(self.upcoming_tokens.len());
+        Ok(())
+    }
+
+    // Called once we run out of lines in the innermost \input file.
+    pub fn end_input_file(&mut self) {
+        self.lexer.end_file();
+        self.input_file_bases.pop();
+        self.transcript.print(Selector::TerminalAndLog, ")");
+    }
+
+    /// Stops reading every \input file that hasn't ended yet, which happens
+    /// when \end is reached inside of one. Like TeX, a " )" is printed for
+    /// each of them.
+    pub fn close_input_files(&mut self) {
+        while self.lexer.is_reading_file() {
+            self.lexer.end_file();
+            self.input_file_bases.pop();
+            self.transcript.print(Selector::TerminalAndLog, " )");
+        }
+    }
+
+    pub fn expand_input(&mut self) -> TeXResult<()> {
+        let head = self.lex_unexpanded_token()?.unwrap();
+
+        if self.state.is_token_equal_to_prim(&head, "input") {
+            let file_name = self.parse_file_name()?;
+            // Like TeX, the space that ends the file name is dropped.
+            self.parse_optional_space_expanded()?;
+            self.start_input_file(&file_name)
+        } else if self.state.is_token_equal_to_prim(&head, "endinput") {
+            // The rest of the current line is still read.
+            self.lexer.end_file_after_current_line();
+            Ok(())
+        } else {
+            panic!("Invalid input head: {:?}", head);
+        }
+    }
+
+    /// Returns the number of the line that's being read in the current file,
+    /// for \inputlineno.
+    pub fn get_input_line_number(&self) -> i32 {
+        self.lexer.get_line_number() as i32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::category::Category;
+    use crate::testing::{with_parser, with_terminal};
+    use crate::token::Token;
+
+    // Tests are run from the root of the repo, so this is where the files
+    // used in these tests can be found.
+    const TEST_FILES: &str = "src/parser/test_files";
+
+    fn lex_all_tokens(parser: &mut Parser) -> Vec<Token> {
+        let mut tokens = Vec::new();
+        while let Some(token) = parser.lex_expanded_token().unwrap() {
+            tokens.push(token);
+        }
+        tokens
+    }
+
+    #[test]
+    fn it_reads_input_files() {
+        let terminal = with_terminal(
+            &[&format!(r"a\input {}/input b%", TEST_FILES)],
+            |parser| {
+                assert_eq!(
+                    lex_all_tokens(parser),
+                    vec![
+                        Token::Char('a', Category::Letter),
+                        Token::Char('x', Category::Letter),
+                        Token::Char(' ', Category::Space),
+                        Token::Char('y', Category::Letter),
+                        Token::Char(' ', Category::Space),
+                        Token::Char('b', Category::Letter),
+                    ]
+                );
+            },
+        );
+
+        assert_eq!(terminal, format!("({}/input.tex)", TEST_FILES));
+    }
+
+    #[test]
+    fn it_reads_input_files_before_tokens_from_macros() {
+        with_parser(
+            &[
+                &format!(r"\def\a{{\input {}/input.tex\relax}}%", TEST_FILES),
+                r"\a%",
+            ],
+            |parser| {
+                parser.parse_assignment(None).unwrap();
+                assert_eq!(
+                    lex_all_tokens(parser),
+                    vec![
+                        Token::Char('x', Category::Letter),
+                        Token::Char(' ', Category::Space),
+                        Token::Char('y', Category::Letter),
+                        Token::Char(' ', Category::Space),
+                        Token::ControlSequence("relax".to_string()),
+                    ]
+                );
+            },
+        );
+    }
+
+    #[test]
+    fn it_ends_input_files_early() {
+        with_parser(
+            &[&format!(r"\input {}/endinput a%", TEST_FILES)],
+            |parser| {
+                assert_eq!(
+                    lex_all_tokens(parser),
+                    vec![
+                        Token::Char('x', Category::Letter),
+                        Token::Char('y', Category::Letter),
+                        Token::Char('a', Category::Letter),
+                    ]
+                );
+            },
+        );
+    }
+
+    #[test]
+    fn it_closes_unfinished_input_files() {
+        let terminal = with_terminal(
+            &[&format!(r"\input {}/input %", TEST_FILES)],
+            |parser| {
+                assert_eq!(
+                    parser.lex_expanded_token().unwrap(),
+                    Some(Token::Char('x', Category::Letter))
+                );
+                parser.close_input_files();
+            },
+        );
+
+        assert_eq!(terminal, format!("({}/input.tex )", TEST_FILES));
+    }
+
+    #[test]
+    fn it_keeps_track_of_input_line_numbers() {
+        with_parser(
+            &[
+                "%",
+                &format!(r"\input {}/lineno \count2=\inputlineno%", TEST_FILES),
+            ],
+            |parser| {
+                parser.parse_assignment(None).unwrap();
+                parser.parse_assignment(None).unwrap();
+                assert_eq!(parser.state.get_count(1), 3);
+                assert_eq!(parser.state.get_count(2), 2);
+            },
+        );
+    }
+
+    #[test]
+    fn it_fails_on_missing_files() {
+        with_parser(&[r"\input nonexistent-file%"], |parser| {
+            assert!(parser.lex_expanded_token().is_err());
+            assert_eq!(
+                parser.take_errors()[0].kind,
+                TeXErrorKind::FileNotFound("nonexistent-file".to_string())
+            );
+        });
+    }
+}
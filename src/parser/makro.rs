@@ -1,15 +1,21 @@
 use std::collections::HashMap;
 
 use crate::category::Category;
+use crate::error::{TeXErrorKind, TeXResult};
 use crate::makro::{Macro, MacroListElem};
+use crate::origin::TokenWithOrigin;
 use crate::parser::Parser;
 use crate::token::Token;
 
-fn parse_parameter_number(ch: char) -> usize {
-    if ch >= '1' && ch <= '9' {
-        ((ch as u8) - (b'0')) as usize
-    } else {
-        panic!("Invalid number after parameter: {}", ch);
+// Parameters can only be numbered from 1 to 9.
+const MAX_PARAMETERS: usize = 9;
+
+fn parse_parameter_number(token: &Token) -> Option<usize> {
+    match token {
+        Token::Char(ch, Category::Other) if *ch >= '1' && *ch <= '9' => {
+            Some(((*ch as u8) - (b'0')) as usize)
+        }
+        _ => None,
     }
 }
 
@@ -27,16 +33,91 @@ fn get_next_non_token_index(makro: &Macro, parameter_index: usize) -> usize {
     end_index
 }
 
-// Used to keep track of the result of parsing a single token/balanced group
+// Used to keep track of the result of parsing a single token/balanced group.
+// The tokens keep track of where they came from, so that the tokens in the
+// arguments to a macro keep their origins when they're used in its
+// replacement.
 enum SingleTokenGroup {
-    BalancedGroup(Token, Vec<Token>, Token),
-    SingleToken(Token),
+    BalancedGroup(TokenWithOrigin, Vec<TokenWithOrigin>, TokenWithOrigin),
+    SingleToken(TokenWithOrigin),
 }
 
 impl<'a> Parser<'a> {
-    // Parses a parameter list and replacement list into a macro object
-    pub fn parse_macro_definition(&mut self) -> Macro {
+    // Lexes the next token of the definition of the macro named `name`,
+    // expanding it first for \edef. If the input ends in the middle of a
+    // definition, there's nothing sensible left to do, so that's a fatal
+    // error.
+    fn lex_definition_token(
+        &mut self,
+        name: &Token,
+        expand: bool,
+    ) -> TeXResult<Token> {
+        let maybe_token = if expand {
+            self.lex_expanded_token()?
+        } else {
+            self.lex_unexpanded_token()?
+        };
+        match maybe_token {
+            Some(token) => Ok(token),
+            None => {
+                Err(self
+                    .fatal_error(TeXErrorKind::RunawayDefinition(name.clone())))
+            }
+        }
+    }
+
+    // Like lex_definition_token(), but for the arguments to a use of a macro,
+    // so the token is returned along with where it came from. Unless the
+    // macro is \long, its arguments can't contain \par. When one is found, it
+    // is left to be read again and this returns None, in which case the
+    // expansion of the macro should be abandoned.
+    fn lex_argument_token(
+        &mut self,
+        name: &Token,
+        long: bool,
+    ) -> TeXResult<Option<TokenWithOrigin>> {
+        match self.lex_unexpanded_token()? {
+            Some(token) => {
+                if !long && token == Token::ControlSequence("par".to_string()) {
+                    self.report_error(TeXErrorKind::ParagraphEnded(
+                        name.clone(),
+                    ))?;
+                    self.add_upcoming_token(token);
+                    Ok(None)
+                } else {
+                    Ok(Some((token, self.last_token_origin())))
+                }
+            }
+            None => {
+                Err(self
+                    .fatal_error(TeXErrorKind::RunawayArgument(name.clone())))
+            }
+        }
+    }
+
+    // Parses a parameter list and replacement list into a macro object. The
+    // name of the macro being defined is used when reporting errors.
+    pub fn parse_macro_definition(&mut self, name: &Token) -> TeXResult<Macro> {
+        self.parse_macro_definition_with_expansion(name, false)
+    }
+
+    // Like parse_macro_definition(), but for \edef, where the replacement
+    // text is expanded as it is read. Tokens marked by \noexpand come through
+    // lex_expanded_token() unexpanded, so they're kept as they are.
+    pub fn parse_expanded_macro_definition(
+        &mut self,
+        name: &Token,
+    ) -> TeXResult<Macro> {
+        self.parse_macro_definition_with_expansion(name, true)
+    }
+
+    fn parse_macro_definition_with_expansion(
+        &mut self,
+        name: &Token,
+        expand: bool,
+    ) -> TeXResult<Macro> {
         let mut parameter_list: Vec<MacroListElem> = Vec::new();
+        let mut num_parameters = 0;
 
         // When the last character of the parameter list (right before the {)
         // is a #, (e.g. `\def\a#{}`) then the following { is appended to the
@@ -46,42 +127,47 @@ impl<'a> Parser<'a> {
         let mut maybe_final_tok: Option<Token> = None;
 
         loop {
-            if let Some(token) = self.lex_unexpanded_token() {
-                match token {
-                    // We've found the beginning of the replacement list
-                    Token::Char(_, Category::BeginGroup) => break,
-
-                    // We've found a parameter token, check the next token
-                    Token::Char(_, Category::Parameter) => {
-                        match self.lex_unexpanded_token() {
-                            // If it's a {, then we're in the special case
-                            // mentioned above. Store the token in
-                            // `maybe_final_tok` and break out.
-                            Some(Token::Char(ch, Category::BeginGroup)) => {
-                                parameter_list.push(MacroListElem::Token(
-                                    Token::Char(ch, Category::BeginGroup),
-                                ));
-                                maybe_final_tok =
-                                    Some(Token::Char(ch, Category::BeginGroup));
-                                break;
-                            }
-                            // If it's an Other, it's probably a number
-                            Some(Token::Char(ch, Category::Other)) => {
-                                let index = parse_parameter_number(ch);
-                                parameter_list
-                                    .push(MacroListElem::Parameter(index));
-                            }
-                            // Anything else is an error
-                            _ => panic!("Invalid token found after parameter"),
-                        }
+            let token = self.lex_definition_token(name, false)?;
+            match token {
+                // We've found the beginning of the replacement list
+                Token::Char(_, Category::BeginGroup) => break,
+
+                // We've found a parameter token, check the next token
+                Token::Char(_, Category::Parameter) => {
+                    let next = self.lex_definition_token(name, false)?;
+
+                    // If it's a {, then we're in the special case mentioned
+                    // above. Store the token in `maybe_final_tok` and break
+                    // out.
+                    if let Token::Char(ch, Category::BeginGroup) = next {
+                        parameter_list.push(MacroListElem::Token(Token::Char(
+                            ch,
+                            Category::BeginGroup,
+                        )));
+                        maybe_final_tok =
+                            Some(Token::Char(ch, Category::BeginGroup));
+                        break;
+                    }
+
+                    if num_parameters == MAX_PARAMETERS {
+                        // Like TeX, we just drop any extra parameters.
+                        self.report_error(TeXErrorKind::TooManyParameters)?;
+                        continue;
                     }
 
-                    // We've found some other kind of token, so simply add it
-                    // to the list
-                    _ => parameter_list.push(MacroListElem::Token(token)),
+                    num_parameters += 1;
+                    if parse_parameter_number(&next) != Some(num_parameters) {
+                        // If the parameter isn't the next number, TeX acts
+                        // as if it were.
+                        self.report_error(TeXErrorKind::ParametersOutOfOrder)?;
+                    }
+                    parameter_list
+                        .push(MacroListElem::Parameter(num_parameters));
                 }
-            } else {
-                panic!("EOF found while parsing macro definition");
+
+                // We've found some other kind of token, so simply add it
+                // to the list
+                _ => parameter_list.push(MacroListElem::Token(token)),
             }
         }
 
@@ -92,44 +178,58 @@ impl<'a> Parser<'a> {
         let mut group_level = 0;
 
         loop {
-            if let Some(token) = self.lex_unexpanded_token() {
-                match token {
-                    Token::Char(_, Category::EndGroup) => {
-                        // If we see a group close and we're at the same group
-                        // level as we were at the start, we're done.
-                        if group_level == 0 {
-                            break;
-                        } else {
-                            replacement_list.push(MacroListElem::Token(token));
-                            group_level -= 1;
-                        }
-                    }
-                    Token::Char(_, Category::BeginGroup) => {
+            // The tokens from \the aren't expanded again, and any parameter
+            // characters in them are kept as they are.
+            if expand && self.is_unexpanded_the_head()? {
+                let tokens = self.expand_the()?;
+                replacement_list
+                    .extend(tokens.into_iter().map(MacroListElem::Token));
+                continue;
+            }
+
+            let token = self.lex_definition_token(name, expand)?;
+            match token {
+                Token::Char(_, Category::EndGroup) => {
+                    // If we see a group close and we're at the same group
+                    // level as we were at the start, we're done.
+                    if group_level == 0 {
+                        break;
+                    } else {
                         replacement_list.push(MacroListElem::Token(token));
-                        group_level += 1;
+                        group_level -= 1;
                     }
-                    Token::Char(_, Category::Parameter) => {
-                        match self.lex_unexpanded_token() {
-                            // If we see a parameter token right after another
-                            // parameter token, we insert the second token into
-                            // our list.
-                            Some(Token::Char(ch, Category::Parameter)) => {
-                                replacement_list.push(MacroListElem::Token(
-                                    Token::Char(ch, Category::Parameter),
-                                ));
-                            }
-                            Some(Token::Char(ch, Category::Other)) => {
-                                let index = parse_parameter_number(ch);
-                                replacement_list
-                                    .push(MacroListElem::Parameter(index));
-                            }
-                            _ => panic!("Invalid token found after parameter"),
+                }
+                Token::Char(_, Category::BeginGroup) => {
+                    replacement_list.push(MacroListElem::Token(token));
+                    group_level += 1;
+                }
+                Token::Char(_, Category::Parameter) => {
+                    let next = self.lex_definition_token(name, false)?;
+                    match (&next, parse_parameter_number(&next)) {
+                        // If we see a parameter token right after another
+                        // parameter token, we insert the second token into
+                        // our list.
+                        (Token::Char(_, Category::Parameter), _) => {
+                            replacement_list.push(MacroListElem::Token(next));
+                        }
+                        (_, Some(index)) if index <= num_parameters => {
+                            replacement_list
+                                .push(MacroListElem::Parameter(index));
+                        }
+                        _ => {
+                            // TeX keeps the parameter token as a normal token
+                            // and reads the token after it again.
+                            self.report_error(
+                                TeXErrorKind::IllegalParameterNumber(
+                                    name.clone(),
+                                ),
+                            )?;
+                            replacement_list.push(MacroListElem::Token(token));
+                            self.add_upcoming_token(next);
                         }
                     }
-                    _ => replacement_list.push(MacroListElem::Token(token)),
                 }
-            } else {
-                panic!("EOF found parsing macro definition");
+                _ => replacement_list.push(MacroListElem::Token(token)),
             }
         }
 
@@ -139,20 +239,28 @@ impl<'a> Parser<'a> {
             replacement_list.push(MacroListElem::Token(token));
         }
 
-        Macro::new(parameter_list, replacement_list)
+        Ok(Macro::new(parameter_list, replacement_list))
     }
 
     // This parses a list of tokens that is delimited group tokens and has a
     // balanced number of begin and end tokens. It returns the list of tokens
-    // and the final ending group token.
-    fn parse_balanced_text(&mut self) -> (Vec<Token>, Token) {
+    // and the final ending group token, or None if a \par was found and
+    // `long` isn't set.
+    fn parse_balanced_text(
+        &mut self,
+        name: &Token,
+        long: bool,
+    ) -> TeXResult<Option<(Vec<TokenWithOrigin>, TokenWithOrigin)>> {
         let mut result = Vec::new();
         // Keep track of the number of { and } tokens we've seen, with the
         // grouping increasing for { and decreasing for }.
         let mut group_level = 0;
         loop {
-            let token = self.lex_unexpanded_token().unwrap();
-            match token {
+            let token = match self.lex_argument_token(name, long)? {
+                Some(token) => token,
+                None => return Ok(None),
+            };
+            match token.0 {
                 Token::Char(_, Category::BeginGroup) => {
                     group_level += 1;
                     result.push(token);
@@ -162,7 +270,7 @@ impl<'a> Parser<'a> {
                         // If we see an EndGroup token and we're at the
                         // outermost group , we're done! We return here so we
                         // have access to the final token.
-                        return (result, token);
+                        return Ok(Some((result, token)));
                     } else {
                         group_level -= 1;
                         result.push(token);
@@ -173,18 +281,103 @@ impl<'a> Parser<'a> {
         }
     }
 
+    // Parses the { at the start of a general text, skipping over any filler
+    // before it.
+    fn parse_general_text_left_brace(&mut self) -> TeXResult<()> {
+        self.parse_filler_expanded()?;
+        match self.lex_expanded_token()? {
+            Some(Token::Char(_, Category::BeginGroup)) => (),
+            rest => {
+                // TeX acts as if the { was there if it's missing.
+                self.report_error(TeXErrorKind::MissingLeftBrace)?;
+                if let Some(token) = rest {
+                    self.add_upcoming_token(token);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Parses the braced text that follows primitives like \uppercase,
+    /// without expanding anything, and returns the tokens between the braces.
+    /// The primitive is used when reporting errors.
+    pub fn parse_unexpanded_general_text(
+        &mut self,
+        name: &Token,
+    ) -> TeXResult<Vec<Token>> {
+        self.parse_general_text_left_brace()?;
+        // \par is allowed in general text, so this always finds the end.
+        match self.parse_balanced_text(name, true)? {
+            Some((tokens, _)) => {
+                Ok(tokens.into_iter().map(|(token, _)| token).collect())
+            }
+            None => unreachable!(),
+        }
+    }
+
+    /// Parses the braced text that follows primitives like \message,
+    /// expanding macros as it goes, and returns the tokens between the braces.
+    /// The primitive is used when reporting errors.
+    pub fn parse_expanded_general_text(
+        &mut self,
+        name: &Token,
+    ) -> TeXResult<Vec<Token>> {
+        self.parse_general_text_left_brace()?;
+
+        let mut result = Vec::new();
+        let mut group_level = 0;
+        loop {
+            // Like in \edef, the tokens from \the aren't expanded again.
+            if self.is_unexpanded_the_head()? {
+                result.extend(self.expand_the()?);
+                continue;
+            }
+
+            let token = match self.lex_expanded_token()? {
+                Some(token) => token,
+                None => {
+                    return Err(self
+                        .fatal_error(TeXErrorKind::RunawayText(name.clone())))
+                }
+            };
+            match token {
+                Token::Char(_, Category::BeginGroup) => group_level += 1,
+                Token::Char(_, Category::EndGroup) => {
+                    if group_level == 0 {
+                        return Ok(result);
+                    }
+                    group_level -= 1;
+                }
+                _ => (),
+            }
+            result.push(token);
+        }
+    }
+
     // While we're parsing tokens for macro parameters, we often want to get
     // either a single token or, if the first token is a {, parse an entire
     // balanced group. This function handles that and returns all the
-    // information about what was parsed in an enm.
-    fn parse_single_token_or_balanced_text(&mut self) -> SingleTokenGroup {
-        let token = self.lex_unexpanded_token().unwrap();
-        match token {
+    // information about what was parsed in an enm. Like
+    // lex_argument_token(), this returns None when a \par ends the argument.
+    fn parse_single_token_or_balanced_text(
+        &mut self,
+        name: &Token,
+        long: bool,
+    ) -> TeXResult<Option<SingleTokenGroup>> {
+        let token = match self.lex_argument_token(name, long)? {
+            Some(token) => token,
+            None => return Ok(None),
+        };
+        match token.0 {
             Token::Char(_, Category::BeginGroup) => {
-                let (inner, close) = self.parse_balanced_text();
-                SingleTokenGroup::BalancedGroup(token, inner, close)
+                match self.parse_balanced_text(name, long)? {
+                    Some((inner, close)) => Ok(Some(
+                        SingleTokenGroup::BalancedGroup(token, inner, close),
+                    )),
+                    None => Ok(None),
+                }
             }
-            _ => SingleTokenGroup::SingleToken(token),
+            _ => Ok(Some(SingleTokenGroup::SingleToken(token))),
         }
     }
 
@@ -193,10 +386,17 @@ impl<'a> Parser<'a> {
     //  * with a single token, we just get that token in a list
     //  * with a balanced group, we get all the tokens inside of the {} but not
     //    the {} themselves
-    fn parse_single_token_or_balanced_text_unwrapped(&mut self) -> Vec<Token> {
-        match self.parse_single_token_or_balanced_text() {
-            SingleTokenGroup::SingleToken(token) => vec![token],
-            SingleTokenGroup::BalancedGroup(_, inner, _) => inner,
+    fn parse_single_token_or_balanced_text_unwrapped(
+        &mut self,
+        name: &Token,
+        long: bool,
+    ) -> TeXResult<Option<Vec<TokenWithOrigin>>> {
+        match self.parse_single_token_or_balanced_text(name, long)? {
+            Some(SingleTokenGroup::SingleToken(token)) => Ok(Some(vec![token])),
+            Some(SingleTokenGroup::BalancedGroup(_, inner, _)) => {
+                Ok(Some(inner))
+            }
+            None => Ok(None),
         }
     }
 
@@ -206,16 +406,18 @@ impl<'a> Parser<'a> {
     // tokens that were parsed before then.
     fn parse_delimited_tokens(
         &mut self,
+        name: &Token,
+        long: bool,
         delimiters: &[MacroListElem],
-    ) -> Vec<Token> {
-        let mut result_tokens: Vec<Token> = Vec::new();
+    ) -> TeXResult<Option<Vec<TokenWithOrigin>>> {
+        let mut result_tokens: Vec<TokenWithOrigin> = Vec::new();
 
         // When we encounter tokens that match some of the delimiters, we need
         // to hold on to them in case later tokens don't match the following
         // delimiters. In that case, we push these buffered tokens onto the
         // result and start looking at the beginning of the delimiters again.
         // This is where we store those buffered tokens.
-        let mut delimiting_tokens_buffer: Vec<Token> = Vec::new();
+        let mut delimiting_tokens_buffer: Vec<TokenWithOrigin> = Vec::new();
 
         // This index keeps track of which delimiter we are currently looking
         // at.
@@ -236,11 +438,14 @@ impl<'a> Parser<'a> {
                 // If the token we're looking for is the opening brace of a
                 // group, we don't want to parse an entire balanced group, we
                 // just want to check if the immediate next token is a {.
-                let check_token = self.lex_unexpanded_token().unwrap();
+                let check_token = match self.lex_argument_token(name, long)? {
+                    Some(token) => token,
+                    None => return Ok(None),
+                };
 
                 // TODO(xymostech): This is a duplicate of the if statement
                 // down below. Figure out a way to deduplicate this.
-                if check_token == *expected_token {
+                if check_token.0 == *expected_token {
                     delimiter_index += 1;
                     delimiting_tokens_buffer.push(check_token);
                 } else {
@@ -249,9 +454,10 @@ impl<'a> Parser<'a> {
                     result_tokens.push(check_token);
                 }
             } else {
-                match self.parse_single_token_or_balanced_text() {
-                    SingleTokenGroup::SingleToken(check_token) => {
-                        if check_token == *expected_token {
+                match self.parse_single_token_or_balanced_text(name, long)? {
+                    None => return Ok(None),
+                    Some(SingleTokenGroup::SingleToken(check_token)) => {
+                        if check_token.0 == *expected_token {
                             // If we found a single token and it matches the
                             // delimiter, continue looking at the next delimiter,
                             // but store the token we found in our buffer just in
@@ -266,7 +472,11 @@ impl<'a> Parser<'a> {
                             result_tokens.push(check_token);
                         }
                     }
-                    SingleTokenGroup::BalancedGroup(open, mut inner, close) => {
+                    Some(SingleTokenGroup::BalancedGroup(
+                        open,
+                        mut inner,
+                        close,
+                    )) => {
                         // If we found a balanced group, this will
                         delimiter_index = 0;
                         result_tokens.append(&mut delimiting_tokens_buffer);
@@ -277,16 +487,21 @@ impl<'a> Parser<'a> {
                 }
             }
         }
-        result_tokens
+        Ok(Some(result_tokens))
     }
 
     // Parse the arguments to a given macro into a map of parameter number ->
-    // replacement value
+    // replacement value, where each token keeps track of where it came from.
+    // If the use of the macro doesn't match its definition or an argument of
+    // a macro that isn't \long contains \par, this reports an error and
+    // returns None, and the expansion should be abandoned.
     pub fn parse_replacement_map(
         &mut self,
+        name: &Token,
         makro: &Macro,
-    ) -> HashMap<usize, Vec<Token>> {
-        let mut replacement_map: HashMap<usize, Vec<Token>> = HashMap::new();
+    ) -> TeXResult<Option<HashMap<usize, Vec<TokenWithOrigin>>>> {
+        let mut replacement_map: HashMap<usize, Vec<TokenWithOrigin>> =
+            HashMap::new();
 
         // We manually iterate through the replacement_list because when we
         // parse a delimited parameter, we advance through all of the
@@ -309,10 +524,15 @@ impl<'a> Parser<'a> {
                     let toks = if is_delimited {
                         let delimiter_last_index =
                             get_next_non_token_index(makro, index);
-                        let delimited_toks = self.parse_delimited_tokens(
+                        let delimited_toks = match self.parse_delimited_tokens(
+                            name,
+                            makro.is_long(),
                             &makro.parameter_list
                                 [index + 1..delimiter_last_index],
-                        );
+                        )? {
+                            Some(toks) => toks,
+                            None => return Ok(None),
+                        };
 
                         // The delimiters following the parameter are parsed in
                         // parse_delimited_tokens, so we skip parsing them here.
@@ -325,33 +545,50 @@ impl<'a> Parser<'a> {
                         // undelimited!) we skip spaces before parsing the
                         // actual token/balanced group that match the
                         // parameter.
-                        self.parse_optional_spaces_unexpanded();
-                        self.parse_single_token_or_balanced_text_unwrapped()
+                        self.parse_optional_spaces_unexpanded()?;
+                        match self
+                            .parse_single_token_or_balanced_text_unwrapped(
+                                name,
+                                makro.is_long(),
+                            )? {
+                            Some(toks) => toks,
+                            None => return Ok(None),
+                        }
                     };
                     replacement_map.insert(*parameter_index, toks);
                 }
                 MacroListElem::Token(search_token) => {
-                    let found_token = self.lex_unexpanded_token().unwrap();
-                    if found_token != *search_token {
-                        panic!(
-                            "Non-matching token found looking for parameter text. \
-                             Found {:?}, expected {:?}",
-                            found_token, search_token
-                        );
+                    let found_token =
+                        match self.lex_argument_token(name, makro.is_long())? {
+                            Some(token) => token,
+                            None => return Ok(None),
+                        };
+                    if found_token.0 != *search_token {
+                        self.report_error(TeXErrorKind::MacroUseMismatch(
+                            name.clone(),
+                        ))?;
+                        return Ok(None);
                     }
                     index += 1;
                 }
             }
         }
 
-        replacement_map
+        Ok(Some(replacement_map))
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use crate::token::Token;
+
+    fn a() -> Token {
+        Token::ControlSequence("a".to_string())
+    }
+
     mod macro_definition {
         use super::super::*;
+        use super::a;
 
         use crate::testing::with_parser;
 
@@ -359,28 +596,34 @@ mod tests {
             with_parser(lines, |parser| {
                 assert_eq!(
                     Some(Token::ControlSequence("def".to_string())),
-                    parser.lex_unexpanded_token()
+                    parser.lex_unexpanded_token().unwrap()
                 );
                 assert_eq!(
                     Some(Token::ControlSequence("a".to_string())),
-                    parser.lex_unexpanded_token()
+                    parser.lex_unexpanded_token().unwrap()
+                );
+                assert_eq!(
+                    expected_macro,
+                    parser.parse_macro_definition(&a()).unwrap()
                 );
-                assert_eq!(expected_macro, parser.parse_macro_definition());
             });
         }
 
-        fn try_parsing_macro(lines: &[&str]) {
+        // Tries to parse a macro definition which contains errors, and
+        // returns the kinds of the errors that were reported.
+        fn try_parsing_macro(lines: &[&str]) -> Vec<TeXErrorKind> {
+            let mut kinds = Vec::new();
             with_parser(lines, |parser| {
                 assert_eq!(
                     Some(Token::ControlSequence("def".to_string())),
-                    parser.lex_unexpanded_token()
+                    parser.lex_unexpanded_token().unwrap()
                 );
-                assert_eq!(
-                    Some(Token::ControlSequence("a".to_string())),
-                    parser.lex_unexpanded_token()
-                );
-                parser.parse_macro_definition();
+                assert_eq!(Some(a()), parser.lex_unexpanded_token().unwrap());
+                let _ = parser.parse_macro_definition(&a());
+                kinds =
+                    parser.take_errors().into_iter().map(|e| e.kind).collect();
             });
+            kinds
         }
 
         #[test]
@@ -475,29 +718,102 @@ mod tests {
         }
 
         #[test]
-        #[should_panic(expected = "EOF found")]
         fn it_fails_on_eof_in_parameter_list() {
-            try_parsing_macro(&["\\def\\a a%"]);
+            assert_eq!(
+                try_parsing_macro(&["\\def\\a a%"]),
+                vec![TeXErrorKind::RunawayDefinition(a())]
+            );
         }
 
         #[test]
-        #[should_panic(expected = "EOF found")]
         fn it_fails_on_eof_in_replacement_list() {
-            try_parsing_macro(&["\\def\\a a{%"]);
+            assert_eq!(
+                try_parsing_macro(&["\\def\\a a{%"]),
+                vec![TeXErrorKind::RunawayDefinition(a())]
+            );
         }
 
         #[test]
-        #[should_panic(expected = "Invalid number after parameter")]
         fn it_fails_on_non_number_in_parameter() {
-            try_parsing_macro(&["\\def\\a #.{}%"]);
+            assert_eq!(
+                try_parsing_macro(&["\\def\\a #.{}%"]),
+                vec![TeXErrorKind::ParametersOutOfOrder]
+            );
+        }
+
+        #[test]
+        fn it_renumbers_out_of_order_parameters() {
+            with_parser(&["\\def\\a #2{#1}%"], |parser| {
+                parser.lex_unexpanded_token().unwrap();
+                parser.lex_unexpanded_token().unwrap();
+                assert_eq!(
+                    parser.parse_macro_definition(&a()).unwrap(),
+                    Macro::new(
+                        vec![MacroListElem::Parameter(1)],
+                        vec![MacroListElem::Parameter(1)],
+                    )
+                );
+                assert_eq!(
+                    parser.take_errors()[0].kind,
+                    TeXErrorKind::ParametersOutOfOrder
+                );
+            });
+        }
+
+        #[test]
+        fn it_keeps_parameter_tokens_with_illegal_numbers() {
+            with_parser(&["\\def\\a #1{#2}%"], |parser| {
+                parser.lex_unexpanded_token().unwrap();
+                parser.lex_unexpanded_token().unwrap();
+                assert_eq!(
+                    parser.parse_macro_definition(&a()).unwrap(),
+                    Macro::new(
+                        vec![MacroListElem::Parameter(1)],
+                        vec![
+                            MacroListElem::Token(Token::Char(
+                                '#',
+                                Category::Parameter
+                            )),
+                            MacroListElem::Token(Token::Char(
+                                '2',
+                                Category::Other
+                            )),
+                        ],
+                    )
+                );
+                assert_eq!(
+                    parser.take_errors()[0].kind,
+                    TeXErrorKind::IllegalParameterNumber(a())
+                );
+            });
         }
     }
 
     mod replacement_tokens {
         use super::super::*;
+        use super::a;
 
         use crate::testing::with_parser;
 
+        // Most of these tests only care about which tokens are in the
+        // arguments, and not where they came from.
+        fn parse_replacement_tokens(
+            parser: &mut Parser,
+            makro: &Macro,
+        ) -> Option<HashMap<usize, Vec<Token>>> {
+            let replacement_map =
+                parser.parse_replacement_map(&a(), makro).unwrap()?;
+            Some(
+                replacement_map
+                    .into_iter()
+                    .map(|(index, tokens)| {
+                        let tokens = tokens.into_iter().map(|(token, _)| token);
+                        (index, tokens.collect())
+                    })
+                    .collect(),
+            )
+        }
+
         fn assert_parses_to_replacements(
             lines: &[&str],
             macro_parameter_list: Vec<MacroListElem>,
@@ -505,31 +821,44 @@ mod tests {
         ) {
             with_parser(lines, |parser| {
                 assert_eq!(
-                    parser.lex_unexpanded_token(),
+                    parser.lex_unexpanded_token().unwrap(),
                     Some(Token::ControlSequence("a".to_string()))
                 );
 
                 let makro = Macro::new(macro_parameter_list, Vec::new());
                 let expected_replacement_map: HashMap<usize, Vec<Token>> =
                     expected_replacements.into_iter().collect();
-                let replacement_map = parser.parse_replacement_map(&makro);
-                assert_eq!(expected_replacement_map, replacement_map);
+                let replacement_map = parse_replacement_tokens(parser, &makro);
+                assert_eq!(Some(expected_replacement_map), replacement_map);
             });
         }
 
+        // Parses the arguments to a macro and returns whether they matched
+        // the parameter list.
         fn try_parsing_replacements(
             lines: &[&str],
             macro_parameter_list: Vec<MacroListElem>,
-        ) {
+        ) -> bool {
+            let mut matched = false;
             with_parser(lines, |parser| {
                 assert_eq!(
-                    parser.lex_unexpanded_token(),
+                    parser.lex_unexpanded_token().unwrap(),
                     Some(Token::ControlSequence("a".to_string()))
                 );
 
                 let makro = Macro::new(macro_parameter_list, Vec::new());
-                parser.parse_replacement_map(&makro);
+                matched = parser
+                    .parse_replacement_map(&a(), &makro)
+                    .unwrap()
+                    .is_some();
+                if !matched {
+                    assert_eq!(
+                        parser.take_errors()[0].kind,
+                        TeXErrorKind::MacroUseMismatch(a())
+                    );
+                }
             });
+            matched
         }
 
         #[test]
@@ -576,7 +905,7 @@ mod tests {
 
         #[test]
         fn it_succeeds_parsing_when_tokens_match_parameters() {
-            try_parsing_replacements(
+            assert!(try_parsing_replacements(
                 &["\\a x\\y%"],
                 vec![
                     MacroListElem::Token(Token::Char('x', Category::Letter)),
@@ -584,13 +913,12 @@ mod tests {
                         "y".to_string(),
                     )),
                 ],
-            );
+            ));
         }
 
         #[test]
-        #[should_panic(expected = "Non-matching token found")]
         fn it_fails_parsing_when_tokens_dont_match_parameters() {
-            try_parsing_replacements(
+            assert!(!try_parsing_replacements(
                 &["\\a xy%"],
                 vec![
                     MacroListElem::Token(Token::Char('x', Category::Letter)),
@@ -598,7 +926,7 @@ mod tests {
                         "y".to_string(),
                     )),
                 ],
-            );
+            ));
         }
 
         #[test]
@@ -757,7 +1085,7 @@ mod tests {
                 )],
             );
 
-            try_parsing_replacements(
+            assert!(try_parsing_replacements(
                 &["\\a x{%"],
                 vec![
                     MacroListElem::Token(Token::Char('x', Category::Letter)),
@@ -766,7 +1094,65 @@ mod tests {
                         Category::BeginGroup,
                     )),
                 ],
-            );
+            ));
+        }
+
+        #[test]
+        fn it_stops_at_par_in_arguments_of_non_long_macros() {
+            with_parser(&[r"\a{x\par}y%"], |parser| {
+                parser.lex_unexpanded_token().unwrap();
+                let makro =
+                    Macro::new(vec![MacroListElem::Parameter(1)], vec![]);
+                assert_eq!(
+                    parser.parse_replacement_map(&a(), &makro).unwrap(),
+                    None
+                );
+                assert_eq!(
+                    parser.take_errors()[0].kind,
+                    TeXErrorKind::ParagraphEnded(a())
+                );
+                // The \par is left to be read again, and whatever came after
+                // it is read normally.
+                assert_eq!(
+                    parser.lex_unexpanded_token().unwrap(),
+                    Some(Token::ControlSequence("par".to_string()))
+                );
+                assert_eq!(
+                    parser.lex_unexpanded_token().unwrap(),
+                    Some(Token::Char('}', Category::EndGroup))
+                );
+                assert_eq!(
+                    parser.lex_unexpanded_token().unwrap(),
+                    Some(Token::Char('y', Category::Letter))
+                );
+            });
+
+            with_parser(&[r"\a x\par.%"], |parser| {
+                parser.lex_unexpanded_token().unwrap();
+                let makro = Macro::new(
+                    vec![
+                        MacroListElem::Parameter(1),
+                        MacroListElem::Token(Token::Char('.', Category::Other)),
+                    ],
+                    vec![],
+                )
+                .with_prefixes(true, false);
+                assert_eq!(
+                    parse_replacement_tokens(parser, &makro),
+                    Some(
+                        vec![(
+                            1,
+                            vec![
+                                Token::Char('x', Category::Letter),
+                                Token::ControlSequence("par".to_string()),
+                            ]
+                        )]
+                        .into_iter()
+                        .collect()
+                    )
+                );
+                assert!(parser.take_errors().is_empty());
+            });
         }
     }
 }
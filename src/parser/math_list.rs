@@ -3,7 +3,9 @@ use std::collections::HashMap;
 
 use crate::boxes::{HorizontalBox, TeXBox, VerticalBox};
 use crate::category::Category;
-use crate::dimension::{Dimen, FilDimen, FilKind, MuDimen, SpringDimen, Unit};
+use crate::debugger::{PartialList, PartialListContents};
+use crate::dimension::{Dimen, FilDimen, FilKind, SpringDimen, Unit};
+use crate::error::{TeXErrorKind, TeXResult};
 use crate::font::Font;
 use crate::glue::{Glue, MuGlue};
 use crate::list::{HorizontalListElem, VerticalListElem};
@@ -12,7 +14,10 @@ use crate::math_list::{
     AtomKind, GeneralizedFraction, MathAtom, MathDelimiter, MathField,
     MathList, MathListElem, MathStyle, MathSymbol,
 };
+use crate::origin::Provenance;
+use crate::parameter::{DimenParameter, MuGlueParameter};
 use crate::parser::boxes::BoxLayout;
+use crate::parser::group::GroupKind;
 use crate::parser::Parser;
 use crate::token::Token;
 
@@ -200,27 +205,29 @@ enum TranslatedMathListElem {
 }
 
 impl<'a> Parser<'a> {
-    fn is_character_head(&mut self) -> bool {
-        let expanded_token = self.peek_expanded_token();
+    fn is_character_head(&mut self) -> TeXResult<bool> {
+        let expanded_token = self.peek_expanded_token()?;
 
         match self.replace_renamed_token(expanded_token) {
-            Some(Token::Char(_, Category::Letter)) => true,
-            Some(Token::Char(_, Category::Other)) => true,
-            Some(tok) => self.state.is_token_equal_to_prim(&tok, "char"),
-            _ => false,
+            Some(Token::Char(_, Category::Letter)) => Ok(true),
+            Some(Token::Char(_, Category::Other)) => Ok(true),
+            Some(tok) => Ok(self.state.is_token_equal_to_prim(&tok, "char")
+                || self.state.get_chardef(&tok).is_some()),
+            _ => Ok(false),
         }
     }
 
-    fn parse_character_to_math_code(&mut self) -> MathCode {
-        let expanded_token = self.lex_expanded_token();
+    fn parse_character_to_math_code(&mut self) -> TeXResult<MathCode> {
+        let expanded_token = self.lex_expanded_token()?;
         let expanded_renamed_token = self.replace_renamed_token(expanded_token);
 
         let ch: char = match expanded_renamed_token {
             Some(Token::Char(ch, _)) => ch,
             Some(tok) => {
                 if self.state.is_token_equal_to_prim(&tok, "char") {
-                    let char_number = self.parse_8bit_number();
-                    char_number as char
+                    self.parse_char_code()?
+                } else if let Some(ch) = self.state.get_chardef(&tok) {
+                    ch
                 } else {
                     panic!("invalid char token head");
                 }
@@ -228,123 +235,130 @@ impl<'a> Parser<'a> {
             _ => panic!("invalid char token head"),
         };
 
-        self.state.get_math_code(ch)
+        Ok(self.state.get_math_code(ch))
     }
 
-    fn is_math_character_head(&mut self) -> bool {
-        let expanded_token = self.peek_expanded_token();
+    fn is_math_character_head(&mut self) -> TeXResult<bool> {
+        let expanded_token = self.peek_expanded_token()?;
         if let Some(expanded_renamed_token) =
             self.replace_renamed_token(expanded_token)
         {
-            self.state
+            Ok(self
+                .state
                 .get_math_chardef(&expanded_renamed_token)
-                .is_some()
+                .is_some())
         } else {
-            false
+            Ok(false)
         }
     }
 
-    fn parse_math_character_to_math_code(&mut self) -> MathCode {
-        let expanded_token = self.lex_expanded_token();
+    fn parse_math_character_to_math_code(&mut self) -> TeXResult<MathCode> {
+        let expanded_token = self.lex_expanded_token()?;
         let expanded_renamed_token =
             self.replace_renamed_token(expanded_token).unwrap();
 
         if let Some(math_code) =
             self.state.get_math_chardef(&expanded_renamed_token)
         {
-            math_code
+            Ok(math_code)
         } else {
             panic!("Invalid math chardef token: {:?}", expanded_renamed_token);
         }
     }
 
-    fn is_math_symbol_head(&mut self) -> bool {
-        self.is_character_head() || self.is_math_character_head()
+    fn is_math_symbol_head(&mut self) -> TeXResult<bool> {
+        Ok(self.is_character_head()? || self.is_math_character_head()?)
     }
 
-    fn parse_math_symbol(&mut self) -> MathCode {
-        if self.is_character_head() {
+    fn parse_math_symbol(&mut self) -> TeXResult<MathCode> {
+        if self.is_character_head()? {
             self.parse_character_to_math_code()
-        } else if self.is_math_character_head() {
+        } else if self.is_math_character_head()? {
             self.parse_math_character_to_math_code()
         } else {
-            panic!("Unimplemented");
+            // This is only called after is_math_symbol_head().
+            unreachable!("Invalid math symbol head");
         }
     }
 
-    fn parse_math_group(&mut self) -> MathList {
-        let begin_group = self.lex_expanded_token();
-        match begin_group {
+    fn parse_math_group(&mut self) -> TeXResult<MathList> {
+        // If either of the braces around the group are missing, TeX acts as
+        // if they had been there.
+        match self.lex_expanded_token()? {
             Some(Token::Char(_, Category::BeginGroup)) => (),
-            tok => panic!("Invalid start of math group: {:?}", tok),
+            rest => {
+                self.report_error(TeXErrorKind::MissingLeftBrace)?;
+                if let Some(token) = rest {
+                    self.add_upcoming_token(token);
+                }
+            }
         }
 
-        self.state.push_state();
-
-        let math_list = self.parse_math_list();
+        self.push_state(GroupKind::Math);
 
-        self.state.pop_state();
+        let math_list = self.parse_math_list()?;
 
-        let end_group = self.lex_expanded_token();
-        match end_group {
+        match self.lex_expanded_token()? {
             Some(Token::Char(_, Category::EndGroup)) => (),
-            tok => panic!("Math group didn't end with an EndGroup: {:?}", tok),
+            rest => {
+                self.report_error(TeXErrorKind::MissingRightBrace)?;
+                if let Some(token) = rest {
+                    self.add_upcoming_token(token);
+                }
+            }
         }
 
-        math_list
+        self.pop_state();
+
+        Ok(math_list)
     }
 
-    fn parse_math_field(&mut self) -> MathField {
-        self.parse_filler_expanded();
+    fn parse_math_field(&mut self) -> TeXResult<MathField> {
+        self.parse_filler_expanded()?;
 
-        if self.is_math_symbol_head() {
-            let math_code = self.parse_math_symbol();
+        if self.is_math_symbol_head()? {
+            let math_code = self.parse_math_symbol()?;
 
-            MathField::Symbol(MathSymbol::from_math_code(&math_code))
+            Ok(MathField::Symbol(MathSymbol::from_math_code(&math_code)))
         } else {
-            MathField::MathList(self.parse_math_group())
+            Ok(MathField::MathList(self.parse_math_group()?))
         }
     }
 
-    fn is_math_superscript_head(&mut self) -> bool {
-        let expanded_token = self.peek_expanded_token();
+    fn is_math_superscript_head(&mut self) -> TeXResult<bool> {
+        let expanded_token = self.peek_expanded_token()?;
         match self.replace_renamed_token(expanded_token) {
-            Some(Token::Char(_, Category::Superscript)) => true,
-            _ => false,
+            Some(Token::Char(_, Category::Superscript)) => Ok(true),
+            _ => Ok(false),
         }
     }
 
-    fn parse_math_superscript(&mut self, atom: MathAtom) -> MathAtom {
-        self.lex_expanded_token();
-
-        if atom.has_superscript() {
-            panic!("Double superscript");
-        }
+    fn parse_math_superscript(
+        &mut self,
+        atom: MathAtom,
+    ) -> TeXResult<MathAtom> {
+        self.lex_expanded_token()?;
 
-        let superscript = self.parse_math_field();
-        atom.with_superscript(superscript)
+        let superscript = self.parse_math_field()?;
+        Ok(atom.with_superscript(superscript))
     }
 
-    fn is_math_subscript_head(&mut self) -> bool {
-        let expanded_token = self.peek_expanded_token();
+    fn is_math_subscript_head(&mut self) -> TeXResult<bool> {
+        let expanded_token = self.peek_expanded_token()?;
         match self.replace_renamed_token(expanded_token) {
-            Some(Token::Char(_, Category::Subscript)) => true,
-            _ => false,
+            Some(Token::Char(_, Category::Subscript)) => Ok(true),
+            _ => Ok(false),
         }
     }
 
-    fn parse_math_subscript(&mut self, atom: MathAtom) -> MathAtom {
-        self.lex_expanded_token();
+    fn parse_math_subscript(&mut self, atom: MathAtom) -> TeXResult<MathAtom> {
+        self.lex_expanded_token()?;
 
-        if atom.has_subscript() {
-            panic!("Double subscript");
-        }
-
-        let subscript = self.parse_math_field();
-        atom.with_subscript(subscript)
+        let subscript = self.parse_math_field()?;
+        Ok(atom.with_subscript(subscript))
     }
 
-    fn is_style_change_head(&mut self) -> bool {
+    fn is_style_change_head(&mut self) -> TeXResult<bool> {
         self.is_next_expanded_token_in_set_of_primitives(&[
             "displaystyle",
             "textstyle",
@@ -353,23 +367,23 @@ impl<'a> Parser<'a> {
         ])
     }
 
-    fn parse_style_change(&mut self) -> MathStyle {
-        let tok = self.lex_expanded_token().unwrap();
+    fn parse_style_change(&mut self) -> TeXResult<MathStyle> {
+        let tok = self.lex_expanded_token()?.unwrap();
 
         if self.state.is_token_equal_to_prim(&tok, "displaystyle") {
-            MathStyle::DisplayStyle
+            Ok(MathStyle::DisplayStyle)
         } else if self.state.is_token_equal_to_prim(&tok, "textstyle") {
-            MathStyle::TextStyle
+            Ok(MathStyle::TextStyle)
         } else if self.state.is_token_equal_to_prim(&tok, "scriptstyle") {
-            MathStyle::ScriptStyle
+            Ok(MathStyle::ScriptStyle)
         } else if self.state.is_token_equal_to_prim(&tok, "scriptscriptstyle") {
-            MathStyle::ScriptScriptStyle
+            Ok(MathStyle::ScriptScriptStyle)
         } else {
             panic!("Invalid style change");
         }
     }
 
-    fn is_generalized_fraction_head(&mut self) -> bool {
+    fn is_generalized_fraction_head(&mut self) -> TeXResult<bool> {
         self.is_next_expanded_token_in_set_of_primitives(&[
             "over",
             "atop",
@@ -382,17 +396,21 @@ impl<'a> Parser<'a> {
 
     fn parse_generalized_fraction_params(
         &mut self,
-    ) -> (Option<MathDelimiter>, Option<MathDelimiter>, Dimen) {
-        let tok = self.lex_expanded_token().unwrap();
-
-        if self.state.is_token_equal_to_prim(&tok, "atop") {
-            (None, None, Dimen::zero())
-        } else {
-            panic!("unimplemented");
+    ) -> TeXResult<(Option<MathDelimiter>, Option<MathDelimiter>, Dimen)> {
+        let tok = self.lex_expanded_token()?.unwrap();
+
+        if !self.state.is_token_equal_to_prim(&tok, "atop") {
+            // Until the other kinds of fractions are implemented, we treat
+            // them all like \atop.
+            self.report_error(TeXErrorKind::Unimplemented(format!(
+                "`{}'",
+                tok.to_string().trim_end()
+            )))?;
         }
+        Ok((None, None, Dimen::zero()))
     }
 
-    pub fn parse_math_list(&mut self) -> MathList {
+    pub fn parse_math_list(&mut self) -> TeXResult<MathList> {
         let mut current_list = Vec::new();
 
         // Keep track of whether there's been a generalized fraction operation
@@ -400,19 +418,30 @@ impl<'a> Parser<'a> {
         // list as well as the generalized fraction parameters here.
         let mut list_fraction = None;
 
+        // Any groups past this depth were started inside of this list.
+        let group_base = self.get_group_depth();
+
+        self.push_debug_list(|| PartialList {
+            mode: "math mode",
+            contents: PartialListContents::Math,
+        });
+        self.modes.push("math mode");
         loop {
-            if self.is_math_symbol_head() {
-                let math_code = self.parse_math_symbol();
+            let expanded_token = self.peek_expanded_token()?;
+            self.trace_command("math mode", &expanded_token);
+
+            if self.is_math_symbol_head()? {
+                let math_code = self.parse_math_symbol()?;
 
                 current_list.push(MathListElem::Atom(
                     MathAtom::from_math_code(&math_code),
                 ));
-            } else if self.is_math_superscript_head()
-                || self.is_math_subscript_head()
+            } else if self.is_math_superscript_head()?
+                || self.is_math_subscript_head()?
             {
-                let is_superscript = self.is_math_superscript_head();
+                let is_superscript = self.is_math_superscript_head()?;
 
-                let last_atom = match current_list.pop() {
+                let mut last_atom = match current_list.pop() {
                     Some(MathListElem::Atom(atom)) => atom,
                     Some(other_elem) => {
                         current_list.push(other_elem);
@@ -421,31 +450,65 @@ impl<'a> Parser<'a> {
                     None => MathAtom::empty_ord(),
                 };
 
+                // If the atom already has a superscript or subscript, TeX
+                // reports an error and attaches the new one to a new empty
+                // atom instead.
+                let double_script_error = if is_superscript {
+                    last_atom
+                        .has_superscript()
+                        .then_some(TeXErrorKind::DoubleSuperscript)
+                } else {
+                    last_atom
+                        .has_subscript()
+                        .then_some(TeXErrorKind::DoubleSubscript)
+                };
+                if let Some(kind) = double_script_error {
+                    self.report_error(kind)?;
+                    current_list.push(MathListElem::Atom(last_atom));
+                    last_atom = MathAtom::empty_ord();
+                }
+
                 current_list.push(MathListElem::Atom(if is_superscript {
-                    self.parse_math_superscript(last_atom)
+                    self.parse_math_superscript(last_atom)?
                 } else {
-                    self.parse_math_subscript(last_atom)
+                    self.parse_math_subscript(last_atom)?
                 }));
-            } else if self.is_assignment_head() {
-                self.parse_assignment(None);
-            } else if self.is_style_change_head() {
-                let style_change = self.parse_style_change();
+            } else if self.is_assignment_head()? {
+                self.parse_assignment(None)?;
+            } else if self.is_show_head()? {
+                self.parse_show()?;
+            } else if self.is_message_head()? {
+                self.parse_message()?;
+            } else if self.is_after_group_head()? {
+                self.parse_after_group()?;
+            } else if self.is_after_assignment_head()? {
+                self.parse_after_assignment()?;
+            } else if self.is_semi_simple_group_head()? {
+                self.parse_semi_simple_group()?;
+            } else if self.is_case_change_head()? {
+                self.parse_case_change()?;
+            } else if self.is_relax_head()? {
+                self.lex_expanded_token()?;
+            } else if self.is_style_change_head()? {
+                let style_change = self.parse_style_change()?;
                 current_list.push(MathListElem::StyleChange(style_change));
-            } else if self.is_box_head() {
-                if let Some(tex_box) = self.parse_box() {
+            } else if self.is_box_head()? {
+                if let Some(tex_box) = self.parse_box()? {
                     current_list
                         .push(MathListElem::Atom(MathAtom::from_box(tex_box)));
                 }
-            } else if self.is_generalized_fraction_head() {
-                if list_fraction.is_some() {
-                    panic!("Ambiguous generalized fraction");
-                }
-
+            } else if self.is_generalized_fraction_head()? {
                 let (
                     gen_frac_left_delim,
                     gen_frac_right_delim,
                     gen_frac_height,
-                ) = self.parse_generalized_fraction_params();
+                ) = self.parse_generalized_fraction_params()?;
+
+                if list_fraction.is_some() {
+                    // TeX ignores any fractions after the first one.
+                    self.report_error(TeXErrorKind::AmbiguousFraction)?;
+                    continue;
+                }
 
                 list_fraction = Some(GeneralizedFraction {
                     left_delim: gen_frac_left_delim,
@@ -456,29 +519,43 @@ impl<'a> Parser<'a> {
                 });
                 current_list = vec![];
             } else {
-                match self.peek_expanded_token() {
+                match self.peek_expanded_token()? {
                     Some(Token::Char(_, Category::BeginGroup)) => {
-                        let inner_list = self.parse_math_group();
+                        let inner_list = self.parse_math_group()?;
                         current_list.push(MathListElem::Atom(
                             MathAtom::from_math_list(inner_list),
                         ));
                     }
                     Some(Token::Char(_, Category::Space)) => {
-                        self.lex_expanded_token();
+                        self.lex_expanded_token()?;
+                    }
+                    Some(Token::Char(_, Category::EndGroup)) => {
+                        if self.does_end_group_end_list(group_base) {
+                            break;
+                        }
+                        self.parse_end_group()?;
+                    }
+                    Some(Token::Char(_, Category::MathShift)) => {
+                        // Groups started with \begingroup need to end before
+                        // math mode does.
+                        if self.get_group_depth() <= group_base {
+                            break;
+                        }
+                        self.insert_group_end()?;
                     }
-                    Some(Token::Char(_, Category::EndGroup)) => break,
-                    Some(Token::Char(_, Category::MathShift)) => break,
                     None => break,
-                    _ => panic!("unimplemented"),
+                    _ => self.skip_unexpected_token("math mode")?,
                 }
             }
         }
+        self.modes.pop();
+        self.pop_debug_list();
 
         match list_fraction {
-            None => current_list,
+            None => Ok(current_list),
             Some(mut fraction) => {
                 fraction.denominator = current_list;
-                vec![MathListElem::GeneralizedFraction(fraction)]
+                Ok(vec![MathListElem::GeneralizedFraction(fraction)])
             }
         }
     }
@@ -489,23 +566,14 @@ impl<'a> Parser<'a> {
         right_type: &AtomKind,
         style: &MathStyle,
     ) -> Option<MuGlue> {
-        // TODO: These should come from the state variables \thinmuskip,
-        // \mediummuskip, and \thickmuskip.
-        let thinskip = MuGlue {
-            space: MuDimen::new(3.0),
-            stretch: MuDimen::zero(),
-            shrink: MuDimen::zero(),
-        };
-        let mediumskip = MuGlue {
-            space: MuDimen::new(4.0),
-            stretch: MuDimen::new(2.0),
-            shrink: MuDimen::new(4.0),
-        };
-        let thickskip = MuGlue {
-            space: MuDimen::new(5.0),
-            stretch: MuDimen::new(5.0),
-            shrink: MuDimen::zero(),
-        };
+        let thinskip = self
+            .state
+            .get_mu_glue_parameter(MuGlueParameter::ThinMuSkip);
+        let mediumskip =
+            self.state.get_mu_glue_parameter(MuGlueParameter::MedMuSkip);
+        let thickskip = self
+            .state
+            .get_mu_glue_parameter(MuGlueParameter::ThickMuSkip);
 
         if let Some(space) = INTER_ATOM_SPACING.get(&(*left_type, *right_type))
         {
@@ -580,6 +648,7 @@ impl<'a> Parser<'a> {
                 let char_elem = HorizontalListElem::Char {
                     chr: symbol.position_number as char,
                     font: font.clone(),
+                    origin: Provenance::none(),
                 };
 
                 let hbox = self.add_to_natural_layout_horizontal_box(
@@ -634,6 +703,7 @@ impl<'a> Parser<'a> {
                 let elem = HorizontalListElem::Char {
                     chr: position_number as char,
                     font: font.clone(),
+                    origin: Provenance::none(),
                 };
 
                 let boxed_elem = self.add_to_natural_layout_horizontal_box(
@@ -706,6 +776,7 @@ impl<'a> Parser<'a> {
                 let char_elem = HorizontalListElem::Char {
                     chr: symbol.position_number as char,
                     font: font.clone(),
+                    origin: Provenance::none(),
                 };
 
                 TranslatedNucleus {
@@ -785,8 +856,8 @@ impl<'a> Parser<'a> {
             translated_nucleus.effective_depth + sub_drop
         };
 
-        // TODO(xymostech): Pull this from \scriptspace
-        let scriptspace = Dimen::from_unit(0.5, Unit::Point);
+        let scriptspace =
+            self.state.get_dimen_parameter(DimenParameter::ScriptSpace);
 
         let sub_sup_translation = match (superscript, subscript) {
             (Some(superscript), None) => {
@@ -1002,12 +1073,16 @@ impl<'a> Parser<'a> {
         match maybe_delim {
             None => {
                 let mut empty_hbox = HorizontalBox::empty();
-                // TODO(xymostech): This should come from \nulldelimiterspace
-                empty_hbox.width = Dimen::from_unit(1.2, Unit::Point);
+                empty_hbox.width = self
+                    .state
+                    .get_dimen_parameter(DimenParameter::NullDelimiterSpace);
                 TeXBox::HorizontalBox(empty_hbox)
             }
             Some(_delim) => {
-                panic!("unimplemented");
+                // parse_generalized_fraction_params() reports the fractions
+                // with delimiters as unimplemented and leaves them out, so
+                // the math lists we build never have any.
+                unreachable!("Fraction delimiters aren't implemented");
             }
         }
     }
@@ -1237,7 +1312,9 @@ impl<'a> Parser<'a> {
                             shift: Dimen::zero(),
                         }
                     } else {
-                        panic!("unimplemented");
+                        // Like with delimiters, parse_generalized_fraction_params()
+                        // only builds fractions without a bar.
+                        unreachable!("Fraction bars aren't implemented");
                     };
 
                     let min_delim_size = self
@@ -1295,7 +1372,9 @@ impl<'a> Parser<'a> {
                         .push(TranslatedMathListElem::StyleChange(new_style));
                 }
                 _ => {
-                    panic!("unimplemented math list elem: {:?}", elem);
+                    // The parser doesn't build boundaries or four-way
+                    // choices yet.
+                    unreachable!("Unimplemented math list elem: {:?}", elem);
                 }
             }
         }
@@ -1350,24 +1429,35 @@ impl<'a> Parser<'a> {
 mod tests {
     use super::*;
     use crate::boxes::{GlueSetRatio, GlueSetRatioKind};
-    use crate::testing::with_parser;
+    use crate::testing::{with_parser, WithoutOrigins};
 
     fn assert_math_list_converts_to_horizontal_list(
         math_list_lines: &[&str],
         horizontal_list_lines: &[&str],
     ) {
-        with_parser(math_list_lines, |math_parser| {
+        // These all start out as zero, so we use the values from plain TeX
+        // that the expected horizontal lists were written with.
+        let mut lines = vec![
+            r"\thinmuskip=3mu \medmuskip=4mu plus 2mu minus 4mu%",
+            r"\thickmuskip=5mu plus 5mu%",
+            r"\scriptspace=0.5pt \nulldelimiterspace=1.2pt%",
+        ];
+        lines.extend_from_slice(math_list_lines);
+
+        with_parser(&lines, |math_parser| {
             with_parser(horizontal_list_lines, |hlist_parser| {
-                let math_list = math_parser.parse_math_list();
+                let math_list = math_parser.parse_math_list().unwrap();
                 let horizontal_list =
-                    hlist_parser.parse_horizontal_list(false, false);
+                    hlist_parser.parse_horizontal_list(false, false).unwrap();
 
                 assert_eq!(
-                    math_parser.convert_math_list_to_horizontal_list(
-                        math_list,
-                        MathStyle::TextStyle
-                    ),
-                    horizontal_list
+                    math_parser
+                        .convert_math_list_to_horizontal_list(
+                            math_list,
+                            MathStyle::TextStyle
+                        )
+                        .without_origins(),
+                    horizontal_list.without_origins()
                 );
             });
         });
@@ -1377,15 +1467,15 @@ mod tests {
     fn it_parses_math_symbols() {
         with_parser(&["a2*%"], |parser| {
             assert_eq!(
-                parser.parse_math_symbol(),
+                parser.parse_math_symbol().unwrap(),
                 MathCode::from_number(0x7161)
             );
             assert_eq!(
-                parser.parse_math_symbol(),
+                parser.parse_math_symbol().unwrap(),
                 MathCode::from_number(0x7032)
             );
             assert_eq!(
-                parser.parse_math_symbol(),
+                parser.parse_math_symbol().unwrap(),
                 MathCode::from_number(0x002a)
             );
         });
@@ -1394,10 +1484,10 @@ mod tests {
     #[test]
     fn it_parses_math_symbols_from_chardefs() {
         with_parser(&[r"\let\x=z%", r"\x%"], |parser| {
-            parser.parse_assignment(None);
+            parser.parse_assignment(None).unwrap();
 
             assert_eq!(
-                parser.parse_math_symbol(),
+                parser.parse_math_symbol().unwrap(),
                 MathCode::from_number(0x717a)
             );
         });
@@ -1407,7 +1497,7 @@ mod tests {
     fn it_parses_basic_atoms_in_math_lists() {
         with_parser(&[r"a*%"], |parser| {
             assert_eq!(
-                parser.parse_math_list(),
+                parser.parse_math_list().unwrap(),
                 vec![
                     MathListElem::Atom(MathAtom::from_math_code(
                         &MathCode::from_number(0x7161)
@@ -1424,7 +1514,7 @@ mod tests {
     fn it_parses_basic_math_groups() {
         with_parser(&[r"{a}%"], |parser| {
             assert_eq!(
-                parser.parse_math_group(),
+                parser.parse_math_group().unwrap(),
                 vec![MathListElem::Atom(MathAtom::from_math_code(
                     &MathCode::from_number(0x7161)
                 )),],
@@ -1433,25 +1523,38 @@ mod tests {
     }
 
     #[test]
-    #[should_panic(expected = "Invalid start of math group")]
-    fn it_fails_parsing_math_groups_not_starting_with_begin_group() {
-        with_parser(&[r"a%"], |parser| {
-            parser.parse_math_group();
-        });
-    }
+    fn it_inserts_missing_braces_around_math_groups() {
+        with_parser(&[r"a}%", r"{a%"], |parser| {
+            let a_code = MathCode::from_number(0x7161);
+            let expected =
+                vec![MathListElem::Atom(MathAtom::from_math_code(&a_code))];
 
-    #[test]
-    #[should_panic(expected = "Math group didn't end with an EndGroup")]
-    fn it_fails_parsing_math_groups_not_ending_with_end_group() {
-        with_parser(&[r"{a%"], |parser| {
-            parser.parse_math_group();
+            assert_eq!(parser.parse_math_group().unwrap(), expected);
+            assert_eq!(
+                parser
+                    .take_errors()
+                    .into_iter()
+                    .map(|error| error.kind)
+                    .collect::<Vec<_>>(),
+                vec![TeXErrorKind::MissingLeftBrace]
+            );
+
+            assert_eq!(parser.parse_math_group().unwrap(), expected);
+            assert_eq!(
+                parser
+                    .take_errors()
+                    .into_iter()
+                    .map(|error| error.kind)
+                    .collect::<Vec<_>>(),
+                vec![TeXErrorKind::MissingRightBrace]
+            );
         });
     }
 
     #[test]
     fn it_scopes_assignments_in_math_fields() {
         with_parser(&[r"\count 0=1%", r"a^{\count 0=2}%"], |parser| {
-            parser.parse_math_list();
+            parser.parse_math_list().unwrap();
 
             assert_eq!(parser.state.get_count(0), 1);
         });
@@ -1461,13 +1564,13 @@ mod tests {
     fn it_parses_symbols_as_math_fields() {
         with_parser(&[r"a2%"], |parser| {
             assert_eq!(
-                parser.parse_math_field(),
+                parser.parse_math_field().unwrap(),
                 MathField::Symbol(MathSymbol::from_math_code(
                     &MathCode::from_number(0x7161)
                 ))
             );
             assert_eq!(
-                parser.parse_math_field(),
+                parser.parse_math_field().unwrap(),
                 MathField::Symbol(MathSymbol::from_math_code(
                     &MathCode::from_number(0x7032)
                 ))
@@ -1479,7 +1582,7 @@ mod tests {
     fn it_parses_groups_as_math_fields() {
         with_parser(&[r"{ab}{}%"], |parser| {
             assert_eq!(
-                parser.parse_math_field(),
+                parser.parse_math_field().unwrap(),
                 MathField::MathList(vec![
                     MathListElem::Atom(MathAtom::from_math_code(
                         &MathCode::from_number(0x7161)
@@ -1489,7 +1592,10 @@ mod tests {
                     )),
                 ],)
             );
-            assert_eq!(parser.parse_math_field(), MathField::MathList(vec![],));
+            assert_eq!(
+                parser.parse_math_field().unwrap(),
+                MathField::MathList(vec![],)
+            );
         });
     }
 
@@ -1497,13 +1603,13 @@ mod tests {
     fn it_ignores_filler_before_math_fields() {
         with_parser(&[r"  a   {a}%"], |parser| {
             assert_eq!(
-                parser.parse_math_field(),
+                parser.parse_math_field().unwrap(),
                 MathField::Symbol(MathSymbol::from_math_code(
                     &MathCode::from_number(0x7161)
                 ))
             );
             assert_eq!(
-                parser.parse_math_field(),
+                parser.parse_math_field().unwrap(),
                 MathField::MathList(vec![MathListElem::Atom(
                     MathAtom::from_math_code(&MathCode::from_number(0x7161))
                 ),],)
@@ -1518,7 +1624,7 @@ mod tests {
 
         with_parser(&[r"a^a%", r"a^{ab}%"], |parser| {
             assert_eq!(
-                parser.parse_math_list(),
+                parser.parse_math_list().unwrap(),
                 vec![
                     MathListElem::Atom(
                         MathAtom::from_math_code(&a_code).with_superscript(
@@ -1550,7 +1656,7 @@ mod tests {
 
         with_parser(&[r"^a%"], |parser| {
             assert_eq!(
-                parser.parse_math_list(),
+                parser.parse_math_list().unwrap(),
                 vec![MathListElem::Atom(
                     MathAtom::empty_ord().with_superscript(MathField::Symbol(
                         MathSymbol::from_math_code(&a_code)
@@ -1561,18 +1667,35 @@ mod tests {
     }
 
     #[test]
-    #[should_panic(expected = "Double superscript")]
-    fn it_fails_on_multiple_superscripts() {
+    fn it_reports_multiple_superscripts() {
+        let a_code = MathCode::from_number(0x7161);
+        let a_field = || MathField::Symbol(MathSymbol::from_math_code(&a_code));
+
         with_parser(&[r"a^a^a%"], |parser| {
-            parser.parse_math_list();
+            assert_eq!(
+                parser.parse_math_list().unwrap(),
+                vec![
+                    MathListElem::Atom(
+                        MathAtom::from_math_code(&a_code)
+                            .with_superscript(a_field())
+                    ),
+                    MathListElem::Atom(
+                        MathAtom::empty_ord().with_superscript(a_field())
+                    ),
+                ]
+            );
+            assert_eq!(
+                parser.take_errors()[0].kind,
+                TeXErrorKind::DoubleSuperscript
+            );
         });
-    }
 
-    #[test]
-    #[should_panic(expected = "Double superscript")]
-    fn it_fails_on_multiple_superscripts_after_subscript() {
         with_parser(&[r"a^a_a^a%"], |parser| {
-            parser.parse_math_list();
+            assert_eq!(parser.parse_math_list().unwrap().len(), 2);
+            assert_eq!(
+                parser.take_errors()[0].kind,
+                TeXErrorKind::DoubleSuperscript
+            );
         });
     }
 
@@ -1583,7 +1706,7 @@ mod tests {
 
         with_parser(&[r"a_a%", r"a_{ab}%"], |parser| {
             assert_eq!(
-                parser.parse_math_list(),
+                parser.parse_math_list().unwrap(),
                 vec![
                     MathListElem::Atom(
                         MathAtom::from_math_code(&a_code).with_subscript(
@@ -1615,7 +1738,7 @@ mod tests {
 
         with_parser(&[r"_a%"], |parser| {
             assert_eq!(
-                parser.parse_math_list(),
+                parser.parse_math_list().unwrap(),
                 vec![MathListElem::Atom(MathAtom::empty_ord().with_subscript(
                     MathField::Symbol(MathSymbol::from_math_code(&a_code))
                 )),],
@@ -1624,18 +1747,21 @@ mod tests {
     }
 
     #[test]
-    #[should_panic(expected = "Double subscript")]
-    fn it_fails_on_multiple_subscripts() {
+    fn it_reports_multiple_subscripts() {
         with_parser(&[r"a_a_a%"], |parser| {
-            parser.parse_math_list();
+            assert_eq!(parser.parse_math_list().unwrap().len(), 2);
+            assert_eq!(
+                parser.take_errors()[0].kind,
+                TeXErrorKind::DoubleSubscript
+            );
         });
-    }
 
-    #[test]
-    #[should_panic(expected = "Double subscript")]
-    fn it_fails_on_multiple_subscripts_after_superscript() {
         with_parser(&[r"a_a^a_a%"], |parser| {
-            parser.parse_math_list();
+            assert_eq!(parser.parse_math_list().unwrap().len(), 2);
+            assert_eq!(
+                parser.take_errors()[0].kind,
+                TeXErrorKind::DoubleSubscript
+            );
         });
     }
 
@@ -1646,11 +1772,11 @@ mod tests {
         let c_code = MathCode::from_number(0x7163);
 
         with_parser(&[r"\hello%", r"a\hello b%"], |parser| {
-            let tok = parser.lex_unexpanded_token().unwrap();
+            let tok = parser.lex_unexpanded_token().unwrap().unwrap();
             parser.state.set_math_chardef(false, &tok, &c_code);
 
             assert_eq!(
-                parser.parse_math_list(),
+                parser.parse_math_list().unwrap(),
                 vec![
                     MathListElem::Atom(MathAtom::from_math_code(&a_code)),
                     MathListElem::Atom(MathAtom::from_math_code(&c_code)),
@@ -1668,7 +1794,7 @@ mod tests {
 
         with_parser(&[r"a\def\x #1{a#1b}%", r"b\x c%"], |parser| {
             assert_eq!(
-                parser.parse_math_list(),
+                parser.parse_math_list().unwrap(),
                 vec![
                     MathListElem::Atom(MathAtom::from_math_code(&a_code)),
                     MathListElem::Atom(MathAtom::from_math_code(&b_code)),
@@ -1681,7 +1807,7 @@ mod tests {
 
         with_parser(&[r"a\def\x{b}_\x%"], |parser| {
             assert_eq!(
-                parser.parse_math_list(),
+                parser.parse_math_list().unwrap(),
                 vec![MathListElem::Atom(
                     MathAtom::from_math_code(&a_code).with_subscript(
                         MathField::Symbol(MathSymbol::from_math_code(&b_code))
@@ -1697,7 +1823,7 @@ mod tests {
             &[r"\displaystyle \textstyle \scriptstyle \scriptscriptstyle%"],
             |parser| {
                 assert_eq!(
-                    parser.parse_math_list(),
+                    parser.parse_math_list().unwrap(),
                     vec![
                         MathListElem::StyleChange(MathStyle::DisplayStyle),
                         MathListElem::StyleChange(MathStyle::TextStyle),
@@ -1715,7 +1841,7 @@ mod tests {
 
         with_parser(&[r"\displaystyle ^a%"], |parser| {
             assert_eq!(
-                parser.parse_math_list(),
+                parser.parse_math_list().unwrap(),
                 vec![
                     MathListElem::StyleChange(MathStyle::DisplayStyle),
                     MathListElem::Atom(MathAtom::empty_ord().with_superscript(
@@ -1732,12 +1858,34 @@ mod tests {
 
         with_parser(&[r"a$%"], |parser| {
             assert_eq!(
-                parser.parse_math_list(),
+                parser.parse_math_list().unwrap(),
                 vec![MathListElem::Atom(MathAtom::from_math_code(&a_code)),]
             );
 
             assert_eq!(
-                parser.lex_expanded_token(),
+                parser.lex_expanded_token().unwrap(),
+                Some(Token::Char('$', Category::MathShift))
+            );
+        });
+    }
+
+    #[test]
+    fn it_ends_semi_simple_groups_before_math_shifts() {
+        let a_code = MathCode::from_number(0x7161);
+
+        with_parser(&[r"\begingroup a$%"], |parser| {
+            assert_eq!(
+                parser.parse_math_list().unwrap(),
+                vec![MathListElem::Atom(MathAtom::from_math_code(&a_code)),]
+            );
+            assert_eq!(parser.get_group_depth(), 0);
+            assert_eq!(
+                parser.take_errors()[0].kind,
+                TeXErrorKind::MissingEndGroup
+            );
+
+            assert_eq!(
+                parser.lex_expanded_token().unwrap(),
                 Some(Token::Char('$', Category::MathShift))
             );
         });
@@ -1869,7 +2017,7 @@ mod tests {
 
         with_parser(&[r"a{bc}d%"], |parser| {
             assert_eq!(
-                parser.parse_math_list(),
+                parser.parse_math_list().unwrap(),
                 vec![
                     MathListElem::Atom(MathAtom::from_math_code(&a_code)),
                     MathListElem::Atom(MathAtom::from_math_list(vec![
@@ -1895,7 +2043,7 @@ mod tests {
             ],
             |parser| {
                 assert_eq!(
-                    parser.parse_math_list(),
+                    parser.parse_math_list().unwrap(),
                     vec![
                         MathListElem::Atom(MathAtom::from_math_code(&one_code)),
                         MathListElem::Atom(MathAtom::from_math_list(vec![
@@ -1930,7 +2078,7 @@ mod tests {
 
         with_parser(&[r"a b ", r"c          d"], |parser| {
             assert_eq!(
-                parser.parse_math_list(),
+                parser.parse_math_list().unwrap(),
                 vec![
                     MathListElem::Atom(MathAtom::from_math_code(&a_code)),
                     MathListElem::Atom(MathAtom::from_math_code(&b_code)),
@@ -2256,15 +2404,15 @@ mod tests {
                 r"\hbox to 10pt{\hfil\copy1\hfil}%",
             ],
             |parser| {
-                parser.parse_assignment(None);
-                parser.parse_assignment(None);
-                parser.parse_assignment(None);
-                let five_pt_box = parser.parse_box().unwrap();
-                let ten_pt_box_with_hfil = parser.parse_box().unwrap();
-                let box_with_hfil = parser.parse_box().unwrap();
-                let box_with_hfill = parser.parse_box().unwrap();
-                parser.parse_assignment(None);
-                parser.parse_assignment(None);
+                parser.parse_assignment(None).unwrap();
+                parser.parse_assignment(None).unwrap();
+                parser.parse_assignment(None).unwrap();
+                let five_pt_box = parser.parse_box().unwrap().unwrap();
+                let ten_pt_box_with_hfil = parser.parse_box().unwrap().unwrap();
+                let box_with_hfil = parser.parse_box().unwrap().unwrap();
+                let box_with_hfill = parser.parse_box().unwrap().unwrap();
+                parser.parse_assignment(None).unwrap();
+                parser.parse_assignment(None).unwrap();
                 let vbox = parser.state.get_box_copy(1).unwrap();
 
                 let ten_pt = Dimen::from_unit(10.0, Unit::Point);
@@ -2274,7 +2422,7 @@ mod tests {
                     parser.rebox_box_to_width(five_pt_box, ten_pt);
                 assert_eq!(
                     five_pt_box_reboxed_to_ten_pt,
-                    parser.parse_box().unwrap()
+                    parser.parse_box().unwrap().unwrap()
                 );
                 assert_eq!(*five_pt_box_reboxed_to_ten_pt.width(), ten_pt);
                 assert_eq!(
@@ -2306,7 +2454,7 @@ mod tests {
                     parser.rebox_box_to_width(box_with_hfil, ten_pt);
                 assert_eq!(
                     box_with_hfil_reboxed_to_ten_pt,
-                    parser.parse_box().unwrap()
+                    parser.parse_box().unwrap().unwrap()
                 );
                 assert_eq!(*box_with_hfil_reboxed_to_ten_pt.width(), ten_pt);
                 assert_eq!(
@@ -2324,7 +2472,7 @@ mod tests {
                     parser.rebox_box_to_width(box_with_hfill, ten_pt);
                 assert_eq!(
                     box_with_hfill_reboxed_to_ten_pt,
-                    parser.parse_box().unwrap()
+                    parser.parse_box().unwrap().unwrap()
                 );
                 assert_eq!(*box_with_hfill_reboxed_to_ten_pt.width(), ten_pt);
                 assert_eq!(
@@ -2341,7 +2489,10 @@ mod tests {
                 // vboxes aren't unboxed and are just surrounded by \hfil
                 let vbox_reboxed_to_ten_pt =
                     parser.rebox_box_to_width(vbox, ten_pt);
-                assert_eq!(vbox_reboxed_to_ten_pt, parser.parse_box().unwrap());
+                assert_eq!(
+                    vbox_reboxed_to_ten_pt,
+                    parser.parse_box().unwrap().unwrap()
+                );
                 assert_eq!(*vbox_reboxed_to_ten_pt.width(), ten_pt);
                 assert_eq!(
                     match vbox_reboxed_to_ten_pt {
@@ -2365,7 +2516,7 @@ mod tests {
 
         with_parser(&[r"a\atop b%"], |parser| {
             assert_eq!(
-                parser.parse_math_list(),
+                parser.parse_math_list().unwrap(),
                 vec![MathListElem::GeneralizedFraction(GeneralizedFraction {
                     left_delim: None,
                     right_delim: None,
@@ -2382,7 +2533,7 @@ mod tests {
 
         with_parser(&[r"{a\atop b} \atop c%"], |parser| {
             assert_eq!(
-                parser.parse_math_list(),
+                parser.parse_math_list().unwrap(),
                 vec![MathListElem::GeneralizedFraction(GeneralizedFraction {
                     left_delim: None,
                     right_delim: None,
@@ -2413,7 +2564,7 @@ mod tests {
 
         with_parser(&[r"abc \atop abc%"], |parser| {
             assert_eq!(
-                parser.parse_math_list(),
+                parser.parse_math_list().unwrap(),
                 vec![MathListElem::GeneralizedFraction(GeneralizedFraction {
                     left_delim: None,
                     right_delim: None,
@@ -2434,10 +2585,20 @@ mod tests {
     }
 
     #[test]
-    #[should_panic(expected = "Ambiguous generalized fraction")]
-    fn it_fails_on_ambiguous_generalized_fractions() {
+    fn it_ignores_ambiguous_generalized_fractions() {
         with_parser(&[r"a \atop b \atop c%"], |parser| {
-            parser.parse_math_list();
+            let list = parser.parse_math_list().unwrap();
+            match &list[..] {
+                [MathListElem::GeneralizedFraction(fraction)] => {
+                    assert_eq!(fraction.numerator.len(), 1);
+                    assert_eq!(fraction.denominator.len(), 2);
+                }
+                _ => panic!("Expected a single fraction: {:?}", list),
+            }
+            assert_eq!(
+                parser.take_errors()[0].kind,
+                TeXErrorKind::AmbiguousFraction
+            );
         });
     }
 
@@ -0,0 +1,203 @@
+use std::cell::RefCell;
+use std::io::Write;
+use std::rc::Rc;
+
+use crate::error::{TeXErrorKind, TeXResult};
+use crate::parser::Parser;
+use crate::token::{format_token_list, Token};
+use crate::transcript::{Selector, MAX_PRINT_LINE};
+
+impl<'a> Parser<'a> {
+    /// Sets where messages, errors, and the output of \show and friends are
+    /// printed. By default, this is stdout.
+    pub fn set_terminal(&mut self, terminal: Rc<RefCell<dyn Write>>) {
+        self.transcript.set_terminal(terminal);
+    }
+
+    /// Sets where the transcript is written. This gets a copy of everything
+    /// printed to the terminal, along with things that are only logged, like
+    /// \write's to negative streams.
+    pub fn set_log_file(&mut self, log: Rc<RefCell<dyn Write>>) {
+        self.transcript.set_log(log);
+    }
+
+    /// Prints some text on its own line, on both the terminal and in the log
+    /// file.
+    pub fn print_line(&mut self, text: &str) {
+        self.transcript.print_nl(Selector::TerminalAndLog);
+        self.transcript.print(Selector::TerminalAndLog, text);
+        self.transcript.print_ln(Selector::TerminalAndLog);
+    }
+
+    /// Prints some text on the same line as whatever came before it,
+    /// separated by a space, unless that would make the line too long. This
+    /// is how TeX prints messages and the names of files as they're opened.
+    pub fn print_on_current_line(&mut self, text: &str) {
+        let length = text.chars().count();
+        if self.transcript.terminal_offset() + length > MAX_PRINT_LINE - 2 {
+            self.transcript.print_ln(Selector::TerminalAndLog);
+        } else if self.transcript.is_mid_line(Selector::TerminalAndLog) {
+            self.transcript.print(Selector::TerminalAndLog, " ");
+        }
+        self.transcript.print(Selector::TerminalAndLog, text);
+    }
+
+    pub fn is_message_head(&mut self) -> TeXResult<bool> {
+        self.is_next_expanded_token_in_set_of_primitives(&[
+            "message",
+            "errmessage",
+            "immediate",
+            "write",
+        ])
+    }
+
+    // Parses the stream number and text of a \write, and writes the text out.
+    // We don't support opening files to write to, so streams 0-15 are never
+    // open, and TeX writes to the terminal and log file when a stream isn't
+    // open. Negative streams only go to the log file.
+    fn parse_write(&mut self, head: &Token) -> TeXResult<()> {
+        let stream = self.parse_number()?;
+        let text = format_token_list(&self.parse_expanded_general_text(head)?);
+
+        let selector = if stream < 0 {
+            Selector::LogOnly
+        } else {
+            Selector::TerminalAndLog
+        };
+        self.transcript.print_nl(selector);
+        self.transcript.print(selector, &text);
+        self.transcript.print_ln(selector);
+        Ok(())
+    }
+
+    pub fn parse_message(&mut self) -> TeXResult<()> {
+        let head = self.lex_expanded_token()?.unwrap();
+
+        if self.state.is_token_equal_to_prim(&head, "message") {
+            let text =
+                format_token_list(&self.parse_expanded_general_text(&head)?);
+            self.print_on_current_line(&text);
+        } else if self.state.is_token_equal_to_prim(&head, "errmessage") {
+            let text =
+                format_token_list(&self.parse_expanded_general_text(&head)?);
+            self.report_error(TeXErrorKind::ErrMessage(text))?;
+        } else if self.state.is_token_equal_to_prim(&head, "immediate") {
+            // \immediate only means something before \write (or \openout and
+            // \closeout, which we don't support), and TeX ignores it before
+            // anything else.
+            if self.is_next_expanded_token_in_set_of_primitives(&["write"])? {
+                let write = self.lex_expanded_token()?.unwrap();
+                self.parse_write(&write)?;
+            }
+        } else if self.state.is_token_equal_to_prim(&head, "write") {
+            // A \write without \immediate happens when the page that it ends
+            // up on is shipped out, which we can't do yet. Writing it right
+            // away is the closest we can get.
+            self.report_error(TeXErrorKind::Unimplemented(
+                "`\\write' without `\\immediate'".to_string(),
+            ))?;
+            self.parse_write(&head)?;
+        } else {
+            panic!("Invalid message head: {:?}", head);
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::testing::{with_terminal, TestOutput};
+
+    // Runs the parser with both the terminal and the log file captured, and
+    // returns what was written to each.
+    fn with_transcript<F>(lines: &[&str], func: F) -> (String, String)
+    where
+        F: FnOnce(&mut Parser),
+    {
+        let log = TestOutput::new();
+        let terminal = with_terminal(lines, |parser| {
+            parser.set_log_file(log.writer());
+            func(parser);
+        });
+        (terminal, log.contents())
+    }
+
+    #[test]
+    fn it_prints_messages() {
+        let (terminal, log) = with_transcript(
+            &[r"\def\a{b}%", r"\message{a\a}\message{c d}%"],
+            |parser| {
+                parser.parse_assignment(None).unwrap();
+                assert!(parser.is_message_head().unwrap());
+                parser.parse_message().unwrap();
+                assert!(parser.is_message_head().unwrap());
+                parser.parse_message().unwrap();
+            },
+        );
+
+        assert_eq!(terminal, "ab c d");
+        assert_eq!(log, "ab c d");
+    }
+
+    #[test]
+    fn it_breaks_lines_before_long_messages() {
+        let long_message = "a".repeat(77);
+        let (terminal, _) = with_transcript(
+            &[&format!(r"\message{{b}}\message{{{}}}%", long_message)],
+            |parser| {
+                parser.parse_message().unwrap();
+                parser.parse_message().unwrap();
+            },
+        );
+
+        assert_eq!(terminal, format!("b\n{}", long_message));
+    }
+
+    #[test]
+    fn it_writes_to_the_terminal_and_log() {
+        let (terminal, log) = with_transcript(
+            &[
+                r"\immediate\write16{a\relax}%",
+                r"\immediate\write-1{b}%",
+                r"\immediate\write5{c}%",
+            ],
+            |parser| {
+                parser.parse_message().unwrap();
+                parser.parse_message().unwrap();
+                parser.parse_message().unwrap();
+            },
+        );
+
+        assert_eq!(terminal, "a\\relax \nc\n");
+        assert_eq!(log, "a\\relax \nb\nc\n");
+    }
+
+    #[test]
+    fn it_reports_error_messages_with_context() {
+        let (terminal, _) = with_transcript(
+            &[r"\def\a{\errmessage{oh no}}%", r"x\a y%"],
+            |parser| {
+                parser.parse_assignment(None).unwrap();
+                parser.lex_unexpanded_token().unwrap();
+                parser.parse_message().unwrap();
+
+                let errors = parser.take_errors();
+                assert_eq!(errors.len(), 1);
+                assert_eq!(
+                    errors[0].kind,
+                    TeXErrorKind::ErrMessage("oh no".to_string())
+                );
+
+                parser.lex_unexpanded_token().unwrap();
+            },
+        );
+
+        assert_eq!(
+            terminal,
+            ["! oh no.", r"l.2 x\a", "        y%", ""].join("\n")
+        );
+    }
+}
@@ -1,17 +1,92 @@
+use std::cell::RefCell;
+use std::io;
+use std::rc::Rc;
+
+use crate::debugger::{Debugger, PartialList};
+use crate::error::TeXError;
 use crate::lexer::Lexer;
+use crate::origin::TokenOrigin;
+use crate::parser::group::Group;
 use crate::state::TeXState;
 use crate::token::Token;
+use crate::trace::ExpansionTracer;
+use crate::transcript::Transcript;
 
 pub struct Parser<'a> {
     lexer: Lexer<'a>,
     state: &'a TeXState,
 
-    // Used in expand module to keep track of the next tokens to parse
-    upcoming_tokens: Vec<Token>,
+    // Used in expand module to keep track of the next tokens to parse, along
+    // with where each of them came from
+    upcoming_tokens: Vec<(Token, Option<Rc<TokenOrigin>>)>,
+
+    // Used in expand module to keep track of where the most recently lexed
+    // token came from
+    last_origin: Option<Rc<TokenOrigin>>,
+
+    // Used in expand module to keep track of a token that was passed to
+    // \noexpand, which acts like \relax instead of being expanded the next
+    // time it's read. This is its index in upcoming_tokens, and we also keep
+    // track of whether the most recently lexed token was that token so that
+    // it can be put back if it was only being peeked at.
+    noexpand_index: Option<usize>,
+    last_token_noexpanded: bool,
 
     // Used in conditional module to keep track of the level of nesting of
     // conditionals
     conditional_depth: usize,
+
+    // Used in trace module to report what we're expanding and executing
+    tracer: Option<Rc<RefCell<dyn ExpansionTracer>>>,
+
+    // Used in trace module to keep track of which macro expansions are still
+    // being read. Each entry is the length of upcoming_tokens right before the
+    // replacement text of a macro was added, along with the macro's name and
+    // the replacement text, so that errors can show how much has been read.
+    expansion_bases: Vec<(usize, Token, Vec<Token>)>,
+
+    // Used in errors module to keep track of all of the errors that have been
+    // reported, how many have been reported since the last paragraph ended,
+    // and whether we should stop at the first one.
+    errors: Vec<TeXError>,
+    error_count: usize,
+    halt_on_error: bool,
+
+    // Used in messages module to print to the terminal and the log file
+    transcript: Transcript,
+
+    // Used in conditional module to keep track of the mode of each list that
+    // is being parsed, so that \ifvmode and friends can check the innermost
+    // one.
+    modes: Vec<&'static str>,
+
+    // Used in trace module to keep track of the last mode that was shown by
+    // \tracingcommands, since it is only shown when it changes
+    shown_mode: Option<&'static str>,
+
+    // Used in debug module to notify an attached debugger about what we're
+    // doing, and to keep track of the lists being built so that the debugger
+    // can look at them
+    debugger: Option<Rc<RefCell<dyn Debugger>>>,
+    debug_lists: Vec<PartialList>,
+
+    // Used in input module to keep track of which upcoming tokens should be
+    // read after each \input file that's being read ends. Each entry is the
+    // length of upcoming_tokens when the file was started.
+    input_file_bases: Vec<usize>,
+
+    // Used in printing module for \jobname
+    job_name: String,
+
+    // Used in group module to keep track of the groups that haven't ended
+    // yet, innermost last
+    groups: Vec<Group>,
+
+    // Used in assignment module to keep track of the token saved with
+    // \afterassignment. When it's used by \setbox, it's moved to
+    // box_after_assignment_token until the { of the box is read.
+    after_assignment_token: Option<Token>,
+    box_after_assignment_token: Option<Token>,
 }
 
 impl<'a> Parser<'a> {
@@ -25,22 +100,48 @@ impl<'a> Parser<'a> {
             lexer,
             state,
             upcoming_tokens: Vec::new(),
+            last_origin: None,
+            noexpand_index: None,
+            last_token_noexpanded: false,
             conditional_depth: 0,
+            tracer: None,
+            expansion_bases: Vec::new(),
+            errors: Vec::new(),
+            error_count: 0,
+            halt_on_error: false,
+            transcript: Transcript::new(Rc::new(RefCell::new(io::stdout()))),
+            modes: Vec::new(),
+            shown_mode: None,
+            debugger: None,
+            debug_lists: Vec::new(),
+            input_file_bases: Vec::new(),
+            job_name: "texput".to_string(),
+            groups: Vec::new(),
+            after_assignment_token: None,
+            box_after_assignment_token: None,
         }
     }
 }
 
 mod assignment;
 mod boxes;
+mod case_change;
 mod conditional;
+mod debug;
 mod dimen;
+mod errors;
 mod expand;
 mod glue;
+mod group;
 mod horizontal_list;
+mod input;
 mod makro;
 mod math_list;
+mod messages;
 mod number;
 mod primitives;
 mod printing;
+mod show;
+mod trace;
 mod variable;
 mod vertical_list;
@@ -1,4 +1,5 @@
 use crate::category::Category;
+use crate::error::{TeXErrorKind, TeXResult};
 use crate::parser::Parser;
 use crate::token::Token;
 
@@ -40,169 +41,247 @@ pub fn token_digit_value(token: &Token) -> u8 {
 }
 
 impl<'a> Parser<'a> {
-    fn is_integer_constant_head(&mut self) -> bool {
-        match self.peek_expanded_token() {
-            Some(token) => is_token_digit(&token),
-            _ => false,
+    fn is_integer_constant_head(&mut self) -> TeXResult<bool> {
+        match self.peek_expanded_token()? {
+            Some(token) => Ok(is_token_digit(&token)),
+            _ => Ok(false),
         }
     }
 
-    fn parse_integer_constant(&mut self) -> u32 {
-        let mut value: u32 = match self.peek_expanded_token() {
-            Some(ref token) if is_token_digit(token) => {
-                self.lex_expanded_token();
-                token_digit_value(token) as u32
+    // Adds another digit onto the end of an integer constant. Like TeX, if
+    // the constant gets too big, an error is reported (only once per constant)
+    // and the largest integer is used instead.
+    fn add_digit_to_constant(
+        &mut self,
+        value: i32,
+        radix: i32,
+        digit: u8,
+        too_big: &mut bool,
+    ) -> TeXResult<i32> {
+        let next_value = value
+            .checked_mul(radix)
+            .and_then(|value| value.checked_add(digit as i32));
+        match next_value {
+            Some(next_value) if !*too_big => Ok(next_value),
+            _ => {
+                if !*too_big {
+                    *too_big = true;
+                    self.report_error(TeXErrorKind::NumberTooBig)?;
+                }
+                Ok(i32::MAX)
             }
-            _ => panic!("Invalid number start"),
-        };
+        }
+    }
 
-        loop {
-            match self.peek_expanded_token() {
-                Some(ref token) if is_token_digit(token) => {
-                    self.lex_expanded_token();
-                    value = 10 * value + token_digit_value(token) as u32;
-                }
-                _ => break,
+    fn parse_integer_constant(&mut self) -> TeXResult<i32> {
+        let mut value: i32 = 0;
+        let mut too_big = false;
+
+        while let Some(ref token) = self.peek_expanded_token()? {
+            if !is_token_digit(token) {
+                break;
             }
+            self.lex_expanded_token()?;
+            value = self.add_digit_to_constant(
+                value,
+                10,
+                token_digit_value(token),
+                &mut too_big,
+            )?;
         }
 
-        self.parse_optional_space_expanded();
+        self.parse_optional_space_expanded()?;
 
-        value
+        Ok(value)
     }
 
-    fn is_hexadecimal_constant_head(&mut self) -> bool {
-        match self.peek_expanded_token() {
-            Some(token) => token == Token::Char('"', Category::Other),
-            _ => false,
+    fn is_hexadecimal_constant_head(&mut self) -> TeXResult<bool> {
+        match self.peek_expanded_token()? {
+            Some(token) => Ok(token == Token::Char('"', Category::Other)),
+            _ => Ok(false),
         }
     }
 
-    fn parse_hexadecimal_constant(&mut self) -> u32 {
-        let quote = self.lex_expanded_token().unwrap();
+    fn parse_hexadecimal_constant(&mut self) -> TeXResult<i32> {
+        let quote = self.lex_expanded_token()?.unwrap();
         if quote != Token::Char('"', Category::Other) {
             panic!("Invalid hexadecimal number start");
         }
 
-        let mut value: u32 = match self.peek_expanded_token() {
+        let mut value: i32 = match self.peek_expanded_token()? {
             Some(ref token) if is_token_hex_digit(token) => {
-                self.lex_expanded_token();
-                token_digit_value(token) as u32
+                self.lex_expanded_token()?;
+                token_digit_value(token) as i32
+            }
+            _ => {
+                self.report_error(TeXErrorKind::MissingNumber)?;
+                return Ok(0);
             }
-            _ => panic!("Invalid hexadecimal number start"),
         };
 
+        let mut too_big = false;
         loop {
-            match self.peek_expanded_token() {
+            match self.peek_expanded_token()? {
                 Some(ref token) if is_token_hex_digit(token) => {
-                    self.lex_expanded_token();
-                    value = 16 * value + token_digit_value(token) as u32;
+                    self.lex_expanded_token()?;
+                    value = self.add_digit_to_constant(
+                        value,
+                        16,
+                        token_digit_value(token),
+                        &mut too_big,
+                    )?;
                 }
                 _ => break,
             }
         }
 
-        self.parse_optional_space_expanded();
+        self.parse_optional_space_expanded()?;
 
-        value
+        Ok(value)
     }
 
-    fn is_character_number_constant_head(&mut self) -> bool {
-        match self.peek_expanded_token() {
-            Some(token) => token == Token::Char('`', Category::Other),
-            _ => false,
+    fn is_character_number_constant_head(&mut self) -> TeXResult<bool> {
+        match self.peek_expanded_token()? {
+            Some(token) => Ok(token == Token::Char('`', Category::Other)),
+            _ => Ok(false),
         }
     }
 
-    fn parse_character_number_constant(&mut self) -> u32 {
-        let backtick = self.lex_expanded_token().unwrap();
+    fn parse_character_number_constant(&mut self) -> TeXResult<u32> {
+        let backtick = self.lex_expanded_token()?.unwrap();
         if backtick != Token::Char('`', Category::Other) {
             panic!("Invalid character number constant start");
         }
 
-        let char_value = match self.lex_unexpanded_token() {
+        let char_value = match self.lex_unexpanded_token()? {
             Some(Token::Char(ch, _)) => ch,
-            Some(Token::ControlSequence(cs)) => {
-                if cs.len() == 1 {
-                    cs.chars().next().unwrap()
-                } else {
-                    panic!(
-                        "Invalid control sequence in character number constant"
-                    );
+            Some(Token::ControlSequence(ref cs)) if cs.chars().count() == 1 => {
+                cs.chars().next().unwrap()
+            }
+            rest => {
+                // Like TeX, we use "0" as the value and read the token again.
+                self.report_error(TeXErrorKind::ImproperAlphabeticConstant)?;
+                if let Some(token) = rest {
+                    self.add_upcoming_token(token);
                 }
+                return Ok('0' as u32);
             }
-            _ => panic!("Invalid char token in character number constant"),
         };
 
-        self.parse_optional_space_expanded();
+        self.parse_optional_space_expanded()?;
+
+        Ok(char_value as u32)
+    }
+
+    // Tokens defined with \chardef and \mathchardef can be used as numbers.
+    fn is_defined_character_head(&mut self) -> TeXResult<bool> {
+        match self.peek_expanded_token()? {
+            Some(token) => Ok(self.state.get_chardef(&token).is_some()
+                || self.state.get_math_chardef(&token).is_some()),
+            None => Ok(false),
+        }
+    }
 
-        char_value as u32
+    fn parse_defined_character(&mut self) -> TeXResult<i32> {
+        let token = self.lex_expanded_token()?.unwrap();
+        if let Some(ch) = self.state.get_chardef(&token) {
+            Ok(ch as i32)
+        } else if let Some(math_code) = self.state.get_math_chardef(&token) {
+            Ok(math_code.to_number() as i32)
+        } else {
+            // This is only called after is_defined_character_head().
+            unreachable!("Invalid defined character: {:?}", token);
+        }
     }
 
-    pub fn is_internal_integer_head(&mut self) -> bool {
-        self.is_integer_variable_head()
+    pub fn is_internal_integer_head(&mut self) -> TeXResult<bool> {
+        Ok(self.is_integer_variable_head()?
+            || self.is_code_variable_head()?
+            || self.is_defined_character_head()?
+            || self.is_next_expanded_token_in_set_of_primitives(&[
+                "inputlineno",
+            ])?)
     }
 
-    pub fn parse_internal_integer(&mut self) -> i32 {
-        if self.is_integer_variable_head() {
-            let variable = self.parse_integer_variable();
-            variable.get(self.state)
+    pub fn parse_internal_integer(&mut self) -> TeXResult<i32> {
+        if self.is_integer_variable_head()? {
+            let variable = self.parse_integer_variable()?;
+            Ok(variable.get(self.state))
+        } else if self.is_code_variable_head()? {
+            let variable = self.parse_code_variable()?;
+            Ok(variable.get(self.state))
+        } else if self.is_defined_character_head()? {
+            self.parse_defined_character()
+        } else if self
+            .is_next_expanded_token_in_set_of_primitives(&["inputlineno"])?
+        {
+            // \inputlineno can be read like an integer variable, but it
+            // can't be assigned to.
+            self.lex_expanded_token()?;
+            Ok(self.get_input_line_number())
         } else {
-            panic!("unimplemented");
+            // This is only called after is_internal_integer_head().
+            unreachable!("Invalid internal integer head");
         }
     }
 
-    fn is_normal_integer_head(&mut self) -> bool {
-        self.is_internal_integer_head()
-            || self.is_integer_constant_head()
-            || self.is_hexadecimal_constant_head()
-            || self.is_character_number_constant_head()
+    fn is_normal_integer_head(&mut self) -> TeXResult<bool> {
+        Ok(self.is_internal_integer_head()?
+            || self.is_integer_constant_head()?
+            || self.is_hexadecimal_constant_head()?
+            || self.is_character_number_constant_head()?)
     }
 
-    fn parse_normal_integer(&mut self) -> i32 {
-        if self.is_internal_integer_head() {
+    fn parse_normal_integer(&mut self) -> TeXResult<i32> {
+        if self.is_internal_integer_head()? {
             self.parse_internal_integer()
-        } else if self.is_integer_constant_head() {
-            self.parse_integer_constant() as i32
-        } else if self.is_hexadecimal_constant_head() {
-            self.parse_hexadecimal_constant() as i32
-        } else if self.is_character_number_constant_head() {
-            self.parse_character_number_constant() as i32
+        } else if self.is_integer_constant_head()? {
+            self.parse_integer_constant()
+        } else if self.is_hexadecimal_constant_head()? {
+            self.parse_hexadecimal_constant()
+        } else if self.is_character_number_constant_head()? {
+            Ok(self.parse_character_number_constant()? as i32)
         } else {
-            panic!("unimplemented");
+            // This is only called after is_normal_integer_head().
+            unreachable!("Invalid normal integer head");
         }
     }
 
-    fn is_coerced_integer_head(&mut self) -> bool {
-        self.is_internal_dimen_head()
+    fn is_coerced_integer_head(&mut self) -> TeXResult<bool> {
+        Ok(self.is_internal_dimen_head()? || self.is_internal_glue_head()?)
     }
 
-    fn parse_coerced_integer(&mut self) -> i32 {
-        let dimen = self.parse_internal_dimen();
-        dimen.as_scaled_points()
+    fn parse_coerced_integer(&mut self) -> TeXResult<i32> {
+        let dimen = if self.is_internal_dimen_head()? {
+            self.parse_internal_dimen()?
+        } else {
+            self.parse_internal_glue()?.space
+        };
+        Ok(dimen.as_scaled_points())
     }
 
-    fn parse_unsigned_number(&mut self) -> i32 {
-        if self.is_normal_integer_head() {
+    fn parse_unsigned_number(&mut self) -> TeXResult<i32> {
+        if self.is_normal_integer_head()? {
             self.parse_normal_integer()
-        } else if self.is_coerced_integer_head() {
+        } else if self.is_coerced_integer_head()? {
             self.parse_coerced_integer()
         } else {
-            panic!("Invalid unsigned number head");
+            self.report_error(TeXErrorKind::MissingNumber)?;
+            Ok(0)
         }
     }
 
     // Parses some number of +s and -s into an overall numeric sign, which is
     // -1 if there are an odd number of -s and 1 otherwise.
-    pub fn parse_optional_signs(&mut self) -> i32 {
+    pub fn parse_optional_signs(&mut self) -> TeXResult<i32> {
         let mut sign: i32 = 1;
 
         loop {
-            match self.peek_expanded_token() {
+            match self.peek_expanded_token()? {
                 Some(Token::Char(chr, Category::Other))
                     if chr == '+' || chr == '-' =>
                 {
-                    self.lex_expanded_token();
+                    self.lex_expanded_token()?;
                     if chr == '-' {
                         sign *= -1;
                     }
@@ -211,31 +290,52 @@ impl<'a> Parser<'a> {
             }
         }
 
-        self.parse_optional_spaces_expanded();
+        self.parse_optional_spaces_expanded()?;
+
+        Ok(sign)
+    }
 
-        sign
+    pub fn parse_4bit_number(&mut self) -> TeXResult<u8> {
+        let number = self.parse_number()?;
+        if !(0..=15).contains(&number) {
+            self.report_error(TeXErrorKind::BadNumber(number))?;
+            return Ok(0);
+        }
+        Ok(number as u8)
     }
 
-    pub fn parse_8bit_number(&mut self) -> u8 {
-        let number = self.parse_number();
+    pub fn parse_8bit_number(&mut self) -> TeXResult<u8> {
+        let number = self.parse_number()?;
         if number < 0 || number > 255 {
-            panic!("Invalid 8-bit number: {}", number);
+            self.report_error(TeXErrorKind::BadRegisterCode(number))?;
+            return Ok(0);
+        }
+        Ok(number as u8)
+    }
+
+    /// Parses a character code, like the number after \char or \catcode.
+    pub fn parse_char_code(&mut self) -> TeXResult<char> {
+        let number = self.parse_number()?;
+        if !(0..=255).contains(&number) {
+            self.report_error(TeXErrorKind::BadCharacterCode(number))?;
+            return Ok(0 as char);
         }
-        number as u8
+        Ok(number as u8 as char)
     }
 
-    pub fn parse_15bit_number(&mut self) -> u16 {
-        let number = self.parse_number();
+    pub fn parse_15bit_number(&mut self) -> TeXResult<u16> {
+        let number = self.parse_number()?;
         if number < 0 || number > 32767 {
-            panic!("Invalid 15-bit number: {}", number);
+            self.report_error(TeXErrorKind::BadMathCode(number))?;
+            return Ok(0);
         }
-        number as u16
+        Ok(number as u16)
     }
 
-    pub fn parse_number(&mut self) -> i32 {
-        let sign = self.parse_optional_signs();
-        let value = self.parse_unsigned_number();
-        sign * value
+    pub fn parse_number(&mut self) -> TeXResult<i32> {
+        let sign = self.parse_optional_signs()?;
+        let value = self.parse_unsigned_number()?;
+        Ok(sign * value)
     }
 }
 
@@ -243,23 +343,63 @@ impl<'a> Parser<'a> {
 mod tests {
     use crate::category::Category;
     use crate::dimension::{Dimen, Unit};
+    use crate::error::TeXErrorKind;
     use crate::font::Font;
     use crate::testing::with_parser;
+    use crate::token::Token;
 
     #[test]
     fn it_parses_8bit_numbers() {
         with_parser(&["0 %", "255 %", "-+-  123 %"], |parser| {
-            assert_eq!(parser.parse_8bit_number(), 0);
-            assert_eq!(parser.parse_8bit_number(), 255);
-            assert_eq!(parser.parse_8bit_number(), 123);
+            assert_eq!(parser.parse_8bit_number().unwrap(), 0);
+            assert_eq!(parser.parse_8bit_number().unwrap(), 255);
+            assert_eq!(parser.parse_8bit_number().unwrap(), 123);
         });
     }
 
     #[test]
-    #[should_panic(expected = "Invalid 8-bit number: -1234")]
     fn it_fails_parsing_8bit_numbers() {
         with_parser(&["-1234%"], |parser| {
-            parser.parse_8bit_number();
+            assert_eq!(parser.parse_8bit_number().unwrap(), 0);
+
+            let errors = parser.take_errors();
+            assert_eq!(errors.len(), 1);
+            assert_eq!(errors[0].kind, TeXErrorKind::BadRegisterCode(-1234));
+        });
+    }
+
+    #[test]
+    fn it_fails_parsing_character_codes() {
+        with_parser(&[r"\chardef\a=300 \char-1 %"], |parser| {
+            parser.parse_assignment(None).unwrap();
+            assert_eq!(
+                parser
+                    .state
+                    .get_chardef(&Token::ControlSequence("a".to_string())),
+                Some(0 as char)
+            );
+            assert!(parser.parse_horizontal_list(true, false).is_ok());
+
+            let errors = parser.take_errors();
+            assert_eq!(errors.len(), 2);
+            assert_eq!(errors[0].kind, TeXErrorKind::BadCharacterCode(300));
+            assert_eq!(errors[0].kind.to_string(), "Bad character code (300)");
+            assert_eq!(errors[1].kind, TeXErrorKind::BadCharacterCode(-1));
+        });
+    }
+
+    #[test]
+    fn it_treats_missing_numbers_as_zero() {
+        with_parser(&["x%"], |parser| {
+            assert_eq!(parser.parse_number().unwrap(), 0);
+            assert_eq!(
+                parser.take_errors()[0].kind,
+                TeXErrorKind::MissingNumber
+            );
+            assert_eq!(
+                parser.lex_unexpanded_token().unwrap(),
+                Some(Token::Char('x', Category::Letter))
+            );
         });
     }
 
@@ -267,7 +407,7 @@ mod tests {
     fn it_parses_numbers_from_variables() {
         with_parser(&["\\count10%"], |parser| {
             parser.state.set_count(false, 10, 1234);
-            assert_eq!(parser.parse_number(), 1234);
+            assert_eq!(parser.parse_number().unwrap(), 1234);
         });
     }
 
@@ -275,17 +415,49 @@ mod tests {
     fn it_parses_negative_integer_variables() {
         with_parser(&["-\\count10%"], |parser| {
             parser.state.set_count(false, 10, 1234);
-            assert_eq!(parser.parse_number(), -1234);
+            assert_eq!(parser.parse_number().unwrap(), -1234);
         });
     }
 
     #[test]
     fn it_parses_hexadecimal_numbers() {
         with_parser(&[r#""0"#, r#""1289"#, r#""ABEF"#, r#""F0F"#], |parser| {
-            assert_eq!(parser.parse_number(), 0x0);
-            assert_eq!(parser.parse_number(), 0x1289);
-            assert_eq!(parser.parse_number(), 0xABEF);
-            assert_eq!(parser.parse_number(), 0xF0F);
+            assert_eq!(parser.parse_number().unwrap(), 0x0);
+            assert_eq!(parser.parse_number().unwrap(), 0x1289);
+            assert_eq!(parser.parse_number().unwrap(), 0xABEF);
+            assert_eq!(parser.parse_number().unwrap(), 0xF0F);
+        });
+    }
+
+    #[test]
+    fn it_treats_numbers_which_are_too_big_as_the_largest_integer() {
+        with_parser(
+            &["2147483647 %", "-99999999999 %", r#""FFFFFFFFF %"#],
+            |parser| {
+                assert_eq!(parser.parse_number().unwrap(), 2147483647);
+                assert_eq!(parser.parse_number().unwrap(), -2147483647);
+                assert_eq!(parser.parse_number().unwrap(), 2147483647);
+
+                let errors = parser.take_errors();
+                assert_eq!(errors.len(), 2);
+                assert_eq!(errors[0].kind, TeXErrorKind::NumberTooBig);
+                assert_eq!(errors[1].kind, TeXErrorKind::NumberTooBig);
+            },
+        );
+    }
+
+    #[test]
+    fn it_treats_hexadecimal_numbers_without_digits_as_zero() {
+        with_parser(&[r#""G%"#], |parser| {
+            assert_eq!(parser.parse_number().unwrap(), 0);
+            assert_eq!(
+                parser.take_errors()[0].kind,
+                TeXErrorKind::MissingNumber
+            );
+            assert_eq!(
+                parser.lex_unexpanded_token().unwrap(),
+                Some(Token::Char('G', Category::Letter))
+            );
         });
     }
 
@@ -294,28 +466,33 @@ mod tests {
         with_parser(&[r"`A%", r"`z%", r"`\a%", r"`\%%", r"`!%"], |parser| {
             parser.state.set_category(false, '!', Category::Active);
 
-            assert_eq!(parser.parse_number(), 65);
-            assert_eq!(parser.parse_number(), 122);
-            assert_eq!(parser.parse_number(), 97);
-            assert_eq!(parser.parse_number(), 37);
-            assert_eq!(parser.parse_number(), 33);
+            assert_eq!(parser.parse_number().unwrap(), 65);
+            assert_eq!(parser.parse_number().unwrap(), 122);
+            assert_eq!(parser.parse_number().unwrap(), 97);
+            assert_eq!(parser.parse_number().unwrap(), 37);
+            assert_eq!(parser.parse_number().unwrap(), 33);
         });
     }
 
     #[test]
-    #[should_panic(
-        expected = "Invalid control sequence in character number constant"
-    )]
     fn it_fails_when_parsing_invalid_control_sequence_character_numbers() {
         with_parser(&[r"`\abc%"], |parser| {
-            parser.parse_number();
+            assert_eq!(parser.parse_number().unwrap(), 48);
+            assert_eq!(
+                parser.take_errors()[0].kind,
+                TeXErrorKind::ImproperAlphabeticConstant
+            );
+            assert_eq!(
+                parser.lex_unexpanded_token().unwrap(),
+                Some(Token::ControlSequence("abc".to_string()))
+            );
         });
     }
 
     #[test]
     fn it_parses_coerced_dimens() {
         with_parser(&[r"\setbox0=\hbox{g}%", r"\wd0%", r"-\ht0%"], |parser| {
-            parser.parse_assignment(None);
+            parser.parse_assignment(None).unwrap();
 
             let metrics = parser
                 .state
@@ -326,11 +503,11 @@ mod tests {
                 .unwrap();
 
             assert_eq!(
-                parser.parse_number(),
+                parser.parse_number().unwrap(),
                 metrics.get_width('g').as_scaled_points()
             );
             assert_eq!(
-                parser.parse_number(),
+                parser.parse_number().unwrap(),
                 -metrics.get_height('g').as_scaled_points()
             );
         });
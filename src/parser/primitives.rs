@@ -3,6 +3,7 @@
  * the TeX grammar.
  */
 use crate::category::Category;
+use crate::error::{TeXErrorKind, TeXResult};
 use crate::parser::Parser;
 use crate::token::Token;
 
@@ -24,142 +25,159 @@ pub fn token_equals_keyword_char(tok: &Token, ch: char) -> bool {
 
 impl<'a> Parser<'a> {
     // Parse 0 or more space tokens and ignore them
-    pub fn parse_optional_spaces_unexpanded(&mut self) {
+    pub fn parse_optional_spaces_unexpanded(&mut self) -> TeXResult<()> {
         while let Some(Token::Char(_, Category::Space)) =
-            self.peek_unexpanded_token()
+            self.peek_unexpanded_token()?
         {
-            self.lex_unexpanded_token();
+            self.lex_unexpanded_token()?;
         }
+        Ok(())
     }
 
     // Parse 0 or more expanded space tokens and ignore them
-    pub fn parse_optional_spaces_expanded(&mut self) {
+    pub fn parse_optional_spaces_expanded(&mut self) -> TeXResult<()> {
         while let Some(Token::Char(_, Category::Space)) =
-            self.peek_expanded_token()
+            self.peek_expanded_token()?
         {
-            self.lex_expanded_token();
+            self.lex_expanded_token()?;
         }
+        Ok(())
     }
 
     // Parse 0 or 1 space tokens and ignore them
-    pub fn parse_optional_space_unexpanded(&mut self) {
+    pub fn parse_optional_space_unexpanded(&mut self) -> TeXResult<()> {
         if let Some(Token::Char(_, Category::Space)) =
-            self.peek_unexpanded_token()
+            self.peek_unexpanded_token()?
         {
-            self.lex_unexpanded_token();
+            self.lex_unexpanded_token()?;
         }
+        Ok(())
     }
 
     // Parse 0 or 1 expanded space tokens and ignore them
-    pub fn parse_optional_space_expanded(&mut self) {
+    pub fn parse_optional_space_expanded(&mut self) -> TeXResult<()> {
         if let Some(Token::Char(_, Category::Space)) =
-            self.peek_expanded_token()
+            self.peek_expanded_token()?
         {
-            self.lex_expanded_token();
+            self.lex_expanded_token()?;
         }
+        Ok(())
     }
 
     // Parses an <equals> without expanding tokens
-    pub fn parse_equals_unexpanded(&mut self) {
-        self.parse_optional_spaces_unexpanded();
+    pub fn parse_equals_unexpanded(&mut self) -> TeXResult<()> {
+        self.parse_optional_spaces_unexpanded()?;
         if let Some(Token::Char('=', Category::Other)) =
-            self.peek_unexpanded_token()
+            self.peek_unexpanded_token()?
         {
-            self.lex_unexpanded_token();
+            self.lex_unexpanded_token()?;
         }
+        Ok(())
     }
 
     // Parses an <equals> while expanding tokens
-    pub fn parse_equals_expanded(&mut self) {
-        self.parse_optional_spaces_expanded();
+    pub fn parse_equals_expanded(&mut self) -> TeXResult<()> {
+        self.parse_optional_spaces_expanded()?;
         if let Some(Token::Char('=', Category::Other)) =
-            self.peek_expanded_token()
+            self.peek_expanded_token()?
         {
-            self.lex_expanded_token();
+            self.lex_expanded_token()?;
         }
+        Ok(())
     }
 
     /// Parses a keyword (which is indicated in the TeXbook grammar by letters
-    /// in typewriter font). Panics if the keyword is not immediately found.
-    pub fn parse_keyword_expanded(&mut self, keyword: &str) {
-        self.parse_optional_spaces_expanded();
+    /// in typewriter font). If the keyword is not immediately found, an error
+    /// is reported and we continue as if it had been there.
+    pub fn parse_keyword_expanded(&mut self, keyword: &str) -> TeXResult<()> {
+        self.parse_optional_spaces_expanded()?;
 
         for keyword_char in keyword.chars() {
-            let token = self.lex_expanded_token();
+            let token = self.lex_expanded_token()?;
             match token {
                 Some(ref tok)
                     if token_equals_keyword_char(tok, keyword_char) => {}
-                _ => panic!(
-                    "Found invalid token {:?} while parsing keyword {}",
-                    token, keyword
-                ),
+                _ => {
+                    self.report_error(TeXErrorKind::MissingKeyword(
+                        keyword.to_string(),
+                    ))?;
+                    if let Some(tok) = token {
+                        self.add_upcoming_token(tok);
+                    }
+                    break;
+                }
             }
         }
+        Ok(())
     }
 
     /// Parses an optional keyword. Returns true if the keyword was parsed and
     /// false if not.
-    pub fn parse_optional_keyword_expanded(&mut self, keyword: &str) -> bool {
-        self.parse_optional_spaces_expanded();
+    pub fn parse_optional_keyword_expanded(
+        &mut self,
+        keyword: &str,
+    ) -> TeXResult<bool> {
+        self.parse_optional_spaces_expanded()?;
 
         let first_char = keyword.chars().next().unwrap();
 
         // If the first token doesn't match the keyword we're looking for, just
         // bail out now.
-        match self.peek_expanded_token() {
+        match self.peek_expanded_token()? {
             Some(ref tok) if token_equals_keyword_char(tok, first_char) => (),
-            _ => return false,
+            _ => return Ok(false),
         }
 
         // Now that we're confident the keyword is actually there, parse it.
         let mut parsed_toks = Vec::new();
 
         for keyword_char in keyword.chars() {
-            let token = self.peek_expanded_token();
+            let token = self.peek_expanded_token()?;
             match token {
                 Some(ref tok)
                     if token_equals_keyword_char(tok, keyword_char) =>
                 {
-                    let tok = self.lex_expanded_token().unwrap();
+                    let tok = self.lex_expanded_token()?.unwrap();
                     parsed_toks.push(tok);
                 }
                 _ => {
                     self.add_upcoming_tokens(parsed_toks);
-                    return false;
+                    return Ok(false);
                 }
             }
         }
 
-        true
+        Ok(true)
     }
 
     /// Parses a <filler>, which is any amount of spaces and \relax
-    pub fn parse_filler_expanded(&mut self) {
-        self.parse_optional_spaces_expanded();
+    pub fn parse_filler_expanded(&mut self) -> TeXResult<()> {
+        self.parse_optional_spaces_expanded()?;
         loop {
-            match self.peek_expanded_token() {
+            match self.peek_expanded_token()? {
                 None => break,
                 Some(token) => {
                     if self.state.is_token_equal_to_prim(&token, "relax") {
-                        self.lex_expanded_token();
+                        self.lex_expanded_token()?;
                     } else {
                         break;
                     }
                 }
             }
-            self.parse_optional_spaces_expanded();
+            self.parse_optional_spaces_expanded()?;
         }
+        Ok(())
     }
 
     pub fn is_next_expanded_token_in_set_of_primitives(
         &mut self,
         primitives: &[&str],
-    ) -> bool {
-        match self.peek_expanded_token() {
-            Some(token) => primitives
+    ) -> TeXResult<bool> {
+        match self.peek_expanded_token()? {
+            Some(token) => Ok(primitives
                 .iter()
-                .any(|prim| self.state.is_token_equal_to_prim(&token, prim)),
-            _ => false,
+                .any(|prim| self.state.is_token_equal_to_prim(&token, prim))),
+            _ => Ok(false),
         }
     }
 
@@ -180,35 +198,38 @@ impl<'a> Parser<'a> {
             .map(|token| self.state.get_renamed_token(&token).unwrap_or(token))
     }
 
-    fn parse_file_name_character(&mut self) -> Option<char> {
-        let ch = match self.peek_expanded_token() {
-            Some(Token::Char(ch, _)) => Some(ch),
-            _ => None,
-        }?;
+    fn parse_file_name_character(&mut self) -> TeXResult<Option<char>> {
+        let ch = match self.peek_expanded_token()? {
+            Some(Token::Char(ch, _)) => ch,
+            _ => return Ok(None),
+        };
 
         if '0' <= ch && ch <= '9'
             || 'a' <= ch && ch <= 'z'
             || 'A' <= ch && ch <= 'Z'
             || ch == '-'
             || ch == '_'
+            || ch == '.'
+            || ch == '/'
         {
-            self.lex_expanded_token();
-            Some(ch)
+            self.lex_expanded_token()?;
+            Ok(Some(ch))
         } else {
-            None
+            Ok(None)
         }
     }
 
     /// Parses a filename. A filename consists of a consecutive string of
-    /// alphanumeric characters and - and _ of any character token type.
-    pub fn parse_file_name(&mut self) -> String {
-        self.parse_optional_spaces_expanded();
+    /// alphanumeric characters and -, _, ., and / of any character token
+    /// type.
+    pub fn parse_file_name(&mut self) -> TeXResult<String> {
+        self.parse_optional_spaces_expanded()?;
 
         let mut chars = Vec::new();
-        while let Some(ch) = self.parse_file_name_character() {
+        while let Some(ch) = self.parse_file_name_character()? {
             chars.push(ch);
         }
-        chars.into_iter().collect::<String>()
+        Ok(chars.into_iter().collect::<String>())
     }
 }
 
@@ -223,42 +244,42 @@ mod tests {
         // Testing a single, unexpanded optional space
         with_parser(&["a aa%"], |parser| {
             assert_eq!(
-                parser.lex_unexpanded_token(),
+                parser.lex_unexpanded_token().unwrap(),
                 Some(Token::Char('a', Category::Letter))
             );
-            parser.parse_optional_space_unexpanded();
+            parser.parse_optional_space_unexpanded().unwrap();
             assert_eq!(
-                parser.lex_unexpanded_token(),
+                parser.lex_unexpanded_token().unwrap(),
                 Some(Token::Char('a', Category::Letter))
             );
-            parser.parse_optional_space_unexpanded();
+            parser.parse_optional_space_unexpanded().unwrap();
             assert_eq!(
-                parser.lex_unexpanded_token(),
+                parser.lex_unexpanded_token().unwrap(),
                 Some(Token::Char('a', Category::Letter))
             );
         });
 
         // Now with an expanded space
         with_parser(&["\\def\\x{ }%", "a a\\x aa%"], |parser| {
-            parser.parse_assignment(None);
+            parser.parse_assignment(None).unwrap();
 
             assert_eq!(
-                parser.lex_expanded_token(),
+                parser.lex_expanded_token().unwrap(),
                 Some(Token::Char('a', Category::Letter))
             );
-            parser.parse_optional_space_expanded();
+            parser.parse_optional_space_expanded().unwrap();
             assert_eq!(
-                parser.lex_expanded_token(),
+                parser.lex_expanded_token().unwrap(),
                 Some(Token::Char('a', Category::Letter))
             );
-            parser.parse_optional_space_expanded();
+            parser.parse_optional_space_expanded().unwrap();
             assert_eq!(
-                parser.lex_expanded_token(),
+                parser.lex_expanded_token().unwrap(),
                 Some(Token::Char('a', Category::Letter))
             );
-            parser.parse_optional_space_expanded();
+            parser.parse_optional_space_expanded().unwrap();
             assert_eq!(
-                parser.lex_expanded_token(),
+                parser.lex_expanded_token().unwrap(),
                 Some(Token::Char('a', Category::Letter))
             );
         });
@@ -271,47 +292,47 @@ mod tests {
         // the lexer, so we only test one space).
         with_parser(&["a aa%"], |parser| {
             assert_eq!(
-                parser.lex_unexpanded_token(),
+                parser.lex_unexpanded_token().unwrap(),
                 Some(Token::Char('a', Category::Letter))
             );
-            parser.parse_optional_spaces_unexpanded();
+            parser.parse_optional_spaces_unexpanded().unwrap();
             assert_eq!(
-                parser.lex_unexpanded_token(),
+                parser.lex_unexpanded_token().unwrap(),
                 Some(Token::Char('a', Category::Letter))
             );
-            parser.parse_optional_spaces_unexpanded();
+            parser.parse_optional_spaces_unexpanded().unwrap();
             assert_eq!(
-                parser.lex_unexpanded_token(),
+                parser.lex_unexpanded_token().unwrap(),
                 Some(Token::Char('a', Category::Letter))
             );
         });
 
         // Testing multiple optional expanded spaces
         with_parser(&["\\def\\x{ }%", "aa a \\x a\\x\\x a%"], |parser| {
-            parser.parse_assignment(None);
+            parser.parse_assignment(None).unwrap();
 
             assert_eq!(
-                parser.lex_expanded_token(),
+                parser.lex_expanded_token().unwrap(),
                 Some(Token::Char('a', Category::Letter))
             );
-            parser.parse_optional_spaces_expanded();
+            parser.parse_optional_spaces_expanded().unwrap();
             assert_eq!(
-                parser.lex_expanded_token(),
+                parser.lex_expanded_token().unwrap(),
                 Some(Token::Char('a', Category::Letter))
             );
-            parser.parse_optional_spaces_expanded();
+            parser.parse_optional_spaces_expanded().unwrap();
             assert_eq!(
-                parser.lex_expanded_token(),
+                parser.lex_expanded_token().unwrap(),
                 Some(Token::Char('a', Category::Letter))
             );
-            parser.parse_optional_spaces_expanded();
+            parser.parse_optional_spaces_expanded().unwrap();
             assert_eq!(
-                parser.lex_expanded_token(),
+                parser.lex_expanded_token().unwrap(),
                 Some(Token::Char('a', Category::Letter))
             );
-            parser.parse_optional_spaces_expanded();
+            parser.parse_optional_spaces_expanded().unwrap();
             assert_eq!(
-                parser.lex_expanded_token(),
+                parser.lex_expanded_token().unwrap(),
                 Some(Token::Char('a', Category::Letter))
             );
         });
@@ -321,17 +342,17 @@ mod tests {
     fn it_parses_equals_signs() {
         with_parser(&["a=a  =a%"], |parser| {
             assert_eq!(
-                parser.lex_unexpanded_token(),
+                parser.lex_unexpanded_token().unwrap(),
                 Some(Token::Char('a', Category::Letter))
             );
-            parser.parse_equals_unexpanded();
+            parser.parse_equals_unexpanded().unwrap();
             assert_eq!(
-                parser.lex_unexpanded_token(),
+                parser.lex_unexpanded_token().unwrap(),
                 Some(Token::Char('a', Category::Letter))
             );
-            parser.parse_equals_unexpanded();
+            parser.parse_equals_unexpanded().unwrap();
             assert_eq!(
-                parser.lex_unexpanded_token(),
+                parser.lex_unexpanded_token().unwrap(),
                 Some(Token::Char('a', Category::Letter))
             );
         });
@@ -340,41 +361,69 @@ mod tests {
     #[test]
     fn it_parses_optional_keywords() {
         with_parser(&["   by    pt   TrUe%"], |parser| {
-            assert_eq!(parser.parse_optional_keyword_expanded("by"), true);
+            assert_eq!(
+                parser.parse_optional_keyword_expanded("by").unwrap(),
+                true
+            );
 
-            assert_eq!(parser.parse_optional_keyword_expanded("by"), false);
-            assert_eq!(parser.parse_optional_keyword_expanded("pc"), false);
-            assert_eq!(parser.parse_optional_keyword_expanded("pt"), true);
+            assert_eq!(
+                parser.parse_optional_keyword_expanded("by").unwrap(),
+                false
+            );
+            assert_eq!(
+                parser.parse_optional_keyword_expanded("pc").unwrap(),
+                false
+            );
+            assert_eq!(
+                parser.parse_optional_keyword_expanded("pt").unwrap(),
+                true
+            );
 
-            assert_eq!(parser.parse_optional_keyword_expanded("pt"), false);
-            assert_eq!(parser.parse_optional_keyword_expanded("trust"), false);
-            assert_eq!(parser.parse_optional_keyword_expanded("true"), true);
+            assert_eq!(
+                parser.parse_optional_keyword_expanded("pt").unwrap(),
+                false
+            );
+            assert_eq!(
+                parser.parse_optional_keyword_expanded("trust").unwrap(),
+                false
+            );
+            assert_eq!(
+                parser.parse_optional_keyword_expanded("true").unwrap(),
+                true
+            );
         });
     }
 
     #[test]
     fn it_parses_required_keywords() {
         with_parser(&[" pt  minus  fillll%"], |parser| {
-            parser.parse_keyword_expanded("pt");
-            parser.parse_keyword_expanded("minus");
-            parser.parse_keyword_expanded("fillll");
+            parser.parse_keyword_expanded("pt").unwrap();
+            parser.parse_keyword_expanded("minus").unwrap();
+            parser.parse_keyword_expanded("fillll").unwrap();
         });
     }
 
     #[test]
-    #[should_panic(expected = "while parsing keyword")]
     fn it_fails_parsing_missing_required_keywords() {
-        with_parser(&[" blah"], |parser| {
-            parser.parse_keyword_expanded("pt");
+        with_parser(&[" pa%"], |parser| {
+            parser.parse_keyword_expanded("pt").unwrap();
+            assert_eq!(
+                parser.take_errors()[0].kind,
+                TeXErrorKind::MissingKeyword("pt".to_string())
+            );
+            assert_eq!(
+                parser.lex_unexpanded_token().unwrap(),
+                Some(Token::Char('a', Category::Letter))
+            );
         });
     }
 
     #[test]
     fn it_fetches_renamed_tokens() {
         with_parser(&[r"\let\bgroup={%", r"\bgroup"], |parser| {
-            parser.parse_assignment(None);
+            parser.parse_assignment(None).unwrap();
 
-            let unreplaced = parser.lex_unexpanded_token();
+            let unreplaced = parser.lex_unexpanded_token().unwrap();
             assert_eq!(
                 parser.replace_renamed_token(unreplaced),
                 Some(Token::Char('{', Category::BeginGroup))
@@ -385,27 +434,37 @@ mod tests {
     #[test]
     fn it_parses_file_names() {
         with_parser(&[r"cmr10%"], |parser| {
-            assert_eq!(parser.parse_file_name(), "cmr10".to_string(),);
+            assert_eq!(parser.parse_file_name().unwrap(), "cmr10".to_string(),);
         });
 
         with_parser(&[r"azAZ09%"], |parser| {
-            assert_eq!(parser.parse_file_name(), "azAZ09".to_string(),);
+            assert_eq!(parser.parse_file_name().unwrap(), "azAZ09".to_string(),);
         });
 
         with_parser(&[r"a-font_name%"], |parser| {
-            assert_eq!(parser.parse_file_name(), "a-font_name".to_string(),);
+            assert_eq!(
+                parser.parse_file_name().unwrap(),
+                "a-font_name".to_string(),
+            );
         });
 
         with_parser(&[r"abcdefghijklmnop%"], |parser| {
             assert_eq!(
-                parser.parse_file_name(),
+                parser.parse_file_name().unwrap(),
                 "abcdefghijklmnop".to_string(),
             );
         });
 
+        with_parser(&[r"../dir/file.tex%"], |parser| {
+            assert_eq!(
+                parser.parse_file_name().unwrap(),
+                "../dir/file.tex".to_string(),
+            );
+        });
+
         with_parser(&[r"abc+%"], |parser| {
-            assert_eq!(parser.parse_file_name(), "abc".to_string(),);
-            assert!(parser.lex_unexpanded_token().is_some());
+            assert_eq!(parser.parse_file_name().unwrap(), "abc".to_string(),);
+            assert!(parser.lex_unexpanded_token().unwrap().is_some());
         });
     }
 }
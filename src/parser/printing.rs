@@ -1,12 +1,66 @@
 use crate::category::Category;
+use crate::error::{TeXErrorKind, TeXResult};
+use crate::font::Font;
+use crate::parameter::IntegerParameter;
 use crate::parser::Parser;
 use crate::token::Token;
 
+// Writes a number as lowercase roman numerals the way TeX does, which is
+// empty for numbers that aren't positive. Each letter in `digits` is followed
+// by how many times smaller the next letter is.
+fn roman_numeral(mut value: i32) -> String {
+    let digits = b"m2d5c2l5x2v5i";
+    let ratio = |index: usize| (digits[index] - b'0') as i32;
+
+    let mut result = String::new();
+    let mut j = 0;
+    let mut v = 1000;
+    loop {
+        while value >= v {
+            result.push(digits[j] as char);
+            value -= v;
+        }
+        if value <= 0 {
+            return result;
+        }
+
+        // See if we can write the value as the next smaller letter that's a
+        // power of ten before this one, like the i in iv.
+        let mut k = j + 2;
+        let mut u = v / ratio(k - 1);
+        if digits[k - 1] == b'2' {
+            k += 2;
+            u /= ratio(k - 1);
+        }
+        if value + u >= v {
+            result.push(digits[k] as char);
+            value += u;
+        } else {
+            j += 2;
+            v /= ratio(j - 1);
+        }
+    }
+}
+
 impl<'a> Parser<'a> {
-    pub fn is_print_head(&mut self) -> bool {
-        match self.peek_unexpanded_token() {
-            Some(token) => self.state.is_token_equal_to_prim(&token, "number"),
-            _ => false,
+    /// Sets the name that \jobname expands to. This is "texput" unless it's
+    /// set.
+    pub fn set_job_name(&mut self, job_name: &str) {
+        self.job_name = job_name.to_string();
+    }
+
+    pub fn is_print_head(&mut self) -> TeXResult<bool> {
+        match self.peek_unexpanded_token()? {
+            Some(token) => Ok(self
+                .state
+                .is_token_equal_to_prim(&token, "number")
+                || self.state.is_token_equal_to_prim(&token, "romannumeral")
+                || self.state.is_token_equal_to_prim(&token, "the")
+                || self.state.is_token_equal_to_prim(&token, "meaning")
+                || self.state.is_token_equal_to_prim(&token, "string")
+                || self.state.is_token_equal_to_prim(&token, "fontname")
+                || self.state.is_token_equal_to_prim(&token, "jobname")),
+            _ => Ok(false),
         }
     }
 
@@ -20,14 +74,133 @@ impl<'a> Parser<'a> {
             .collect()
     }
 
-    pub fn expand_print(&mut self) -> Vec<Token> {
-        let head = self.lex_unexpanded_token().unwrap();
+    // Turns text into tokens the way TeX does when it prints something into
+    // the input, where spaces become Space tokens and everything else becomes
+    // an Other token.
+    fn print_string(&mut self, string: &str) -> Vec<Token> {
+        string
+            .chars()
+            .map(|chr| {
+                if chr == ' ' {
+                    Token::Char(chr, Category::Space)
+                } else {
+                    Token::Char(chr, Category::Other)
+                }
+            })
+            .collect()
+    }
+
+    fn is_font_head(&mut self) -> TeXResult<bool> {
+        match self.peek_expanded_token()? {
+            Some(token) => {
+                Ok(self.state.is_token_equal_to_prim(&token, "font")
+                    || self.state.get_fontdef(&token).is_some())
+            }
+            None => Ok(false),
+        }
+    }
+
+    // Reads a font identifier, like \font or \tenrm. If there isn't one, an
+    // error is reported and the token is left to be read again. None stands
+    // for \nullfont, which TeX uses instead.
+    fn parse_font_identifier(&mut self) -> TeXResult<Option<Font>> {
+        if !self.is_font_head()? {
+            self.report_error(TeXErrorKind::MissingFontIdentifier)?;
+            return Ok(None);
+        }
+
+        let token = self.lex_expanded_token()?.unwrap();
+        Ok(Some(match self.state.get_fontdef(&token) {
+            Some(font) => font,
+            None => self.state.get_current_font(),
+        }))
+    }
+
+    // Returns the text of a control sequence with the current \escapechar in
+    // front of it. Like in TeX, there's nothing in front of it if
+    // \escapechar isn't a character code.
+    fn get_escaped_control_sequence(&self, name: &str) -> String {
+        let escape_char = self
+            .state
+            .get_integer_parameter(IntegerParameter::EscapeChar);
+        if (0..=255).contains(&escape_char) {
+            format!("{}{}", escape_char as u8 as char, name)
+        } else {
+            name.to_string()
+        }
+    }
+
+    /// Reads the internal quantity after a \the (or \showthe) and returns the
+    /// tokens that it expands to. Token lists expand to themselves, fonts to
+    /// their identifier, and everything else to the characters that TeX
+    /// would print for the value, like "3.0pt plus 1.0fil".
+    pub fn parse_the_tokens(&mut self) -> TeXResult<Vec<Token>> {
+        let value = if self.is_token_list_variable_head()? {
+            let variable = self.parse_token_list_variable()?;
+            return Ok(variable.get(self.state).to_vec());
+        } else if self.is_font_head()? {
+            let font = self.parse_font_identifier()?.unwrap();
+            let identifier = match self.state.get_font_identifier(&font) {
+                Some(identifier) => identifier,
+                None => Token::ControlSequence(font.font_name),
+            };
+            return Ok(vec![identifier]);
+        } else if self.is_internal_integer_head()? {
+            self.parse_internal_integer()?.to_string()
+        } else if self.is_internal_dimen_head()? {
+            self.parse_internal_dimen()?.to_string()
+        } else if self.is_internal_glue_head()? {
+            self.parse_internal_glue()?.to_string()
+        } else if self.is_internal_mu_glue_head()? {
+            self.parse_internal_mu_glue()?.to_string()
+        } else {
+            // Like TeX, we skip the token and use 0 for the value.
+            if let Some(token) = self.lex_expanded_token()? {
+                self.report_error(TeXErrorKind::CantUseAfterThe(token))?;
+            }
+            "0".to_string()
+        };
+        Ok(self.print_string(&value))
+    }
+
+    pub fn expand_print(&mut self) -> TeXResult<Vec<Token>> {
+        let head = self.lex_unexpanded_token()?.unwrap();
 
         if self.state.is_token_equal_to_prim(&head, "number") {
-            let value = self.parse_number();
-            self.print_number(value)
+            let value = self.parse_number()?;
+            Ok(self.print_number(value))
+        } else if self.state.is_token_equal_to_prim(&head, "romannumeral") {
+            let value = self.parse_number()?;
+            Ok(self.print_string(&roman_numeral(value)))
+        } else if self.state.is_token_equal_to_prim(&head, "the") {
+            self.parse_the_tokens()
+        } else if self.state.is_token_equal_to_prim(&head, "meaning") {
+            let meaning = match self.lex_unexpanded_token()? {
+                Some(token) => self.get_meaning(&token),
+                None => return Ok(Vec::new()),
+            };
+            Ok(self.print_string(&meaning))
+        } else if self.state.is_token_equal_to_prim(&head, "string") {
+            let text = match self.lex_unexpanded_token()? {
+                Some(Token::ControlSequence(name)) => {
+                    self.get_escaped_control_sequence(&name)
+                }
+                Some(Token::Char(ch, _)) => ch.to_string(),
+                None => return Ok(Vec::new()),
+            };
+            Ok(self.print_string(&text))
+        } else if self.state.is_token_equal_to_prim(&head, "fontname") {
+            let text = match self.parse_font_identifier()? {
+                Some(font) => self.get_font_description(&font),
+                None => "nullfont".to_string(),
+            };
+            Ok(self.print_string(&text))
+        } else if self.state.is_token_equal_to_prim(&head, "jobname") {
+            let job_name = self.job_name.clone();
+            Ok(self.print_string(&job_name))
         } else {
-            panic!("unimplemented");
+            // This is only called after is_print_head().
+            unreachable!("Invalid print head: {:?}", head);
         }
     }
 }
@@ -51,16 +224,16 @@ mod tests {
                 parser.state.set_count(false, 1, 123);
 
                 // 0
-                assert!(parser.is_print_head());
+                assert!(parser.is_print_head().unwrap());
                 assert_eq!(
-                    parser.expand_print(),
+                    parser.expand_print().unwrap(),
                     vec![Token::Char('0', Category::Other)]
                 );
 
                 // 12345
-                assert!(parser.is_print_head());
+                assert!(parser.is_print_head().unwrap());
                 assert_eq!(
-                    parser.expand_print(),
+                    parser.expand_print().unwrap(),
                     vec![
                         Token::Char('1', Category::Other),
                         Token::Char('2', Category::Other),
@@ -71,9 +244,9 @@ mod tests {
                 );
 
                 // -1
-                assert!(parser.is_print_head());
+                assert!(parser.is_print_head().unwrap());
                 assert_eq!(
-                    parser.expand_print(),
+                    parser.expand_print().unwrap(),
                     vec![
                         Token::Char('-', Category::Other),
                         Token::Char('1', Category::Other),
@@ -81,9 +254,9 @@ mod tests {
                 );
 
                 // \count1 = 123
-                assert!(parser.is_print_head());
+                assert!(parser.is_print_head().unwrap());
                 assert_eq!(
-                    parser.expand_print(),
+                    parser.expand_print().unwrap(),
                     vec![
                         Token::Char('1', Category::Other),
                         Token::Char('2', Category::Other),
@@ -93,4 +266,214 @@ mod tests {
             },
         );
     }
+
+    #[test]
+    fn it_expands_the() {
+        with_parser(
+            &[
+                r"\count1=-5 \dimen2=12.5pt \skip3=3pt plus 1fil minus 1pt %",
+                r"\thinmuskip=3mu plus 2fill minus 1mu \font\x=cmr10 %",
+                r"\toks4={\a b}%",
+                r"\the\count1 %",
+                r"\the\dimen2 %",
+                r"\the\skip3 %",
+                r"\the\thinmuskip%",
+                r"\the\catcode`a %",
+                r"\the\x%",
+                r"\the\toks4 %",
+            ],
+            |parser| {
+                for _ in 0..6 {
+                    parser.parse_assignment(None).unwrap();
+                }
+
+                let printed = |text: &str| -> Vec<Token> {
+                    text.chars()
+                        .map(|chr| match chr {
+                            ' ' => Token::Char(' ', Category::Space),
+                            chr => Token::Char(chr, Category::Other),
+                        })
+                        .collect()
+                };
+
+                assert!(parser.is_print_head().unwrap());
+                assert_eq!(parser.expand_print().unwrap(), printed("-5"));
+                assert!(parser.is_print_head().unwrap());
+                assert_eq!(parser.expand_print().unwrap(), printed("12.5pt"));
+                assert!(parser.is_print_head().unwrap());
+                assert_eq!(
+                    parser.expand_print().unwrap(),
+                    printed("3.0pt plus 1.0fil minus 1.0pt")
+                );
+                assert!(parser.is_print_head().unwrap());
+                assert_eq!(
+                    parser.expand_print().unwrap(),
+                    printed("3.0mu plus 2.0fill minus 1.0mu")
+                );
+                assert!(parser.is_print_head().unwrap());
+                assert_eq!(parser.expand_print().unwrap(), printed("11"));
+                assert!(parser.is_print_head().unwrap());
+                assert_eq!(
+                    parser.expand_print().unwrap(),
+                    vec![Token::ControlSequence("x".to_string())]
+                );
+                assert!(parser.is_print_head().unwrap());
+                assert_eq!(
+                    parser.expand_print().unwrap(),
+                    vec![
+                        Token::ControlSequence("a".to_string()),
+                        Token::Char('b', Category::Letter),
+                    ]
+                );
+            },
+        );
+    }
+
+    #[test]
+    fn it_writes_roman_numerals() {
+        assert_eq!(roman_numeral(1984), "mcmlxxxiv");
+        assert_eq!(roman_numeral(4), "iv");
+        assert_eq!(roman_numeral(49), "xlix");
+        assert_eq!(roman_numeral(2999), "mmcmxcix");
+        assert_eq!(roman_numeral(0), "");
+        assert_eq!(roman_numeral(-5), "");
+    }
+
+    #[test]
+    fn it_expands_romannumerals() {
+        with_parser(&[r"\romannumeral 12 \romannumeral0 %"], |parser| {
+            let other = |chr| Token::Char(chr, Category::Other);
+
+            assert!(parser.is_print_head().unwrap());
+            assert_eq!(
+                parser.expand_print().unwrap(),
+                vec![other('x'), other('i'), other('i')]
+            );
+            assert!(parser.is_print_head().unwrap());
+            assert_eq!(parser.expand_print().unwrap(), vec![]);
+        });
+    }
+
+    #[test]
+    fn it_expands_jobnames() {
+        with_parser(&[r"\jobname\jobname"], |parser| {
+            let other = |chr| Token::Char(chr, Category::Other);
+
+            assert!(parser.is_print_head().unwrap());
+            assert_eq!(
+                parser.expand_print().unwrap(),
+                "texput".chars().map(other).collect::<Vec<_>>()
+            );
+
+            parser.set_job_name("story");
+            assert!(parser.is_print_head().unwrap());
+            assert_eq!(
+                parser.expand_print().unwrap(),
+                "story".chars().map(other).collect::<Vec<_>>()
+            );
+        });
+    }
+
+    #[test]
+    fn it_expands_fontnames() {
+        with_parser(
+            &[
+                r"\font\a=cmr10 \font\b=cmr10 at 5pt %",
+                r"\fontname\a\fontname\b\fontname\font\fontname1%",
+            ],
+            |parser| {
+                parser.parse_assignment(None).unwrap();
+                parser.parse_assignment(None).unwrap();
+
+                let printed = |text: &str| -> Vec<Token> {
+                    text.chars()
+                        .map(|chr| match chr {
+                            ' ' => Token::Char(' ', Category::Space),
+                            chr => Token::Char(chr, Category::Other),
+                        })
+                        .collect()
+                };
+
+                assert!(parser.is_print_head().unwrap());
+                assert_eq!(parser.expand_print().unwrap(), printed("cmr10"));
+                assert!(parser.is_print_head().unwrap());
+                assert_eq!(
+                    parser.expand_print().unwrap(),
+                    printed("cmr10 at 5.0pt")
+                );
+                assert!(parser.is_print_head().unwrap());
+                assert_eq!(parser.expand_print().unwrap(), printed("cmr10"));
+
+                // The 1 isn't a font, so it's left to be read again.
+                assert!(parser.is_print_head().unwrap());
+                assert_eq!(parser.expand_print().unwrap(), printed("nullfont"));
+                assert_eq!(
+                    parser.take_errors()[0].kind,
+                    TeXErrorKind::MissingFontIdentifier
+                );
+                assert_eq!(
+                    parser.lex_unexpanded_token().unwrap(),
+                    Some(Token::Char('1', Category::Other))
+                );
+            },
+        );
+    }
+
+    #[test]
+    fn it_uses_the_escapechar_in_strings() {
+        with_parser(
+            &[r"\escapechar=33 \string\a", r"\escapechar=-1 \string\a"],
+            |parser| {
+                let other = |chr| Token::Char(chr, Category::Other);
+
+                parser.parse_assignment(None).unwrap();
+                assert!(parser.is_print_head().unwrap());
+                assert_eq!(
+                    parser.expand_print().unwrap(),
+                    vec![other('!'), other('a')]
+                );
+
+                parser.parse_assignment(None).unwrap();
+                assert!(parser.is_print_head().unwrap());
+                assert_eq!(parser.expand_print().unwrap(), vec![other('a')]);
+            },
+        );
+    }
+
+    #[test]
+    fn it_expands_meanings() {
+        with_parser(&[r"\def\a{b c}%", r"\meaning\a"], |parser| {
+            parser.parse_assignment(None).unwrap();
+
+            assert!(parser.is_print_head().unwrap());
+            let expected: Vec<Token> = "macro:->b c"
+                .chars()
+                .map(|chr| match chr {
+                    ' ' => Token::Char(' ', Category::Space),
+                    chr => Token::Char(chr, Category::Other),
+                })
+                .collect();
+            assert_eq!(parser.expand_print().unwrap(), expected);
+        });
+    }
+
+    #[test]
+    fn it_expands_strings() {
+        with_parser(&[r"\string\abc\string a\string\ %"], |parser| {
+            let other = |chr| Token::Char(chr, Category::Other);
+
+            assert!(parser.is_print_head().unwrap());
+            assert_eq!(
+                parser.expand_print().unwrap(),
+                vec![other('\\'), other('a'), other('b'), other('c')]
+            );
+            assert!(parser.is_print_head().unwrap());
+            assert_eq!(parser.expand_print().unwrap(), vec![other('a')]);
+            assert!(parser.is_print_head().unwrap());
+            assert_eq!(
+                parser.expand_print().unwrap(),
+                vec![other('\\'), Token::Char(' ', Category::Space)]
+            );
+        });
+    }
 }
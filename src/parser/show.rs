@@ -0,0 +1,412 @@
+use crate::box_display::BoxFormatter;
+use crate::category::Category;
+use crate::error::TeXResult;
+use crate::font::Font;
+use crate::parameter::IntegerParameter;
+use crate::parser::Parser;
+use crate::state::TokenDefinition;
+use crate::token::{format_token_list, Token};
+
+// Describes what a character token means, the way that TeX does in \meaning
+// and \show.
+fn char_meaning(ch: char, cat: Category) -> String {
+    let description = match cat {
+        Category::BeginGroup => "begin-group character",
+        Category::EndGroup => "end-group character",
+        Category::MathShift => "math shift character",
+        Category::AlignmentTab => "alignment tab character",
+        Category::Parameter => "macro parameter character",
+        Category::Superscript => "superscript character",
+        Category::Subscript => "subscript character",
+        Category::Space => "blank space",
+        Category::Letter => "the letter",
+        _ => "the character",
+    };
+    format!("{} {}", description, ch)
+}
+
+impl<'a> Parser<'a> {
+    // Describes a font the way TeX does, including the size it was loaded at
+    // if that's different from its design size.
+    pub fn get_font_description(&self, font: &Font) -> String {
+        let design_size = self
+            .state
+            .with_metrics_for_font(font, |metrics| metrics.get_design_size());
+
+        match design_size {
+            Some(design_size)
+                if font.scale.as_scaled_points()
+                    != (design_size * 65536.0) as i32 =>
+            {
+                format!("{} at {}", font.font_name, font.scale)
+            }
+            _ => font.font_name.clone(),
+        }
+    }
+
+    // Returns the control sequence that TeX uses to refer to a font when
+    // showing boxes, which is the one that most recently loaded it.
+    pub fn get_font_identifier_text(&self, font: &Font) -> String {
+        match self.state.get_font_identifier(font) {
+            Some(Token::ControlSequence(name)) => format!("\\{}", name),
+            Some(Token::Char(ch, _)) => ch.to_string(),
+            None => format!("\\{}", font.font_name),
+        }
+    }
+
+    /// Returns the current meaning of a token as text, in the same format as
+    /// TeX's \meaning (e.g. "macro:#1->x#1" or "the letter a").
+    pub fn get_meaning(&self, token: &Token) -> String {
+        if let Token::Char(ch, cat) = token {
+            if *cat != Category::Active {
+                return char_meaning(*ch, *cat);
+            }
+        }
+
+        if let Some(makro) = self.state.get_macro(token) {
+            let mut prefixes = String::new();
+            if makro.is_long() {
+                prefixes.push_str("\\long");
+            }
+            if makro.is_outer() {
+                prefixes.push_str("\\outer");
+            }
+            if !prefixes.is_empty() {
+                prefixes.push(' ');
+            }
+            format!("{}macro:{}", prefixes, makro)
+        } else if let Some(Token::Char(ch, cat)) =
+            self.state.get_renamed_token(token)
+        {
+            char_meaning(ch, cat)
+        } else if let Some(primitive) = self.state.get_primitive(token) {
+            format!("\\{}", primitive)
+        } else if let Some(font) = self.state.get_fontdef(token) {
+            format!("select font {}", self.get_font_description(&font))
+        } else if let Some(math_code) = self.state.get_math_chardef(token) {
+            format!("\\mathchar\"{:X}", math_code.to_number())
+        } else if let Some(definition) = self.state.get_definition(token) {
+            match definition {
+                TokenDefinition::Char(ch) => format!("\\char\"{:X}", ch as u32),
+                TokenDefinition::CountRegister(index) => {
+                    format!("\\count{}", index)
+                }
+                TokenDefinition::DimenRegister(index) => {
+                    format!("\\dimen{}", index)
+                }
+                TokenDefinition::SkipRegister(index) => {
+                    format!("\\skip{}", index)
+                }
+                TokenDefinition::MuSkipRegister(index) => {
+                    format!("\\muskip{}", index)
+                }
+                TokenDefinition::ToksRegister(index) => {
+                    format!("\\toks{}", index)
+                }
+                _ => "undefined".to_string(),
+            }
+        } else {
+            "undefined".to_string()
+        }
+    }
+
+    pub fn is_show_head(&mut self) -> TeXResult<bool> {
+        self.is_next_expanded_token_in_set_of_primitives(&[
+            "show", "showthe", "showbox",
+        ])
+    }
+
+    pub fn parse_show(&mut self) -> TeXResult<()> {
+        let head = self.lex_expanded_token()?.unwrap();
+
+        if self.state.is_token_equal_to_prim(&head, "show") {
+            let token = match self.lex_unexpanded_token()? {
+                Some(token) => token,
+                None => return Ok(()),
+            };
+
+            // Unlike \meaning, \show puts the replacement text of macros on
+            // its own line.
+            let meaning = self.get_meaning(&token);
+            let meaning = if self.state.get_macro(&token).is_some() {
+                meaning.replacen("macro:", "macro:\n", 1)
+            } else {
+                meaning
+            };
+
+            let text = match token {
+                Token::ControlSequence(ref name) => {
+                    format!("\\{}={}", name, meaning)
+                }
+                Token::Char(ch, Category::Active) => {
+                    format!("{}={}", ch, meaning)
+                }
+                _ => meaning,
+            };
+            self.print_line(&format!("> {}.", text));
+        } else if self.state.is_token_equal_to_prim(&head, "showthe") {
+            let value = format_token_list(&self.parse_the_tokens()?);
+            self.print_line(&format!("> {}.", value));
+        } else if self.state.is_token_equal_to_prim(&head, "showbox") {
+            let index = self.parse_8bit_number()?;
+            let contents = match self.state.get_box_copy(index) {
+                Some(tex_box) => {
+                    let formatter = BoxFormatter::new(
+                        self.state.get_integer_parameter(
+                            IntegerParameter::ShowBoxDepth,
+                        ),
+                        self.state.get_integer_parameter(
+                            IntegerParameter::ShowBoxBreadth,
+                        ),
+                        |font| self.get_font_identifier_text(font),
+                    );
+                    format!("\n{}", formatter.format_box(&tex_box))
+                }
+                None => "void".to_string(),
+            };
+            self.print_line(&format!("> \\box{}={}", index, contents));
+        } else {
+            panic!("Invalid show head: {:?}", head);
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::dimension::{Dimen, Unit};
+    use crate::error::TeXErrorKind;
+    use crate::math_code::MathCode;
+    use crate::testing::{with_parser, with_terminal};
+
+    #[test]
+    fn it_gets_the_meaning_of_tokens() {
+        with_parser(
+            &[r"\def\a#1.{x#1\b}%", r"\let\c=a%", r"\let\d=\hbox%"],
+            |parser| {
+                parser.parse_assignment(None).unwrap();
+                parser.parse_assignment(None).unwrap();
+                parser.parse_assignment(None).unwrap();
+
+                let cs = |name: &str| Token::ControlSequence(name.to_string());
+                assert_eq!(parser.get_meaning(&cs("a")), r"macro:#1.->x#1\b ");
+                assert_eq!(parser.get_meaning(&cs("c")), "the letter a");
+                assert_eq!(parser.get_meaning(&cs("d")), r"\hbox");
+                assert_eq!(parser.get_meaning(&cs("hbox")), r"\hbox");
+                assert_eq!(parser.get_meaning(&cs("e")), "undefined");
+                assert_eq!(
+                    parser.get_meaning(&Token::Char('{', Category::BeginGroup)),
+                    "begin-group character {"
+                );
+                assert_eq!(
+                    parser.get_meaning(&Token::Char('1', Category::Other)),
+                    "the character 1"
+                );
+
+                parser.state.set_math_chardef(
+                    false,
+                    &cs("f"),
+                    &MathCode::from_number(0x7161),
+                );
+                assert_eq!(parser.get_meaning(&cs("f")), "\\mathchar\"7161");
+
+                parser.state.set_fontdef(
+                    false,
+                    &cs("g"),
+                    &Font {
+                        font_name: "fake".to_string(),
+                        scale: Dimen::from_unit(12.0, Unit::Point),
+                    },
+                );
+                assert_eq!(parser.get_meaning(&cs("g")), "select font fake");
+            },
+        );
+    }
+
+    #[test]
+    fn it_gets_the_meaning_of_shorthand_definitions() {
+        with_parser(
+            &[
+                r"\chardef\a=65 \countdef\b=1 \dimendef\c=2 \skipdef\d=3 %",
+                r"\muskipdef\e=4 \toksdef\f=5 %",
+            ],
+            |parser| {
+                for _ in 0..6 {
+                    parser.parse_assignment(None).unwrap();
+                }
+
+                let cs = |name: &str| Token::ControlSequence(name.to_string());
+                assert_eq!(parser.get_meaning(&cs("a")), "\\char\"41");
+                assert_eq!(parser.get_meaning(&cs("b")), r"\count1");
+                assert_eq!(parser.get_meaning(&cs("c")), r"\dimen2");
+                assert_eq!(parser.get_meaning(&cs("d")), r"\skip3");
+                assert_eq!(parser.get_meaning(&cs("e")), r"\muskip4");
+                assert_eq!(parser.get_meaning(&cs("f")), r"\toks5");
+            },
+        );
+    }
+
+    #[test]
+    fn it_gets_the_meaning_of_long_and_outer_macros() {
+        with_parser(
+            &[r"\long\def\a{x}\outer\def\b{}\long\outer\def\c#1{}%"],
+            |parser| {
+                parser.parse_assignment(None).unwrap();
+                parser.parse_assignment(None).unwrap();
+                parser.parse_assignment(None).unwrap();
+
+                let cs = |name: &str| Token::ControlSequence(name.to_string());
+                assert_eq!(parser.get_meaning(&cs("a")), r"\long macro:->x");
+                assert_eq!(parser.get_meaning(&cs("b")), r"\outer macro:->");
+                assert_eq!(
+                    parser.get_meaning(&cs("c")),
+                    r"\long\outer macro:#1->"
+                );
+            },
+        );
+    }
+
+    #[test]
+    fn it_shows_meanings() {
+        let output = with_terminal(
+            &[
+                r"\def\a{b}\long\def\b{}%",
+                r"\show\a",
+                r"\show a%",
+                r"\show\undefined",
+                r"\show\b",
+            ],
+            |parser| {
+                parser.parse_assignment(None).unwrap();
+                parser.parse_assignment(None).unwrap();
+                for _ in 0..4 {
+                    assert!(parser.is_show_head().unwrap());
+                    parser.parse_show().unwrap();
+                }
+            },
+        );
+
+        assert_eq!(
+            output,
+            concat!(
+                "> \\a=macro:\n->b.\n> the letter a.\n",
+                "> \\undefined=undefined.\n> \\b=\\long macro:\n->.\n"
+            )
+        );
+    }
+
+    #[test]
+    fn it_shows_internal_quantities() {
+        let output = with_terminal(
+            &[r"\count1=-5 %", r"\showthe\count1 %", r"\showthe\wd0%"],
+            |parser| {
+                parser.parse_assignment(None).unwrap();
+                parser.parse_show().unwrap();
+                parser.parse_show().unwrap();
+            },
+        );
+
+        assert_eq!(output, "> -5.\n> 0.0pt.\n");
+    }
+
+    #[test]
+    fn it_shows_registers() {
+        let output = with_terminal(
+            &[
+                r"\dimen1=1.5pt %",
+                r"\skip1=1pt plus 2fil minus 3pt %",
+                r"\muskip1=1mu minus 2mu %",
+                r"\toks1={\a b}%",
+                r"\showthe\dimen1 %",
+                r"\showthe\skip1 %",
+                r"\showthe\muskip1 %",
+                r"\showthe\toks1 %",
+            ],
+            |parser| {
+                for _ in 0..4 {
+                    parser.parse_assignment(None).unwrap();
+                }
+                for _ in 0..4 {
+                    parser.parse_show().unwrap();
+                }
+            },
+        );
+
+        assert_eq!(
+            output,
+            [
+                "> 1.5pt.",
+                "> 1.0pt plus 2.0fil minus 3.0pt.",
+                "> 1.0mu minus 2.0mu.",
+                r"> \a b.",
+                "",
+            ]
+            .join("\n")
+        );
+    }
+
+    #[test]
+    fn it_reports_invalid_showthe_quantities() {
+        let output = with_terminal(&[r"\showthe a%"], |parser| {
+            parser.parse_show().unwrap();
+            assert_eq!(
+                parser.take_errors()[0].kind,
+                TeXErrorKind::CantUseAfterThe(Token::Char(
+                    'a',
+                    Category::Letter
+                ))
+            );
+        });
+
+        assert_eq!(
+            output,
+            [
+                r"! You can't use `a' after \the.",
+                r"l.1 \showthe a",
+                r"              %",
+                "> 0.",
+                "",
+            ]
+            .join("\n")
+        );
+    }
+
+    #[test]
+    fn it_shows_boxes() {
+        let output = with_terminal(
+            &[
+                r"\setbox1=\hbox{\hskip 1pt\hbox{\hskip 2pt}}%",
+                r"\showbox0 %",
+                r"\showbox1 %",
+                r"\showboxdepth=1 \showboxbreadth=1 %",
+                r"\showbox1 %",
+            ],
+            |parser| {
+                parser.parse_assignment(None).unwrap();
+                parser.parse_show().unwrap();
+                parser.parse_show().unwrap();
+                parser.parse_assignment(None).unwrap();
+                parser.parse_assignment(None).unwrap();
+                parser.parse_show().unwrap();
+            },
+        );
+
+        assert_eq!(
+            output,
+            [
+                r"> \box0=void",
+                r"> \box1=",
+                r"\hbox(0.0+0.0)x3.0 []",
+                r"> \box1=",
+                r"\hbox(0.0+0.0)x3.0",
+                r".\glue 1.0",
+                r".etc.",
+                "",
+            ]
+            .join("\n")
+        );
+    }
+}
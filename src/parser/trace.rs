@@ -0,0 +1,561 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use crate::box_display::BoxFormatter;
+use crate::debugger::DebugEvent;
+use crate::makro::Macro;
+use crate::origin::{TokenOrigin, TokenWithOrigin};
+use crate::parameter::IntegerParameter;
+use crate::parser::group::GroupKind;
+use crate::parser::Parser;
+use crate::state::{RestoredEntry, StateEntry};
+use crate::token::{format_token_list, Token};
+use crate::trace::ExpansionTracer;
+use crate::transcript::Selector;
+
+impl<'a> Parser<'a> {
+    /// Attaches a tracer which will be notified about every macro expansion,
+    /// conditional evaluation, and primitive execution from now on.
+    pub fn set_tracer(&mut self, tracer: Rc<RefCell<dyn ExpansionTracer>>) {
+        self.tracer = Some(tracer);
+    }
+
+    // Called whenever a token is about to be read out of upcoming_tokens, so
+    // that we can tell when we've finished reading the replacement text of a
+    // macro.
+    pub fn end_finished_expansions(&mut self) {
+        while let Some((base, _, _)) = self.expansion_bases.last() {
+            if self.upcoming_tokens.len() <= *base {
+                self.expansion_bases.pop();
+            } else {
+                break;
+            }
+        }
+    }
+
+    fn is_tracing(&self, parameter: IntegerParameter) -> bool {
+        self.state.get_integer_parameter(parameter) > 0
+    }
+
+    // Like TeX, the output from \tracingmacros and friends only goes to the
+    // log file unless \tracingonline is positive.
+    fn get_diagnostic_selector(&self) -> Selector {
+        if self.is_tracing(IntegerParameter::TracingOnline) {
+            Selector::TerminalAndLog
+        } else {
+            Selector::LogOnly
+        }
+    }
+
+    pub fn trace_macro_expansion(
+        &mut self,
+        token: &Token,
+        origin: Option<&Rc<TokenOrigin>>,
+        makro: &Macro,
+        arguments: &HashMap<usize, Vec<TokenWithOrigin>>,
+        replacement: &[TokenWithOrigin],
+    ) {
+        let arguments: HashMap<usize, Vec<Token>> = arguments
+            .iter()
+            .map(|(index, tokens)| {
+                (
+                    *index,
+                    tokens.iter().map(|(token, _)| token.clone()).collect(),
+                )
+            })
+            .collect();
+        let replacement: Vec<Token> =
+            replacement.iter().map(|(token, _)| token.clone()).collect();
+
+        // TeX shows the definition of the macro after a blank line, followed
+        // by the value of each of the arguments, like
+        //
+        //   \a #1->x#1
+        //   #1<-y
+        if self.is_tracing(IntegerParameter::TracingMacros) {
+            let selector = self.get_diagnostic_selector();
+            self.transcript.print_ln(selector);
+            self.transcript
+                .print(selector, &format!("{}{}", token, makro));
+
+            let mut arguments: Vec<(&usize, &Vec<Token>)> =
+                arguments.iter().collect();
+            arguments.sort_by_key(|(index, _)| *index);
+            for (index, argument) in arguments {
+                self.transcript.print_nl(selector);
+                self.transcript.print(
+                    selector,
+                    &format!("#{}<-{}", index, format_token_list(argument)),
+                );
+            }
+            self.transcript.print_nl(selector);
+        }
+
+        if let Some(tracer) = &self.tracer {
+            tracer.borrow_mut().macro_expanded(
+                self.expansion_bases.len(),
+                token,
+                origin,
+                &arguments,
+                &replacement,
+            );
+        }
+
+        // This must be called right before the replacement is added to
+        // upcoming_tokens.
+        self.expansion_bases.push((
+            self.upcoming_tokens.len(),
+            token.clone(),
+            replacement,
+        ));
+    }
+
+    pub fn trace_conditional(&mut self, token: &Token, result: bool) {
+        if let Some(tracer) = &self.tracer {
+            tracer.borrow_mut().conditional_evaluated(
+                self.expansion_bases.len(),
+                token,
+                result,
+            );
+        }
+    }
+
+    /// Reports that the given token is about to be executed as a command in
+    /// the given mode. With \tracingcommands, TeX shows each command along
+    /// with the mode whenever it changes, like
+    ///
+    ///   {vertical mode: \hbox}
+    ///   {restricted horizontal mode: the letter a}
+    pub fn trace_command(
+        &mut self,
+        mode: &'static str,
+        maybe_token: &Option<Token>,
+    ) {
+        self.trace_primitive(maybe_token);
+
+        if let Some(token) = maybe_token {
+            self.debug_step(DebugEvent::Command { token, mode });
+
+            if self.is_tracing(IntegerParameter::TracingCommands) {
+                let mut text = "{".to_string();
+                if self.shown_mode != Some(mode) {
+                    text.push_str(&format!("{}: ", mode));
+                    self.shown_mode = Some(mode);
+                }
+                text.push_str(&self.get_meaning(token));
+                text.push('}');
+
+                let selector = self.get_diagnostic_selector();
+                self.transcript.print_nl(selector);
+                self.transcript.print(selector, &text);
+                self.transcript.print_nl(selector);
+            }
+        }
+    }
+
+    // Describes the current value of something in the state, like
+    // "\count1=5" or "\a=macro:->x".
+    fn describe_state_entry(&self, entry: &StateEntry) -> String {
+        match entry {
+            StateEntry::Category(ch) => format!(
+                "\\catcode{}={}",
+                *ch as u32,
+                self.state.get_category(*ch) as u8
+            ),
+            StateEntry::MathCode(ch) => format!(
+                "\\mathcode{}={}",
+                *ch as u32,
+                self.state.get_math_code(*ch).to_number()
+            ),
+            StateEntry::LowercaseCode(ch) => format!(
+                "\\lccode{}={}",
+                *ch as u32,
+                self.state.get_lowercase_code(*ch) as u32
+            ),
+            StateEntry::UppercaseCode(ch) => format!(
+                "\\uccode{}={}",
+                *ch as u32,
+                self.state.get_uppercase_code(*ch) as u32
+            ),
+            StateEntry::SpaceFactorCode(ch) => format!(
+                "\\sfcode{}={}",
+                *ch as u32,
+                self.state.get_space_factor_code(*ch)
+            ),
+            StateEntry::DelimiterCode(ch) => format!(
+                "\\delcode{}={}",
+                *ch as u32,
+                self.state.get_delimiter_code(*ch)
+            ),
+            StateEntry::Definition(token) => {
+                let name = match token {
+                    Token::ControlSequence(name) => format!("\\{}", name),
+                    Token::Char(ch, _) => ch.to_string(),
+                };
+                format!("{}={}", name, self.get_meaning(token))
+            }
+            StateEntry::Count(index) => {
+                format!("\\count{}={}", index, self.state.get_count(*index))
+            }
+            StateEntry::Dimen(index) => {
+                format!("\\dimen{}={}", index, self.state.get_dimen(*index))
+            }
+            StateEntry::Skip(index) => {
+                format!("\\skip{}={}", index, self.state.get_skip(*index))
+            }
+            StateEntry::MuSkip(index) => {
+                format!("\\muskip{}={}", index, self.state.get_muskip(*index))
+            }
+            StateEntry::Toks(index) => format!(
+                "\\toks{}={}",
+                index,
+                format_token_list(&self.state.get_toks(*index))
+            ),
+            StateEntry::IntegerParameter(parameter) => format!(
+                "\\{}={}",
+                parameter.get_primitive(),
+                self.state.get_integer_parameter(*parameter)
+            ),
+            StateEntry::DimenParameter(parameter) => format!(
+                "\\{}={}",
+                parameter.get_primitive(),
+                self.state.get_dimen_parameter(*parameter)
+            ),
+            StateEntry::GlueParameter(parameter) => format!(
+                "\\{}={}",
+                parameter.get_primitive(),
+                self.state.get_glue_parameter(*parameter)
+            ),
+            StateEntry::MuGlueParameter(parameter) => format!(
+                "\\{}={}",
+                parameter.get_primitive(),
+                self.state.get_mu_glue_parameter(*parameter)
+            ),
+            StateEntry::Box(index) => {
+                // TeX only shows the outermost box here.
+                let contents = match self.state.get_box_copy(*index) {
+                    Some(tex_box) => BoxFormatter::new(0, 1, |font| {
+                        self.get_font_identifier_text(font)
+                    })
+                    .format_box(&tex_box),
+                    None => "void".to_string(),
+                };
+                format!("\\box{}={}", index, contents)
+            }
+            StateEntry::CurrentFont => format!(
+                "current font={}",
+                self.get_font_identifier_text(&self.state.get_current_font())
+            ),
+        }
+    }
+
+    /// Shows the values that were restored when a group ended, if
+    /// \tracingrestores is on. TeX shows each of them like
+    ///
+    ///   {restoring \count1=0}
+    ///
+    /// or, for values which were assigned globally inside of the group,
+    ///
+    ///   {retaining \count1=5}
+    pub fn trace_restores(&mut self, restored_entries: Vec<RestoredEntry>) {
+        if self.is_tracing(IntegerParameter::TracingRestores) {
+            let selector = self.get_diagnostic_selector();
+            for restored_entry in restored_entries {
+                let (action, entry) = match &restored_entry {
+                    RestoredEntry::Restored(entry) => ("restoring", entry),
+                    RestoredEntry::Retained(entry) => ("retaining", entry),
+                };
+                let text = format!(
+                    "{{{} {}}}",
+                    action,
+                    self.describe_state_entry(entry)
+                );
+                self.transcript.print(selector, &text);
+                self.transcript.print_nl(selector);
+            }
+        }
+    }
+
+    /// Shows when groups start and end, if \tracinggroups is on. Like in
+    /// e-TeX, this looks like
+    ///
+    ///   {entering simple group (level 1) at line 3}
+    ///   {leaving simple group (level 1) entered at line 3}
+    pub fn trace_group(
+        &mut self,
+        leaving: bool,
+        kind: GroupKind,
+        level: usize,
+        line: i32,
+    ) {
+        if self.is_tracing(IntegerParameter::TracingGroups) {
+            let mut text = format!(
+                "{{{} {} group (level {})",
+                if leaving { "leaving" } else { "entering" },
+                kind.get_name(),
+                level
+            );
+            if line != 0 {
+                let at = if leaving { "entered at" } else { "at" };
+                text.push_str(&format!(" {} line {}", at, line));
+            }
+            text.push('}');
+
+            let selector = self.get_diagnostic_selector();
+            self.transcript.print_nl(selector);
+            self.transcript.print(selector, &text);
+            self.transcript.print_nl(selector);
+        }
+    }
+
+    // Reports that the given token is about to be executed, if it is a
+    // primitive. This is passed a peeked token, so it takes an Option to make
+    // it easy to call before matching on the peeked value.
+    pub fn trace_primitive(&mut self, maybe_token: &Option<Token>) {
+        if let (Some(tracer), Some(token)) = (&self.tracer, maybe_token) {
+            if self.state.get_primitive(token).is_some() {
+                tracer
+                    .borrow_mut()
+                    .primitive_executed(self.expansion_bases.len(), token);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::category::Category;
+    use crate::testing::{with_parser, with_terminal, TestOutput};
+    use crate::trace::{ExpansionRecorder, TraceEvent, TraceNode};
+
+    fn cs(name: &str) -> Token {
+        Token::ControlSequence(name.to_string())
+    }
+
+    #[test]
+    fn it_records_nested_macro_expansions() {
+        with_parser(
+            &[
+                r"\def\a#1{\b{#1}\b{#1}}%",
+                r"\def\b#1{#1\ifnum1<2 x\fi}%",
+                r"\a y%",
+            ],
+            |parser| {
+                let recorder = Rc::new(RefCell::new(ExpansionRecorder::new()));
+                parser.set_tracer(recorder.clone());
+
+                parser.parse_assignment(None).unwrap();
+                parser.parse_assignment(None).unwrap();
+                while parser.lex_expanded_token().unwrap().is_some() {}
+
+                let recorder = recorder.borrow();
+                let roots = recorder.get_roots();
+                assert_eq!(roots.len(), 1);
+                assert_eq!(
+                    roots[0].event,
+                    TraceEvent::Macro {
+                        token: cs("a"),
+                        origin: Some(Rc::new(TokenOrigin::Source {
+                            file: None,
+                            line: 3,
+                            column: 1,
+                        })),
+                        arguments: vec![vec![Token::Char(
+                            'y',
+                            Category::Letter
+                        )]],
+                        replacement: vec![
+                            cs("b"),
+                            Token::Char('{', Category::BeginGroup),
+                            Token::Char('y', Category::Letter),
+                            Token::Char('}', Category::EndGroup),
+                            cs("b"),
+                            Token::Char('{', Category::BeginGroup),
+                            Token::Char('y', Category::Letter),
+                            Token::Char('}', Category::EndGroup),
+                        ],
+                    }
+                );
+
+                assert_eq!(roots[0].children.len(), 2);
+                for child in roots[0].children.iter() {
+                    assert_eq!(
+                        child.children,
+                        vec![TraceNode {
+                            event: TraceEvent::Conditional {
+                                token: cs("ifnum"),
+                                result: true,
+                            },
+                            children: vec![],
+                        }]
+                    );
+                }
+            },
+        );
+    }
+
+    #[test]
+    fn it_records_primitives_and_serializes_them() {
+        with_parser(&[r"\def\a{\hskip 1pt}%", r"\hbox{\a}%"], |parser| {
+            parser.parse_assignment(None).unwrap();
+            let recorder = Rc::new(RefCell::new(ExpansionRecorder::new()));
+            parser.set_tracer(recorder.clone());
+
+            parser.parse_box().unwrap();
+
+            assert_eq!(
+                recorder.borrow().to_json(),
+                concat!(
+                    r#"[{"type":"macro","token":"\\a ","#,
+                    r#""origin":"<input>:2:7","arguments":[],"#,
+                    r#""replacement":"\\hskip 1pt","children":["#,
+                    r#"{"type":"primitive","token":"\\hskip ","children":[]}"#,
+                    r#"]}]"#
+                )
+            );
+        });
+    }
+
+    #[test]
+    fn it_doesnt_nest_expansions_after_the_replacement_is_read() {
+        with_parser(&[r"\def\a{x}%", r"\a\a%"], |parser| {
+            parser.parse_assignment(None).unwrap();
+            let recorder = Rc::new(RefCell::new(ExpansionRecorder::new()));
+            parser.set_tracer(recorder.clone());
+
+            while parser.lex_expanded_token().unwrap().is_some() {}
+
+            let recorder = recorder.borrow();
+            assert_eq!(recorder.get_roots().len(), 2);
+            assert!(recorder.get_roots()[0].children.is_empty());
+        });
+    }
+
+    #[test]
+    fn it_traces_macros_in_the_log_file() {
+        let log = TestOutput::new();
+        let terminal = with_terminal(
+            &[r"\def\a#1#2{x#1}%", r"\tracingmacros=1 \a y{zz}%"],
+            |parser| {
+                parser.set_log_file(log.writer());
+                parser.parse_assignment(None).unwrap();
+                parser.parse_assignment(None).unwrap();
+
+                assert_eq!(
+                    parser.lex_expanded_token().unwrap(),
+                    Some(Token::Char('x', Category::Letter))
+                );
+                assert_eq!(
+                    parser.lex_expanded_token().unwrap(),
+                    Some(Token::Char('y', Category::Letter))
+                );
+            },
+        );
+
+        assert_eq!(terminal, "");
+        assert_eq!(log.contents(), "\n\\a #1#2->x#1\n#1<-y\n#2<-zz\n");
+    }
+
+    #[test]
+    fn it_traces_commands() {
+        let output = with_terminal(
+            &[
+                r"\tracingcommands=1 \tracingonline=1 %",
+                r"\hbox{\hskip 1pt\hskip 2pt}%",
+            ],
+            |parser| {
+                parser.parse_assignment(None).unwrap();
+                parser.parse_assignment(None).unwrap();
+                parser.parse_box().unwrap();
+            },
+        );
+
+        assert_eq!(
+            output,
+            [
+                r"{restricted horizontal mode: \hskip}",
+                r"{\hskip}",
+                r"{end-group character }}",
+                "",
+            ]
+            .join("\n")
+        );
+    }
+
+    #[test]
+    fn it_traces_restored_values() {
+        let output = with_terminal(
+            &[
+                r"\tracingrestores=1 \tracingonline=1 %",
+                r"\hbox{\count1=5 \showboxdepth=2 }%",
+            ],
+            |parser| {
+                parser.parse_assignment(None).unwrap();
+                parser.parse_assignment(None).unwrap();
+                parser.parse_box().unwrap();
+            },
+        );
+
+        assert_eq!(
+            output,
+            "{restoring \\showboxdepth=0}\n{restoring \\count1=0}\n"
+        );
+    }
+
+    #[test]
+    fn it_traces_retained_values() {
+        let output = with_terminal(
+            &[
+                r"\tracingrestores=1 \tracingonline=1 %",
+                r"\hbox{\count1=1 \global\count1=2 \count1=3 \count2=0 }%",
+            ],
+            |parser| {
+                parser.parse_assignment(None).unwrap();
+                parser.parse_assignment(None).unwrap();
+                parser.parse_box().unwrap();
+            },
+        );
+
+        assert_eq!(
+            output,
+            [
+                r"{restoring \count2=0}",
+                r"{restoring \count1=2}",
+                r"{retaining \count1=2}",
+                "",
+            ]
+            .join("\n")
+        );
+    }
+
+    #[test]
+    fn it_traces_groups() {
+        let output = with_terminal(
+            &[
+                r"\tracinggroups=1 \tracingonline=1 %",
+                r"\hbox{\begingroup",
+                r"\endgroup}%",
+            ],
+            |parser| {
+                parser.parse_assignment(None).unwrap();
+                parser.parse_assignment(None).unwrap();
+                parser.parse_box().unwrap();
+            },
+        );
+
+        assert_eq!(
+            output,
+            [
+                "{entering hbox group (level 1) at line 2}",
+                "{entering semi simple group (level 2) at line 2}",
+                "{leaving semi simple group (level 2) entered at line 2}",
+                "{leaving hbox group (level 1) entered at line 2}",
+                "",
+            ]
+            .join("\n")
+        );
+    }
+}
@@ -1,40 +1,229 @@
+use crate::error::TeXResult;
+use crate::parameter::{
+    DimenParameter, GlueParameter, IntegerParameter, MuGlueParameter,
+};
 use crate::parser::Parser;
-use crate::variable::{DimenVariable, IntegerVariable};
+use crate::state::TokenDefinition;
+use crate::token::Token;
+use crate::variable::{
+    CodeVariable, DimenVariable, GlueVariable, IntegerVariable, MuGlueVariable,
+    TokenListVariable,
+};
 
 impl<'a> Parser<'a> {
-    pub fn is_integer_variable_head(&mut self) -> bool {
-        self.is_next_expanded_token_in_set_of_primitives(&["count"])
+    // Looks up which parameter a token refers to, using one of the
+    // from_primitive functions in crate::parameter.
+    fn get_parameter_for_token<T>(
+        &self,
+        token: &Token,
+        from_primitive: fn(&str) -> Option<T>,
+    ) -> Option<T> {
+        self.state.get_primitive(token).and_then(from_primitive)
     }
 
-    pub fn parse_integer_variable(&mut self) -> IntegerVariable {
-        let token = self.lex_expanded_token().unwrap();
+    fn is_next_expanded_token_parameter<T>(
+        &mut self,
+        from_primitive: fn(&str) -> Option<T>,
+    ) -> TeXResult<bool> {
+        Ok(match self.peek_expanded_token()? {
+            Some(token) => self
+                .get_parameter_for_token(&token, from_primitive)
+                .is_some(),
+            None => false,
+        })
+    }
+
+    // Returns the definition of the next token, which tells us if it is a
+    // register shorthand made with something like \countdef.
+    fn peek_definition(&mut self) -> TeXResult<Option<TokenDefinition>> {
+        Ok(self
+            .peek_expanded_token()?
+            .and_then(|token| self.state.get_definition(&token)))
+    }
+
+    pub fn is_integer_variable_head(&mut self) -> TeXResult<bool> {
+        if self.is_next_expanded_token_in_set_of_primitives(&["count"])?
+            || matches!(
+                self.peek_definition()?,
+                Some(TokenDefinition::CountRegister(_))
+            )
+        {
+            return Ok(true);
+        }
+
+        self.is_next_expanded_token_parameter(IntegerParameter::from_primitive)
+    }
+
+    pub fn parse_integer_variable(&mut self) -> TeXResult<IntegerVariable> {
+        let token = self.lex_expanded_token()?.unwrap();
 
         if self.state.is_token_equal_to_prim(&token, "count") {
-            let index = self.parse_8bit_number();
-            IntegerVariable::CountRegister(index)
+            let index = self.parse_8bit_number()?;
+            Ok(IntegerVariable::CountRegister(index))
+        } else if let Some(TokenDefinition::CountRegister(index)) =
+            self.state.get_definition(&token)
+        {
+            Ok(IntegerVariable::CountRegister(index))
+        } else if let Some(parameter) = self
+            .get_parameter_for_token(&token, IntegerParameter::from_primitive)
+        {
+            Ok(IntegerVariable::Parameter(parameter))
+        } else {
+            // This is only called after is_integer_variable_head().
+            unreachable!("Invalid integer variable head: {:?}", token);
+        }
+    }
+
+    pub fn is_code_variable_head(&mut self) -> TeXResult<bool> {
+        self.is_next_expanded_token_in_set_of_primitives(&[
+            "catcode", "mathcode", "lccode", "uccode", "sfcode", "delcode",
+        ])
+    }
+
+    pub fn parse_code_variable(&mut self) -> TeXResult<CodeVariable> {
+        let token = self.lex_expanded_token()?.unwrap();
+        let ch = self.parse_char_code()?;
+
+        if self.state.is_token_equal_to_prim(&token, "catcode") {
+            Ok(CodeVariable::Category(ch))
+        } else if self.state.is_token_equal_to_prim(&token, "mathcode") {
+            Ok(CodeVariable::MathCode(ch))
+        } else if self.state.is_token_equal_to_prim(&token, "lccode") {
+            Ok(CodeVariable::LowercaseCode(ch))
+        } else if self.state.is_token_equal_to_prim(&token, "uccode") {
+            Ok(CodeVariable::UppercaseCode(ch))
+        } else if self.state.is_token_equal_to_prim(&token, "sfcode") {
+            Ok(CodeVariable::SpaceFactorCode(ch))
+        } else if self.state.is_token_equal_to_prim(&token, "delcode") {
+            Ok(CodeVariable::DelimiterCode(ch))
         } else {
-            panic!("unimplemented");
+            panic!("Invalid code variable head: {:?}", token);
         }
     }
 
-    pub fn is_dimen_variable_head(&mut self) -> bool {
-        self.is_next_expanded_token_in_set_of_primitives(&["wd", "ht", "dp"])
+    pub fn is_dimen_variable_head(&mut self) -> TeXResult<bool> {
+        Ok(self.is_next_expanded_token_in_set_of_primitives(&[
+            "dimen", "wd", "ht", "dp",
+        ])? || matches!(
+            self.peek_definition()?,
+            Some(TokenDefinition::DimenRegister(_))
+        ) || self
+            .is_next_expanded_token_parameter(DimenParameter::from_primitive)?)
     }
 
-    pub fn parse_dimen_variable(&mut self) -> DimenVariable {
-        let token = self.lex_expanded_token().unwrap();
+    pub fn parse_dimen_variable(&mut self) -> TeXResult<DimenVariable> {
+        let token = self.lex_expanded_token()?.unwrap();
 
-        if self.state.is_token_equal_to_prim(&token, "wd") {
-            let index = self.parse_8bit_number();
-            DimenVariable::BoxWidth(index)
+        if self.state.is_token_equal_to_prim(&token, "dimen") {
+            let index = self.parse_8bit_number()?;
+            Ok(DimenVariable::DimenRegister(index))
+        } else if let Some(TokenDefinition::DimenRegister(index)) =
+            self.state.get_definition(&token)
+        {
+            Ok(DimenVariable::DimenRegister(index))
+        } else if self.state.is_token_equal_to_prim(&token, "wd") {
+            let index = self.parse_8bit_number()?;
+            Ok(DimenVariable::BoxWidth(index))
         } else if self.state.is_token_equal_to_prim(&token, "ht") {
-            let index = self.parse_8bit_number();
-            DimenVariable::BoxHeight(index)
+            let index = self.parse_8bit_number()?;
+            Ok(DimenVariable::BoxHeight(index))
         } else if self.state.is_token_equal_to_prim(&token, "dp") {
-            let index = self.parse_8bit_number();
-            DimenVariable::BoxDepth(index)
+            let index = self.parse_8bit_number()?;
+            Ok(DimenVariable::BoxDepth(index))
+        } else if let Some(parameter) =
+            self.get_parameter_for_token(&token, DimenParameter::from_primitive)
+        {
+            Ok(DimenVariable::Parameter(parameter))
+        } else {
+            // This is only called after is_dimen_variable_head().
+            unreachable!("Invalid dimen variable head: {:?}", token);
+        }
+    }
+
+    pub fn is_glue_variable_head(&mut self) -> TeXResult<bool> {
+        Ok(self.is_next_expanded_token_in_set_of_primitives(&["skip"])?
+            || matches!(
+                self.peek_definition()?,
+                Some(TokenDefinition::SkipRegister(_))
+            )
+            || self.is_next_expanded_token_parameter(
+                GlueParameter::from_primitive,
+            )?)
+    }
+
+    pub fn parse_glue_variable(&mut self) -> TeXResult<GlueVariable> {
+        let token = self.lex_expanded_token()?.unwrap();
+
+        if self.state.is_token_equal_to_prim(&token, "skip") {
+            let index = self.parse_8bit_number()?;
+            Ok(GlueVariable::SkipRegister(index))
+        } else if let Some(TokenDefinition::SkipRegister(index)) =
+            self.state.get_definition(&token)
+        {
+            Ok(GlueVariable::SkipRegister(index))
+        } else if let Some(parameter) =
+            self.get_parameter_for_token(&token, GlueParameter::from_primitive)
+        {
+            Ok(GlueVariable::Parameter(parameter))
         } else {
-            panic!("unimplemented");
+            panic!("Invalid glue variable head: {:?}", token);
+        }
+    }
+
+    pub fn is_mu_glue_variable_head(&mut self) -> TeXResult<bool> {
+        Ok(
+            self.is_next_expanded_token_in_set_of_primitives(&["muskip"])?
+                || matches!(
+                    self.peek_definition()?,
+                    Some(TokenDefinition::MuSkipRegister(_))
+                )
+                || self.is_next_expanded_token_parameter(
+                    MuGlueParameter::from_primitive,
+                )?,
+        )
+    }
+
+    pub fn parse_mu_glue_variable(&mut self) -> TeXResult<MuGlueVariable> {
+        let token = self.lex_expanded_token()?.unwrap();
+
+        if self.state.is_token_equal_to_prim(&token, "muskip") {
+            let index = self.parse_8bit_number()?;
+            Ok(MuGlueVariable::MuSkipRegister(index))
+        } else if let Some(TokenDefinition::MuSkipRegister(index)) =
+            self.state.get_definition(&token)
+        {
+            Ok(MuGlueVariable::MuSkipRegister(index))
+        } else if let Some(parameter) = self
+            .get_parameter_for_token(&token, MuGlueParameter::from_primitive)
+        {
+            Ok(MuGlueVariable::Parameter(parameter))
+        } else {
+            panic!("Invalid muglue variable head: {:?}", token);
+        }
+    }
+
+    pub fn is_token_list_variable_head(&mut self) -> TeXResult<bool> {
+        Ok(self.is_next_expanded_token_in_set_of_primitives(&["toks"])?
+            || matches!(
+                self.peek_definition()?,
+                Some(TokenDefinition::ToksRegister(_))
+            ))
+    }
+
+    pub fn parse_token_list_variable(
+        &mut self,
+    ) -> TeXResult<TokenListVariable> {
+        let token = self.lex_expanded_token()?.unwrap();
+
+        if self.state.is_token_equal_to_prim(&token, "toks") {
+            let index = self.parse_8bit_number()?;
+            Ok(TokenListVariable::ToksRegister(index))
+        } else if let Some(TokenDefinition::ToksRegister(index)) =
+            self.state.get_definition(&token)
+        {
+            Ok(TokenListVariable::ToksRegister(index))
+        } else {
+            panic!("Invalid token list variable head: {:?}", token);
         }
     }
 }
@@ -50,47 +239,147 @@ mod tests {
         with_parser(
             &["\\let\\x=\\count%", "\\count0%", "\\count255%", "\\x255%"],
             |parser| {
-                parser.parse_assignment(None);
+                parser.parse_assignment(None).unwrap();
 
-                assert!(parser.is_integer_variable_head());
+                assert!(parser.is_integer_variable_head().unwrap());
                 assert_eq!(
-                    parser.parse_integer_variable(),
+                    parser.parse_integer_variable().unwrap(),
                     IntegerVariable::CountRegister(0)
                 );
-                assert!(parser.is_integer_variable_head());
+                assert!(parser.is_integer_variable_head().unwrap());
                 assert_eq!(
-                    parser.parse_integer_variable(),
+                    parser.parse_integer_variable().unwrap(),
                     IntegerVariable::CountRegister(255)
                 );
-                assert!(parser.is_integer_variable_head());
+                assert!(parser.is_integer_variable_head().unwrap());
                 assert_eq!(
-                    parser.parse_integer_variable(),
+                    parser.parse_integer_variable().unwrap(),
                     IntegerVariable::CountRegister(255)
                 );
             },
         );
     }
 
+    #[test]
+    fn it_parses_integer_parameters() {
+        with_parser(&[r"\showboxdepth=3 %", r"\showboxdepth%"], |parser| {
+            parser.parse_assignment(None).unwrap();
+
+            assert!(parser.is_integer_variable_head().unwrap());
+            let variable = parser.parse_integer_variable().unwrap();
+            assert_eq!(
+                variable,
+                IntegerVariable::Parameter(IntegerParameter::ShowBoxDepth)
+            );
+            assert_eq!(variable.get(parser.state), 3);
+        });
+    }
+
+    #[test]
+    fn it_parses_dimen_and_glue_parameters() {
+        with_parser(
+            &[r"\hsize%", r"\baselineskip%", r"\thinmuskip%"],
+            |parser| {
+                assert!(parser.is_dimen_variable_head().unwrap());
+                assert_eq!(
+                    parser.parse_dimen_variable().unwrap(),
+                    DimenVariable::Parameter(DimenParameter::HSize)
+                );
+
+                assert!(!parser.is_dimen_variable_head().unwrap());
+                assert!(parser.is_glue_variable_head().unwrap());
+                assert_eq!(
+                    parser.parse_glue_variable().unwrap(),
+                    GlueVariable::Parameter(GlueParameter::BaselineSkip)
+                );
+
+                assert!(!parser.is_glue_variable_head().unwrap());
+                assert!(parser.is_mu_glue_variable_head().unwrap());
+                assert_eq!(
+                    parser.parse_mu_glue_variable().unwrap(),
+                    MuGlueVariable::Parameter(MuGlueParameter::ThinMuSkip)
+                );
+            },
+        );
+    }
+
+    #[test]
+    fn it_parses_code_variables() {
+        with_parser(
+            &[r"\catcode`a%", r"\lccode65%", r"\delcode`\.%"],
+            |parser| {
+                assert!(parser.is_code_variable_head().unwrap());
+                let variable = parser.parse_code_variable().unwrap();
+                assert_eq!(variable, CodeVariable::Category('a'));
+                assert_eq!(variable.get(parser.state), 11);
+
+                assert!(parser.is_code_variable_head().unwrap());
+                let variable = parser.parse_code_variable().unwrap();
+                assert_eq!(variable, CodeVariable::LowercaseCode('A'));
+                assert_eq!(variable.get(parser.state), 97);
+
+                assert!(parser.is_code_variable_head().unwrap());
+                let variable = parser.parse_code_variable().unwrap();
+                assert_eq!(variable, CodeVariable::DelimiterCode('.'));
+                assert_eq!(variable.get(parser.state), 0);
+            },
+        );
+    }
+
     #[test]
     fn it_parses_box_dimen_variables() {
         with_parser(&["\\wd0%", "\\ht255%", "\\dp123%"], |parser| {
-            assert!(parser.is_dimen_variable_head());
+            assert!(parser.is_dimen_variable_head().unwrap());
             assert_eq!(
-                parser.parse_dimen_variable(),
+                parser.parse_dimen_variable().unwrap(),
                 DimenVariable::BoxWidth(0)
             );
 
-            assert!(parser.is_dimen_variable_head());
+            assert!(parser.is_dimen_variable_head().unwrap());
             assert_eq!(
-                parser.parse_dimen_variable(),
+                parser.parse_dimen_variable().unwrap(),
                 DimenVariable::BoxHeight(255)
             );
 
-            assert!(parser.is_dimen_variable_head());
+            assert!(parser.is_dimen_variable_head().unwrap());
             assert_eq!(
-                parser.parse_dimen_variable(),
+                parser.parse_dimen_variable().unwrap(),
                 DimenVariable::BoxDepth(123)
             );
         });
     }
+
+    #[test]
+    fn it_parses_register_variables() {
+        with_parser(
+            &[r"\dimen1%", r"\skip2%", r"\muskip3%", r"\toks4%"],
+            |parser| {
+                assert!(parser.is_dimen_variable_head().unwrap());
+                assert_eq!(
+                    parser.parse_dimen_variable().unwrap(),
+                    DimenVariable::DimenRegister(1)
+                );
+
+                assert!(!parser.is_dimen_variable_head().unwrap());
+                assert!(parser.is_glue_variable_head().unwrap());
+                assert_eq!(
+                    parser.parse_glue_variable().unwrap(),
+                    GlueVariable::SkipRegister(2)
+                );
+
+                assert!(!parser.is_glue_variable_head().unwrap());
+                assert!(parser.is_mu_glue_variable_head().unwrap());
+                assert_eq!(
+                    parser.parse_mu_glue_variable().unwrap(),
+                    MuGlueVariable::MuSkipRegister(3)
+                );
+
+                assert!(parser.is_token_list_variable_head().unwrap());
+                assert_eq!(
+                    parser.parse_token_list_variable().unwrap(),
+                    TokenListVariable::ToksRegister(4)
+                );
+            },
+        );
+    }
 }
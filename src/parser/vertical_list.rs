@@ -1,8 +1,12 @@
 use crate::category::Category;
+use crate::debugger::{PartialList, PartialListContents};
 use crate::dimension::{Dimen, Unit};
+use crate::error::{TeXErrorKind, TeXResult};
 use crate::glue::Glue;
 use crate::list::VerticalListElem;
+use crate::parameter::{DimenParameter, GlueParameter};
 use crate::parser::assignment::SpecialVariables;
+use crate::parser::group::GroupKind;
 use crate::parser::Parser;
 use crate::token::Token;
 
@@ -12,16 +16,16 @@ impl<'a> Parser<'a> {
     fn handle_enter_horizontal_mode(
         &mut self,
         indent: bool,
-    ) -> VerticalListElem {
+    ) -> TeXResult<VerticalListElem> {
         // TODO(xymostech): This will eventually potentially
         // produce a series of boxes instead of just one, if there
         // are line breaks. Handle that.
-        let tex_box = self.parse_unrestricted_horizontal_box(indent);
+        let tex_box = self.parse_unrestricted_horizontal_box(indent)?;
         // TODO(xymostech): Add \parskip glue before the box.
-        VerticalListElem::Box {
+        Ok(VerticalListElem::Box {
             tex_box,
             shift: Dimen::zero(),
-        }
+        })
     }
 
     /// Checks if a token is the start of something that only is valid in
@@ -36,6 +40,7 @@ impl<'a> Parser<'a> {
 
         if self.state.is_token_equal_to_prim(tok, "hskip")
             || self.state.is_token_equal_to_prim(tok, "char")
+            || self.state.get_chardef(tok).is_some()
         {
             return true;
         }
@@ -45,133 +50,177 @@ impl<'a> Parser<'a> {
 
     fn parse_vertical_list_elem(
         &mut self,
-        group_level: &mut usize,
+        group_base: usize,
         prev_depth: &mut Dimen,
         internal: bool,
-    ) -> Option<VerticalListElem> {
-        let expanded_token = self.peek_expanded_token();
+    ) -> TeXResult<Option<VerticalListElem>> {
+        let mode = if internal {
+            "internal vertical mode"
+        } else {
+            "vertical mode"
+        };
+        let expanded_token = self.peek_expanded_token()?;
+        self.trace_command(mode, &expanded_token);
         let expanded_renamed_token = self.replace_renamed_token(expanded_token);
-        match expanded_renamed_token {
+        Ok(match expanded_renamed_token {
             None => {
                 if internal {
                     None
                 } else {
-                    panic!(r"Emergency stop, EOF found before \end");
+                    // There's no way to recover from not finding an \end.
+                    return Err(self.fatal_error(TeXErrorKind::EmergencyStop));
                 }
             }
             Some(ref tok) if self.is_horizontal_mode_head(tok) => {
-                Some(self.handle_enter_horizontal_mode(true))
+                Some(self.handle_enter_horizontal_mode(true)?)
             }
             Some(Token::Char(_, cat)) => match cat {
                 Category::Space => {
-                    self.lex_expanded_token();
+                    self.lex_expanded_token()?;
                     self.parse_vertical_list_elem(
-                        group_level,
-                        prev_depth,
-                        internal,
-                    )
+                        group_base, prev_depth, internal,
+                    )?
                 }
                 Category::BeginGroup => {
-                    self.lex_expanded_token();
-                    *group_level += 1;
-                    self.state.push_state();
+                    self.lex_expanded_token()?;
+                    self.push_state(GroupKind::Simple);
                     self.parse_vertical_list_elem(
-                        group_level,
-                        prev_depth,
-                        internal,
-                    )
+                        group_base, prev_depth, internal,
+                    )?
                 }
                 Category::EndGroup => {
-                    if *group_level == 0 {
+                    if self.does_end_group_end_list(group_base) {
                         if internal {
                             None
                         } else {
-                            panic!("Too many }'s!");
+                            // Extra }s at the outer level are ignored.
+                            self.lex_expanded_token()?;
+                            self.report_error(TeXErrorKind::ExtraRightBrace)?;
+                            self.parse_vertical_list_elem(
+                                group_base, prev_depth, internal,
+                            )?
                         }
                     } else {
-                        self.lex_expanded_token();
-                        *group_level -= 1;
-                        self.state.pop_state();
+                        self.parse_end_group()?;
                         self.parse_vertical_list_elem(
-                            group_level,
-                            prev_depth,
-                            internal,
-                        )
+                            group_base, prev_depth, internal,
+                        )?
                     }
                 }
-                _ => panic!("unimplemented"),
+                _ => {
+                    self.skip_unexpected_token(mode)?;
+                    self.parse_vertical_list_elem(
+                        group_base, prev_depth, internal,
+                    )?
+                }
             },
             Some(ref tok) if self.state.is_token_equal_to_prim(tok, "end") => {
+                self.lex_expanded_token()?;
                 if internal {
-                    panic!(r"You can't use \end in internal vertical mode")
+                    self.report_error(TeXErrorKind::CantUseInMode(
+                        tok.clone(),
+                        mode,
+                    ))?;
+                    self.parse_vertical_list_elem(
+                        group_base, prev_depth, internal,
+                    )?
+                } else {
+                    None
                 }
-                self.lex_expanded_token();
-                None
             }
             Some(ref tok) if self.state.is_token_equal_to_prim(tok, "par") => {
                 // \par is completely ignored
-                self.lex_expanded_token();
-                self.parse_vertical_list_elem(group_level, prev_depth, internal)
+                self.lex_expanded_token()?;
+                self.parse_vertical_list_elem(group_base, prev_depth, internal)?
             }
             Some(ref tok)
                 if self.state.is_token_equal_to_prim(tok, "vskip") =>
             {
-                self.lex_expanded_token();
-                let glue = self.parse_glue();
+                self.lex_expanded_token()?;
+                let glue = self.parse_glue()?;
                 Some(VerticalListElem::VSkip(glue))
             }
             Some(ref tok)
                 if self.state.is_token_equal_to_prim(tok, "moveleft") =>
             {
-                self.lex_expanded_token();
-                let shift = self.parse_dimen();
-                if let Some(tex_box) = self.parse_box() {
+                self.lex_expanded_token()?;
+                let shift = self.parse_dimen()?;
+                if let Some(tex_box) = self.parse_box()? {
                     Some(VerticalListElem::Box {
                         tex_box,
                         shift: shift * -1,
                     })
                 } else {
                     self.parse_vertical_list_elem(
-                        group_level,
-                        prev_depth,
-                        internal,
-                    )
+                        group_base, prev_depth, internal,
+                    )?
                 }
             }
             Some(ref tok)
                 if self.state.is_token_equal_to_prim(tok, "moveright") =>
             {
-                self.lex_expanded_token();
-                let shift = self.parse_dimen();
-                if let Some(tex_box) = self.parse_box() {
+                self.lex_expanded_token()?;
+                let shift = self.parse_dimen()?;
+                if let Some(tex_box) = self.parse_box()? {
                     Some(VerticalListElem::Box { tex_box, shift })
                 } else {
                     self.parse_vertical_list_elem(
-                        group_level,
-                        prev_depth,
-                        internal,
-                    )
+                        group_base, prev_depth, internal,
+                    )?
                 }
             }
             _ => {
-                if self.is_assignment_head() {
+                if self.is_assignment_head()? {
                     self.parse_assignment(Some(SpecialVariables {
                         prev_depth: Some(prev_depth),
-                    }));
+                    }))?;
+                    self.parse_vertical_list_elem(
+                        group_base, prev_depth, internal,
+                    )?
+                } else if self.is_show_head()? {
+                    self.parse_show()?;
+                    self.parse_vertical_list_elem(
+                        group_base, prev_depth, internal,
+                    )?
+                } else if self.is_message_head()? {
+                    self.parse_message()?;
                     self.parse_vertical_list_elem(
-                        group_level,
-                        prev_depth,
-                        internal,
-                    )
-                } else if self.is_next_expanded_token_in_set_of_primitives(&[
-                    "indent", "noindent",
-                ]) {
-                    let tok = self.lex_expanded_token().unwrap();
+                        group_base, prev_depth, internal,
+                    )?
+                } else if self.is_after_group_head()? {
+                    self.parse_after_group()?;
+                    self.parse_vertical_list_elem(
+                        group_base, prev_depth, internal,
+                    )?
+                } else if self.is_after_assignment_head()? {
+                    self.parse_after_assignment()?;
+                    self.parse_vertical_list_elem(
+                        group_base, prev_depth, internal,
+                    )?
+                } else if self.is_semi_simple_group_head()? {
+                    self.parse_semi_simple_group()?;
+                    self.parse_vertical_list_elem(
+                        group_base, prev_depth, internal,
+                    )?
+                } else if self.is_case_change_head()? {
+                    self.parse_case_change()?;
+                    self.parse_vertical_list_elem(
+                        group_base, prev_depth, internal,
+                    )?
+                } else if self.is_relax_head()? {
+                    self.lex_expanded_token()?;
+                    self.parse_vertical_list_elem(
+                        group_base, prev_depth, internal,
+                    )?
+                } else if self.is_next_expanded_token_in_set_of_primitives(
+                    &["indent", "noindent"],
+                )? {
+                    let tok = self.lex_expanded_token()?.unwrap();
                     let indent =
                         self.state.is_token_equal_to_prim(&tok, "indent");
-                    Some(self.handle_enter_horizontal_mode(indent))
-                } else if self.is_box_head() {
-                    let maybe_tex_box = self.parse_box();
+                    Some(self.handle_enter_horizontal_mode(indent)?)
+                } else if self.is_box_head()? {
+                    let maybe_tex_box = self.parse_box()?;
                     if let Some(tex_box) = maybe_tex_box {
                         // TODO(xymostech): Insert interline glue here.
                         Some(VerticalListElem::Box {
@@ -180,41 +229,52 @@ impl<'a> Parser<'a> {
                         })
                     } else {
                         self.parse_vertical_list_elem(
-                            group_level,
-                            prev_depth,
-                            internal,
-                        )
+                            group_base, prev_depth, internal,
+                        )?
                     }
                 } else {
-                    panic!("unimplemented");
+                    self.skip_unexpected_token(mode)?;
+                    self.parse_vertical_list_elem(
+                        group_base, prev_depth, internal,
+                    )?
                 }
             }
-        }
+        })
     }
 
     pub fn parse_vertical_list(
         &mut self,
         internal: bool,
-    ) -> Vec<VerticalListElem> {
+    ) -> TeXResult<Vec<VerticalListElem>> {
         let mut result = Vec::new();
 
         // The depth of the most recent box.
         let mut prev_depth = Dimen::from_unit(-1000.0, Unit::Point);
 
-        // TODO(xymostech): Store these as \baselineskip, \lineskiplimit,
-        // \lineskip, and \topskip parameters
-        let baselineskip =
-            Glue::from_dimen(Dimen::from_unit(12.0, Unit::Point));
-        let lineskiplimit = Dimen::from_unit(0.0, Unit::Point);
-        let lineskip = Glue::from_dimen(Dimen::from_unit(1.0, Unit::Point));
-        let topskip = Glue::from_dimen(Dimen::from_unit(10.0, Unit::Point));
-
-        let mut group_level = 0;
-        while let Some(elem) = self.parse_vertical_list_elem(
-            &mut group_level,
-            &mut prev_depth,
-            internal,
-        ) {
+        let mode = if internal {
+            "internal vertical mode"
+        } else {
+            "vertical mode"
+        };
+        let group_base = self.get_group_depth();
+        self.modes.push(mode);
+        loop {
+            self.push_debug_list(|| PartialList {
+                mode,
+                contents: PartialListContents::Vertical(result.clone()),
+            });
+            let maybe_elem = self.parse_vertical_list_elem(
+                group_base,
+                &mut prev_depth,
+                internal,
+            )?;
+            self.pop_debug_list();
+
+            let elem = match maybe_elem {
+                Some(elem) => elem,
+                None => break,
+            };
+
             // Handle box elements specially so we can add interline glue
             if let VerticalListElem::Box {
                 ref tex_box,
@@ -226,8 +286,9 @@ impl<'a> Parser<'a> {
                 // that doesn't exist yet.
                 if !internal && result.is_empty() {
                     let box_height = tex_box.height();
-                    let total_skip =
-                        topskip.clone() - Glue::from_dimen(*box_height);
+                    let topskip =
+                        self.state.get_glue_parameter(GlueParameter::TopSkip);
+                    let total_skip = topskip - Glue::from_dimen(*box_height);
 
                     if total_skip.space > Dimen::zero() {
                         result.push(VerticalListElem::VSkip(total_skip));
@@ -239,13 +300,19 @@ impl<'a> Parser<'a> {
                     // Calculate how much interline glue we'd add if we just
                     // take into account baselineskip - prev_depth - box.height
                     let box_height = tex_box.height();
-                    let total_skip = baselineskip.clone()
+                    let baselineskip = self
+                        .state
+                        .get_glue_parameter(GlueParameter::BaselineSkip);
+                    let total_skip = baselineskip
                         - Glue::from_dimen(*box_height + prev_depth);
 
                     // If the interline glue would be less than lineskiplimit,
                     // use lineskip instead.
+                    let lineskiplimit = self
+                        .state
+                        .get_dimen_parameter(DimenParameter::LineSkipLimit);
                     let interline_glue = if total_skip.space < lineskiplimit {
-                        lineskip.clone()
+                        self.state.get_glue_parameter(GlueParameter::LineSkip)
                     } else {
                         total_skip
                     };
@@ -270,8 +337,9 @@ impl<'a> Parser<'a> {
                 result.push(elem);
             }
         }
+        self.modes.pop();
 
-        result
+        Ok(result)
     }
 }
 
@@ -282,7 +350,7 @@ mod tests {
     use crate::boxes::{GlueSetRatio, GlueSetRatioKind, TeXBox, VerticalBox};
     use crate::dimension::{FilDimen, FilKind, SpringDimen};
     use crate::font::Font;
-    use crate::testing::with_parser;
+    use crate::testing::{with_parser, WithoutOrigins};
 
     lazy_static! {
         static ref CMR10: Font = Font {
@@ -293,7 +361,10 @@ mod tests {
 
     fn assert_parses_to(lines: &[&str], expected_list: &[VerticalListElem]) {
         with_parser(lines, |parser| {
-            assert_eq!(parser.parse_vertical_list(true), expected_list);
+            assert_eq!(
+                parser.parse_vertical_list(true).unwrap().without_origins(),
+                expected_list
+            );
         });
     }
 
@@ -302,7 +373,10 @@ mod tests {
         expected_list: &[VerticalListElem],
     ) {
         with_parser(lines, |parser| {
-            assert_eq!(parser.parse_vertical_list(false), expected_list);
+            assert_eq!(
+                parser.parse_vertical_list(false).unwrap().without_origins(),
+                expected_list
+            );
         });
     }
 
@@ -396,7 +470,7 @@ mod tests {
     fn it_finishes_parsing_before_unmatched_close_group() {
         with_parser(&[r"{\vskip 1pt{{}\vskip 1pt}{}}}%"], |parser| {
             assert_eq!(
-                parser.parse_vertical_list(true),
+                parser.parse_vertical_list(true).unwrap().without_origins(),
                 &[
                     VerticalListElem::VSkip(Glue {
                         space: Dimen::from_unit(1.0, Unit::Point),
@@ -412,16 +486,27 @@ mod tests {
             );
 
             assert_eq!(
-                parser.lex_unexpanded_token(),
+                parser.lex_unexpanded_token().unwrap(),
                 Some(Token::Char('}', Category::EndGroup))
             );
         });
     }
 
     #[test]
-    #[should_panic(expected = r"You can't use \end in internal vertical mode")]
     fn it_should_fail_with_end_in_internal_vertical_mode() {
-        assert_parses_to(&[r"\vskip 0pt\end%"], &[]);
+        with_parser(&[r"\vskip 0pt\end%"], |parser| {
+            assert_eq!(
+                parser.parse_vertical_list(true).unwrap().without_origins(),
+                &[VerticalListElem::VSkip(Glue::from_dimen(Dimen::zero()))]
+            );
+            assert_eq!(
+                parser.take_errors()[0].kind,
+                TeXErrorKind::CantUseInMode(
+                    Token::ControlSequence("end".to_string()),
+                    "internal vertical mode"
+                )
+            );
+        });
     }
 
     #[test]
@@ -431,13 +516,13 @@ mod tests {
 
     #[test]
     fn it_ends_non_internal_vertical_mode() {
-        with_parser(&[r"\hbox{}\end a%"], |parser| {
-            let list = parser.parse_vertical_list(false);
+        with_parser(&[r"\topskip=10pt \hbox{}\end a%"], |parser| {
+            let list = parser.parse_vertical_list(false).unwrap();
             // \topskip + \hbox{}
             assert_eq!(list.len(), 2);
 
             assert_eq!(
-                parser.lex_unexpanded_token(),
+                parser.lex_unexpanded_token().unwrap(),
                 Some(Token::Char('a', Category::Letter))
             );
         });
@@ -446,7 +531,7 @@ mod tests {
     #[test]
     fn it_adds_topskip() {
         assert_parses_to_non_internal(
-            &[r"\vbox{}\end%"],
+            &[r"\topskip=10pt \vbox{}\end%"],
             &[
                 VerticalListElem::VSkip(Glue::from_dimen(Dimen::from_unit(
                     10.0,
@@ -466,7 +551,7 @@ mod tests {
         );
 
         assert_parses_to_non_internal(
-            &[r"\vbox to5pt{\vskip 0pt plus1pt}\end%"],
+            &[r"\topskip=10pt \vbox to5pt{\vskip 0pt plus1pt}\end%"],
             &[
                 VerticalListElem::VSkip(Glue::from_dimen(Dimen::from_unit(
                     5.0,
@@ -496,7 +581,7 @@ mod tests {
         );
 
         assert_parses_to_non_internal(
-            &[r"\vbox to15pt{\vskip 0pt plus1pt}\end%"],
+            &[r"\topskip=10pt \vbox to15pt{\vskip 0pt plus1pt}\end%"],
             &[VerticalListElem::Box {
                 tex_box: TeXBox::VerticalBox(VerticalBox {
                     height: Dimen::from_unit(15.0, Unit::Point),
@@ -521,18 +606,73 @@ mod tests {
     }
 
     #[test]
-    #[should_panic(expected = "Too many }'s")]
     fn it_should_fail_with_too_many_end_groups() {
-        with_parser(&["{{}{{}}}}%"], |parser| {
-            parser.parse_vertical_list(false);
+        with_parser(&[r"{{}{{}}}}\end%"], |parser| {
+            assert_eq!(
+                parser.parse_vertical_list(false).unwrap().without_origins(),
+                &[]
+            );
+            assert_eq!(
+                parser.take_errors()[0].kind,
+                TeXErrorKind::ExtraRightBrace
+            );
         });
     }
 
     #[test]
-    #[should_panic(expected = r"EOF found before \end")]
     fn it_should_fail_with_no_end() {
         with_parser(&[r"\vskip 0pt%"], |parser| {
-            parser.parse_vertical_list(false);
+            assert_eq!(
+                parser.parse_vertical_list(false).unwrap_err().kind,
+                TeXErrorKind::EmergencyStop
+            );
+            parser.take_errors();
+        });
+    }
+
+    #[test]
+    fn it_doesnt_end_semi_simple_groups_with_braces() {
+        with_parser(&[r"\begingroup\vskip 1pt}\endgroup%"], |parser| {
+            assert_eq!(
+                parser.parse_vertical_list(true).unwrap().without_origins(),
+                &[VerticalListElem::VSkip(Glue::from_dimen(Dimen::from_unit(
+                    1.0,
+                    Unit::Point
+                )))]
+            );
+            assert_eq!(
+                parser.take_errors()[0].kind,
+                TeXErrorKind::ExtraRightBraceOrForgotten("\\endgroup")
+            );
+        });
+    }
+
+    #[test]
+    fn it_skips_undefined_control_sequences() {
+        with_parser(&[r"\vskip 1pt\undefined\vskip 2pt%"], |parser| {
+            assert_eq!(
+                parser.parse_vertical_list(true).unwrap().without_origins(),
+                &[
+                    VerticalListElem::VSkip(Glue::from_dimen(
+                        Dimen::from_unit(1.0, Unit::Point)
+                    )),
+                    VerticalListElem::VSkip(Glue::from_dimen(
+                        Dimen::from_unit(2.0, Unit::Point)
+                    )),
+                ]
+            );
+
+            let errors = parser.take_errors();
+            assert_eq!(
+                errors[0].kind,
+                TeXErrorKind::UndefinedControlSequence(Token::ControlSequence(
+                    "undefined".to_string()
+                ))
+            );
+            assert_eq!(
+                errors[0].origin.as_ref().unwrap().source_location(),
+                Some((None, 1, 11))
+            );
         });
     }
 
@@ -543,24 +683,25 @@ mod tests {
                 r"\setbox0=\hbox{a}%",
                 r"\setbox1=\hbox{b}%",
                 r"\setbox2=\hbox{b}%",
+                r"\baselineskip=12pt%",
                 r"\vskip 1pt%",
                 r"\hbox{a}%",
                 r"\vskip 2pt%",
                 r"\box2",
             ],
             |parser| {
-                parser.parse_assignment(None);
-                parser.parse_assignment(None);
+                parser.parse_assignment(None).unwrap();
+                parser.parse_assignment(None).unwrap();
 
-                let box0 = parser.state.get_box(0).unwrap();
-                let box1 = parser.state.get_box(1).unwrap();
+                let box0 = parser.state.get_box(0).unwrap().without_origins();
+                let box1 = parser.state.get_box(1).unwrap().without_origins();
 
                 let interline_glue = Dimen::from_unit(12.0, Unit::Point)
                     - *box0.depth()
                     - *box1.height();
 
                 assert_eq!(
-                    parser.parse_vertical_list(true),
+                    parser.parse_vertical_list(true).unwrap().without_origins(),
                     &[
                         VerticalListElem::VSkip(Glue::from_dimen(
                             Dimen::from_unit(1.0, Unit::Point)
@@ -591,24 +732,25 @@ mod tests {
             &[
                 r"\setbox0=\hbox{a}%",
                 r"\setbox1=\hbox{g}%",
+                r"\baselineskip=12pt%",
                 r"\vskip 1pt%",
                 r"\noindent a\par%",
                 r"\vskip 2pt%",
                 r"\noindent g\par%",
             ],
             |parser| {
-                parser.parse_assignment(None);
-                parser.parse_assignment(None);
+                parser.parse_assignment(None).unwrap();
+                parser.parse_assignment(None).unwrap();
 
-                let box0 = parser.state.get_box(0).unwrap();
-                let box1 = parser.state.get_box(1).unwrap();
+                let box0 = parser.state.get_box(0).unwrap().without_origins();
+                let box1 = parser.state.get_box(1).unwrap().without_origins();
 
                 let interline_glue = Dimen::from_unit(12.0, Unit::Point)
                     - *box0.depth()
                     - *box1.height();
 
                 assert_eq!(
-                    parser.parse_vertical_list(true),
+                    parser.parse_vertical_list(true).unwrap().without_origins(),
                     &[
                         VerticalListElem::VSkip(Glue::from_dimen(
                             Dimen::from_unit(1.0, Unit::Point)
@@ -641,26 +783,27 @@ mod tests {
                 r"\wd2=20pt%",
                 r"\setbox0=\hbox{\copy2 a}%",
                 r"\setbox1=\hbox{\copy2 g}%",
+                r"\parindent=20pt \baselineskip=12pt%",
                 r"\vskip 1pt%",
                 r"\indent a\par%",
                 r"\vskip 2pt%",
                 r"\indent g\par%",
             ],
             |parser| {
-                parser.parse_assignment(None);
-                parser.parse_assignment(None);
-                parser.parse_assignment(None);
-                parser.parse_assignment(None);
+                parser.parse_assignment(None).unwrap();
+                parser.parse_assignment(None).unwrap();
+                parser.parse_assignment(None).unwrap();
+                parser.parse_assignment(None).unwrap();
 
-                let box0 = parser.state.get_box(0).unwrap();
-                let box1 = parser.state.get_box(1).unwrap();
+                let box0 = parser.state.get_box(0).unwrap().without_origins();
+                let box1 = parser.state.get_box(1).unwrap().without_origins();
 
                 let interline_glue = Dimen::from_unit(12.0, Unit::Point)
                     - *box0.depth()
                     - *box1.height();
 
                 assert_eq!(
-                    parser.parse_vertical_list(true),
+                    parser.parse_vertical_list(true).unwrap().without_origins(),
                     &[
                         VerticalListElem::VSkip(Glue::from_dimen(
                             Dimen::from_unit(1.0, Unit::Point)
@@ -695,6 +838,7 @@ mod tests {
                 r"\setbox2=\hbox{\copy0 @}%",
                 r"\setbox3=\hbox{\copy0 $a$}%",
                 r"\setbox4=\hbox{\copy0 \hskip1pt}%",
+                r"\parindent=20pt \baselineskip=12pt%",
                 r"a\par%",
                 r"@\par%",
                 r"$a$\par%",
@@ -702,17 +846,18 @@ mod tests {
                 r"\char 97\par%",
             ],
             |parser| {
-                parser.parse_assignment(None);
-                parser.parse_assignment(None);
-                parser.parse_assignment(None);
-                parser.parse_assignment(None);
-                parser.parse_assignment(None);
-                parser.parse_assignment(None);
-
-                let box1 = parser.state.get_box(1).unwrap();
-                let box2 = parser.state.get_box(2).unwrap();
-                let box3 = parser.state.get_box(3).unwrap();
-                let box4 = parser.state.get_box(4).unwrap();
+                parser.parse_assignment(None).unwrap();
+                parser.parse_assignment(None).unwrap();
+                parser.parse_assignment(None).unwrap();
+                parser.parse_assignment(None).unwrap();
+                parser.parse_assignment(None).unwrap();
+                parser.parse_assignment(None).unwrap();
+                parser.parse_assignment(None).unwrap();
+
+                let box1 = parser.state.get_box(1).unwrap().without_origins();
+                let box2 = parser.state.get_box(2).unwrap().without_origins();
+                let box3 = parser.state.get_box(3).unwrap().without_origins();
+                let box4 = parser.state.get_box(4).unwrap().without_origins();
 
                 let interline_glue1 = Dimen::from_unit(12.0, Unit::Point)
                     - *box1.depth()
@@ -728,7 +873,7 @@ mod tests {
                     - *box1.height();
 
                 assert_eq!(
-                    parser.parse_vertical_list(true),
+                    parser.parse_vertical_list(true).unwrap().without_origins(),
                     &[
                         VerticalListElem::Box {
                             tex_box: box1.clone(),
@@ -779,24 +924,29 @@ mod tests {
                 r"\dp1=8pt%",
                 r"\setbox2=\hbox{}%",
                 r"\ht2=5pt%",
+                r"\baselineskip=12pt \lineskip=1pt%",
                 r"\copy0%",
                 r"\copy1%",
                 r"\copy2%",
             ],
             |parser| {
-                parser.parse_assignment(None);
-                parser.parse_assignment(None);
-                parser.parse_assignment(None);
-                parser.parse_assignment(None);
-                parser.parse_assignment(None);
-                parser.parse_assignment(None);
-                parser.parse_assignment(None);
+                parser.parse_assignment(None).unwrap();
+                parser.parse_assignment(None).unwrap();
+                parser.parse_assignment(None).unwrap();
+                parser.parse_assignment(None).unwrap();
+                parser.parse_assignment(None).unwrap();
+                parser.parse_assignment(None).unwrap();
+                parser.parse_assignment(None).unwrap();
 
                 assert_eq!(
-                    parser.parse_vertical_list(true),
+                    parser.parse_vertical_list(true).unwrap().without_origins(),
                     &[
                         VerticalListElem::Box {
-                            tex_box: parser.state.get_box(0).unwrap(),
+                            tex_box: parser
+                                .state
+                                .get_box(0)
+                                .unwrap()
+                                .without_origins(),
                             shift: Dimen::zero()
                         },
                         // 12pt - 5pt - 5pt = 2pt of interline glue
@@ -804,7 +954,11 @@ mod tests {
                             Dimen::from_unit(2.0, Unit::Point)
                         )),
                         VerticalListElem::Box {
-                            tex_box: parser.state.get_box(1).unwrap(),
+                            tex_box: parser
+                                .state
+                                .get_box(1)
+                                .unwrap()
+                                .without_origins(),
                             shift: Dimen::zero()
                         },
                         // 12pt - 8pt - 5pt = -1pt
@@ -814,7 +968,11 @@ mod tests {
                             Dimen::from_unit(1.0, Unit::Point)
                         )),
                         VerticalListElem::Box {
-                            tex_box: parser.state.get_box(2).unwrap(),
+                            tex_box: parser
+                                .state
+                                .get_box(2)
+                                .unwrap()
+                                .without_origins(),
                             shift: Dimen::zero()
                         },
                     ]
@@ -823,11 +981,25 @@ mod tests {
         );
     }
 
+    #[test]
+    fn it_ignores_relax() {
+        assert_parses_to(
+            &[
+                r"\relax\vskip 1pt\csname undefined\endcsname%",
+                r"\noexpand\undefined\relax%",
+            ],
+            &[VerticalListElem::VSkip(Glue::from_dimen(Dimen::from_unit(
+                1.0,
+                Unit::Point,
+            )))],
+        );
+    }
+
     #[test]
     fn it_ignores_par() {
         with_parser(&[r"\vskip1pt", r"", r"\vskip1pt%"], |parser| {
             assert_eq!(
-                parser.parse_vertical_list(true),
+                parser.parse_vertical_list(true).unwrap().without_origins(),
                 &[
                     VerticalListElem::VSkip(Glue::from_dimen(
                         Dimen::from_unit(1.0, Unit::Point)
@@ -846,17 +1018,20 @@ mod tests {
             &[
                 r"\hbox{a}%",
                 r"\vbox{b}%",
+                r"\baselineskip=12pt%",
                 r"\moveleft 2pt \hbox{a}\vskip 2pt\moveright 3pt \vbox{b}%",
             ],
             |parser| {
-                let abox = parser.parse_box().unwrap();
-                let bbox = parser.parse_box().unwrap();
+                let abox =
+                    parser.parse_box().unwrap().unwrap().without_origins();
+                let bbox =
+                    parser.parse_box().unwrap().unwrap().without_origins();
 
                 let metrics =
                     parser.state.get_metrics_for_font(&CMR10).unwrap();
 
                 assert_eq!(
-                    parser.parse_vertical_list(true),
+                    parser.parse_vertical_list(true).unwrap().without_origins(),
                     &[
                         VerticalListElem::Box {
                             tex_box: abox,
@@ -887,10 +1062,11 @@ mod tests {
                 r"\hbox{a}\moveleft 2pt \box10\moveright 2pt \box11%",
             ],
             |parser| {
-                let abox = parser.parse_box().unwrap();
+                let abox =
+                    parser.parse_box().unwrap().unwrap().without_origins();
 
                 assert_eq!(
-                    parser.parse_vertical_list(true),
+                    parser.parse_vertical_list(true).unwrap().without_origins(),
                     &[VerticalListElem::Box {
                         tex_box: abox,
                         shift: Dimen::zero(),
@@ -903,7 +1079,7 @@ mod tests {
     #[test]
     fn it_allows_prev_depth_assignments() {
         with_parser(&[r"\prevdepth=2pt%", r"\vskip 2pt%"], |parser| {
-            parser.parse_vertical_list(true);
+            parser.parse_vertical_list(true).unwrap();
         });
     }
 
@@ -918,6 +1094,7 @@ mod tests {
                 r"\dp1=8pt%",
                 r"\setbox2=\hbox{}%",
                 r"\ht2=5pt%",
+                r"\baselineskip=12pt%",
                 r"\copy0%",
                 r"\prevdepth=3pt%",
                 r"\copy1%",
@@ -926,10 +1103,14 @@ mod tests {
             ],
             |parser| {
                 assert_eq!(
-                    parser.parse_vertical_list(true),
+                    parser.parse_vertical_list(true).unwrap().without_origins(),
                     &[
                         VerticalListElem::Box {
-                            tex_box: parser.state.get_box(0).unwrap(),
+                            tex_box: parser
+                                .state
+                                .get_box(0)
+                                .unwrap()
+                                .without_origins(),
                             shift: Dimen::zero()
                         },
                         // prevdepth=3pt instead of the original 5pt
@@ -938,13 +1119,21 @@ mod tests {
                             Dimen::from_unit(4.0, Unit::Point)
                         )),
                         VerticalListElem::Box {
-                            tex_box: parser.state.get_box(1).unwrap(),
+                            tex_box: parser
+                                .state
+                                .get_box(1)
+                                .unwrap()
+                                .without_origins(),
                             shift: Dimen::zero()
                         },
                         // no interline glue because prevdepth was set to
                         // -1000pt
                         VerticalListElem::Box {
-                            tex_box: parser.state.get_box(2).unwrap(),
+                            tex_box: parser
+                                .state
+                                .get_box(2)
+                                .unwrap()
+                                .without_origins(),
                             shift: Dimen::zero()
                         },
                     ]
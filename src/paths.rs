@@ -1,4 +1,5 @@
-/// Module for finding paths to useful files
+/// Module for finding paths to useful files with kpathsea. This is only built
+/// with the "kpathsea" feature.
 use std::sync::Mutex;
 
 lazy_static! {
@@ -14,23 +15,37 @@ lazy_static! {
     static ref SHARED_KPATHS: Mutex<Option<kpathsea::Kpaths>> = Mutex::new(None);
 }
 
-/// Given a font name (like "cmr10"), returns a path to the font if it can be
-/// found.
-pub fn get_path_to_font(font_name: &str) -> Option<String> {
+// Runs a function with the shared kpathsea instance, setting it up first if
+// this is the first time it's needed. If we can't initialize kpathsea
+// successfully, we just say we couldn't find anything.
+fn with_kpaths<F>(func: F) -> Option<String>
+where
+    F: FnOnce(&kpathsea::Kpaths) -> Option<String>,
+{
     let mut maybe_kpse = SHARED_KPATHS.lock().unwrap();
 
     if let Some(ref kpse) = *maybe_kpse {
-        kpse.find_file(font_name)
+        func(kpse)
     } else {
         match kpathsea::Kpaths::new() {
             Ok(kpse) => {
-                let result = kpse.find_file(font_name);
+                let result = func(&kpse);
                 *maybe_kpse = Some(kpse);
                 result
             }
-            // If we can't initialize kpathsea successfully, just say we
-            // couldn't find the font.
             Err(_) => None,
         }
     }
 }
+
+/// Given a font name (like "cmr10"), returns a path to the font if it can be
+/// found.
+pub fn get_path_to_font(font_name: &str) -> Option<String> {
+    with_kpaths(|kpse| kpse.find_file(font_name))
+}
+
+/// Given the full name of a file from \input (like "story.tex"), returns a
+/// path to the file if kpathsea knows where to find it.
+pub fn get_path_to_tex_file(file_name: &str) -> Option<String> {
+    with_kpaths(|kpse| kpse.find_file(file_name))
+}
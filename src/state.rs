@@ -1,5 +1,6 @@
 use std::cell::{Ref, RefCell};
 use std::collections::HashMap;
+use std::hash::Hash;
 use std::rc::Rc;
 
 use crate::boxes::TeXBox;
@@ -7,8 +8,14 @@ use crate::category::Category;
 use crate::dimension::{Dimen, Unit};
 use crate::font::Font;
 use crate::font_metrics::FontMetrics;
+use crate::font_provider::{default_font_provider, FontProvider};
+use crate::glue::{Glue, MuGlue};
 use crate::makro::Macro;
 use crate::math_code::MathCode;
+use crate::parameter::{
+    all_parameter_primitives, DimenParameter, GlueParameter, IntegerParameter,
+    MuGlueParameter, INTEGER_PARAMETERS,
+};
 use crate::token::Token;
 
 // A list of all primitive control sequences, used so that we can \let other
@@ -19,14 +26,40 @@ const ALL_PRIMITIVES: &[&str] = &[
     "fi",
     "else",
     "def",
+    "gdef",
+    "edef",
+    "xdef",
     "let",
     "global",
+    "long",
+    "outer",
     "count",
+    "dimen",
+    "skip",
+    "muskip",
+    "toks",
     "ifnum",
+    "ifdim",
+    "ifodd",
+    "ifx",
+    "if",
+    "ifcat",
+    "ifcase",
+    "or",
+    "ifvmode",
+    "ifhmode",
+    "ifmmode",
+    "ifinner",
+    "ifvoid",
+    "ifhbox",
+    "ifvbox",
+    "ifeof",
     "advance",
     "multiply",
     "divide",
     "number",
+    "romannumeral",
+    "the",
     "par",
     "hskip",
     "hbox",
@@ -43,7 +76,27 @@ const ALL_PRIMITIVES: &[&str] = &[
     "copy",
     "vbox",
     "mathchardef",
+    "chardef",
+    "countdef",
+    "dimendef",
+    "skipdef",
+    "muskipdef",
+    "toksdef",
     "mathcode",
+    "catcode",
+    "lccode",
+    "uccode",
+    "sfcode",
+    "delcode",
+    "uppercase",
+    "lowercase",
+    "csname",
+    "endcsname",
+    "string",
+    "fontname",
+    "jobname",
+    "expandafter",
+    "noexpand",
     "displaystyle",
     "textstyle",
     "scriptstyle",
@@ -61,6 +114,21 @@ const ALL_PRIMITIVES: &[&str] = &[
     "overwithdelims",
     "atopwithdelims",
     "abovewithdelims",
+    "show",
+    "showthe",
+    "meaning",
+    "showbox",
+    "message",
+    "errmessage",
+    "aftergroup",
+    "begingroup",
+    "endgroup",
+    "afterassignment",
+    "immediate",
+    "write",
+    "input",
+    "endinput",
+    "inputlineno",
 ];
 
 fn is_primitive(maybe_prim: &str) -> bool {
@@ -69,18 +137,145 @@ fn is_primitive(maybe_prim: &str) -> bool {
             return true;
         }
     }
-    false
+    all_parameter_primitives().any(|prim| prim == maybe_prim)
 }
 
-#[derive(Clone)]
-enum TokenDefinition {
+/// The different meanings that a token can be given with \def, \let, etc.
+#[derive(Clone, PartialEq, Debug)]
+pub enum TokenDefinition {
     Macro(Rc<Macro>),
     Token(Token),
     MathCode(MathCode),
+    // The shorthands made by \chardef, \countdef, \dimendef, \skipdef,
+    // \muskipdef and \toksdef.
+    Char(char),
+    CountRegister(u8),
+    DimenRegister(u8),
+    SkipRegister(u8),
+    MuSkipRegister(u8),
+    ToksRegister(u8),
     Primitive(&'static str),
     Font(Font),
 }
 
+/// Identifies a single value in the state which is affected by grouping, like
+/// the category code of a character or the value of a count register.
+#[derive(Debug, PartialEq, Eq, Clone, Hash)]
+pub enum StateEntry {
+    Category(char),
+    MathCode(char),
+    LowercaseCode(char),
+    UppercaseCode(char),
+    SpaceFactorCode(char),
+    DelimiterCode(char),
+    Definition(Token),
+    Count(u8),
+    Dimen(u8),
+    Skip(u8),
+    MuSkip(u8),
+    Toks(u8),
+    IntegerParameter(IntegerParameter),
+    DimenParameter(DimenParameter),
+    GlueParameter(GlueParameter),
+    MuGlueParameter(MuGlueParameter),
+    Box(u8),
+    CurrentFont,
+}
+
+/// What happened to an entry which was saved when it was assigned inside of a
+/// group, once that group ended. Entries are restored to the value they had
+/// before the group, unless they were assigned globally since then, in which
+/// case TeX retains their new value.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum RestoredEntry {
+    Restored(StateEntry),
+    Retained(StateEntry),
+}
+
+/// The value of a single StateEntry.
+#[derive(Debug, PartialEq, Clone)]
+pub enum StateValue {
+    Category(Category),
+    MathCode(MathCode),
+    LowercaseCode(char),
+    UppercaseCode(char),
+    SpaceFactorCode(u16),
+    DelimiterCode(i32),
+    // None means that the token is undefined.
+    Definition(Option<TokenDefinition>),
+    Count(i32),
+    Dimen(Dimen),
+    Skip(Glue),
+    MuSkip(MuGlue),
+    Toks(Vec<Token>),
+    IntegerParameter(i32),
+    DimenParameter(Dimen),
+    GlueParameter(Glue),
+    MuGlueParameter(MuGlue),
+    Box(Option<TeXBox>),
+    Font(Font),
+}
+
+/// A single difference between two snapshots of the state.
+#[derive(Debug, PartialEq, Clone)]
+pub struct StateChange {
+    pub entry: StateEntry,
+    pub before: StateValue,
+    pub after: StateValue,
+}
+
+/// A copy of everything in the state that is affected by grouping, as it was
+/// when the snapshot was taken. Snapshots can be compared with each other to
+/// find out what happened in between them, e.g. what plain.tex changed.
+#[derive(Clone)]
+pub struct StateSnapshot {
+    name: String,
+    inner: TeXStateInner,
+}
+
+impl StateSnapshot {
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns everything whose value is different in a later snapshot,
+    /// grouped by kind (categories, then math codes, definitions, count,
+    /// dimen, skip, muskip and toks registers, integer parameters, boxes, and
+    /// finally the current font).
+    pub fn diff(&self, later: &StateSnapshot) -> Vec<StateChange> {
+        self.inner
+            .get_changed_entries(&later.inner)
+            .into_iter()
+            .map(|entry| StateChange {
+                before: self.inner.get_value(&entry),
+                after: later.inner.get_value(&entry),
+                entry,
+            })
+            // Boxes which were copied into different snapshots are always
+            // seen as changed, even if they have the same contents.
+            .filter(|change| change.before != change.after)
+            .collect()
+    }
+}
+
+// Returns the keys of either of two maps whose values are different between
+// them.
+fn changed_keys<K, V, F>(
+    a: &HashMap<K, V>,
+    b: &HashMap<K, V>,
+    is_same: F,
+) -> Vec<K>
+where
+    K: Eq + Hash + Clone,
+    F: Fn(Option<&V>, Option<&V>) -> bool,
+{
+    a.keys()
+        .chain(b.keys().filter(|key| !a.contains_key(key)))
+        .filter(|key| !is_same(a.get(key), b.get(key)))
+        .cloned()
+        .collect()
+}
+
 // This contains all of the mutable state about our TeX environment
 #[derive(Clone)]
 pub struct TeXStateInner {
@@ -92,6 +287,21 @@ pub struct TeXStateInner {
     // with. Set and retrieved with \mathcode, only used in math mode.
     math_code_map: HashMap<char, MathCode>,
 
+    // The lowercase and uppercase versions of characters, set with \lccode
+    // and \uccode and used by \lowercase and \uppercase. Characters which
+    // aren't in these maps have a code of 0, meaning they don't change.
+    lowercase_code_map: HashMap<char, char>,
+    uppercase_code_map: HashMap<char, char>,
+
+    // The space factor codes of characters, set with \sfcode. Characters
+    // which aren't in the map have a code of 1000.
+    space_factor_code_map: HashMap<char, u16>,
+
+    // The delimiter codes of characters, set with \delcode. Characters which
+    // aren't in the map have a code of -1, meaning they can't be used as
+    // delimiters.
+    delimiter_code_map: HashMap<char, i32>,
+
     // There are several ways to redefine what a given token means, with \def,
     // \let, \chardef, etc. This map contains the definition of each redefined
     // token.
@@ -103,6 +313,25 @@ pub struct TeXStateInner {
     // close track of that).
     count_registers: [i32; 256],
 
+    // TeX's 256 dimen registers.
+    dimen_registers: [Dimen; 256],
+
+    // TeX's 256 skip, muskip and toks registers. Like boxes, we expect most
+    // of these to be unused, so registers which aren't in the maps are zero
+    // (or empty, for toks). Token lists are Rc so that entering a group
+    // doesn't copy them.
+    skip_registers: HashMap<u8, Glue>,
+    muskip_registers: HashMap<u8, MuGlue>,
+    toks_registers: HashMap<u8, Rc<Vec<Token>>>,
+
+    // The values of TeX's integer, dimen, glue and muglue parameters, like
+    // \showboxdepth and \baselineskip. Parameters which aren't in the maps
+    // are zero.
+    integer_parameters: HashMap<IntegerParameter, i32>,
+    dimen_parameters: HashMap<DimenParameter, Dimen>,
+    glue_parameters: HashMap<GlueParameter, Glue>,
+    mu_glue_parameters: HashMap<MuGlueParameter, MuGlue>,
+
     // TeX's 256 box registers. The values are designed such that:
     //  * When entering a new group, we don't make a copy of a box by making
     //    the values Rc.
@@ -134,6 +363,7 @@ impl TeXStateInner {
         }
         // Other various default categories
         initial_categories.insert('\u{0000}', Category::Ignored);
+        initial_categories.insert('\u{007f}', Category::Invalid);
         initial_categories.insert('\u{00ff}', Category::Invalid);
         initial_categories.insert('\n', Category::EndOfLine);
         initial_categories.insert('\\', Category::Escape);
@@ -161,20 +391,59 @@ impl TeXStateInner {
             }
         }
 
+        // Like in IniTeX, letters are their own lowercase and uppercase
+        // codes, and uppercase letters have a space factor code of 999.
+        let mut initial_lowercase_codes = HashMap::new();
+        let mut initial_uppercase_codes = HashMap::new();
+        let mut initial_space_factor_codes = HashMap::new();
+        for ch in ('a'..='z').chain('A'..='Z') {
+            initial_lowercase_codes.insert(ch, ch.to_ascii_lowercase());
+            initial_uppercase_codes.insert(ch, ch.to_ascii_uppercase());
+        }
+        for ch in 'A'..='Z' {
+            initial_space_factor_codes.insert(ch, 999);
+        }
+
+        let mut initial_delimiter_codes = HashMap::new();
+        initial_delimiter_codes.insert('.', 0);
+
         let mut token_definitions = HashMap::new();
 
-        for primitive in ALL_PRIMITIVES {
+        for primitive in ALL_PRIMITIVES
+            .iter()
+            .copied()
+            .chain(all_parameter_primitives())
+        {
             token_definitions.insert(
                 Token::ControlSequence(primitive.to_string()),
                 TokenDefinition::Primitive(primitive),
             );
         }
 
+        let initial_integer_parameters = INTEGER_PARAMETERS
+            .iter()
+            .map(|(_, parameter)| (*parameter, parameter.get_initial_value()))
+            .filter(|(_, value)| *value != 0)
+            .collect();
+
         TeXStateInner {
             category_map: initial_categories,
             math_code_map: initial_math_codes,
+            lowercase_code_map: initial_lowercase_codes,
+            uppercase_code_map: initial_uppercase_codes,
+            space_factor_code_map: initial_space_factor_codes,
+            delimiter_code_map: initial_delimiter_codes,
             token_definition_map: token_definitions,
             count_registers: [0; 256],
+            dimen_registers: [Dimen::zero(); 256],
+            skip_registers: HashMap::new(),
+            muskip_registers: HashMap::new(),
+            toks_registers: HashMap::new(),
+            integer_parameters: initial_integer_parameters,
+            // Like in IniTeX, the other parameters all start out as zero.
+            dimen_parameters: HashMap::new(),
+            glue_parameters: HashMap::new(),
+            mu_glue_parameters: HashMap::new(),
             box_registers: HashMap::new(),
             current_font: Font {
                 // TODO(xymostech): This should initially be "nullfont"
@@ -206,6 +475,58 @@ impl TeXStateInner {
         self.math_code_map.insert(ch, mathcode.clone());
     }
 
+    fn get_lowercase_code(&self, ch: char) -> char {
+        *self.lowercase_code_map.get(&ch).unwrap_or(&'\0')
+    }
+
+    fn set_lowercase_code(&mut self, ch: char, code: char) {
+        self.lowercase_code_map.insert(ch, code);
+    }
+
+    fn get_uppercase_code(&self, ch: char) -> char {
+        *self.uppercase_code_map.get(&ch).unwrap_or(&'\0')
+    }
+
+    fn set_uppercase_code(&mut self, ch: char, code: char) {
+        self.uppercase_code_map.insert(ch, code);
+    }
+
+    fn get_space_factor_code(&self, ch: char) -> u16 {
+        *self.space_factor_code_map.get(&ch).unwrap_or(&1000)
+    }
+
+    fn set_space_factor_code(&mut self, ch: char, code: u16) {
+        self.space_factor_code_map.insert(ch, code);
+    }
+
+    fn get_delimiter_code(&self, ch: char) -> i32 {
+        *self.delimiter_code_map.get(&ch).unwrap_or(&-1)
+    }
+
+    fn set_delimiter_code(&mut self, ch: char, code: i32) {
+        self.delimiter_code_map.insert(ch, code);
+    }
+
+    fn get_definition(&self, token: &Token) -> Option<TokenDefinition> {
+        self.token_definition_map.get(token).cloned()
+    }
+
+    // Characters that aren't active can't be redefined, so they always mean
+    // themselves.
+    fn get_meaning_definition(&self, token: &Token) -> Option<TokenDefinition> {
+        match token {
+            Token::Char(_, cat) if *cat != Category::Active => {
+                Some(TokenDefinition::Token(token.clone()))
+            }
+            _ => self.get_definition(token),
+        }
+    }
+
+    fn is_meaning_equal(&self, token1: &Token, token2: &Token) -> bool {
+        self.get_meaning_definition(token1)
+            == self.get_meaning_definition(token2)
+    }
+
     fn get_math_chardef(&self, token: &Token) -> Option<MathCode> {
         if let Some(TokenDefinition::MathCode(math_code)) =
             self.token_definition_map.get(token)
@@ -223,6 +544,27 @@ impl TeXStateInner {
         );
     }
 
+    fn get_chardef(&self, token: &Token) -> Option<char> {
+        if let Some(TokenDefinition::Char(ch)) =
+            self.token_definition_map.get(token)
+        {
+            Some(*ch)
+        } else {
+            None
+        }
+    }
+
+    // Used for all of the shorthand definitions other than \mathchardef,
+    // which has its own setter.
+    fn set_shorthand_definition(
+        &mut self,
+        token: &Token,
+        definition: &TokenDefinition,
+    ) {
+        self.token_definition_map
+            .insert(token.clone(), definition.clone());
+    }
+
     fn get_macro(&self, token: &Token) -> Option<Rc<Macro>> {
         if let Some(TokenDefinition::Macro(makro)) =
             self.token_definition_map.get(token)
@@ -295,6 +637,16 @@ impl TeXStateInner {
         false
     }
 
+    fn get_primitive(&self, token: &Token) -> Option<&'static str> {
+        if let Some(TokenDefinition::Primitive(prim)) =
+            self.token_definition_map.get(token)
+        {
+            Some(prim)
+        } else {
+            None
+        }
+    }
+
     fn get_count(&self, register_index: u8) -> i32 {
         self.count_registers[register_index as usize]
     }
@@ -307,6 +659,96 @@ impl TeXStateInner {
         self.count_registers[register_index as usize] = value;
     }
 
+    fn get_dimen(&self, register_index: u8) -> Dimen {
+        self.dimen_registers[register_index as usize]
+    }
+
+    fn set_dimen(&mut self, register_index: u8, value: Dimen) {
+        self.dimen_registers[register_index as usize] = value;
+    }
+
+    fn get_skip(&self, register_index: u8) -> Glue {
+        self.skip_registers
+            .get(&register_index)
+            .cloned()
+            .unwrap_or_else(Glue::zero)
+    }
+
+    fn set_skip(&mut self, register_index: u8, value: &Glue) {
+        self.skip_registers.insert(register_index, value.clone());
+    }
+
+    fn get_muskip(&self, register_index: u8) -> MuGlue {
+        self.muskip_registers
+            .get(&register_index)
+            .cloned()
+            .unwrap_or_else(MuGlue::zero)
+    }
+
+    fn set_muskip(&mut self, register_index: u8, value: &MuGlue) {
+        self.muskip_registers.insert(register_index, value.clone());
+    }
+
+    fn get_toks(&self, register_index: u8) -> Rc<Vec<Token>> {
+        self.toks_registers
+            .get(&register_index)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    fn set_toks(&mut self, register_index: u8, tokens: &Rc<Vec<Token>>) {
+        self.toks_registers.insert(register_index, tokens.clone());
+    }
+
+    fn get_integer_parameter(&self, parameter: IntegerParameter) -> i32 {
+        *self.integer_parameters.get(&parameter).unwrap_or(&0)
+    }
+
+    fn set_integer_parameter(
+        &mut self,
+        parameter: IntegerParameter,
+        value: i32,
+    ) {
+        self.integer_parameters.insert(parameter, value);
+    }
+
+    fn get_dimen_parameter(&self, parameter: DimenParameter) -> Dimen {
+        self.dimen_parameters
+            .get(&parameter)
+            .cloned()
+            .unwrap_or_else(Dimen::zero)
+    }
+
+    fn set_dimen_parameter(&mut self, parameter: DimenParameter, value: Dimen) {
+        self.dimen_parameters.insert(parameter, value);
+    }
+
+    fn get_glue_parameter(&self, parameter: GlueParameter) -> Glue {
+        self.glue_parameters
+            .get(&parameter)
+            .cloned()
+            .unwrap_or_else(Glue::zero)
+    }
+
+    fn set_glue_parameter(&mut self, parameter: GlueParameter, value: &Glue) {
+        self.glue_parameters.insert(parameter, value.clone());
+    }
+
+    fn get_mu_glue_parameter(&self, parameter: MuGlueParameter) -> MuGlue {
+        self.mu_glue_parameters
+            .get(&parameter)
+            .cloned()
+            .unwrap_or_else(MuGlue::zero)
+    }
+
+    fn set_mu_glue_parameter(
+        &mut self,
+        parameter: MuGlueParameter,
+        value: &MuGlue,
+    ) {
+        self.mu_glue_parameters.insert(parameter, value.clone());
+    }
+
     fn get_current_font(&self) -> Font {
         self.current_font.clone()
     }
@@ -346,6 +788,246 @@ impl TeXStateInner {
         self.box_registers.insert(box_index, tex_box);
     }
 
+    // Returns every value which is different in another state, in a
+    // consistent order.
+    fn get_changed_entries(&self, other: &TeXStateInner) -> Vec<StateEntry> {
+        let mut entries = Vec::new();
+
+        let mut categories =
+            changed_keys(&self.category_map, &other.category_map, |a, b| {
+                a.unwrap_or(&Category::Other) == b.unwrap_or(&Category::Other)
+            });
+        categories.sort();
+        entries.extend(categories.into_iter().map(StateEntry::Category));
+
+        let mut math_codes =
+            changed_keys(&self.math_code_map, &other.math_code_map, |a, b| {
+                a == b
+            });
+        math_codes.sort();
+        entries.extend(math_codes.into_iter().map(StateEntry::MathCode));
+
+        let mut lowercase_codes = changed_keys(
+            &self.lowercase_code_map,
+            &other.lowercase_code_map,
+            |a, b| a.unwrap_or(&'\0') == b.unwrap_or(&'\0'),
+        );
+        lowercase_codes.sort();
+        entries
+            .extend(lowercase_codes.into_iter().map(StateEntry::LowercaseCode));
+
+        let mut uppercase_codes = changed_keys(
+            &self.uppercase_code_map,
+            &other.uppercase_code_map,
+            |a, b| a.unwrap_or(&'\0') == b.unwrap_or(&'\0'),
+        );
+        uppercase_codes.sort();
+        entries
+            .extend(uppercase_codes.into_iter().map(StateEntry::UppercaseCode));
+
+        let mut space_factor_codes = changed_keys(
+            &self.space_factor_code_map,
+            &other.space_factor_code_map,
+            |a, b| a.unwrap_or(&1000) == b.unwrap_or(&1000),
+        );
+        space_factor_codes.sort();
+        entries.extend(
+            space_factor_codes
+                .into_iter()
+                .map(StateEntry::SpaceFactorCode),
+        );
+
+        let mut delimiter_codes = changed_keys(
+            &self.delimiter_code_map,
+            &other.delimiter_code_map,
+            |a, b| a.unwrap_or(&-1) == b.unwrap_or(&-1),
+        );
+        delimiter_codes.sort();
+        entries
+            .extend(delimiter_codes.into_iter().map(StateEntry::DelimiterCode));
+
+        let mut tokens = changed_keys(
+            &self.token_definition_map,
+            &other.token_definition_map,
+            |a, b| a == b,
+        );
+        tokens.sort_by_key(|token| token.to_string());
+        entries.extend(tokens.into_iter().map(StateEntry::Definition));
+
+        entries.extend(
+            (0..=255)
+                .filter(|&index| {
+                    self.count_registers[index as usize]
+                        != other.count_registers[index as usize]
+                })
+                .map(StateEntry::Count),
+        );
+
+        entries.extend(
+            (0..=255)
+                .filter(|&index| {
+                    self.dimen_registers[index as usize]
+                        != other.dimen_registers[index as usize]
+                })
+                .map(StateEntry::Dimen),
+        );
+
+        let mut skips = changed_keys(
+            &self.skip_registers,
+            &other.skip_registers,
+            |a, b| {
+                a.cloned().unwrap_or_else(Glue::zero)
+                    == b.cloned().unwrap_or_else(Glue::zero)
+            },
+        );
+        skips.sort();
+        entries.extend(skips.into_iter().map(StateEntry::Skip));
+
+        let mut muskips = changed_keys(
+            &self.muskip_registers,
+            &other.muskip_registers,
+            |a, b| {
+                a.cloned().unwrap_or_else(MuGlue::zero)
+                    == b.cloned().unwrap_or_else(MuGlue::zero)
+            },
+        );
+        muskips.sort();
+        entries.extend(muskips.into_iter().map(StateEntry::MuSkip));
+
+        let mut toks = changed_keys(
+            &self.toks_registers,
+            &other.toks_registers,
+            |a, b| {
+                a.map_or(&[][..], |a| a.as_slice())
+                    == b.map_or(&[][..], |b| b.as_slice())
+            },
+        );
+        toks.sort();
+        entries.extend(toks.into_iter().map(StateEntry::Toks));
+
+        let mut parameters = changed_keys(
+            &self.integer_parameters,
+            &other.integer_parameters,
+            |a, b| a.unwrap_or(&0) == b.unwrap_or(&0),
+        );
+        parameters.sort_by_key(|parameter| parameter.get_primitive());
+        entries
+            .extend(parameters.into_iter().map(StateEntry::IntegerParameter));
+
+        let mut parameters = changed_keys(
+            &self.dimen_parameters,
+            &other.dimen_parameters,
+            |a, b| {
+                a.cloned().unwrap_or_else(Dimen::zero)
+                    == b.cloned().unwrap_or_else(Dimen::zero)
+            },
+        );
+        parameters.sort_by_key(|parameter| parameter.get_primitive());
+        entries.extend(parameters.into_iter().map(StateEntry::DimenParameter));
+
+        let mut parameters = changed_keys(
+            &self.glue_parameters,
+            &other.glue_parameters,
+            |a, b| {
+                a.cloned().unwrap_or_else(Glue::zero)
+                    == b.cloned().unwrap_or_else(Glue::zero)
+            },
+        );
+        parameters.sort_by_key(|parameter| parameter.get_primitive());
+        entries.extend(parameters.into_iter().map(StateEntry::GlueParameter));
+
+        let mut parameters = changed_keys(
+            &self.mu_glue_parameters,
+            &other.mu_glue_parameters,
+            |a, b| {
+                a.cloned().unwrap_or_else(MuGlue::zero)
+                    == b.cloned().unwrap_or_else(MuGlue::zero)
+            },
+        );
+        parameters.sort_by_key(|parameter| parameter.get_primitive());
+        entries.extend(parameters.into_iter().map(StateEntry::MuGlueParameter));
+
+        // Boxes are shared between levels until they're set, so we only need
+        // to check if they're the same box.
+        let mut boxes =
+            changed_keys(&self.box_registers, &other.box_registers, |a, b| {
+                match (a, b) {
+                    (Some(a), Some(b)) => Rc::ptr_eq(a, b),
+                    (None, None) => true,
+                    _ => false,
+                }
+            });
+        boxes.sort();
+        entries.extend(boxes.into_iter().map(StateEntry::Box));
+
+        if self.current_font != other.current_font {
+            entries.push(StateEntry::CurrentFont);
+        }
+
+        entries
+    }
+
+    fn get_value(&self, entry: &StateEntry) -> StateValue {
+        match entry {
+            StateEntry::Category(ch) => {
+                StateValue::Category(self.get_category(*ch))
+            }
+            StateEntry::MathCode(ch) => {
+                StateValue::MathCode(self.get_math_code(*ch))
+            }
+            StateEntry::LowercaseCode(ch) => {
+                StateValue::LowercaseCode(self.get_lowercase_code(*ch))
+            }
+            StateEntry::UppercaseCode(ch) => {
+                StateValue::UppercaseCode(self.get_uppercase_code(*ch))
+            }
+            StateEntry::SpaceFactorCode(ch) => {
+                StateValue::SpaceFactorCode(self.get_space_factor_code(*ch))
+            }
+            StateEntry::DelimiterCode(ch) => {
+                StateValue::DelimiterCode(self.get_delimiter_code(*ch))
+            }
+            StateEntry::Definition(token) => StateValue::Definition(
+                self.token_definition_map.get(token).cloned(),
+            ),
+            StateEntry::Count(index) => {
+                StateValue::Count(self.get_count(*index))
+            }
+            StateEntry::Dimen(index) => {
+                StateValue::Dimen(self.get_dimen(*index))
+            }
+            StateEntry::Skip(index) => StateValue::Skip(self.get_skip(*index)),
+            StateEntry::MuSkip(index) => {
+                StateValue::MuSkip(self.get_muskip(*index))
+            }
+            StateEntry::Toks(index) => {
+                StateValue::Toks(self.get_toks(*index).to_vec())
+            }
+            StateEntry::IntegerParameter(parameter) => {
+                StateValue::IntegerParameter(
+                    self.get_integer_parameter(*parameter),
+                )
+            }
+            StateEntry::DimenParameter(parameter) => {
+                StateValue::DimenParameter(self.get_dimen_parameter(*parameter))
+            }
+            StateEntry::GlueParameter(parameter) => {
+                StateValue::GlueParameter(self.get_glue_parameter(*parameter))
+            }
+            StateEntry::MuGlueParameter(parameter) => {
+                StateValue::MuGlueParameter(
+                    self.get_mu_glue_parameter(*parameter),
+                )
+            }
+            StateEntry::Box(index) => {
+                StateValue::Box(self.get_box_copy(*index))
+            }
+            StateEntry::CurrentFont => {
+                StateValue::Font(self.get_current_font())
+            }
+        }
+    }
+
     fn with_box<T, F>(&self, box_index: u8, func: F) -> Option<T>
     where
         F: FnOnce(&mut TeXBox) -> T,
@@ -369,6 +1051,16 @@ impl TeXStateInner {
 // level of the stack using \global.
 struct TeXStateStack {
     state_stack: Vec<TeXStateInner>,
+
+    // Like TeX's save stack, this has the entries which were assigned locally
+    // in each group that hasn't ended yet, in the order they were saved, along
+    // with the level they had been assigned at before that.
+    saved_entries: Vec<Vec<(StateEntry, Option<usize>)>>,
+
+    // The group level that each entry was last assigned locally at, like
+    // TeX's eq_level. Entries which were last assigned at the outermost level
+    // or globally aren't in the map.
+    assignment_levels: HashMap<StateEntry, usize>,
 }
 
 // Since we're mostly want to just be calling the same-named functions from
@@ -387,11 +1079,14 @@ macro_rules! generate_inner_func {
 // When we have setter functions that are optionally global (i.e. optionally
 // operate on all of the levels of TeXStateInner), we can use this macro to
 // automatically define them.
+// The entry that the setter assigns to is given after a =>, so that it can be
+// saved in the current group.
 macro_rules! generate_inner_global_func {
     (fn $func_name:ident(
-        global: bool, $($var_name:ident : $var_type:ty),*)) =>
+        global: bool, $($var_name:ident : $var_type:ty),*) => $entry:expr) =>
     {
         fn $func_name(&mut self, global: bool, $($var_name: $var_type),*) {
+            self.save_entry(global, $entry);
             if global {
                 for state in &mut self.state_stack {
                     state.$func_name($($var_name),*);
@@ -408,34 +1103,122 @@ impl TeXStateStack {
     fn new() -> TeXStateStack {
         TeXStateStack {
             state_stack: vec![TeXStateInner::new()],
+            saved_entries: Vec::new(),
+            assignment_levels: HashMap::new(),
+        }
+    }
+
+    fn get_group_level(&self) -> usize {
+        self.state_stack.len() - 1
+    }
+
+    fn take_snapshot(&self, name: &str) -> StateSnapshot {
+        let mut inner = self.state_stack[self.state_stack.len() - 1].clone();
+        // Boxes are shared with the live state, where they can be changed in
+        // place or taken out with \box, so we keep our own copies of them.
+        for tex_box in inner.box_registers.values_mut() {
+            let copy = tex_box.borrow().clone();
+            *tex_box = Rc::new(RefCell::new(copy));
+        }
+        StateSnapshot {
+            name: name.to_string(),
+            inner,
         }
     }
 
     fn push_state(&mut self) {
         let top_state = self.state_stack[self.state_stack.len() - 1].clone();
         self.state_stack.push(top_state);
+        self.saved_entries.push(Vec::new());
     }
 
-    fn pop_state(&mut self) {
-        self.state_stack.pop().unwrap();
+    // Returns the entries which were saved in the group, most recently saved
+    // first, like the order TeX unwinds its save stack in.
+    fn pop_state(&mut self) -> Vec<RestoredEntry> {
+        self.state_stack.pop();
+        let saved_entries = self.saved_entries.pop().unwrap_or_default();
+
+        saved_entries
+            .into_iter()
+            .rev()
+            .map(|(entry, previous_level)| {
+                if self.assignment_levels.contains_key(&entry) {
+                    match previous_level {
+                        Some(level) => {
+                            self.assignment_levels.insert(entry.clone(), level)
+                        }
+                        None => self.assignment_levels.remove(&entry),
+                    };
+                    RestoredEntry::Restored(entry)
+                } else {
+                    RestoredEntry::Retained(entry)
+                }
+            })
+            .collect()
+    }
+
+    // Keeps track of an assignment to an entry. Like in TeX, the first local
+    // assignment to an entry in a group saves it so that it can be restored
+    // when the group ends (even if the value doesn't change), while global
+    // assignments mean that it will be retained instead.
+    fn save_entry(&mut self, global: bool, entry: StateEntry) {
+        let level = self.get_group_level();
+        if global {
+            self.assignment_levels.remove(&entry);
+        } else if level > 0 {
+            let previous_level =
+                self.assignment_levels.insert(entry.clone(), level);
+            if previous_level != Some(level) {
+                self.saved_entries[level - 1].push((entry, previous_level));
+            }
+        }
     }
 
     generate_inner_func!(fn get_category(ch: char) -> Category);
-    generate_inner_global_func!(fn set_category(global: bool, ch: char, cat: Category));
+    generate_inner_global_func!(fn set_category(global: bool, ch: char, cat: Category) => StateEntry::Category(ch));
     generate_inner_func!(fn get_math_code(ch: char) -> MathCode);
-    generate_inner_global_func!(fn set_math_code(global: bool, ch: char, mathcode: &MathCode));
+    generate_inner_global_func!(fn set_math_code(global: bool, ch: char, mathcode: &MathCode) => StateEntry::MathCode(ch));
+    generate_inner_func!(fn get_lowercase_code(ch: char) -> char);
+    generate_inner_global_func!(fn set_lowercase_code(global: bool, ch: char, code: char) => StateEntry::LowercaseCode(ch));
+    generate_inner_func!(fn get_uppercase_code(ch: char) -> char);
+    generate_inner_global_func!(fn set_uppercase_code(global: bool, ch: char, code: char) => StateEntry::UppercaseCode(ch));
+    generate_inner_func!(fn get_space_factor_code(ch: char) -> u16);
+    generate_inner_global_func!(fn set_space_factor_code(global: bool, ch: char, code: u16) => StateEntry::SpaceFactorCode(ch));
+    generate_inner_func!(fn get_delimiter_code(ch: char) -> i32);
+    generate_inner_global_func!(fn set_delimiter_code(global: bool, ch: char, code: i32) => StateEntry::DelimiterCode(ch));
+    generate_inner_func!(fn get_definition(token: &Token) -> Option<TokenDefinition>);
+    generate_inner_func!(fn is_meaning_equal(token1: &Token, token2: &Token) -> bool);
     generate_inner_func!(fn get_math_chardef(token: &Token) -> Option<MathCode>);
-    generate_inner_global_func!(fn set_math_chardef(global: bool, token: &Token, mathcode: &MathCode));
+    generate_inner_global_func!(fn set_math_chardef(global: bool, token: &Token, mathcode: &MathCode) => StateEntry::Definition(token.clone()));
+    generate_inner_func!(fn get_chardef(token: &Token) -> Option<char>);
+    generate_inner_global_func!(fn set_shorthand_definition(global: bool, token: &Token, definition: &TokenDefinition) => StateEntry::Definition(token.clone()));
     generate_inner_func!(fn get_macro(token: &Token) -> Option<Rc<Macro>>);
-    generate_inner_global_func!(fn set_macro(global: bool, token: &Token, makro: &Rc<Macro>));
+    generate_inner_global_func!(fn set_macro(global: bool, token: &Token, makro: &Rc<Macro>) => StateEntry::Definition(token.clone()));
     generate_inner_func!(fn get_renamed_token(token: &Token) -> Option<Token>);
-    generate_inner_global_func!(fn set_let(global: bool, set_token: &Token, to_token: &Token));
+    generate_inner_global_func!(fn set_let(global: bool, set_token: &Token, to_token: &Token) => StateEntry::Definition(set_token.clone()));
     generate_inner_func!(fn is_token_equal_to_prim(token: &Token, cs: &str) -> bool);
+    generate_inner_func!(fn get_primitive(token: &Token) -> Option<&'static str>);
     generate_inner_func!(fn get_count(register_index: u8) -> i32);
-    generate_inner_global_func!(fn set_count(global: bool, register_index: u8, value: i32));
+    generate_inner_global_func!(fn set_count(global: bool, register_index: u8, value: i32) => StateEntry::Count(register_index));
+    generate_inner_func!(fn get_dimen(register_index: u8) -> Dimen);
+    generate_inner_global_func!(fn set_dimen(global: bool, register_index: u8, value: Dimen) => StateEntry::Dimen(register_index));
+    generate_inner_func!(fn get_skip(register_index: u8) -> Glue);
+    generate_inner_global_func!(fn set_skip(global: bool, register_index: u8, value: &Glue) => StateEntry::Skip(register_index));
+    generate_inner_func!(fn get_muskip(register_index: u8) -> MuGlue);
+    generate_inner_global_func!(fn set_muskip(global: bool, register_index: u8, value: &MuGlue) => StateEntry::MuSkip(register_index));
+    generate_inner_func!(fn get_toks(register_index: u8) -> Rc<Vec<Token>>);
+    generate_inner_global_func!(fn set_toks(global: bool, register_index: u8, tokens: &Rc<Vec<Token>>) => StateEntry::Toks(register_index));
+    generate_inner_func!(fn get_integer_parameter(parameter: IntegerParameter) -> i32);
+    generate_inner_global_func!(fn set_integer_parameter(global: bool, parameter: IntegerParameter, value: i32) => StateEntry::IntegerParameter(parameter));
+    generate_inner_func!(fn get_dimen_parameter(parameter: DimenParameter) -> Dimen);
+    generate_inner_global_func!(fn set_dimen_parameter(global: bool, parameter: DimenParameter, value: Dimen) => StateEntry::DimenParameter(parameter));
+    generate_inner_func!(fn get_glue_parameter(parameter: GlueParameter) -> Glue);
+    generate_inner_global_func!(fn set_glue_parameter(global: bool, parameter: GlueParameter, value: &Glue) => StateEntry::GlueParameter(parameter));
+    generate_inner_func!(fn get_mu_glue_parameter(parameter: MuGlueParameter) -> MuGlue);
+    generate_inner_global_func!(fn set_mu_glue_parameter(global: bool, parameter: MuGlueParameter, value: &MuGlue) => StateEntry::MuGlueParameter(parameter));
     generate_inner_func!(fn get_current_font() -> Font);
-    generate_inner_global_func!(fn set_current_font(global: bool, font: &Font));
-    generate_inner_global_func!(fn set_fontdef(global: bool, token: &Token, font: &Font));
+    generate_inner_global_func!(fn set_current_font(global: bool, font: &Font) => StateEntry::CurrentFont);
+    generate_inner_global_func!(fn set_fontdef(global: bool, token: &Token, font: &Font) => StateEntry::Definition(token.clone()));
     generate_inner_func!(fn get_fontdef(token: &Token) -> Option<Font>);
     generate_inner_func!(fn get_box(box_index: u8) -> Option<TeXBox>);
     generate_inner_func!(fn get_box_copy(box_index: u8) -> Option<TeXBox>);
@@ -444,6 +1227,7 @@ impl TeXStateStack {
     // between the different stack levels, we can't handle generating this
     // function automatically with `generate_inner_global_func!()`.
     fn set_box(&mut self, global: bool, box_index: u8, tex_box: TeXBox) {
+        self.save_entry(global, StateEntry::Box(box_index));
         let wrapped_box = Rc::new(RefCell::new(Some(tex_box)));
         if global {
             for state in &mut self.state_stack {
@@ -476,6 +1260,14 @@ pub struct TeXState {
     // in the `TeXStateInner` because loading the font metrics is global and
     // isn't affected by grouping.
     font_metrics: RefCell<HashMap<Font, FontMetrics>>,
+
+    // Where the TFM files for fonts are loaded from.
+    font_provider: RefCell<Rc<dyn FontProvider>>,
+
+    // The control sequence which was most recently used to load each font,
+    // which TeX uses to refer to the font when showing boxes. Like the
+    // metrics, this isn't affected by grouping.
+    font_identifiers: RefCell<HashMap<Font, Token>>,
 }
 
 // Since we're mostly want to just be calling the same-named functions from
@@ -492,11 +1284,19 @@ macro_rules! generate_stack_func {
     }
 }
 
+impl Default for TeXState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl TeXState {
     pub fn new() -> TeXState {
         TeXState {
             state_stack: RefCell::new(TeXStateStack::new()),
             font_metrics: RefCell::new(HashMap::new()),
+            font_provider: RefCell::new(default_font_provider()),
+            font_identifiers: RefCell::new(HashMap::new()),
         }
     }
 
@@ -510,22 +1310,53 @@ impl TeXState {
         func(&mut stack)
     }
 
+    generate_stack_func!(fn get_group_level() -> usize);
+    generate_stack_func!(fn take_snapshot(name: &str) -> StateSnapshot);
     generate_stack_func!(fn push_state());
-    generate_stack_func!(fn pop_state());
+    generate_stack_func!(fn pop_state() -> Vec<RestoredEntry>);
 
     generate_stack_func!(fn get_category(ch: char) -> Category);
     generate_stack_func!(fn set_category(global: bool, ch: char, cat: Category));
     generate_stack_func!(fn get_math_code(ch: char) -> MathCode);
     generate_stack_func!(fn set_math_code(global: bool, ch: char, mathcode: &MathCode));
+    generate_stack_func!(fn get_lowercase_code(ch: char) -> char);
+    generate_stack_func!(fn set_lowercase_code(global: bool, ch: char, code: char));
+    generate_stack_func!(fn get_uppercase_code(ch: char) -> char);
+    generate_stack_func!(fn set_uppercase_code(global: bool, ch: char, code: char));
+    generate_stack_func!(fn get_space_factor_code(ch: char) -> u16);
+    generate_stack_func!(fn set_space_factor_code(global: bool, ch: char, code: u16));
+    generate_stack_func!(fn get_delimiter_code(ch: char) -> i32);
+    generate_stack_func!(fn set_delimiter_code(global: bool, ch: char, code: i32));
+    generate_stack_func!(fn get_definition(token: &Token) -> Option<TokenDefinition>);
+    generate_stack_func!(fn is_meaning_equal(token1: &Token, token2: &Token) -> bool);
     generate_stack_func!(fn get_math_chardef(token: &Token) -> Option<MathCode>);
     generate_stack_func!(fn set_math_chardef(global: bool, token: &Token, mathcode: &MathCode));
+    generate_stack_func!(fn get_chardef(token: &Token) -> Option<char>);
+    generate_stack_func!(fn set_shorthand_definition(global: bool, token: &Token, definition: &TokenDefinition));
     generate_stack_func!(fn get_macro(token: &Token) -> Option<Rc<Macro>>);
     generate_stack_func!(fn set_macro(global: bool, token: &Token, makro: &Rc<Macro>));
     generate_stack_func!(fn get_renamed_token(token: &Token) -> Option<Token>);
     generate_stack_func!(fn set_let(global: bool, set_token: &Token, to_token: &Token));
     generate_stack_func!(fn is_token_equal_to_prim(token: &Token, cs: &str) -> bool);
+    generate_stack_func!(fn get_primitive(token: &Token) -> Option<&'static str>);
     generate_stack_func!(fn get_count(register_index: u8) -> i32);
     generate_stack_func!(fn set_count(global: bool, register_index: u8, value: i32));
+    generate_stack_func!(fn get_dimen(register_index: u8) -> Dimen);
+    generate_stack_func!(fn set_dimen(global: bool, register_index: u8, value: Dimen));
+    generate_stack_func!(fn get_skip(register_index: u8) -> Glue);
+    generate_stack_func!(fn set_skip(global: bool, register_index: u8, value: &Glue));
+    generate_stack_func!(fn get_muskip(register_index: u8) -> MuGlue);
+    generate_stack_func!(fn set_muskip(global: bool, register_index: u8, value: &MuGlue));
+    generate_stack_func!(fn get_toks(register_index: u8) -> Rc<Vec<Token>>);
+    generate_stack_func!(fn set_toks(global: bool, register_index: u8, tokens: &Rc<Vec<Token>>));
+    generate_stack_func!(fn get_integer_parameter(parameter: IntegerParameter) -> i32);
+    generate_stack_func!(fn set_integer_parameter(global: bool, parameter: IntegerParameter, value: i32));
+    generate_stack_func!(fn get_dimen_parameter(parameter: DimenParameter) -> Dimen);
+    generate_stack_func!(fn set_dimen_parameter(global: bool, parameter: DimenParameter, value: Dimen));
+    generate_stack_func!(fn get_glue_parameter(parameter: GlueParameter) -> Glue);
+    generate_stack_func!(fn set_glue_parameter(global: bool, parameter: GlueParameter, value: &Glue));
+    generate_stack_func!(fn get_mu_glue_parameter(parameter: MuGlueParameter) -> MuGlue);
+    generate_stack_func!(fn set_mu_glue_parameter(global: bool, parameter: MuGlueParameter, value: &MuGlue));
     generate_stack_func!(fn get_current_font() -> Font);
     generate_stack_func!(fn set_current_font(global: bool, font: &Font));
     generate_stack_func!(fn set_fontdef(global: bool, token: &Token, font: &Font));
@@ -548,6 +1379,27 @@ impl TeXState {
         self.with_stack(|stack| stack.with_box(box_index, func))
     }
 
+    pub fn set_font_identifier(&self, font: &Font, token: &Token) {
+        self.font_identifiers
+            .borrow_mut()
+            .insert(font.clone(), token.clone());
+    }
+
+    pub fn get_font_identifier(&self, font: &Font) -> Option<Token> {
+        self.font_identifiers.borrow().get(font).cloned()
+    }
+
+    pub fn get_font_provider(&self) -> Rc<dyn FontProvider> {
+        self.font_provider.borrow().clone()
+    }
+
+    /// Changes where fonts are loaded from. Metrics which were already loaded
+    /// are forgotten, so that they'll be loaded again from the new provider.
+    pub fn set_font_provider(&self, provider: Rc<dyn FontProvider>) {
+        *self.font_provider.borrow_mut() = provider;
+        self.font_metrics.borrow_mut().clear();
+    }
+
     /// Returns a reference to the font metrics for a given font.
     /// NOTE: this will load the font metrics for a font if they haven't been
     /// loaded yet, which attempts to generate a mutable font metrics ref in
@@ -562,8 +1414,11 @@ impl TeXState {
 
         if !has_metrics {
             let mut font_metrics_mut = self.font_metrics.borrow_mut();
-            font_metrics_mut
-                .insert(font.clone(), FontMetrics::from_font(font)?);
+            let provider = self.get_font_provider();
+            font_metrics_mut.insert(
+                font.clone(),
+                FontMetrics::from_font_with_provider(font, &*provider)?,
+            );
         }
 
         Some(Ref::map(self.font_metrics.borrow(), |x| {
@@ -590,6 +1445,8 @@ mod tests {
     use super::*;
 
     use crate::boxes::HorizontalBox;
+    use crate::dimension::MuDimen;
+    use crate::testing::test_font_provider;
 
     #[test]
     fn it_correctly_sets_categories() {
@@ -599,6 +1456,21 @@ mod tests {
         assert_eq!(state.get_category('@'), Category::Letter);
     }
 
+    #[test]
+    fn it_sets_categories_initially() {
+        let state = TeXState::new();
+
+        assert_eq!(state.get_category('a'), Category::Letter);
+        assert_eq!(state.get_category('Z'), Category::Letter);
+        assert_eq!(state.get_category('\\'), Category::Escape);
+        assert_eq!(state.get_category('%'), Category::Comment);
+        assert_eq!(state.get_category(' '), Category::Space);
+        assert_eq!(state.get_category('\n'), Category::EndOfLine);
+        assert_eq!(state.get_category('\u{0000}'), Category::Ignored);
+        assert_eq!(state.get_category('\u{007f}'), Category::Invalid);
+        assert_eq!(state.get_category('1'), Category::Other);
+    }
+
     #[test]
     fn it_allows_mutation_with_existing_refs() {
         let state = TeXState::new();
@@ -648,6 +1520,91 @@ mod tests {
         assert_eq!(state.get_category('@'), Category::Other);
     }
 
+    #[test]
+    fn it_returns_restored_values_after_group_ends() {
+        let state = TeXState::new();
+
+        state.push_state();
+        state.set_count(false, 3, 1);
+        state.set_category(false, '@', Category::Letter);
+        state.set_count(true, 4, 1);
+        state.set_integer_parameter(false, IntegerParameter::TracingMacros, 2);
+
+        assert_eq!(
+            state.pop_state(),
+            vec![
+                RestoredEntry::Restored(StateEntry::IntegerParameter(
+                    IntegerParameter::TracingMacros
+                )),
+                RestoredEntry::Restored(StateEntry::Category('@')),
+                RestoredEntry::Restored(StateEntry::Count(3)),
+            ]
+        );
+    }
+
+    #[test]
+    fn it_retains_values_assigned_globally_inside_of_groups() {
+        let state = TeXState::new();
+
+        state.push_state();
+        state.set_count(false, 1, 1);
+        state.push_state();
+        state.set_count(false, 1, 2);
+        state.set_count(true, 1, 3);
+        state.set_count(false, 1, 4);
+
+        assert_eq!(
+            state.pop_state(),
+            vec![
+                RestoredEntry::Restored(StateEntry::Count(1)),
+                RestoredEntry::Retained(StateEntry::Count(1)),
+            ]
+        );
+        assert_eq!(state.get_count(1), 3);
+
+        assert_eq!(
+            state.pop_state(),
+            vec![RestoredEntry::Retained(StateEntry::Count(1))]
+        );
+        assert_eq!(state.get_count(1), 3);
+    }
+
+    #[test]
+    fn it_restores_registers_after_group_ends() {
+        let state = TeXState::new();
+        let point = Dimen::from_unit(1.0, Unit::Point);
+        let tokens = Rc::new(vec![Token::Char('a', Category::Letter)]);
+
+        state.push_state();
+        state.set_dimen(false, 1, point);
+        state.set_skip(false, 2, &Glue::from_dimen(point));
+        state.set_skip(false, 3, &Glue::zero());
+        state.set_muskip(
+            true,
+            4,
+            &MuGlue {
+                space: MuDimen::new(1.0),
+                ..MuGlue::zero()
+            },
+        );
+        state.set_toks(false, 5, &tokens);
+        state.set_toks(true, 6, &tokens);
+
+        assert_eq!(
+            state.pop_state(),
+            vec![
+                RestoredEntry::Restored(StateEntry::Toks(5)),
+                RestoredEntry::Restored(StateEntry::Skip(3)),
+                RestoredEntry::Restored(StateEntry::Skip(2)),
+                RestoredEntry::Restored(StateEntry::Dimen(1)),
+            ]
+        );
+        assert_eq!(state.get_dimen(1), Dimen::zero());
+        assert_eq!(state.get_skip(2), Glue::zero());
+        assert!(state.get_toks(5).is_empty());
+        assert_eq!(state.get_toks(6), tokens);
+    }
+
     #[test]
     fn it_compares_control_sequences() {
         let state = TeXState::new();
@@ -922,6 +1879,7 @@ mod tests {
     #[test]
     fn it_allows_for_temporary_access_of_font_metrics() {
         let state = TeXState::new();
+        state.set_font_provider(test_font_provider());
 
         let font = Font {
             font_name: "cmr10".to_string(),
@@ -935,4 +1893,65 @@ mod tests {
             Some(1274110073)
         );
     }
+
+    #[test]
+    fn it_diffs_snapshots() {
+        let state = TeXState::new();
+        let test_box = TeXBox::HorizontalBox(HorizontalBox {
+            height: Dimen::from_unit(0.0, Unit::Point),
+            depth: Dimen::from_unit(0.0, Unit::Point),
+            width: Dimen::from_unit(0.0, Unit::Point),
+            list: Vec::new(),
+            glue_set_ratio: None,
+        });
+        state.set_box(false, 0, test_box.clone());
+
+        let before = state.take_snapshot("before");
+        state.set_category(false, '@', Category::Letter);
+        state.set_count(false, 1, 5);
+        state.set_let(
+            false,
+            &Token::ControlSequence("a".to_string()),
+            &Token::ControlSequence("relax".to_string()),
+        );
+        // Changes inside of groups which are undone don't show up.
+        state.push_state();
+        state.set_count(false, 2, 5);
+        state.pop_state();
+        // Boxes which are taken out of the state are still in the snapshot.
+        state.get_box(0);
+        let after = state.take_snapshot("after");
+
+        assert_eq!(before.get_name(), "before");
+        assert_eq!(
+            before.diff(&after),
+            vec![
+                StateChange {
+                    entry: StateEntry::Category('@'),
+                    before: StateValue::Category(Category::Other),
+                    after: StateValue::Category(Category::Letter),
+                },
+                StateChange {
+                    entry: StateEntry::Definition(Token::ControlSequence(
+                        "a".to_string()
+                    )),
+                    before: StateValue::Definition(None),
+                    after: StateValue::Definition(Some(
+                        TokenDefinition::Primitive("relax")
+                    )),
+                },
+                StateChange {
+                    entry: StateEntry::Count(1),
+                    before: StateValue::Count(0),
+                    after: StateValue::Count(5),
+                },
+                StateChange {
+                    entry: StateEntry::Box(0),
+                    before: StateValue::Box(Some(test_box)),
+                    after: StateValue::Box(None),
+                },
+            ]
+        );
+        assert_eq!(after.diff(&after), vec![]);
+    }
 }
@@ -1,14 +1,144 @@
+use std::cell::RefCell;
+use std::io::{self, Write};
+use std::rc::Rc;
+
+use crate::boxes::TeXBox;
+use crate::font::Font;
+use crate::font_metrics::FontMetrics;
+use crate::font_provider::{DirectoryFontProvider, FontProvider};
+use crate::list::{HorizontalListElem, VerticalListElem};
+use crate::origin::Provenance;
 use crate::parser::Parser;
 use crate::state::TeXState;
 
+// Tests only load fonts which are bundled in src/tfm/test_files, so that they
+// don't depend on what fonts (if any) are installed.
+pub fn test_font_provider() -> Rc<dyn FontProvider> {
+    Rc::new(DirectoryFontProvider::new(
+        vec!["src/tfm/test_files".into()],
+    ))
+}
+
+pub fn test_font_metrics(font: &Font) -> Option<FontMetrics> {
+    FontMetrics::from_font_with_provider(font, &*test_font_provider())
+}
+
 #[cfg(test)]
 pub fn with_parser<T>(lines: &[&str], cb: T)
 where
     T: FnOnce(&mut Parser),
 {
     let state = TeXState::new();
+    state.set_font_provider(test_font_provider());
     let mut parser = Parser::new(lines, &state);
+    // Tests which care about what is printed should use with_terminal()
+    // instead.
+    parser.set_terminal(Rc::new(RefCell::new(io::sink())));
 
     cb(&mut parser);
-    assert_eq!(parser.lex_unexpanded_token(), None);
+    assert_eq!(parser.lex_unexpanded_token().unwrap(), None);
+    // Tests which expect errors to be reported should check for them (and
+    // clear them) with take_errors().
+    assert_eq!(parser.get_errors(), &[]);
+}
+
+// An in-memory terminal or log file, which tests can read back everything
+// that was printed to.
+#[derive(Clone, Default)]
+pub struct TestOutput(Rc<RefCell<Vec<u8>>>);
+
+impl TestOutput {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn writer(&self) -> Rc<RefCell<dyn Write>> {
+        self.0.clone()
+    }
+
+    pub fn contents(&self) -> String {
+        String::from_utf8(self.0.borrow().clone()).unwrap()
+    }
+}
+
+// Like with_parser(), but returns everything that was printed to the
+// terminal.
+pub fn with_terminal<T>(lines: &[&str], cb: T) -> String
+where
+    T: FnOnce(&mut Parser),
+{
+    let terminal = TestOutput::new();
+    with_parser(lines, |parser| {
+        parser.set_terminal(terminal.writer());
+        cb(parser);
+    });
+    terminal.contents()
+}
+
+// Clears the origins of the characters in a list, for tests which only care
+// about what was typeset and not where it came from.
+pub trait WithoutOrigins {
+    fn without_origins(self) -> Self;
+}
+
+impl WithoutOrigins for HorizontalListElem {
+    fn without_origins(self) -> Self {
+        match self {
+            HorizontalListElem::Char { chr, font, .. } => {
+                HorizontalListElem::Char {
+                    chr,
+                    font,
+                    origin: Provenance::none(),
+                }
+            }
+            HorizontalListElem::Box { tex_box, shift } => {
+                HorizontalListElem::Box {
+                    tex_box: tex_box.without_origins(),
+                    shift,
+                }
+            }
+            other => other,
+        }
+    }
+}
+
+impl WithoutOrigins for VerticalListElem {
+    fn without_origins(self) -> Self {
+        match self {
+            VerticalListElem::Box { tex_box, shift } => VerticalListElem::Box {
+                tex_box: tex_box.without_origins(),
+                shift,
+            },
+            other => other,
+        }
+    }
+}
+
+impl WithoutOrigins for TeXBox {
+    fn without_origins(self) -> Self {
+        match self {
+            TeXBox::HorizontalBox(mut hbox) => {
+                hbox.list = hbox.list.without_origins();
+                TeXBox::HorizontalBox(hbox)
+            }
+            TeXBox::VerticalBox(mut vbox) => {
+                vbox.list = vbox.list.without_origins();
+                TeXBox::VerticalBox(vbox)
+            }
+        }
+    }
+}
+
+impl<T: WithoutOrigins> WithoutOrigins for Vec<T> {
+    fn without_origins(self) -> Self {
+        self.into_iter()
+            .map(WithoutOrigins::without_origins)
+            .collect()
+    }
+}
+
+impl<T: WithoutOrigins> WithoutOrigins for Option<T> {
+    fn without_origins(self) -> Self {
+        self.map(WithoutOrigins::without_origins)
+    }
 }
@@ -1,7 +1,7 @@
 /// Integration tests to ensure that high-level expectations hold
 use crate::box_to_dvi::DVIFileWriter;
-use crate::dvi::{interpret_dvi_file, DVIFile};
-use crate::testing::with_parser;
+use crate::dvi::{interpret_dvi_file_with_font_provider, DVIFile};
+use crate::testing::{test_font_provider, with_parser};
 
 /// This test ensures that we pass the stage #2 goals.
 #[test]
@@ -21,6 +21,7 @@ fn it_parses_horizontal_boxes() {
         |parser| {
             let result: String = parser
                 .parse_outer_vertical_box()
+                .unwrap()
                 .to_chars()
                 .into_iter()
                 .collect();
@@ -41,6 +42,7 @@ fn it_parses_horizontal_boxes() {
 fn it_parses_vertical_boxes() {
     with_parser(
         &[
+            r"\parindent=20pt \baselineskip=12pt \lineskip=1pt \topskip=10pt",
             r"a\par",
             r"b\vskip1pt",
             r"\indent c\par",
@@ -60,6 +62,7 @@ fn it_parses_vertical_boxes() {
         |parser| {
             let result: String = parser
                 .parse_outer_vertical_box()
+                .unwrap()
                 .to_chars()
                 .into_iter()
                 .collect();
@@ -91,6 +94,7 @@ fn assert_tex_file_converts_to_dvi(
     let lines = tex_file_contents.split('\n').collect::<Vec<&str>>();
 
     let mut file_writer = DVIFileWriter::new();
+    file_writer.set_font_provider(test_font_provider());
     file_writer.start(
         (25400000, 473628672),
         1000,
@@ -98,17 +102,19 @@ fn assert_tex_file_converts_to_dvi(
     );
 
     with_parser(&lines[..], |parser| {
-        let page = parser.parse_outer_vertical_box();
+        let page = parser.parse_outer_vertical_box().unwrap();
         file_writer.add_page(&page.list, &None, [1, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
     });
 
     file_writer.end();
 
     let test_file = file_writer.to_file();
-    let test_pages = interpret_dvi_file(test_file);
+    let test_pages =
+        interpret_dvi_file_with_font_provider(test_file, test_font_provider());
 
     let real_file = DVIFile::new(dvi_contents).unwrap();
-    let real_pages = interpret_dvi_file(real_file);
+    let real_pages =
+        interpret_dvi_file_with_font_provider(real_file, test_font_provider());
 
     for (test_page, real_page) in test_pages.iter().zip(real_pages.iter()) {
         for (key, val) in real_page.iter() {
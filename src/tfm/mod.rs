@@ -69,4 +69,4 @@ mod file_reader;
 mod read_tfm;
 
 #[cfg(test)]
-mod test_data;
+pub mod test_data;
@@ -1,3 +1,5 @@
+use std::fmt;
+
 use crate::category::Category;
 
 #[derive(Debug, PartialEq, Eq, Clone, Hash)]
@@ -5,3 +7,51 @@ pub enum Token {
     ControlSequence(String),
     Char(char, Category),
 }
+
+impl fmt::Display for Token {
+    // Prints tokens the way TeX does when it shows token lists: control
+    // sequences made of letters are followed by a space so that they don't run
+    // into the next token, while single non-letter control sequences (like
+    // \{) are not.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Token::ControlSequence(name) => {
+                let mut chars = name.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(ch), None) if !ch.is_ascii_alphabetic() => {
+                        write!(f, "\\{}", ch)
+                    }
+                    _ => write!(f, "\\{} ", name),
+                }
+            }
+            Token::Char(ch, _) => write!(f, "{}", ch),
+        }
+    }
+}
+
+/// Prints a list of tokens as a single string, using the same rules as the
+/// Display implementation for individual tokens.
+pub fn format_token_list(tokens: &[Token]) -> String {
+    tokens.iter().map(|token| token.to_string()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_formats_token_lists() {
+        assert_eq!(
+            format_token_list(&[
+                Token::ControlSequence("hskip".to_string()),
+                Token::Char('1', Category::Other),
+                Token::Char('p', Category::Letter),
+                Token::Char('t', Category::Letter),
+                Token::ControlSequence("{".to_string()),
+                Token::ControlSequence("a".to_string()),
+                Token::Char(' ', Category::Space),
+            ]),
+            "\\hskip 1pt\\{\\a  "
+        );
+    }
+}
@@ -0,0 +1,302 @@
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use crate::origin::TokenOrigin;
+use crate::token::{format_token_list, Token};
+
+/// A hook which is called by the parser whenever it does something
+/// interesting: expanding a macro, evaluating a conditional, or executing a
+/// primitive. The `depth` passed to each function is the number of macro
+/// expansions that are currently being processed, so an event with depth 1
+/// happened while reading the replacement text of the most recent event with
+/// depth 0.
+///
+/// All of the functions have empty default implementations so that tracers
+/// only need to implement the events that they care about.
+pub trait ExpansionTracer {
+    /// Called when a macro is expanded. `origin` is where the macro token
+    /// came from, if that's known.
+    fn macro_expanded(
+        &mut self,
+        _depth: usize,
+        _token: &Token,
+        _origin: Option<&Rc<TokenOrigin>>,
+        _arguments: &HashMap<usize, Vec<Token>>,
+        _replacement: &[Token],
+    ) {
+    }
+
+    fn conditional_evaluated(
+        &mut self,
+        _depth: usize,
+        _token: &Token,
+        _result: bool,
+    ) {
+    }
+
+    fn primitive_executed(&mut self, _depth: usize, _token: &Token) {}
+}
+
+#[derive(Debug, PartialEq)]
+pub enum TraceEvent {
+    Macro {
+        token: Token,
+        origin: Option<Rc<TokenOrigin>>,
+        // The arguments to the macro, ordered by parameter number.
+        arguments: Vec<Vec<Token>>,
+        replacement: Vec<Token>,
+    },
+    Conditional {
+        token: Token,
+        result: bool,
+    },
+    Primitive {
+        token: Token,
+    },
+}
+
+#[derive(Debug, PartialEq)]
+pub struct TraceNode {
+    pub event: TraceEvent,
+    pub children: Vec<TraceNode>,
+}
+
+/// An ExpansionTracer which records every event into a tree, where the
+/// children of each macro expansion are the events that happened while its
+/// replacement text was being read.
+#[derive(Debug, Default)]
+pub struct ExpansionRecorder {
+    roots: Vec<TraceNode>,
+}
+
+fn escape_json_string(string: &str) -> String {
+    let mut result = String::with_capacity(string.len() + 2);
+    result.push('"');
+    for ch in string.chars() {
+        match ch {
+            '"' => result.push_str("\\\""),
+            '\\' => result.push_str("\\\\"),
+            '\n' => result.push_str("\\n"),
+            '\r' => result.push_str("\\r"),
+            '\t' => result.push_str("\\t"),
+            ch if (ch as u32) < 0x20 => {
+                result.push_str(&format!("\\u{:04x}", ch as u32))
+            }
+            ch => result.push(ch),
+        }
+    }
+    result.push('"');
+    result
+}
+
+impl TraceNode {
+    fn to_json(&self) -> String {
+        let fields = match &self.event {
+            TraceEvent::Macro {
+                token,
+                origin,
+                arguments,
+                replacement,
+            } => {
+                let origin = match origin {
+                    Some(origin) => escape_json_string(&origin.to_string()),
+                    None => "null".to_string(),
+                };
+                let arguments: Vec<String> = arguments
+                    .iter()
+                    .map(|arg| escape_json_string(&format_token_list(arg)))
+                    .collect();
+                format!(
+                    "\"type\":\"macro\",\"token\":{},\"origin\":{},\
+                     \"arguments\":[{}],\"replacement\":{}",
+                    escape_json_string(&token.to_string()),
+                    origin,
+                    arguments.join(","),
+                    escape_json_string(&format_token_list(replacement)),
+                )
+            }
+            TraceEvent::Conditional { token, result } => format!(
+                "\"type\":\"conditional\",\"token\":{},\"result\":{}",
+                escape_json_string(&token.to_string()),
+                result
+            ),
+            TraceEvent::Primitive { token } => format!(
+                "\"type\":\"primitive\",\"token\":{}",
+                escape_json_string(&token.to_string()),
+            ),
+        };
+
+        let children: Vec<String> =
+            self.children.iter().map(|child| child.to_json()).collect();
+
+        format!("{{{},\"children\":[{}]}}", fields, children.join(","))
+    }
+}
+
+impl ExpansionRecorder {
+    pub fn new() -> Self {
+        ExpansionRecorder { roots: Vec::new() }
+    }
+
+    pub fn get_roots(&self) -> &[TraceNode] {
+        &self.roots
+    }
+
+    fn add_event(&mut self, depth: usize, event: TraceEvent) {
+        let node = TraceNode {
+            event,
+            children: Vec::new(),
+        };
+
+        // Walk down the most recent branch of the tree to find the parent of
+        // this event. If the tree isn't as deep as we expected (which happens
+        // if the recorder was attached in the middle of an expansion), we just
+        // attach it as deep as we can.
+        let mut siblings = &mut self.roots;
+        for _ in 0..depth {
+            if siblings.is_empty() {
+                break;
+            }
+            siblings = &mut siblings.last_mut().unwrap().children;
+        }
+        siblings.push(node);
+    }
+
+    /// Serializes the recorded expansion tree into a JSON array. Each node is
+    /// an object with a "type" key of "macro", "conditional", or "primitive"
+    /// and a "children" key with the nested events. Tokens are printed the way
+    /// TeX would print them, and macros have an "origin" key saying where
+    /// they were expanded (or null if that isn't known).
+    pub fn to_json(&self) -> String {
+        let roots: Vec<String> =
+            self.roots.iter().map(|node| node.to_json()).collect();
+        format!("[{}]", roots.join(","))
+    }
+}
+
+impl ExpansionTracer for ExpansionRecorder {
+    fn macro_expanded(
+        &mut self,
+        depth: usize,
+        token: &Token,
+        origin: Option<&Rc<TokenOrigin>>,
+        arguments: &HashMap<usize, Vec<Token>>,
+        replacement: &[Token],
+    ) {
+        let mut arguments: Vec<(&usize, &Vec<Token>)> =
+            arguments.iter().collect();
+        arguments.sort_by_key(|(param_num, _)| **param_num);
+
+        self.add_event(
+            depth,
+            TraceEvent::Macro {
+                token: token.clone(),
+                origin: origin.cloned(),
+                arguments: arguments
+                    .into_iter()
+                    .map(|(_, arg)| arg.clone())
+                    .collect(),
+                replacement: replacement.to_vec(),
+            },
+        );
+    }
+
+    fn conditional_evaluated(
+        &mut self,
+        depth: usize,
+        token: &Token,
+        result: bool,
+    ) {
+        self.add_event(
+            depth,
+            TraceEvent::Conditional {
+                token: token.clone(),
+                result,
+            },
+        );
+    }
+
+    fn primitive_executed(&mut self, depth: usize, token: &Token) {
+        self.add_event(
+            depth,
+            TraceEvent::Primitive {
+                token: token.clone(),
+            },
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::category::Category;
+
+    #[test]
+    fn it_nests_events_by_depth() {
+        let mut recorder = ExpansionRecorder::new();
+        let a = Token::ControlSequence("a".to_string());
+        let b = Token::ControlSequence("b".to_string());
+        let relax = Token::ControlSequence("relax".to_string());
+
+        recorder.macro_expanded(
+            0,
+            &a,
+            None,
+            &HashMap::new(),
+            std::slice::from_ref(&b),
+        );
+        recorder.macro_expanded(
+            1,
+            &b,
+            None,
+            &HashMap::new(),
+            std::slice::from_ref(&relax),
+        );
+        recorder.primitive_executed(2, &relax);
+        recorder.primitive_executed(0, &relax);
+
+        assert_eq!(recorder.get_roots().len(), 2);
+        assert_eq!(recorder.get_roots()[0].children.len(), 1);
+        assert_eq!(
+            recorder.get_roots()[0].children[0].children,
+            vec![TraceNode {
+                event: TraceEvent::Primitive {
+                    token: relax.clone()
+                },
+                children: vec![],
+            }]
+        );
+    }
+
+    #[test]
+    fn it_serializes_to_json() {
+        let mut recorder = ExpansionRecorder::new();
+        let mut arguments = HashMap::new();
+        arguments.insert(2, vec![Token::Char('"', Category::Other)]);
+        arguments.insert(1, vec![Token::ControlSequence("x".to_string())]);
+
+        let origin = Rc::new(TokenOrigin::Source {
+            file: Some(Rc::from("story.tex")),
+            line: 3,
+            column: 2,
+        });
+        recorder.macro_expanded(
+            0,
+            &Token::ControlSequence("a".to_string()),
+            Some(&origin),
+            &arguments,
+            &[Token::Char('y', Category::Letter)],
+        );
+        recorder.conditional_evaluated(
+            1,
+            &Token::ControlSequence("ifnum".to_string()),
+            false,
+        );
+
+        assert_eq!(
+            recorder.to_json(),
+            r#"[{"type":"macro","token":"\\a ","origin":"story.tex:3:2","arguments":["\\x ","\""],"replacement":"y","children":[{"type":"conditional","token":"\\ifnum ","result":false,"children":[]}]}]"#
+        );
+    }
+}
@@ -0,0 +1,223 @@
+use std::cell::RefCell;
+use std::io::Write;
+use std::rc::Rc;
+
+/// Like TeX, we break lines that we print once they get this long.
+pub const MAX_PRINT_LINE: usize = 79;
+
+// When showing the context of an error, the line showing what has been read
+// is at most HALF_ERROR_LINE characters long, and both lines together are at
+// most ERROR_LINE characters long.
+const ERROR_LINE: usize = 72;
+const HALF_ERROR_LINE: usize = 42;
+
+/// Where printed text should end up, like TeX's `selector`.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Selector {
+    TerminalAndLog,
+    LogOnly,
+}
+
+// One of the places we print to, along with how many characters have been
+// printed on the current line.
+struct Output {
+    writer: Rc<RefCell<dyn Write>>,
+    offset: usize,
+}
+
+impl Output {
+    fn new(writer: Rc<RefCell<dyn Write>>) -> Self {
+        Output { writer, offset: 0 }
+    }
+
+    // There's nothing useful that we can do if printing fails, so errors are
+    // ignored everywhere here.
+    fn print_ln(&mut self) {
+        let _ = writeln!(self.writer.borrow_mut());
+        self.offset = 0;
+    }
+
+    fn print_char(&mut self, ch: char) {
+        if ch == '\n' {
+            self.print_ln();
+            return;
+        }
+
+        let _ = write!(self.writer.borrow_mut(), "{}", ch);
+        self.offset += 1;
+        if self.offset == MAX_PRINT_LINE {
+            self.print_ln();
+        }
+    }
+}
+
+/// Keeps track of the terminal and the transcript (.log) file, and prints to
+/// them the way TeX does: every line of output is wrapped at MAX_PRINT_LINE
+/// columns, and the log file gets a copy of everything that goes to the
+/// terminal.
+pub struct Transcript {
+    terminal: Output,
+    log: Option<Output>,
+}
+
+impl Transcript {
+    pub fn new(terminal: Rc<RefCell<dyn Write>>) -> Self {
+        Transcript {
+            terminal: Output::new(terminal),
+            log: None,
+        }
+    }
+
+    pub fn set_terminal(&mut self, terminal: Rc<RefCell<dyn Write>>) {
+        self.terminal = Output::new(terminal);
+    }
+
+    pub fn set_log(&mut self, log: Rc<RefCell<dyn Write>>) {
+        self.log = Some(Output::new(log));
+    }
+
+    fn outputs(&mut self, selector: Selector) -> Vec<&mut Output> {
+        let mut outputs = Vec::new();
+        if selector == Selector::TerminalAndLog {
+            outputs.push(&mut self.terminal);
+        }
+        if let Some(log) = &mut self.log {
+            outputs.push(log);
+        }
+        outputs
+    }
+
+    /// Returns true if something has been printed on the current line of any
+    /// of the selected outputs.
+    pub fn is_mid_line(&mut self, selector: Selector) -> bool {
+        self.outputs(selector)
+            .iter()
+            .any(|output| output.offset > 0)
+    }
+
+    /// Returns how many characters have been printed on the current line of
+    /// the terminal.
+    pub fn terminal_offset(&self) -> usize {
+        self.terminal.offset
+    }
+
+    pub fn print(&mut self, selector: Selector, text: &str) {
+        for output in self.outputs(selector) {
+            for ch in text.chars() {
+                output.print_char(ch);
+            }
+        }
+    }
+
+    pub fn print_ln(&mut self, selector: Selector) {
+        for output in self.outputs(selector) {
+            output.print_ln();
+        }
+    }
+
+    /// Starts a new line on each selected output where something has already
+    /// been printed on the current line, like TeX's print_nl.
+    pub fn print_nl(&mut self, selector: Selector) {
+        for output in self.outputs(selector) {
+            if output.offset > 0 {
+                output.print_ln();
+            }
+        }
+    }
+}
+
+/// Lays out one level of the context of an error the way TeX does, as two
+/// lines where the first has the description of the level (e.g. "l.5 ")
+/// followed by the text that has already been read, and the second has the
+/// text that hasn't been read yet, lined up to start where the first line
+/// ends. Text that doesn't fit is replaced with "...".
+pub fn format_context_lines(
+    description: &str,
+    read: &str,
+    unread: &str,
+) -> (String, String) {
+    let first: Vec<char> = description.chars().chain(read.chars()).collect();
+    let unread: Vec<char> = unread.chars().collect();
+
+    let (first_line, indent) = if first.len() <= HALF_ERROR_LINE {
+        (first.iter().collect(), first.len())
+    } else {
+        let start = first.len() - HALF_ERROR_LINE + 3;
+        (
+            format!("...{}", first[start..].iter().collect::<String>()),
+            HALF_ERROR_LINE,
+        )
+    };
+
+    let second_line = if unread.len() + indent <= ERROR_LINE {
+        unread.iter().collect()
+    } else {
+        let end = ERROR_LINE - indent - 3;
+        format!("{}...", unread[..end].iter().collect::<String>())
+    };
+
+    (first_line, format!("{}{}", " ".repeat(indent), second_line))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::testing::TestOutput;
+
+    fn with_transcript<F>(func: F) -> (String, String)
+    where
+        F: FnOnce(&mut Transcript),
+    {
+        let terminal = TestOutput::new();
+        let log = TestOutput::new();
+
+        let mut transcript = Transcript::new(terminal.writer());
+        transcript.set_log(log.writer());
+        func(&mut transcript);
+
+        (terminal.contents(), log.contents())
+    }
+
+    #[test]
+    fn it_prints_to_the_terminal_and_log() {
+        let (terminal, log) = with_transcript(|transcript| {
+            transcript.print(Selector::TerminalAndLog, "both");
+            transcript.print_nl(Selector::LogOnly);
+            transcript.print(Selector::LogOnly, "log");
+            transcript.print_ln(Selector::LogOnly);
+            transcript.print_nl(Selector::TerminalAndLog);
+            transcript.print(Selector::TerminalAndLog, "a\nb");
+        });
+
+        assert_eq!(terminal, "both\na\nb");
+        assert_eq!(log, "both\nlog\na\nb");
+    }
+
+    #[test]
+    fn it_wraps_long_lines() {
+        let (terminal, log) = with_transcript(|transcript| {
+            transcript.print(Selector::LogOnly, "x");
+            transcript.print(Selector::TerminalAndLog, &"a".repeat(100));
+        });
+
+        assert_eq!(terminal, format!("{}\n{}", "a".repeat(79), "a".repeat(21)));
+        assert_eq!(log, format!("x{}\n{}", "a".repeat(78), "a".repeat(22)));
+    }
+
+    #[test]
+    fn it_formats_short_context_lines() {
+        assert_eq!(
+            format_context_lines("l.1 ", r"\hbox", "{a}"),
+            (r"l.1 \hbox".to_string(), "         {a}".to_string())
+        );
+    }
+
+    #[test]
+    fn it_truncates_long_context_lines() {
+        let (first, second) =
+            format_context_lines("l.1 ", &"a".repeat(50), &"b".repeat(50));
+        assert_eq!(first, format!("...{}", "a".repeat(39)));
+        assert_eq!(second, format!("{}{}...", " ".repeat(42), "b".repeat(27)));
+    }
+}
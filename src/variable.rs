@@ -1,9 +1,19 @@
+use std::rc::Rc;
+
+use crate::category::Category;
 use crate::dimension::Dimen;
+use crate::glue::{Glue, MuGlue};
+use crate::math_code::MathCode;
+use crate::parameter::{
+    DimenParameter, GlueParameter, IntegerParameter, MuGlueParameter,
+};
 use crate::state::TeXState;
+use crate::token::Token;
 
 #[derive(PartialEq, Eq, Debug)]
 pub enum IntegerVariable {
     CountRegister(u8),
+    Parameter(IntegerParameter),
 }
 
 impl IntegerVariable {
@@ -12,18 +22,93 @@ impl IntegerVariable {
             Self::CountRegister(index) => {
                 state.set_count(global, *index, value)
             }
+            Self::Parameter(parameter) => {
+                state.set_integer_parameter(global, *parameter, value)
+            }
         }
     }
 
     pub fn get(&self, state: &TeXState) -> i32 {
         match self {
             Self::CountRegister(index) => state.get_count(*index),
+            Self::Parameter(parameter) => {
+                state.get_integer_parameter(*parameter)
+            }
+        }
+    }
+}
+
+/// An entry in one of the tables which assign codes to characters, like
+/// \catcode`a.
+#[derive(PartialEq, Eq, Debug)]
+pub enum CodeVariable {
+    Category(char),
+    MathCode(char),
+    LowercaseCode(char),
+    UppercaseCode(char),
+    SpaceFactorCode(char),
+    DelimiterCode(char),
+}
+
+impl CodeVariable {
+    /// The largest value that this code can be set to. Only delimiter codes
+    /// can be negative.
+    pub fn get_max_value(&self) -> i32 {
+        match self {
+            Self::Category(_) => 15,
+            Self::MathCode(_) => 0x8000,
+            Self::LowercaseCode(_) | Self::UppercaseCode(_) => 255,
+            Self::SpaceFactorCode(_) => 0x7FFF,
+            Self::DelimiterCode(_) => 0xFF_FFFF,
+        }
+    }
+
+    // The value must be in the range allowed by get_max_value().
+    pub fn set(&self, state: &TeXState, global: bool, value: i32) {
+        match self {
+            Self::Category(ch) => state.set_category(
+                global,
+                *ch,
+                Category::from_number(value as u8).unwrap(),
+            ),
+            Self::MathCode(ch) => state.set_math_code(
+                global,
+                *ch,
+                &MathCode::from_number(value as u32),
+            ),
+            Self::LowercaseCode(ch) => {
+                state.set_lowercase_code(global, *ch, value as u8 as char)
+            }
+            Self::UppercaseCode(ch) => {
+                state.set_uppercase_code(global, *ch, value as u8 as char)
+            }
+            Self::SpaceFactorCode(ch) => {
+                state.set_space_factor_code(global, *ch, value as u16)
+            }
+            Self::DelimiterCode(ch) => {
+                state.set_delimiter_code(global, *ch, value)
+            }
+        }
+    }
+
+    pub fn get(&self, state: &TeXState) -> i32 {
+        match self {
+            Self::Category(ch) => state.get_category(*ch) as i32,
+            Self::MathCode(ch) => state.get_math_code(*ch).to_number() as i32,
+            Self::LowercaseCode(ch) => state.get_lowercase_code(*ch) as i32,
+            Self::UppercaseCode(ch) => state.get_uppercase_code(*ch) as i32,
+            Self::SpaceFactorCode(ch) => {
+                state.get_space_factor_code(*ch) as i32
+            }
+            Self::DelimiterCode(ch) => state.get_delimiter_code(*ch),
         }
     }
 }
 
 #[derive(PartialEq, Eq, Debug)]
 pub enum DimenVariable {
+    DimenRegister(u8),
+    Parameter(DimenParameter),
     BoxWidth(u8),
     BoxHeight(u8),
     BoxDepth(u8),
@@ -32,6 +117,8 @@ pub enum DimenVariable {
 impl DimenVariable {
     pub fn get(&self, state: &TeXState) -> Dimen {
         match self {
+            Self::DimenRegister(index) => state.get_dimen(*index),
+            Self::Parameter(parameter) => state.get_dimen_parameter(*parameter),
             Self::BoxWidth(index) => state
                 .with_box(*index, |tex_box| *tex_box.width())
                 .unwrap_or_else(Dimen::zero),
@@ -44,8 +131,16 @@ impl DimenVariable {
         }
     }
 
-    pub fn set(&self, state: &TeXState, _global: bool, new_dimen: Dimen) {
+    // Box dimensions are always changed in place, like in TeX, so only
+    // registers and parameters can be set globally.
+    pub fn set(&self, state: &TeXState, global: bool, new_dimen: Dimen) {
         match self {
+            Self::DimenRegister(index) => {
+                state.set_dimen(global, *index, new_dimen)
+            }
+            Self::Parameter(parameter) => {
+                state.set_dimen_parameter(global, *parameter, new_dimen)
+            }
             Self::BoxWidth(index) => {
                 state.with_box(*index, |tex_box| {
                     *tex_box.mut_width() = new_dimen
@@ -65,6 +160,77 @@ impl DimenVariable {
     }
 }
 
+#[derive(PartialEq, Eq, Debug)]
+pub enum GlueVariable {
+    SkipRegister(u8),
+    Parameter(GlueParameter),
+}
+
+impl GlueVariable {
+    pub fn get(&self, state: &TeXState) -> Glue {
+        match self {
+            Self::SkipRegister(index) => state.get_skip(*index),
+            Self::Parameter(parameter) => state.get_glue_parameter(*parameter),
+        }
+    }
+
+    pub fn set(&self, state: &TeXState, global: bool, value: &Glue) {
+        match self {
+            Self::SkipRegister(index) => state.set_skip(global, *index, value),
+            Self::Parameter(parameter) => {
+                state.set_glue_parameter(global, *parameter, value)
+            }
+        }
+    }
+}
+
+#[derive(PartialEq, Eq, Debug)]
+pub enum MuGlueVariable {
+    MuSkipRegister(u8),
+    Parameter(MuGlueParameter),
+}
+
+impl MuGlueVariable {
+    pub fn get(&self, state: &TeXState) -> MuGlue {
+        match self {
+            Self::MuSkipRegister(index) => state.get_muskip(*index),
+            Self::Parameter(parameter) => {
+                state.get_mu_glue_parameter(*parameter)
+            }
+        }
+    }
+
+    pub fn set(&self, state: &TeXState, global: bool, value: &MuGlue) {
+        match self {
+            Self::MuSkipRegister(index) => {
+                state.set_muskip(global, *index, value)
+            }
+            Self::Parameter(parameter) => {
+                state.set_mu_glue_parameter(global, *parameter, value)
+            }
+        }
+    }
+}
+
+#[derive(PartialEq, Eq, Debug)]
+pub enum TokenListVariable {
+    ToksRegister(u8),
+}
+
+impl TokenListVariable {
+    pub fn get(&self, state: &TeXState) -> Rc<Vec<Token>> {
+        match self {
+            Self::ToksRegister(index) => state.get_toks(*index),
+        }
+    }
+
+    pub fn set(&self, state: &TeXState, global: bool, tokens: &Rc<Vec<Token>>) {
+        match self {
+            Self::ToksRegister(index) => state.set_toks(global, *index, tokens),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -163,4 +329,21 @@ mod tests {
             Dimen::from_unit(3.0, Unit::Point)
         );
     }
+
+    #[test]
+    fn it_sets_dimen_registers_globally() {
+        let state = TeXState::new();
+        let variable = DimenVariable::DimenRegister(12);
+
+        state.push_state();
+        variable.set(&state, false, Dimen::from_unit(1.0, Unit::Point));
+        assert_eq!(variable.get(&state), Dimen::from_unit(1.0, Unit::Point));
+        state.pop_state();
+        assert_eq!(variable.get(&state), Dimen::zero());
+
+        state.push_state();
+        variable.set(&state, true, Dimen::from_unit(2.0, Unit::Point));
+        state.pop_state();
+        assert_eq!(variable.get(&state), Dimen::from_unit(2.0, Unit::Point));
+    }
 }